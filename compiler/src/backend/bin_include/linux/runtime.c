@@ -0,0 +1,68 @@
+// the runtime for programs compiled on linux
+// the compiled program's 'start' is called from main, and its result is printed on stdout
+// every value handed out by read_int is also reported on stderr, so whoever runs the
+// program can tell in which order the input was consumed
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <inttypes.h>
+
+int64_t start(void);
+
+int64_t read_int_aligned(void) {
+    char line[64];
+
+    if (fgets(line, sizeof(line), stdin) == NULL) {
+        fprintf(stderr, "error: ran out of input\n");
+        exit(2);
+    }
+
+    char *end;
+    int64_t n = strtoll(line, &end, 10);
+
+    if (end == line) {
+        fprintf(stderr, "error: not an integer: %s\n", line);
+        exit(2);
+    }
+
+    fprintf(stderr, "read %" PRId64 "\n", n);
+
+    return n;
+}
+
+// the compiled code doesn't keep the stack 16 byte aligned at calls,
+// so line it up before going into c
+__asm__(
+    ".text\n"
+    ".globl read_int\n"
+    "read_int:\n"
+    "    push %rbp\n"
+    "    mov %rsp, %rbp\n"
+    "    and $-16, %rsp\n"
+    "    call read_int_aligned\n"
+    "    mov %rbp, %rsp\n"
+    "    pop %rbp\n"
+    "    ret\n"
+);
+
+// the compiled code jumps here when an overflow is checked, it doesn't return
+void overflow_error_aligned(void) {
+    fprintf(stderr, "error: integer overflow\n");
+    exit(3);
+}
+
+__asm__(
+    ".text\n"
+    ".globl overflow_error\n"
+    "overflow_error:\n"
+    "    and $-16, %rsp\n"
+    "    call overflow_error_aligned\n"
+);
+
+int main(void) {
+    int64_t result = start();
+
+    printf("%" PRId64 "\n", result);
+
+    return 0;
+}
@@ -2,8 +2,22 @@ pub mod x64_backend;
 pub mod x64_def;
 pub mod x64_print;
 pub mod x64_build;
+pub mod x64_peephole;
+pub mod x64_verify;
+pub mod x64_emulator;
+pub mod x64_text;
 
 #[cfg(test)]
 mod x64_backend_tests;
+#[cfg(test)]
 mod x64_print_tests;
+#[cfg(test)]
+mod x64_peephole_tests;
+#[cfg(test)]
+mod x64_verify_tests;
+#[cfg(test)]
+mod x64_emulator_tests;
+#[cfg(test)]
+mod x64_text_tests;
+#[cfg(all(test, target_os = "windows"))]
 mod x64_build_tests;
\ No newline at end of file
@@ -1,38 +1,49 @@
 // the first part of the journey from c-lang to x64
 
-#![allow(unused)]
-
-use natord;
-
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::collections::{BTreeSet, HashSet};
 use std::cell::RefCell;
 
 use super::x64_def;
+use super::x64_peephole;
 
-use crate::types::{IdString};
+use crate::types::{IdString, Overflow};
 use crate::ir::explicate;
 
+/*
+    the backend is a sequence of passes, each one taking and returning an X64Program
+
+        select_instructions -> assign_homes -> patch_instructions
+            -> peephole (O1 and up) -> add_prologue_epilogue
+
+    the pass manager runs them one by one, see PASSES in the pipeline
+*/
+
+pub use assign_homes::assign_homes;
+pub use patch_instructions::patch_instructions;
+pub use peephole::peephole;
+pub use prologue_epilogue::add_prologue_epilogue;
+
 pub struct IRToX64Transformer {
-    externals: RefCell<HashSet<IdString>>,
+    externals: RefCell<BTreeSet<IdString>>,
     cprog: explicate::IRProgram,
-    blocks: HashMap<IdString, x64_def::Block>,
-    vars: Vec::<x64_def::Home>,
-    rbp_offset: i64,
-    prologue_tag: Rc::<String>,
-    epilogue_tag: Rc::<String>,
-    prologue_necessary: bool, // do we need a frame pointer ?
-    memory_patch: x64_def::Reg, // we might need a register for the case when we end up with an operation taking to memory operands
-    mp_used: bool, // flag for above value
+    overflow: Overflow,
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct BlockData {
-    vars: HashSet<x64_def::Home>,
+    vars: HashSet<IdString>, // put in order once, when the program is done
     instr: Vec<x64_def::Instr>,
 }
 
+// when overflow is checked every addition and negation is followed by a jo to this
+// block, which calls the runtime's error handler. the handler doesn't return
+pub const OVERFLOW_LABEL: &str = "overflow";
+pub const OVERFLOW_HANDLER: &str = "overflow_error";
+
+// we might need a register for the case when we end up with an operation taking two memory operands
+pub const MEMORY_PATCH: x64_def::Reg = x64_def::Reg::R15;
+
 // map the ir code to x64 instructions 
 mod select_instruction {
 
@@ -40,9 +51,19 @@ mod select_instruction {
     use super::BlockData;
     use super::IRToX64Transformer;
     use super::explicate::{Atm, Stmt, Tail, Exp};
+    use crate::symbol::{PLUS, MINUS, READ};
 
     impl IRToX64Transformer {
 
+        // after an instruction that can overflow
+        fn check_overflow(&self, blk_data: &mut BlockData) {
+            if self.overflow == crate::types::Overflow::Checked {
+                self.externals.borrow_mut().insert(crate::idstr!(super::OVERFLOW_HANDLER));
+
+                blk_data.instr.push(Instr::JmpIf(Cc::O, crate::idstr!(super::OVERFLOW_LABEL)));
+            }
+        }
+
         fn handle_atom(&self, atm: &Atm, blk_data: &mut BlockData) -> Arg {
 
             match atm {
@@ -51,19 +72,14 @@ mod select_instruction {
                 },
 
                 Atm::Var { name } => {
-                    blk_data.vars.insert(
-                        Home {
-                            name: name.clone(),
-                            loc: VarLoc::Undefined,
-                        }
-                    );
+                    blk_data.vars.insert(*name);
 
-                    Arg::Var(name.clone())
+                    Arg::Var(*name)
                 }
             }
         }
 
-        fn handle_stmt(&self, stmt: &Stmt, blk_data: &mut BlockData) {
+        pub fn handle_stmt(&self, stmt: &Stmt, blk_data: &mut BlockData) {
             match stmt {
                 Stmt::Assign(atm, expr) => {
                     let assignee = self.handle_atom(atm, blk_data);
@@ -76,30 +92,32 @@ mod select_instruction {
 
                         Exp::Prim { op, args } => {
 
-                            match &op[..] {
-                                "read" => {
+                            match *op {
+                                READ => {
                                     // this function is named "read_int" in the runtime library
                                     let runtime_name = crate::idstr!("read_int");
 
-                                    self.externals.borrow_mut().insert(runtime_name.clone());
+                                    self.externals.borrow_mut().insert(runtime_name);
 
                                     blk_data.instr.push(Instr::Call(runtime_name, 0));
                                     blk_data.instr.push(Instr::Mov64(assignee, Arg::Reg(Reg::Rax)));
                                 }
 
-                                "-" => {
+                                MINUS => {
                                     let assigned = self.handle_atom(&args[0], blk_data);
 
                                     blk_data.instr.push(Instr::Mov64(assignee.clone(), assigned));
                                     blk_data.instr.push(Instr::Neg64(assignee));
+                                    self.check_overflow(blk_data);
                                 },
 
-                                "+" => {
+                                PLUS => {
                                     let latm = self.handle_atom(&args[0], blk_data);
                                     let ratm = self.handle_atom(&args[1], blk_data);
 
                                     blk_data.instr.push(Instr::Mov64(assignee.clone(), latm));
                                     blk_data.instr.push(Instr::Add64(assignee, ratm));
+                                    self.check_overflow(blk_data);
                                 },
 
                                 _ => {
@@ -115,9 +133,27 @@ mod select_instruction {
         pub fn select_instruction(&self, tail: &Tail, blk_data: &mut BlockData) {
 
             match tail {
-                Tail::Seq(stmt, tail) => {
-                    self.handle_stmt(stmt, blk_data);
-                    self.select_instruction(tail, blk_data);
+                Tail::Goto(label) => {
+                    blk_data.instr.push(Instr::Jmp(*label));
+                },
+
+                Tail::If(cnd, thn, els) => {
+                    match cnd {
+                        // the branch is known at compile time
+                        Atm::Int(n) => {
+                            let target = if *n != 0 { thn } else { els };
+
+                            blk_data.instr.push(Instr::Jmp(*target));
+                        },
+
+                        Atm::Var { .. } => {
+                            let the_atom = self.handle_atom(cnd, blk_data);
+
+                            blk_data.instr.push(Instr::Cmp64(the_atom, Arg::Imm(0)));
+                            blk_data.instr.push(Instr::JmpIf(Cc::Ne, *thn));
+                            blk_data.instr.push(Instr::Jmp(*els));
+                        }
+                    }
                 },
 
                 Tail::Return(exp) => {
@@ -129,27 +165,29 @@ mod select_instruction {
                         },
 
                         Exp::Prim { op, args } => {
-                            match &op[..] {
-                                "read" => {
+                            match *op {
+                                READ => {
                                     // this function is named "read_int" in the runtime library
                                     let runtime_name = crate::idstr!("read_int");
 
-                                    self.externals.borrow_mut().insert(runtime_name.clone());
+                                    self.externals.borrow_mut().insert(runtime_name);
 
                                     blk_data.instr.push(Instr::Call(runtime_name, 0));
                                 },
 
-                                "-" => {
+                                MINUS => {
                                     let the_atm = self.handle_atom(&args[0], blk_data);
                                     blk_data.instr.push(Instr::Mov64(Arg::Reg(Reg::Rax), the_atm.clone()));
                                     blk_data.instr.push(Instr::Neg64(Arg::Reg(Reg::Rax)));
+                                    self.check_overflow(blk_data);
                                 },
-                                "+" => {
+                                PLUS => {
                                     let latm = self.handle_atom(&args[0], blk_data);
                                     let ratm = self.handle_atom(&args[1], blk_data);
 
                                     blk_data.instr.push(Instr::Mov64(Arg::Reg(Reg::Rax), latm));
                                     blk_data.instr.push(Instr::Add64(Arg::Reg(Reg::Rax), ratm));
+                                    self.check_overflow(blk_data);
                                 },
 
                                 _ => {
@@ -158,6 +196,9 @@ mod select_instruction {
                             }
                         },
                     }
+
+                    // the epilogue is added in front of this once the frame is known
+                    blk_data.instr.push(Instr::Ret);
                 }
             }
         }
@@ -168,39 +209,42 @@ mod select_instruction {
 // currently this is just an offset from rbp (i.e. variables live on the stack)
 mod assign_homes {
 
-    use std::collections::HashSet;
-
     use super::x64_def::*;
-    use super::IRToX64Transformer;
 
-    impl IRToX64Transformer {
-        pub fn assign_homes(&mut self) {
+    pub fn assign_homes(prog: X64Program) -> X64Program {
 
-            let mut the_vars = self.vars.clone();
+        let mut the_vars = prog.vars;
 
-            // sort variables in natural order so we end up with a deterministic
-            // output when stack variables are used
-            the_vars.sort_by(
-                |a, b|
-                natord::compare(&*a.name, &*b.name)
-            );
+        // sort variables in natural order so we end up with a deterministic
+        // output when stack variables are used
+        the_vars.sort_by(
+            |a, b|
+            natord::compare(&a.name, &b.name)
+        );
 
-            let mut found_homes: Vec<Home> = vec!();
+        // a variable used in more than one block only needs one home
+        the_vars.dedup_by(|a, b| a.name == b.name);
 
-            for var in the_vars {
-                let mut assigned = var.clone();
+        let mut rbp_offset = 0;
 
-                let next_rbp_offset = self.next_rbp_offset();
+        let mut found_homes: Vec<Home> = vec!();
 
-                assigned.loc = VarLoc::Rbp(next_rbp_offset);
+        for var in the_vars {
+            let mut assigned = var;
 
-                found_homes.push(assigned);
-            }
+            // rbp_offset starts at 0, so need to decrement
+            // the offset first, so that rbp isn't overwritten
+            rbp_offset += 8;
 
-            if found_homes.len() > 0 {
-                self.prologue_necessary = true;
-                self.vars = found_homes;
-            }
+            assigned.loc = VarLoc::Rbp(rbp_offset);
+
+            found_homes.push(assigned);
+        }
+
+        X64Program {
+            external: prog.external,
+            vars: found_homes,
+            blocks: prog.blocks,
         }
     }
 }
@@ -217,7 +261,8 @@ mod assign_homes {
 mod patch_instructions {
 
     use super::x64_def::*;
-    use super::IRToX64Transformer;
+    use super::MEMORY_PATCH;
+    use super::x64_peephole::{fits_in_imm32};
 
     fn patch(instr: Vec<Instr>) -> (bool, Vec<Instr>) {
 
@@ -226,33 +271,54 @@ mod patch_instructions {
 
         for instruction in &instr {
             match instruction {
-                Instr::Add64(src, dest) => {
-                    patched_instructions.push(instruction.clone());
-                },
-
-                Instr::Mov64(src, dest) => {
+                // only a mov into a register takes an immediate that doesn't fit in 32 bits
+                // e.g. (+ x.1 9223372036854775807) assigned to x.1
+                //                 Add64(Var("x.1"), Imm(9223372036854775807))
+                Instr::Add64(a, Arg::Imm(n)) |
+                Instr::Sub64(a, Arg::Imm(n)) |
+                Instr::Cmp64(a, Arg::Imm(n)) |
+                Instr::Mov64(a @ (Arg::Var(_) | Arg::Deref(..)), Arg::Imm(n)) if !fits_in_imm32(*n) => {
+                    patched_instructions.push(Instr::Mov64(Arg::Reg(MEMORY_PATCH), Arg::Imm(*n)));
+
+                    patched_instructions.push(
+                        match instruction {
+                            Instr::Add64(..) => Instr::Add64(a.clone(), Arg::Reg(MEMORY_PATCH)),
+                            Instr::Sub64(..) => Instr::Sub64(a.clone(), Arg::Reg(MEMORY_PATCH)),
+                            Instr::Cmp64(..) => Instr::Cmp64(a.clone(), Arg::Reg(MEMORY_PATCH)),
+                            _ => Instr::Mov64(a.clone(), Arg::Reg(MEMORY_PATCH)),
+                        }
+                    );
 
-                    match (src, dest) {
+                    patched = true;
+                },
 
-                        (Arg::Var(x), Arg::Var(y)) => {
-                            patched_instructions.push(Instr::Mov64(Arg::Reg(Reg::R15), Arg::Var(y.clone())));
+                Instr::Mov64(Arg::Var(x), Arg::Var(y)) => {
+                    patched_instructions.push(Instr::Mov64(Arg::Reg(MEMORY_PATCH), Arg::Var(*y)));
 
-                            patched_instructions.push(
-                                Instr::Mov64(Arg::Var(x.clone()), Arg::Reg(Reg::R15)),
-                            );
+                    patched_instructions.push(
+                        Instr::Mov64(Arg::Var(*x), Arg::Reg(MEMORY_PATCH)),
+                    );
 
-                            patched = true;
-                        },
+                    patched = true;
+                },
 
-                        _ => {
-                            patched_instructions.push(instruction.clone());
+                // the same goes for arithmetic and comparisons, e.g. (+ x.1 y.2) assigned to z.3
+                //                 Add64(Var("z.3"), Var("y.2"))
+                Instr::Add64(Arg::Var(x), Arg::Var(y)) |
+                Instr::Sub64(Arg::Var(x), Arg::Var(y)) |
+                Instr::Cmp64(Arg::Var(x), Arg::Var(y)) => {
+                    patched_instructions.push(Instr::Mov64(Arg::Reg(MEMORY_PATCH), Arg::Var(*y)));
+
+                    patched_instructions.push(
+                        match instruction {
+                            Instr::Add64(..) => Instr::Add64(Arg::Var(*x), Arg::Reg(MEMORY_PATCH)),
+                            Instr::Sub64(..) => Instr::Sub64(Arg::Var(*x), Arg::Reg(MEMORY_PATCH)),
+                            _ => Instr::Cmp64(Arg::Var(*x), Arg::Reg(MEMORY_PATCH)),
                         }
-                    }
-                },
+                    );
 
-                Instr::Sub64(src, dest) => {
-                    patched_instructions.push(instruction.clone());
-                }
+                    patched = true;
+                },
 
                 _ => {
                     patched_instructions.push(instruction.clone());
@@ -263,98 +329,81 @@ mod patch_instructions {
         (patched, patched_instructions)
     }
 
-    impl IRToX64Transformer {
-        pub fn patch_instructions(&mut self) {
-            for block in &mut self.blocks {
-                let instructions = block.1.instr.clone();
+    pub fn patch_instructions(mut prog: X64Program) -> X64Program {
+        for block in prog.blocks.values_mut() {
+            let instructions = std::mem::take(&mut block.instr);
 
-                let (patched, instructions) = patch(instructions);
+            let (_, instructions) = patch(instructions);
 
-                if patched {
-                    self.mp_used = true;
-                    block.1.instr = instructions;
-                }
-            }
+            block.instr = instructions;
         }
+
+        prog
     }
 }
 
-impl IRToX64Transformer {
+// clean up the selected instructions, see x64_peephole for the rules
+mod peephole {
 
-    fn next_rbp_offset(&mut self) -> i64 {
-        // rbp_offset starts at 0, so need to decrement
-        // the offset first, so that rbp isn't overwritten
-        self.rbp_offset += 8;
+    use super::x64_def::*;
+    use super::x64_peephole::{optimize_block, PeepholeStats, RULES};
 
-        self.rbp_offset
-    }
+    pub fn peephole(mut prog: X64Program, stats: &mut PeepholeStats) -> X64Program {
+        for block in prog.blocks.values_mut() {
+            let instructions = std::mem::take(&mut block.instr);
 
-    pub fn new(cprog: explicate::IRProgram) -> Self {
-        IRToX64Transformer {
-            externals: RefCell::new(crate::set!()),
-            cprog: cprog,
-            blocks: HashMap::new(),
-            vars: Vec::new(),
-            rbp_offset: 0,
-            prologue_tag: crate::idstr!("prologue"),
-            epilogue_tag: crate::idstr!("epilogue"),
-            prologue_necessary: false,
-            memory_patch: x64_def::Reg::R15,
-            mp_used: false,
+            block.instr = optimize_block(instructions, RULES, stats);
         }
-    }
-
-    pub fn transform(&mut self) -> x64_def::X64Program {
 
-        use x64_def::*;
+        prog
+    }
+}
 
-        for (label, tail) in &self.cprog.labels {
+// set up the stack frame when start is entered, and tear it down again
+// before every return
+mod prologue_epilogue {
 
-            let mut blk_data = BlockData::default();
+    use super::x64_def::*;
+    use super::MEMORY_PATCH;
 
-            self.select_instruction(
-                tail,
-                &mut blk_data
-            );
+    fn uses_reg(instr: &Instr, reg: Reg) -> bool {
+        let is_reg = |arg: &Arg| matches!(arg, Arg::Reg(r) | Arg::Deref(r, _) if *r == reg);
 
-            self.blocks.insert(
-                label.clone(),
-                Block {
-                    info: (),
-                    instr: blk_data.instr
-                }
-            );
-
-            self.vars.extend(blk_data.vars);
+        match instr {
+            Instr::Add64(a, b) | Instr::Sub64(a, b) |
+            Instr::Mov64(a, b) | Instr::Cmp64(a, b) => is_reg(a) || is_reg(b),
+            Instr::Neg64(a) | Instr::Push(a) | Instr::Pop(a) => is_reg(a),
+            _ => false,
         }
+    }
 
-        // this will let us know if we need to patch the entry point
-        self.assign_homes();
+    pub fn add_prologue_epilogue(mut prog: X64Program) -> X64Program {
 
-        // this might set mp_used
-        self.patch_instructions();
+        // do we need a frame pointer ?
+        let prologue_necessary = !prog.vars.is_empty();
 
-        let start = self.blocks.get_mut(&crate::idstr!("start")).unwrap();
+        // patch_instructions might have needed the memory patch register,
+        // it is callee saved, so it has to be restored before returning
+        let mp_used =
+            prog.blocks
+            .values()
+            .any(|block| block.instr.iter().any(|i| uses_reg(i, MEMORY_PATCH)));
 
-        let mut fn_start = start.instr.clone();
+        let mut fn_start: Vec<Instr> = vec!();
 
         let mut fn_end: Vec<Instr> = vec!();
 
-        if self.prologue_necessary {
+        if prologue_necessary {
             // patch the entry function if we need to
 
-            fn_start.insert(0, Instr::Push(Arg::Reg(Reg::Rbp)));
-            fn_start.insert(1, Instr::Mov64(Arg::Reg(Reg::Rbp), Arg::Reg(Reg::Rsp)));
+            fn_start.push(Instr::Push(Arg::Reg(Reg::Rbp)));
+            fn_start.push(Instr::Mov64(Arg::Reg(Reg::Rbp), Arg::Reg(Reg::Rsp)));
 
             // need to also allocate space for variables, i.e. decrement RSP
             let mut rsp_decrement = 0;
-            for home in &self.vars {
-                match home.loc {
-                    VarLoc::Rbp(_) => {
-                        rsp_decrement += 8;
-                    },
-
-                    _ => {}
+            for home in &prog.vars {
+                if let VarLoc::Rbp(_) = home.loc {
+                    rsp_decrement += 8;
                 }
             }
 
@@ -365,23 +414,111 @@ impl IRToX64Transformer {
             fn_end.push(Instr::Mov64(Arg::Reg(Reg::Rsp), Arg::Reg(Reg::Rbp)));
             fn_end.push(Instr::Pop(Arg::Reg(Reg::Rbp)));
 
-            if self.mp_used {
-                fn_start.insert(0, Instr::Push(Arg::Reg(self.memory_patch)));
-                fn_end.push(Instr::Pop(Arg::Reg(self.memory_patch)));
+            if mp_used {
+                fn_start.insert(0, Instr::Push(Arg::Reg(MEMORY_PATCH)));
+                fn_end.push(Instr::Pop(Arg::Reg(MEMORY_PATCH)));
             }
 
         }
 
-        fn_end.push(Instr::Ret);
+        // every block that returns from the function has to restore the stack first
+        for block in prog.blocks.values_mut() {
+            if fn_end.is_empty() {
+                break;
+            }
+
+            let mut new_instr = Vec::with_capacity(block.instr.len() + fn_end.len());
+
+            for instr in std::mem::take(&mut block.instr) {
+                if instr == Instr::Ret {
+                    new_instr.extend(fn_end.iter().cloned());
+                }
 
-        fn_start.extend(fn_end);
+                new_instr.push(instr);
+            }
+
+            block.instr = new_instr;
+        }
+
+        if let Some(start) = prog.blocks.get_mut(&crate::idstr!("start")) {
+            fn_start.append(&mut start.instr);
+
+            start.instr = fn_start;
+        }
+
+        prog
+    }
+}
+
+impl IRToX64Transformer {
+
+    pub fn new(cprog: explicate::IRProgram) -> Self {
+        IRToX64Transformer {
+            externals: RefCell::new(crate::set!()),
+            cprog: cprog,
+            overflow: Overflow::Wrap,
+        }
+    }
+
+    pub fn set_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+
+        self
+    }
+
+    // the first backend pass, variables are still there and have no home yet
+    pub fn select_instructions(&self) -> x64_def::X64Program {
+
+        use x64_def::*;
+
+        let mut blocks = BTreeMap::new();
+        // a variable used in more than one block still only gets one home
+        let mut vars: HashSet<IdString> = HashSet::new();
+
+        for (label, block) in &self.cprog.labels {
+
+            let mut blk_data = BlockData::default();
+
+            for stmt in &block.stmts {
+                self.handle_stmt(stmt, &mut blk_data);
+            }
+
+            self.select_instruction(
+                &block.tail,
+                &mut blk_data
+            );
+
+            blocks.insert(
+                *label,
+                Block {
+                    info: (),
+                    instr: blk_data.instr
+                }
+            );
+
+            vars.extend(blk_data.vars);
+        }
+
+        let handler = crate::idstr!(OVERFLOW_HANDLER);
+
+        if self.externals.borrow().contains(&handler) {
+            blocks.insert(
+                crate::idstr!(OVERFLOW_LABEL),
+                Block {
+                    info: (),
+                    instr: vec!(Instr::Call(handler, 0), Instr::Ret),
+                }
+            );
+        }
 
-        start.instr = fn_start;
+        // by name, like the labels
+        let mut vars: Vec<IdString> = vars.into_iter().collect();
+        vars.sort();
 
         X64Program {
             external: self.externals.take(),
-            vars: self.vars.to_owned(),
-            blocks: self.blocks.to_owned()
+            vars: vars.into_iter().map(|name| Home { name: name, loc: VarLoc::Undefined }).collect(),
+            blocks: blocks,
         }
     }
 }
@@ -3,9 +3,12 @@ use crate::frontend::parser::{Parser};
 use crate::frontend::uniquify::{uniquify_program};
 use crate::frontend::decomplify::{decomplify_program};
 use crate::ir::explicate::{explicate_control};
+use crate::utility::{test_backend_helper};
+use crate::pipeline::{PassManager, ProgramForm};
+use crate::types::{OptLevel, Overflow};
 
 use super::x64_def::*;
-use super::x64_backend::{IRToX64Transformer};
+use super::x64_emulator::{emulate};
 
 #[test]
 fn x64_ret_constant() {
@@ -16,12 +19,11 @@ fn x64_ret_constant() {
     .parse(); 
 
     let x64_asm =
-        IRToX64Transformer::new(
+        test_backend_helper(
             explicate_control(
                 decomplify_program(uniquify_program(ast))
             )
-        )
-        .transform();
+        );
 
     let start_label = crate::idstr!("start");
 
@@ -41,6 +43,8 @@ fn x64_ret_constant() {
     };
 
     assert_eq!(x64_asm, expected);
+
+    assert_eq!(emulate(&x64_asm, vec!()), Ok(2));
 }
 
 #[test]
@@ -52,12 +56,11 @@ fn x64_add_negate() {
     .parse(); 
 
     let x64_asm =
-        IRToX64Transformer::new(
+        test_backend_helper(
             explicate_control(
                 decomplify_program(uniquify_program(ast))
             )
-        )
-        .transform();
+        );
 
     let temp_var = crate::idstr!("tmp.0");
 
@@ -70,10 +73,10 @@ fn x64_add_negate() {
                 Instr::Push(Arg::Reg(Reg::Rbp)),
                 Instr::Mov64(Arg::Reg(Reg::Rbp), Arg::Reg(Reg::Rsp)),
                 Instr::Sub64(Arg::Reg(Reg::Rsp), Arg::Imm(8)),
-                Instr::Mov64(Arg::Var(temp_var.clone()), Arg::Imm(1)),
-                Instr::Neg64(Arg::Var(temp_var.clone())),
+                Instr::Mov64(Arg::Var(temp_var), Arg::Imm(1)),
+                Instr::Neg64(Arg::Var(temp_var)),
                 Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Imm(2)),
-                Instr::Add64(Arg::Reg(Reg::Rax), Arg::Var(temp_var.clone())),
+                Instr::Add64(Arg::Reg(Reg::Rax), Arg::Var(temp_var)),
                 Instr::Mov64(Arg::Reg(Reg::Rsp), Arg::Reg(Reg::Rbp)),
                 Instr::Pop(Arg::Reg(Reg::Rbp)),
                 Instr::Ret
@@ -89,6 +92,8 @@ fn x64_add_negate() {
     };
 
     assert_eq!(x64_asm, expected);
+
+    assert_eq!(emulate(&x64_asm, vec!()), Ok(1));
 }
 
 #[test]
@@ -106,12 +111,11 @@ fn x64_patch_instruction() {
     .parse(); 
 
     let x64_asm =
-        IRToX64Transformer::new(
+        test_backend_helper(
             explicate_control(
                 decomplify_program(uniquify_program(ast))
             )
-        )
-        .transform();
+        );
 
     let x_var = crate::idstr!("x.1");
     let y_var = crate::idstr!("y.2");
@@ -126,10 +130,10 @@ fn x64_patch_instruction() {
                 Instr::Push(Arg::Reg(Reg::Rbp)),
                 Instr::Mov64(Arg::Reg(Reg::Rbp), Arg::Reg(Reg::Rsp)),
                 Instr::Sub64(Arg::Reg(Reg::Rsp), Arg::Imm(16)),
-                Instr::Mov64(Arg::Var(x_var.clone()), Arg::Imm(42)),
-                Instr::Mov64(Arg::Reg(Reg::R15), Arg::Var(x_var.clone())),
-                Instr::Mov64(Arg::Var(y_var.clone()), Arg::Reg(Reg::R15)),
-                Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Var(y_var.clone())),
+                Instr::Mov64(Arg::Var(x_var), Arg::Imm(42)),
+                Instr::Mov64(Arg::Reg(Reg::R15), Arg::Var(x_var)),
+                Instr::Mov64(Arg::Var(y_var), Arg::Reg(Reg::R15)),
+                Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Var(y_var)),
                 Instr::Mov64(Arg::Reg(Reg::Rsp), Arg::Reg(Reg::Rbp)),
                 Instr::Pop(Arg::Reg(Reg::Rbp)),
                 Instr::Pop(Arg::Reg(Reg::R15)),
@@ -153,4 +157,101 @@ fn x64_patch_instruction() {
             assert_eq!(x64_block.instr[i], exp_block.instr[i]);
         }
     }
-}
\ No newline at end of file
+
+    // r15 was saved and restored around the patched instructions
+    assert_eq!(emulate(&x64_asm, vec!()), Ok(42));
+}
+
+#[test]
+fn x64_patch_add_instruction() {
+
+    // adding two variables also ends up with two memory operands
+    let ast =
+    Parser::new(
+        Lexer::new("(let ([x (read)]) (let ([y (read)]) (let ([z (+ x y)]) z)))")
+        .lex())
+    .parse();
+
+    let x64_asm =
+        test_backend_helper(
+            explicate_control(
+                decomplify_program(uniquify_program(ast))
+            )
+        );
+
+    let z_var = crate::idstr!("z.3");
+    let y_var = crate::idstr!("y.2");
+
+    let instr = &x64_asm.blocks[&crate::idstr!("start")].instr;
+
+    let add_at = instr.iter().position(|i| matches!(i, Instr::Add64(..))).unwrap();
+
+    assert_eq!(instr[add_at - 1], Instr::Mov64(Arg::Reg(Reg::R15), Arg::Var(y_var)));
+    assert_eq!(instr[add_at], Instr::Add64(Arg::Var(z_var), Arg::Reg(Reg::R15)));
+
+    assert_eq!(emulate(&x64_asm, vec!("30", "12")), Ok(42));
+}
+
+#[test]
+fn x64_patch_big_immediate() {
+
+    // only a mov into a register takes a 64 bit immediate
+    let ast =
+    Parser::new(
+        Lexer::new("(let ([x (read)]) (let ([y (+ x 4294967296)]) y))")
+        .lex())
+    .parse();
+
+    let x64_asm =
+        test_backend_helper(
+            explicate_control(
+                decomplify_program(uniquify_program(ast))
+            )
+        );
+
+    let instr = &x64_asm.blocks[&crate::idstr!("start")].instr;
+
+    let add_at = instr.iter().position(|i| matches!(i, Instr::Add64(..))).unwrap();
+
+    assert_eq!(instr[add_at - 1], Instr::Mov64(Arg::Reg(Reg::R15), Arg::Imm(4294967296)));
+    assert_eq!(instr[add_at], Instr::Add64(Arg::Var(crate::idstr!("y.2")), Arg::Reg(Reg::R15)));
+
+    assert_eq!(emulate(&x64_asm, vec!("-4294967254")), Ok(42));
+}
+
+#[test]
+fn x64_checked_overflow() {
+
+    let ast =
+    Parser::new(
+        Lexer::new("(let ([x (read)]) (- x))")
+        .lex())
+    .parse();
+
+    let x64_asm =
+        PassManager::for_level(OptLevel::O0)
+        .set_overflow(Overflow::Checked)
+        .run(ProgramForm::Ir(
+            explicate_control(
+                decomplify_program(uniquify_program(ast))
+            )
+        ))
+        .into_x64();
+
+    let overflow = crate::idstr!("overflow");
+
+    // every neg and add is followed by a jump to the handler
+    for block in x64_asm.blocks.values() {
+        for (i, instr) in block.instr.iter().enumerate() {
+            if let Instr::Neg64(_) | Instr::Add64(..) = instr {
+                assert_eq!(block.instr[i + 1], Instr::JmpIf(Cc::O, overflow));
+            }
+        }
+    }
+
+    assert!(x64_asm.external.contains(&crate::idstr!("overflow_error")));
+    assert_eq!(x64_asm.blocks[&overflow].instr[0], Instr::Call(crate::idstr!("overflow_error"), 0));
+
+    assert_eq!(emulate(&x64_asm, vec!("-42")), Ok(42));
+    assert_eq!(emulate(&x64_asm, vec!("-9223372036854775808")).map_err(|e| e.contains("integer overflow")), Err(true));
+}
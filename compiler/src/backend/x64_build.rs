@@ -5,6 +5,8 @@ use std::fs::{File, create_dir_all};
 use std::env;
 use std::io::prelude::*;
 use std::process::{Command};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use std::collections::HashMap;
 
@@ -43,11 +45,76 @@ static KERNEL32: &'static [u8] = include_bytes!("bin_include/win64/kernel32.lib"
 #[cfg(target_os = "windows")]
 static UUID: &'static [u8] = include_bytes!("bin_include/win64/uuid.lib");
 
+// LINUX:
+// nasm and a c compiler have to be installed, the runtime is compiled together with the program
+
+#[cfg(target_os = "linux")]
+static LINUX_RUNTIME: &str = include_str!("bin_include/linux/runtime.c");
+
+// can the program be started at all
+fn tool_exists(name: &str) -> bool {
+    Command::new(name).arg("-v").output().is_ok()
+}
+
+// run one of the build tools, the error is whatever it complained about
+fn run_tool(name: &str, args: &[&str]) -> Result<(), String> {
+    let output =
+        Command::new(name)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to call {}: {}", name, e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed:\n{}", name, String::from_utf8_lossy(&output.stderr)))
+    }
+}
+
+// the tools needed to build an executable, i.e. whether native code can be run here
+#[cfg(target_os = "linux")]
+pub fn native_toolchain() -> Result<(), String> {
+    for tool in &["nasm", "cc"] {
+        if !tool_exists(tool) {
+            return Err(format!("'{}' was not found", tool));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn native_toolchain() -> Result<(), String> {
+    Err("running native code is only supported on linux".to_owned())
+}
+
 pub struct X64Builder {
     filename: String,
     content: String,
 }
 
+// every build gets a folder of its own, tests build at the same time
+static NEXT_BUILD: AtomicUsize = AtomicUsize::new(0);
+
+// a built executable, its folder and everything the build left in it are
+// removed when it is dropped
+pub struct Executable {
+    folder: PathBuf,
+    path: PathBuf,
+}
+
+impl Executable {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Executable {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.folder);
+    }
+}
+
 impl X64Builder {
 
     #[cfg(target_os = "windows")]
@@ -185,4 +252,52 @@ impl X64Builder {
 
         env::set_current_dir(previous_working_dir).unwrap();
     }
+
+    // builds the executable in a new folder under the temp folder
+    #[cfg(target_os = "linux")]
+    pub fn build(&self) -> Result<Executable, String> {
+
+        native_toolchain()?;
+
+        let base_folder = temp_dir().join(format!(
+            "rustcomp_{}_{}",
+            std::process::id(),
+            NEXT_BUILD.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        create_dir_all(base_folder.clone()).map_err(|e| e.to_string())?;
+
+        let file_path = base_folder.join(self.filename.clone());
+
+        // from here on a failed build cleans up after itself as well
+        let executable = Executable {
+            folder: base_folder.clone(),
+            path: file_path.with_extension("out"),
+        };
+
+        let asm_file_path = file_path.with_extension("asm");
+        let obj_file_path = file_path.with_extension("o");
+        let runtime_path = base_folder.join("runtime.c");
+
+        std::fs::write(&asm_file_path, &self.content).map_err(|e| e.to_string())?;
+        std::fs::write(&runtime_path, LINUX_RUNTIME).map_err(|e| e.to_string())?;
+
+        run_tool("nasm", &[
+            "-f",
+            "elf64",
+            asm_file_path.to_str().unwrap(),
+            "-o",
+            obj_file_path.to_str().unwrap(),
+        ])?;
+
+        run_tool("cc", &[
+            "-no-pie",
+            obj_file_path.to_str().unwrap(),
+            runtime_path.to_str().unwrap(),
+            "-o",
+            executable.path.to_str().unwrap(),
+        ])?;
+
+        Ok(executable)
+    }
 }
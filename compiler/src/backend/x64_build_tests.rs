@@ -5,9 +5,9 @@ use crate::frontend::parser::{Parser};
 use crate::frontend::uniquify::{uniquify_program};
 use crate::frontend::decomplify::{decomplify_program};
 use crate::ir::explicate::{explicate_control};
+use crate::utility::{test_backend_helper};
 
 use super::x64_def::*;
-use super::x64_backend::{IRToX64Transformer};
 use super::x64_print::{X64Printer};
 use super::x64_build::{X64Builder};
 
@@ -20,12 +20,11 @@ fn x64_build_constant() {
     .parse(); 
 
     let x64_asm =
-        IRToX64Transformer::new(
+        test_backend_helper(
             explicate_control(
                 decomplify_program(uniquify_program(ast))
             )
-        )
-        .transform();
+        );
 
     let asm_text = X64Printer::new(x64_asm).print();
     
@@ -42,12 +41,11 @@ fn x64_build_add_two_read() {
     .parse(); 
 
     let x64_asm =
-        IRToX64Transformer::new(
+        test_backend_helper(
             explicate_control(
                 decomplify_program(uniquify_program(ast))
             )
-        )
-        .transform();
+        );
 
     let asm_text = X64Printer::new(x64_asm).print();
     
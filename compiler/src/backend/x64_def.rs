@@ -3,13 +3,12 @@
 
 #![allow(dead_code)]
 
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 
 use crate::types::{IdString};
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Reg {
     Rsp, Rbp, Rax, Rbx,
     Rcx, Rdx, Rsi, Rdi,
@@ -19,13 +18,13 @@ pub enum Reg {
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Arg {
-    Var(Rc<String>), // for the first pass where variables are still present
+    Var(IdString), // for the first pass where variables are still present
     Imm(i64),
     Reg(Reg),
     Deref(Reg, i64),
 }
 
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum VarLoc {
     // a variable can live in either
     Reg(Reg), // a register or
@@ -33,12 +32,20 @@ pub enum VarLoc {
     Undefined, // initial value
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Home {
     pub name: IdString,
     pub loc: VarLoc,
 }
 
+// condition codes for the conditional jumps
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Cc {
+    E,  // equal
+    Ne, // not equal
+    O,  // overflow
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Instr {
     Add64(Arg, Arg),
@@ -50,6 +57,8 @@ pub enum Instr {
     Push(Arg),
     Pop(Arg),
     Jmp(IdString),
+    Cmp64(Arg, Arg),
+    JmpIf(Cc, IdString),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -60,7 +69,8 @@ pub struct Block {
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct X64Program {
-    pub external: HashSet<IdString>,
+    // ordered collections, so the same program is always printed the same way
+    pub external: BTreeSet<IdString>,
     pub vars: Vec<Home>, // vars that have a defined home (stack or register)
-    pub blocks: HashMap<IdString, Block>,
+    pub blocks: BTreeMap<IdString, Block>,
 }
@@ -0,0 +1,458 @@
+// an emulator for x64 programs, so the backend can be tested without an assembler
+//
+// it runs the output of any of the backend passes:
+// - before homes are assigned, every Arg::Var is kept in a variable store
+// - once a variable has a home, it lives in that register or at its offset from rbp
+//
+// the machine has the 16 registers, a stack of qwords, and the flags set by
+// add, sub, neg and cmp. 'start' is entered like a function the runtime called:
+// the return address is on the stack and the callee saved registers hold values
+// that have to be there again when it returns. the code before prologue-epilogue
+// hasn't saved them yet, so that check can be turned off
+//
+// calls can only go to the runtime stubs in RUNTIME_STUBS, they follow the calling
+// convention: the result is in rax and the other caller saved registers are garbage
+// afterwards. reading a register or memory that was never written is an error,
+// which catches values that were expected to survive a call
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use runtime::types::{RuntimeI64, RuntimeValue};
+
+use crate::types::{IdString, OVERFLOW_ERROR};
+use crate::interpreter::{Interpretable, InterpretResult, CachedRuntimeCall, Limits, DEFAULT_FUEL};
+
+use super::x64_def::*;
+
+// where the stack starts, and what's on it when start is entered. before
+// prologue-epilogue the homes are below the caller's rbp, which is far enough
+// above the stack that even thousands of them don't reach the return address
+const STACK_TOP: i64 = 0x7fff_0000;
+const RETURN_ADDRESS: i64 = 0x0040_1000;
+const CALLER_RBP: i64 = 0x7fff_0000_1000;
+
+// callee saved in both the windows and the system v abi
+const CALLEE_SAVED: [Reg; 6] = [Reg::Rbx, Reg::Rbp, Reg::R12, Reg::R13, Reg::R14, Reg::R15];
+
+const CALLER_SAVED: [Reg; 9] = [
+    Reg::Rax, Reg::Rcx, Reg::Rdx, Reg::Rsi, Reg::Rdi,
+    Reg::R8, Reg::R9, Reg::R10, Reg::R11,
+];
+
+pub type StubFn = fn(&mut X64Emulator) -> Result<(), String>;
+
+pub struct RuntimeStub {
+    pub name: &'static str,
+    pub run: StubFn,
+}
+
+pub const RUNTIME_STUBS: &[RuntimeStub] = &[
+    RuntimeStub { name: "read_int", run: read_int },
+    RuntimeStub { name: "overflow_error", run: overflow_error },
+];
+
+// where the compiled code goes when an overflow is checked, it doesn't return
+fn overflow_error(_: &mut X64Emulator) -> Result<(), String> {
+    Err(OVERFLOW_ERROR.to_owned())
+}
+
+fn read_int(emu: &mut X64Emulator) -> Result<(), String> {
+    let input = emu.crc.read_line();
+
+    match input.trim().parse::<RuntimeI64>() {
+        Ok(n) => {
+            emu.crc.set_cached_result_of(crate::symbol::READ, RuntimeValue::RuntimeI64(n));
+            emu.set_reg(Reg::Rax, n);
+
+            Ok(())
+        },
+
+        Err(error) => Err(format!("read_int: {}", error)),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Flags {
+    pub zf: bool,
+    pub sf: bool,
+    pub of: bool,
+    pub cf: bool,
+}
+
+// the callee saved registers start out as something recognizable
+fn callee_saved_value(reg: Reg) -> i64 {
+    match reg {
+        Reg::Rbp => CALLER_RBP,
+        _ => 0x5a5a_0000 + reg as i64,
+    }
+}
+
+pub struct X64Emulator<'a> {
+    program: &'a X64Program,
+    crc: &'a mut CachedRuntimeCall,
+    homes: HashMap<IdString, VarLoc>,
+    regs: [Option<i64>; 16],
+    memory: HashMap<i64, i64>, // qwords by address
+    vars: HashMap<IdString, i64>, // variables without a home
+    flags: Flags,
+    fuel: usize,
+    steps: usize,
+    out_of_fuel: bool,
+    abi_checks: bool,
+    errors: Vec<String>,
+}
+
+impl<'a> X64Emulator<'a> {
+
+    pub fn new(program: &'a X64Program, crc: &'a mut CachedRuntimeCall) -> Self {
+        let homes =
+            program.vars
+            .iter()
+            .filter(|home| home.loc != VarLoc::Undefined)
+            .map(|home| (home.name, home.loc))
+            .collect();
+
+        X64Emulator {
+            program: program,
+            crc: crc,
+            homes: homes,
+            regs: [None; 16],
+            memory: HashMap::new(),
+            vars: HashMap::new(),
+            flags: Flags::default(),
+            fuel: DEFAULT_FUEL,
+            steps: 0,
+            out_of_fuel: false,
+            abi_checks: true,
+            errors: vec!(),
+        }
+    }
+
+    pub fn set_fuel(mut self, fuel: usize) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    // calls only go to the runtime and jumps don't nest, so only the fuel applies
+    pub fn set_limits(self, limits: Limits) -> Self {
+        self.set_fuel(limits.fuel)
+    }
+
+    // check that the callee saved registers are restored when start returns
+    pub fn set_abi_checks(mut self, abi_checks: bool) -> Self {
+        self.abi_checks = abi_checks;
+        self
+    }
+
+    // how many instructions the last run executed
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    pub fn reg(&self, reg: Reg) -> Result<i64, String> {
+        match self.regs[reg as usize] {
+            Some(value) => Ok(value),
+            None => Err(format!("read of {:?} before it was written", reg)),
+        }
+    }
+
+    fn set_reg(&mut self, reg: Reg, value: i64) {
+        self.regs[reg as usize] = Some(value);
+    }
+
+    fn load(&self, address: i64) -> Result<i64, String> {
+        match self.memory.get(&address) {
+            Some(value) => Ok(*value),
+            None => Err(format!("read of uninitialized memory at {:#x}", address)),
+        }
+    }
+
+    fn store(&mut self, address: i64, value: i64) {
+        self.memory.insert(address, value);
+    }
+
+    fn read(&self, arg: &Arg) -> Result<i64, String> {
+        match arg {
+            Arg::Imm(n) => Ok(*n),
+
+            Arg::Reg(reg) => self.reg(*reg),
+
+            Arg::Deref(reg, offset) => self.load(self.reg(*reg)? + offset),
+
+            Arg::Var(name) => match self.homes.get(name) {
+                Some(VarLoc::Reg(reg)) => self.reg(*reg),
+                Some(VarLoc::Rbp(offset)) => self.load(self.reg(Reg::Rbp)? - offset),
+                _ => match self.vars.get(name) {
+                    Some(value) => Ok(*value),
+                    None => Err(format!("read of '{}' before it was written", name)),
+                },
+            },
+        }
+    }
+
+    fn write(&mut self, arg: &Arg, value: i64) -> Result<(), String> {
+        match arg {
+            Arg::Imm(n) => return Err(format!("can't write to the immediate {}", n)),
+
+            Arg::Reg(reg) => self.set_reg(*reg, value),
+
+            Arg::Deref(reg, offset) => {
+                let address = self.reg(*reg)? + offset;
+                self.store(address, value);
+            },
+
+            Arg::Var(name) => match self.homes.get(name).cloned() {
+                Some(VarLoc::Reg(reg)) => self.set_reg(reg, value),
+                Some(VarLoc::Rbp(offset)) => {
+                    let address = self.reg(Reg::Rbp)? - offset;
+                    self.store(address, value);
+                },
+                _ => {
+                    self.vars.insert(*name, value);
+                },
+            },
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, value: i64) -> Result<(), String> {
+        let rsp = self.reg(Reg::Rsp)? - 8;
+
+        self.set_reg(Reg::Rsp, rsp);
+        self.store(rsp, value);
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<i64, String> {
+        let rsp = self.reg(Reg::Rsp)?;
+        let value = self.load(rsp)?;
+
+        self.set_reg(Reg::Rsp, rsp + 8);
+
+        Ok(value)
+    }
+
+    fn set_flags(&mut self, result: i64, overflow: bool, carry: bool) {
+        self.flags = Flags {
+            zf: result == 0,
+            sf: result < 0,
+            of: overflow,
+            cf: carry,
+        };
+    }
+
+    fn condition(&self, cc: &Cc) -> bool {
+        match cc {
+            Cc::E => self.flags.zf,
+            Cc::Ne => !self.flags.zf,
+            Cc::O => self.flags.of,
+        }
+    }
+
+    fn call(&mut self, name: &IdString) -> Result<(), String> {
+        if !self.program.external.contains(name) {
+            return Err(format!("call to '{}' which isn't external", name));
+        }
+
+        let stub = match RUNTIME_STUBS.iter().find(|stub| *name == stub.name) {
+            Some(stub) => stub,
+            None => return Err(format!("no runtime stub for '{}'", name)),
+        };
+
+        for reg in CALLER_SAVED {
+            self.regs[reg as usize] = None;
+        }
+
+        (stub.run)(self)
+    }
+
+    // the value returned from start, once it returns to the runtime
+    fn returned(&mut self) -> Result<i64, String> {
+        for reg in CALLEE_SAVED {
+            if self.abi_checks && self.regs[reg as usize] != Some(callee_saved_value(reg)) {
+                return Err(format!("{:?} wasn't restored before returning", reg));
+            }
+        }
+
+        if self.reg(Reg::Rsp)? != STACK_TOP + 8 {
+            return Err("the stack isn't balanced when returning".to_owned());
+        }
+
+        self.reg(Reg::Rax)
+    }
+
+    // Ok(Some(label)) to continue at the start of another block
+    // Ok(None) to carry on with the next instruction
+    // Err(..) when the program stops, either with its result or an error
+    fn step(&mut self, instr: &Instr) -> Result<Option<IdString>, Result<i64, String>> {
+        match instr {
+            Instr::Mov64(dest, src) => {
+                let value = self.read(src).map_err(Err)?;
+                self.write(dest, value).map_err(Err)?;
+            },
+
+            Instr::Add64(dest, src) => {
+                let a = self.read(dest).map_err(Err)?;
+                let b = self.read(src).map_err(Err)?;
+
+                let (result, overflow) = a.overflowing_add(b);
+                let (_, carry) = (a as u64).overflowing_add(b as u64);
+
+                self.write(dest, result).map_err(Err)?;
+                self.set_flags(result, overflow, carry);
+            },
+
+            Instr::Sub64(dest, src) => {
+                let a = self.read(dest).map_err(Err)?;
+                let b = self.read(src).map_err(Err)?;
+
+                let (result, overflow) = a.overflowing_sub(b);
+
+                self.write(dest, result).map_err(Err)?;
+                self.set_flags(result, overflow, (a as u64) < (b as u64));
+            },
+
+            Instr::Cmp64(a, b) => {
+                let a = self.read(a).map_err(Err)?;
+                let b = self.read(b).map_err(Err)?;
+
+                let (result, overflow) = a.overflowing_sub(b);
+
+                self.set_flags(result, overflow, (a as u64) < (b as u64));
+            },
+
+            Instr::Neg64(arg) => {
+                let a = self.read(arg).map_err(Err)?;
+
+                let (result, overflow) = a.overflowing_neg();
+
+                self.write(arg, result).map_err(Err)?;
+                self.set_flags(result, overflow, a != 0);
+            },
+
+            Instr::Push(arg) => {
+                let value = self.read(arg).map_err(Err)?;
+                self.push(value).map_err(Err)?;
+            },
+
+            Instr::Pop(arg) => {
+                let value = self.pop().map_err(Err)?;
+                self.write(arg, value).map_err(Err)?;
+            },
+
+            Instr::Call(name, _) => {
+                self.call(name).map_err(Err)?;
+            },
+
+            Instr::Ret => {
+                let address = self.pop().map_err(Err)?;
+
+                if address != RETURN_ADDRESS {
+                    return Err(Err(format!("ret to {:#x}, which isn't the return address", address)));
+                }
+
+                return Err(self.returned());
+            },
+
+            Instr::Jmp(label) => {
+                return Ok(Some(*label));
+            },
+
+            Instr::JmpIf(cc, label) => {
+                if self.condition(cc) {
+                    return Ok(Some(*label));
+                }
+            },
+        }
+
+        Ok(None)
+    }
+
+    pub fn run(&mut self) -> Result<i64, String> {
+        self.regs = [None; 16];
+        self.memory.clear();
+        self.vars.clear();
+        self.steps = 0;
+        self.out_of_fuel = false;
+
+        for reg in CALLEE_SAVED {
+            self.set_reg(reg, callee_saved_value(reg));
+        }
+
+        self.set_reg(Reg::Rsp, STACK_TOP + 8);
+        self.push(RETURN_ADDRESS)?;
+
+        let program = self.program;
+
+        let mut label = crate::idstr!("start");
+
+        loop {
+            let block = match program.blocks.get(&label) {
+                Some(block) => block,
+                None => return Err(format!("jump to unknown label '{}'", label)),
+            };
+
+            let mut next = None;
+
+            for instr in &block.instr {
+                if self.steps >= self.fuel {
+                    self.out_of_fuel = true;
+                    return Err(format!("ran out of fuel after {} instructions", self.steps));
+                }
+
+                self.steps += 1;
+
+                match self.step(instr) {
+                    Ok(None) => {},
+                    Ok(Some(target)) => {
+                        next = Some(target);
+                        break;
+                    },
+                    Err(result) => {
+                        return result.map_err(|e| format!("{}: {:?}: {}", label, instr, e));
+                    },
+                }
+            }
+
+            match next {
+                Some(target) => label = target,
+                None => return Err(format!("fell off the end of block '{}'", label)),
+            }
+        }
+    }
+}
+
+impl<'a> Interpretable for X64Emulator<'a> {
+    fn interpret(&mut self) -> InterpretResult {
+        let value = match self.run() {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.errors.push(e);
+                None
+            }
+        };
+
+        InterpretResult {
+            value: value,
+            had_error: !self.errors.is_empty(),
+            errors: self.errors.clone(),
+            out_of_fuel: self.out_of_fuel,
+            too_deep: false, // a jump doesn't nest here, there is no depth to limit
+        }
+    }
+}
+
+// returns the result of running 'program' with 'input' for read_int
+pub fn emulate(program: &X64Program, input: Vec<&str>) -> Result<i64, String> {
+    let mut crc = CachedRuntimeCall::new().set_input(input);
+
+    X64Emulator::new(program, &mut crc).run()
+}
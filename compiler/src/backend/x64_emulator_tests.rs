@@ -0,0 +1,167 @@
+use crate::utility::{test_parse_helper};
+use crate::interpreter::{CachedRuntimeCall, Interpreter};
+use crate::pipeline::{PassManager, ProgramForm};
+
+use super::x64_def::*;
+use super::x64_emulator::*;
+
+fn label(name: &'static str) -> crate::types::IdString {
+    crate::idstr!(name)
+}
+
+fn program(blocks: Vec<(&'static str, Vec<Instr>)>) -> X64Program {
+    X64Program {
+        external: crate::set!(label("read_int")),
+        vars: vec!(),
+        blocks: blocks.into_iter().map(|(l, instr)| (label(l), Block { info: (), instr: instr })).collect(),
+    }
+}
+
+fn rax(n: i64) -> Instr {
+    Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Imm(n))
+}
+
+#[test]
+fn emulator_every_backend_pass() {
+    // the output of each backend pass on its own gives the same result
+    let passes = [
+        "uniquify", "decomplify", "explicate-control",
+        "select-instructions", "assign-homes", "patch-instructions", "peephole", "prologue-epilogue",
+    ];
+
+    let source = "(let ([x (read)]) (let ([y (+ x (- (read)))]) (let ([z (+ y x)]) (+ z (read)))))";
+
+    for until in 4..=passes.len() {
+        let p = PassManager::custom(&passes[..until]).unwrap()
+            .run(ProgramForm::Ast(test_parse_helper(source)))
+            .into_x64();
+
+        let mut crc = CachedRuntimeCall::new().set_input(vec!("10", "3", "100"));
+
+        // the callee saved registers are only taken care of by the last pass
+        let result = {
+            let mut emulator = X64Emulator::new(&p, &mut crc).set_abi_checks(until == passes.len());
+            Interpreter::new(&mut emulator).run()
+        };
+
+        assert_eq!(result.value, Some(117), "after {}: {:?}", passes[until - 1], result.errors);
+        assert_eq!(crc.cached_results_of(crate::idstr!("read")).len(), 3);
+    }
+}
+
+#[test]
+fn emulator_branches_on_flags() {
+    let p = program(vec!(
+        ("start", vec!(
+            Instr::Call(label("read_int"), 0),
+            Instr::Cmp64(Arg::Reg(Reg::Rax), Arg::Imm(0)),
+            Instr::JmpIf(Cc::E, label("zero")),
+            Instr::Jmp(label("other")),
+        )),
+        ("zero", vec!(rax(10), Instr::Ret)),
+        ("other", vec!(rax(20), Instr::Ret)),
+    ));
+
+    assert_eq!(emulate(&p, vec!("0")), Ok(10));
+    assert_eq!(emulate(&p, vec!("5")), Ok(20));
+}
+
+#[test]
+fn emulator_stack() {
+    let p = program(vec!(
+        ("start", vec!(
+            Instr::Push(Arg::Reg(Reg::Rbp)),
+            Instr::Mov64(Arg::Reg(Reg::Rbp), Arg::Reg(Reg::Rsp)),
+            Instr::Sub64(Arg::Reg(Reg::Rsp), Arg::Imm(16)),
+            Instr::Mov64(Arg::Deref(Reg::Rbp, -8), Arg::Imm(40)),
+            Instr::Push(Arg::Imm(2)),
+            Instr::Pop(Arg::Reg(Reg::Rax)),
+            Instr::Add64(Arg::Reg(Reg::Rax), Arg::Deref(Reg::Rbp, -8)),
+            Instr::Mov64(Arg::Reg(Reg::Rsp), Arg::Reg(Reg::Rbp)),
+            Instr::Pop(Arg::Reg(Reg::Rbp)),
+            Instr::Ret,
+        )),
+    ));
+
+    assert_eq!(emulate(&p, vec!()), Ok(42));
+}
+
+#[test]
+fn emulator_unbalanced_stack() {
+    let p = program(vec!(("start", vec!(Instr::Push(Arg::Imm(1)), rax(1), Instr::Ret))));
+
+    assert!(emulate(&p, vec!()).unwrap_err().contains("isn't the return address"));
+}
+
+#[test]
+fn emulator_callee_saved() {
+    let p = program(vec!(("start", vec!(Instr::Mov64(Arg::Reg(Reg::R15), Arg::Imm(1)), rax(1), Instr::Ret))));
+
+    assert!(emulate(&p, vec!()).unwrap_err().contains("R15 wasn't restored"));
+
+    let mut crc = CachedRuntimeCall::new();
+
+    assert_eq!(X64Emulator::new(&p, &mut crc).set_abi_checks(false).run(), Ok(1));
+}
+
+#[test]
+fn emulator_caller_saved_are_clobbered() {
+    let p = program(vec!(
+        ("start", vec!(
+            Instr::Mov64(Arg::Reg(Reg::Rcx), Arg::Imm(1)),
+            Instr::Call(label("read_int"), 0),
+            Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Reg(Reg::Rcx)),
+            Instr::Ret,
+        )),
+    ));
+
+    assert!(emulate(&p, vec!("5")).unwrap_err().contains("read of Rcx before it was written"));
+}
+
+#[test]
+fn emulator_uninitialized() {
+    let x = label("x");
+
+    let p = program(vec!(("start", vec!(Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Var(x)), Instr::Ret))));
+
+    assert!(emulate(&p, vec!()).unwrap_err().contains("read of 'x' before it was written"));
+
+    let p = program(vec!(("start", vec!(Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Deref(Reg::Rsp, -8)), Instr::Ret))));
+
+    assert!(emulate(&p, vec!()).unwrap_err().contains("uninitialized memory"));
+}
+
+#[test]
+fn emulator_bad_control_flow() {
+    let p = program(vec!(("start", vec!(rax(1)))));
+
+    assert_eq!(emulate(&p, vec!()), Err("fell off the end of block 'start'".to_owned()));
+
+    let p = program(vec!(("start", vec!(Instr::Jmp(label("start"))))));
+
+    let mut crc = CachedRuntimeCall::new();
+
+    assert_eq!(
+        X64Emulator::new(&p, &mut crc).set_fuel(100).run(),
+        Err("ran out of fuel after 100 instructions".to_owned())
+    );
+
+    let mut p = program(vec!(("start", vec!(Instr::Call(label("print_int"), 0), Instr::Ret))));
+
+    assert!(emulate(&p, vec!()).unwrap_err().contains("isn't external"));
+
+    p.external.insert(label("print_int"));
+
+    assert!(emulate(&p, vec!()).unwrap_err().contains("no runtime stub for 'print_int'"));
+}
+
+#[test]
+fn emulator_flags() {
+    let p = program(vec!(("start", vec!(rax(i64::MAX), Instr::Add64(Arg::Reg(Reg::Rax), Arg::Imm(1)), Instr::Ret))));
+
+    let mut crc = CachedRuntimeCall::new();
+    let mut emulator = X64Emulator::new(&p, &mut crc);
+
+    assert_eq!(emulator.run(), Ok(i64::MIN));
+    assert_eq!(emulator.flags(), Flags { zf: false, sf: true, of: true, cf: false });
+}
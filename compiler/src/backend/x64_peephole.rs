@@ -0,0 +1,398 @@
+// peephole optimization of the selected x64 instructions
+//
+// the instruction selection is a very literal translation of the ir, so it
+// leaves behind obvious waste, e.g.
+//
+//      mov x, y        where x is never used again, becomes
+//      neg x                   mov rax, y
+//      mov rax, x              neg rax
+//
+// every rewrite is a rule in the RULES table, each rule looks at a small window
+// of instructions starting at some index and either declines or returns the
+// replacement for the instructions it consumed.
+// the rules are applied until none of them fire anymore
+
+#![allow(dead_code)]
+
+use super::x64_def::*;
+
+pub type RewriteFn = fn(&[Instr], usize) -> Option<(usize, Vec<Instr>)>;
+
+pub struct PeepholeRule {
+    pub name: &'static str,
+    pub rewrite: RewriteFn,
+}
+
+pub const RULES: &[PeepholeRule] = &[
+    PeepholeRule { name: "self-move", rewrite: self_move },
+    PeepholeRule { name: "add-zero", rewrite: add_zero },
+    PeepholeRule { name: "move-back", rewrite: move_back },
+    PeepholeRule { name: "forward-constant", rewrite: forward_constant },
+    PeepholeRule { name: "compute-in-destination", rewrite: compute_in_destination },
+];
+
+pub fn find_rule(name: &str) -> Option<&'static PeepholeRule> {
+    RULES.iter().find(|rule| rule.name == name)
+}
+
+// how many times each rule rewrote something
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeepholeStats {
+    counts: Vec<(&'static str, usize)>,
+}
+
+impl PeepholeStats {
+
+    pub fn new() -> Self {
+        PeepholeStats {
+            counts: vec!(),
+        }
+    }
+
+    fn record(&mut self, rule: &'static str) {
+        match self.counts.iter_mut().find(|c| c.0 == rule) {
+            Some(count) => {
+                count.1 += 1;
+            },
+
+            None => {
+                self.counts.push((rule, 1));
+            }
+        }
+    }
+
+    pub fn count(&self, rule: &str) -> usize {
+        match self.counts.iter().find(|c| c.0 == rule) {
+            Some(count) => count.1,
+            None => 0,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|c| c.1).sum()
+    }
+
+    pub fn counts(&self) -> &Vec<(&'static str, usize)> {
+        &self.counts
+    }
+}
+
+impl std::fmt::Display for PeepholeStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rule in RULES {
+            writeln!(f, "{}: {}", rule.name, self.count(rule.name))?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn optimize_block(instr: Vec<Instr>, rules: &[PeepholeRule], stats: &mut PeepholeStats) -> Vec<Instr> {
+
+    let mut instr = instr;
+
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        let mut i = 0;
+
+        while i < instr.len() {
+
+            let mut fired = false;
+
+            for rule in rules {
+                if let Some((consumed, replacement)) = (rule.rewrite)(&instr, i) {
+                    instr.splice(i..i + consumed, replacement);
+                    stats.record(rule.name);
+
+                    fired = true;
+                    changed = true;
+                    break;
+                }
+            }
+
+            // a rewrite might have created a new opportunity with the instruction
+            // right before it, otherwise move on
+            if fired {
+                i = i.saturating_sub(1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    instr
+}
+
+fn is_memory(arg: &Arg) -> bool {
+    matches!(arg, Arg::Var(_) | Arg::Deref(..))
+}
+
+pub fn fits_in_imm32(n: i64) -> bool {
+    n >= i32::MIN as i64 && n <= i32::MAX as i64
+}
+
+// could writing to one of the arguments change the value of the other?
+// variables might end up on the stack, so they are assumed to alias any dereference
+fn may_alias(a: &Arg, b: &Arg) -> bool {
+    match (a, b) {
+        (Arg::Imm(_), _) | (_, Arg::Imm(_)) => false,
+        (Arg::Deref(..), Arg::Var(_)) | (Arg::Var(_), Arg::Deref(..)) => true,
+        (Arg::Deref(..), Arg::Deref(..)) => true,
+        _ => a == b,
+    }
+}
+
+// does evaluating 'operand' read 'arg'
+fn operand_reads(operand: &Arg, arg: &Arg) -> bool {
+    if may_alias(operand, arg) {
+        return true;
+    }
+
+    match (operand, arg) {
+        (Arg::Deref(reg, _), Arg::Reg(r)) => reg == r,
+        _ => false,
+    }
+}
+
+fn is_caller_saved(reg: &Reg) -> bool {
+    matches!(
+        reg,
+        Reg::Rax | Reg::Rcx | Reg::Rdx |
+        Reg::R8 | Reg::R9 | Reg::R10 | Reg::R11
+    )
+}
+
+enum Usage {
+    Read,
+    Written,
+    Untouched,
+}
+
+fn usage(instr: &Instr, arg: &Arg) -> Usage {
+    match instr {
+        Instr::Add64(dest, src) | Instr::Sub64(dest, src) => {
+            if operand_reads(dest, arg) || operand_reads(src, arg) {
+                Usage::Read
+            } else {
+                Usage::Untouched
+            }
+        },
+
+        Instr::Mov64(dest, src) => {
+            if operand_reads(src, arg) {
+                Usage::Read
+            } else if dest == arg {
+                Usage::Written
+            } else if let Arg::Deref(..) = dest {
+                // the address of the destination is read
+                if operand_reads(dest, arg) && !may_alias(dest, arg) {
+                    Usage::Read
+                } else {
+                    Usage::Untouched
+                }
+            } else {
+                Usage::Untouched
+            }
+        },
+
+        Instr::Neg64(a) | Instr::Push(a) => {
+            if operand_reads(a, arg) || *arg == Arg::Reg(Reg::Rsp) {
+                Usage::Read
+            } else {
+                Usage::Untouched
+            }
+        },
+
+        Instr::Pop(a) => {
+            if *arg == Arg::Reg(Reg::Rsp) {
+                Usage::Read
+            } else if a == arg {
+                Usage::Written
+            } else {
+                Usage::Untouched
+            }
+        },
+
+        Instr::Call(..) => {
+            match arg {
+                Arg::Reg(reg) if is_caller_saved(reg) => Usage::Written,
+                Arg::Reg(Reg::Rsp) => Usage::Read,
+                _ => Usage::Untouched,
+            }
+        },
+
+        Instr::Ret => {
+            match arg {
+                Arg::Reg(Reg::Rax) | Arg::Reg(Reg::Rsp) => Usage::Read,
+                _ => Usage::Untouched,
+            }
+        },
+
+        Instr::Cmp64(a, b) => {
+            if operand_reads(a, arg) || operand_reads(b, arg) {
+                Usage::Read
+            } else {
+                Usage::Untouched
+            }
+        },
+
+        // whatever is on the other side of the jump might need the value
+        Instr::Jmp(_) | Instr::JmpIf(..) => Usage::Read,
+    }
+}
+
+// is the value of 'arg' never read again from 'from' onwards
+//
+// blocks end in a ret or a jump, which are handled above, so falling off the end only
+// happens for partial sequences: be conservative about rax (the result) and the frame registers
+pub fn is_dead_after(instr: &[Instr], from: usize, arg: &Arg) -> bool {
+    for instruction in instr.iter().skip(from) {
+        match usage(instruction, arg) {
+            Usage::Read => return false,
+            Usage::Written => return true,
+            Usage::Untouched => {},
+        }
+    }
+
+    !matches!(
+        arg,
+        Arg::Reg(Reg::Rax) | Arg::Reg(Reg::Rsp) | Arg::Reg(Reg::Rbp) | Arg::Deref(..)
+    )
+}
+
+// mov a, a
+fn self_move(instr: &[Instr], at: usize) -> Option<(usize, Vec<Instr>)> {
+    match &instr[at] {
+        Instr::Mov64(dest, src) if dest == src => Some((1, vec!())),
+        _ => None,
+    }
+}
+
+// add a, 0 | sub a, 0
+// when overflow is checked the jo after it goes as well, it would test the flags
+// of whatever came before. adding 0 can't overflow anyway
+fn add_zero(instr: &[Instr], at: usize) -> Option<(usize, Vec<Instr>)> {
+    match &instr[at] {
+        Instr::Add64(_, Arg::Imm(0)) | Instr::Sub64(_, Arg::Imm(0)) => {
+            match instr.get(at + 1) {
+                Some(Instr::JmpIf(Cc::O, _)) => Some((2, vec!())),
+                _ => Some((1, vec!())),
+            }
+        },
+
+        _ => None,
+    }
+}
+
+// mov a, b
+// mov b, a     <- b already holds the value of a
+fn move_back(instr: &[Instr], at: usize) -> Option<(usize, Vec<Instr>)> {
+    if at + 1 >= instr.len() {
+        return None;
+    }
+
+    match (&instr[at], &instr[at + 1]) {
+        (Instr::Mov64(a, b), Instr::Mov64(c, d)) if a == d && b == c => {
+            Some((2, vec!(instr[at].clone())))
+        },
+
+        _ => None,
+    }
+}
+
+// mov t, 42
+// mov d, t     where t is dead afterwards
+//
+// becomes
+//
+// mov d, 42
+fn forward_constant(instr: &[Instr], at: usize) -> Option<(usize, Vec<Instr>)> {
+    if at + 1 >= instr.len() {
+        return None;
+    }
+
+    match (&instr[at], &instr[at + 1]) {
+        (Instr::Mov64(tmp, Arg::Imm(n)), Instr::Mov64(dest, src)) => {
+            if src != tmp || dest == tmp {
+                return None;
+            }
+
+            // a 64 bit immediate can only be moved into a register
+            if is_memory(dest) && !fits_in_imm32(*n) {
+                return None;
+            }
+
+            if !is_dead_after(instr, at + 2, tmp) {
+                return None;
+            }
+
+            Some((2, vec!(Instr::Mov64(dest.clone(), Arg::Imm(*n)))))
+        },
+
+        _ => None,
+    }
+}
+
+// mov t, a             mov t, a
+// neg t                add t, b
+// mov d, t             mov d, t     where t is dead afterwards
+//
+// becomes
+//
+// mov d, a             mov d, a
+// neg d                add d, b
+fn compute_in_destination(instr: &[Instr], at: usize) -> Option<(usize, Vec<Instr>)> {
+    if at + 2 >= instr.len() {
+        return None;
+    }
+
+    let (tmp, first) = match &instr[at] {
+        Instr::Mov64(tmp, first) => (tmp, first),
+        _ => return None,
+    };
+
+    let dest = match &instr[at + 2] {
+        Instr::Mov64(dest, src) if src == tmp && dest != tmp => dest,
+        _ => return None,
+    };
+
+    if is_memory(dest) && is_memory(first) {
+        return None;
+    }
+
+    if let Arg::Imm(n) = first {
+        if is_memory(dest) && !fits_in_imm32(*n) {
+            return None;
+        }
+    }
+
+    let operation = match &instr[at + 1] {
+        Instr::Neg64(a) if a == tmp => {
+            Instr::Neg64(dest.clone())
+        },
+
+        Instr::Add64(a, second) if a == tmp => {
+            // dest is written before the second operand is read
+            if operand_reads(second, dest) || operand_reads(dest, tmp) {
+                return None;
+            }
+
+            if is_memory(dest) && is_memory(second) {
+                return None;
+            }
+
+            Instr::Add64(dest.clone(), second.clone())
+        },
+
+        _ => return None,
+    };
+
+    if !is_dead_after(instr, at + 3, tmp) {
+        return None;
+    }
+
+    Some((3, vec!(Instr::Mov64(dest.clone(), first.clone()), operation)))
+}
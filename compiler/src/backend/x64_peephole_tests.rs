@@ -0,0 +1,255 @@
+#![allow(unused)]
+
+use crate::types::{OptLevel, Overflow};
+use crate::utility::{test_ir_helper};
+use crate::pipeline::{PassManager, ProgramForm};
+
+use super::x64_def::*;
+use super::x64_peephole::*;
+
+fn var(name: &'static str) -> Arg {
+    Arg::Var(crate::idstr!(name))
+}
+
+fn rax() -> Arg {
+    Arg::Reg(Reg::Rax)
+}
+
+fn run_rule(name: &'static str, instr: Vec<Instr>) -> (Vec<Instr>, PeepholeStats) {
+    let rule = find_rule(name).unwrap();
+
+    let mut stats = PeepholeStats::new();
+
+    let optimized = optimize_block(instr, std::slice::from_ref(rule), &mut stats);
+
+    (optimized, stats)
+}
+
+#[test]
+fn peephole_self_move() {
+    let (instr, stats) = run_rule("self-move", vec!(
+        Instr::Mov64(var("x"), Arg::Imm(1)),
+        Instr::Mov64(var("x"), var("x")),
+        Instr::Mov64(rax(), var("x")),
+    ));
+
+    assert_eq!(instr, vec!(
+        Instr::Mov64(var("x"), Arg::Imm(1)),
+        Instr::Mov64(rax(), var("x")),
+    ));
+
+    assert_eq!(stats.count("self-move"), 1);
+}
+
+#[test]
+fn peephole_add_zero() {
+    let (instr, stats) = run_rule("add-zero", vec!(
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), Arg::Imm(0)),
+        Instr::Sub64(rax(), Arg::Imm(0)),
+        Instr::Add64(rax(), Arg::Imm(1)),
+    ));
+
+    assert_eq!(instr, vec!(
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), Arg::Imm(1)),
+    ));
+
+    assert_eq!(stats.count("add-zero"), 2);
+}
+
+#[test]
+fn peephole_add_zero_checked() {
+    let overflow = crate::idstr!("overflow");
+
+    let (instr, stats) = run_rule("add-zero", vec!(
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), Arg::Imm(0)),
+        Instr::JmpIf(Cc::O, overflow),
+        Instr::Add64(rax(), Arg::Imm(1)),
+        Instr::JmpIf(Cc::O, overflow),
+    ));
+
+    // the jo of the add that is gone would test the flags of the mov
+    assert_eq!(instr, vec!(
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), Arg::Imm(1)),
+        Instr::JmpIf(Cc::O, overflow),
+    ));
+
+    assert_eq!(stats.count("add-zero"), 1);
+}
+
+#[test]
+fn peephole_move_back() {
+    let (instr, stats) = run_rule("move-back", vec!(
+        Instr::Mov64(Arg::Reg(Reg::R15), var("x")),
+        Instr::Mov64(var("x"), Arg::Reg(Reg::R15)),
+        Instr::Mov64(rax(), var("x")),
+    ));
+
+    assert_eq!(instr, vec!(
+        Instr::Mov64(Arg::Reg(Reg::R15), var("x")),
+        Instr::Mov64(rax(), var("x")),
+    ));
+
+    assert_eq!(stats.count("move-back"), 1);
+}
+
+#[test]
+fn peephole_forward_constant() {
+    let (instr, stats) = run_rule("forward-constant", vec!(
+        Instr::Mov64(var("tmp.0"), Arg::Imm(42)),
+        Instr::Mov64(var("x"), var("tmp.0")),
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), var("x")),
+    ));
+
+    assert_eq!(instr, vec!(
+        Instr::Mov64(var("x"), Arg::Imm(42)),
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), var("x")),
+    ));
+
+    assert_eq!(stats.count("forward-constant"), 1);
+}
+
+#[test]
+fn peephole_forward_constant_live_temporary() {
+    // tmp.0 is still needed by the add, so the move has to stay
+    let program = vec!(
+        Instr::Mov64(var("tmp.0"), Arg::Imm(42)),
+        Instr::Mov64(var("x"), var("tmp.0")),
+        Instr::Mov64(rax(), var("x")),
+        Instr::Add64(rax(), var("tmp.0")),
+    );
+
+    let (instr, stats) = run_rule("forward-constant", program.clone());
+
+    assert_eq!(instr, program);
+    assert_eq!(stats.total(), 0);
+}
+
+#[test]
+fn peephole_forward_constant_too_large_for_memory() {
+    let program = vec!(
+        Instr::Mov64(Arg::Reg(Reg::R15), Arg::Imm(1 << 40)),
+        Instr::Mov64(var("x"), Arg::Reg(Reg::R15)),
+        Instr::Mov64(rax(), var("x")),
+    );
+
+    let (instr, _) = run_rule("forward-constant", program.clone());
+
+    assert_eq!(instr, program);
+}
+
+#[test]
+fn peephole_compute_in_destination_negate() {
+    let (instr, stats) = run_rule("compute-in-destination", vec!(
+        Instr::Mov64(var("x"), var("y")),
+        Instr::Neg64(var("x")),
+        Instr::Mov64(rax(), var("x")),
+    ));
+
+    assert_eq!(instr, vec!(
+        Instr::Mov64(rax(), var("y")),
+        Instr::Neg64(rax()),
+    ));
+
+    assert_eq!(stats.count("compute-in-destination"), 1);
+}
+
+#[test]
+fn peephole_compute_in_destination_add() {
+    let (instr, stats) = run_rule("compute-in-destination", vec!(
+        Instr::Mov64(var("x"), var("y")),
+        Instr::Add64(var("x"), Arg::Imm(2)),
+        Instr::Mov64(rax(), var("x")),
+    ));
+
+    assert_eq!(instr, vec!(
+        Instr::Mov64(rax(), var("y")),
+        Instr::Add64(rax(), Arg::Imm(2)),
+    ));
+
+    assert_eq!(stats.count("compute-in-destination"), 1);
+}
+
+#[test]
+fn peephole_compute_in_destination_clobbered_operand() {
+    // rax is written before the add would read it
+    let program = vec!(
+        Instr::Mov64(var("x"), var("y")),
+        Instr::Add64(var("x"), rax()),
+        Instr::Mov64(rax(), var("x")),
+    );
+
+    let (instr, _) = run_rule("compute-in-destination", program.clone());
+
+    assert_eq!(instr, program);
+}
+
+#[test]
+fn peephole_is_dead_after() {
+    let program = vec!(
+        Instr::Mov64(var("x"), Arg::Imm(1)),
+        Instr::Mov64(var("y"), var("x")),
+        Instr::Mov64(var("x"), Arg::Imm(2)),
+        Instr::Mov64(rax(), var("y")),
+    );
+
+    assert!(!is_dead_after(&program, 1, &var("x")));
+    assert!(is_dead_after(&program, 2, &var("x")));
+    assert!(is_dead_after(&program, 4, &var("y")));
+    assert!(!is_dead_after(&program, 4, &rax()));
+}
+
+#[test]
+fn peephole_all_rules_on_program() {
+    let ir = test_ir_helper("(let ([x (- (read))]) (+ x 1))");
+
+    let x64_asm = PassManager::for_level(OptLevel::O0).run(ProgramForm::Ir(ir.clone())).into_x64();
+
+    let mut pm = PassManager::for_level(OptLevel::O1);
+    let optimized = pm.run(ProgramForm::Ir(ir)).into_x64();
+
+    let start = crate::idstr!("start");
+
+    assert!(
+        optimized.blocks[&start].instr.len() < x64_asm.blocks[&start].instr.len()
+    );
+
+    assert!(pm.peephole_stats().total() > 0);
+}
+
+#[test]
+fn peephole_disabled_at_o0() {
+    let ir = test_ir_helper("(let ([x (- (read))]) (+ x 1))");
+
+    let mut pm = PassManager::for_level(OptLevel::O0);
+    let _ = pm.run(ProgramForm::Ir(ir));
+
+    assert_eq!(pm.peephole_stats().total(), 0);
+}
+
+#[test]
+fn peephole_checked_program_keeps_jo_after_arithmetic() {
+    // (+ (read) 0) with partial evaluation turned off
+    let ir = crate::ir::parser::parse_ir("locals: tmp.0\nstart:\n    tmp.0 = (read);\n    return (+ tmp.0 0);").unwrap();
+
+    let optimized =
+        PassManager::for_level(OptLevel::O1)
+        .set_overflow(Overflow::Checked)
+        .run(ProgramForm::Ir(ir))
+        .into_x64();
+
+    let start = &optimized.blocks[&crate::idstr!("start")].instr;
+
+    assert!(!start.iter().any(|instr| matches!(instr, Instr::Add64(..))), "{:?}", start);
+
+    for (i, instr) in start.iter().enumerate() {
+        if let Instr::JmpIf(Cc::O, _) = instr {
+            assert!(matches!(start[i - 1], Instr::Add64(..) | Instr::Sub64(..) | Instr::Neg64(..)), "{:?}", start);
+        }
+    }
+}
@@ -8,18 +8,13 @@ use super::x64_def::*;
 
 pub struct X64Printer {
     asm: X64Program,
+    homes: HashMap<IdString, Home>, // asm.vars by name
 }
 
 impl X64Printer {
 
     fn get_var(&self, name: &IdString) -> Option<&Home> {
-        for home in &self.asm.vars {
-            if home.name == *name {
-                return Some(home)
-            }
-        }
-
-        None
+        self.homes.get(name)
     }
 
     fn reg_to_string(&self, reg: &Reg) -> String {
@@ -90,6 +85,22 @@ impl X64Printer {
         }
     }
 
+    fn cc_to_string(&self, cc: &Cc) -> String {
+        match cc {
+            Cc::E => {
+                "e".to_owned()
+            },
+
+            Cc::Ne => {
+                "ne".to_owned()
+            },
+
+            Cc::O => {
+                "o".to_owned()
+            }
+        }
+    }
+
     fn arg_to_string(&self, arg: &Arg) -> String {
         match arg {
             Arg::Var(name) => {
@@ -189,14 +200,37 @@ impl X64Printer {
                     "jmp {}\n",
                     label
                 )
+            },
+
+            Instr::Cmp64(arg1, arg2) => {
+                format!(
+                    "cmp {}, {}\n",
+                    self.arg_to_string(arg1),
+                    self.arg_to_string(arg2)
+                )
+            },
+
+            Instr::JmpIf(cc, label) => {
+                format!(
+                    "j{} {}\n",
+                    self.cc_to_string(cc),
+                    label
+                )
             }
 
         }
     }
 
     pub fn new(asm: X64Program) -> Self {
+        let homes =
+            asm.vars
+            .iter()
+            .map(|home| (home.name, home.clone()))
+            .collect();
+
         Self {
             asm: asm,
+            homes: homes,
         }
     }
 
@@ -209,13 +243,13 @@ impl X64Printer {
 
         for ext in &self.asm.external {
             let mut new_ext = ext_prepend.clone();
-            new_ext.push_str(&*ext.clone());
+            new_ext.push_str(ext);
             external_functions.push(new_ext);
         }
 
-        let globals = vec!("global start".to_owned());
+        let globals = ["global start".to_owned()];
 
-        if external_functions.len() > 0 {
+        if !external_functions.is_empty() {
             program += &external_functions.join("\n");
             program += "\n\n";
         }
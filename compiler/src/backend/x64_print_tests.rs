@@ -1,10 +1,9 @@
 #![allow(unused)]
 
-use std::rc::Rc;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
-use crate::utility::{test_x64_helper};
+use crate::utility::{test_x64_helper, test_backend_helper};
 
 use super::x64_def::*;
 use super::x64_backend::{IRToX64Transformer};
@@ -123,4 +122,67 @@ start:
 ".to_owned();
 
     assert_eq!(asm_text, expect_print);
-}
\ No newline at end of file
+}
+
+// several labels and variables used in more than one of them
+fn branching_ir() -> crate::ir::explicate::IRProgram {
+    use crate::ir::explicate::*;
+
+    let var = |name: &str| Atm::Var { name: crate::idstr!(name) };
+    let read = || Exp::Prim { op: crate::idstr!("read"), args: vec!() };
+
+    let assign = |name: &str, exp: Exp| Stmt::Assign(var(name), exp);
+
+    IRProgram {
+        locals: vec!(crate::idstr!("a"), crate::idstr!("b"), crate::idstr!("c")),
+        labels: crate::map!(
+            crate::idstr!("start") => Block::new(vec!(assign("a", read()), assign("b", read())), Tail::If(var("a"), crate::idstr!("left"), crate::idstr!("right"))),
+            crate::idstr!("left") => Block::new(vec!(assign("c", Exp::Prim { op: crate::idstr!("+"), args: vec!(var("a"), var("b")) })), Tail::Goto(crate::idstr!("end"))),
+            crate::idstr!("right") => Block::new(vec!(assign("c", Exp::Prim { op: crate::idstr!("-"), args: vec!(var("b")) })), Tail::Goto(crate::idstr!("end"))),
+            crate::idstr!("end") => Block::from(Tail::Return(Exp::Atm(var("c"))))
+        ),
+    }
+}
+
+#[test]
+fn x64_print_branches() {
+    let asm_text = X64Printer::new(test_backend_helper(branching_ir())).print();
+
+    assert!(asm_text.contains("    cmp "));
+    assert!(asm_text.contains("    jne left\n    jmp right\n"));
+    assert!(asm_text.contains("    jmp end\n"));
+
+    // only the block that returns gets the epilogue
+    assert_eq!(asm_text.matches("    ret\n").count(), 1);
+}
+
+#[test]
+fn x64_print_shared_variable_has_one_home() {
+    let asm = test_backend_helper(branching_ir());
+
+    // a, b and c are used in several labels but each gets a single stack slot
+    assert_eq!(asm.vars.len(), 3);
+}
+
+#[test]
+fn x64_select_instructions_lists_each_variable_once() {
+    let asm = IRToX64Transformer::new(branching_ir()).select_instructions();
+
+    // the pseudo-x86 program also names each variable once, not once per block
+    assert_eq!(asm.vars.len(), 3);
+}
+
+#[test]
+fn x64_print_is_deterministic() {
+    let first = X64Printer::new(test_backend_helper(branching_ir())).print();
+
+    for _ in 0..100 {
+        let again = X64Printer::new(test_backend_helper(branching_ir())).print();
+
+        assert_eq!(first, again);
+    }
+
+    for _ in 0..100 {
+        assert_eq!(helper("(let ([x (read)]) (let ([y (read)]) (+ y (- x))))"), helper("(let ([x (read)]) (let ([y (read)]) (+ y (- x))))"));
+    }
+}
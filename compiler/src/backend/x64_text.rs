@@ -0,0 +1,357 @@
+/*
+    a text form of x64 programs that still has variables, pseudo-x86,
+    and a parser for it. x64_print makes the nasm source for the assembler,
+    this is for reading and for writing tests by hand
+
+        externals: read_int
+        vars: x.1 -> [rbp-8], tmp.2 -> rbx, y.3
+        start:
+            call read_int
+            mov x.1, rax
+            mov tmp.2, x.1
+            neg tmp.2
+            add tmp.2, [rbp-16]
+            cmp tmp.2, 0
+            je done
+            jmp start
+        done:
+            mov rax, tmp.2
+            ret
+
+    the operands are in intel order, destination first. a register name is
+    always a register, anything else that isn't a number is a variable. a
+    variable without '-> home' hasn't been given a location yet
+
+    one instruction per line, # starts a comment. start comes first, the other
+    labels follow in order
+*/
+
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::types::{IdString};
+use crate::ir::parser::{Scanner, Tok};
+
+use super::x64_def::*;
+
+const REGISTERS: &[(&str, Reg)] = &[
+    ("rsp", Reg::Rsp), ("rbp", Reg::Rbp), ("rax", Reg::Rax), ("rbx", Reg::Rbx),
+    ("rcx", Reg::Rcx), ("rdx", Reg::Rdx), ("rsi", Reg::Rsi), ("rdi", Reg::Rdi),
+    ("r8", Reg::R8), ("r9", Reg::R9), ("r10", Reg::R10), ("r11", Reg::R11),
+    ("r12", Reg::R12), ("r13", Reg::R13), ("r14", Reg::R14), ("r15", Reg::R15),
+];
+
+const CONDITION_CODES: &[(&str, Cc)] = &[("je", Cc::E), ("jne", Cc::Ne), ("jo", Cc::O)];
+
+pub fn reg_name(reg: Reg) -> &'static str {
+    REGISTERS.iter().find(|(_, r)| *r == reg).unwrap().0
+}
+
+pub fn reg_named(name: &str) -> Option<Reg> {
+    REGISTERS.iter().find(|(n, _)| *n == name).map(|(_, reg)| *reg)
+}
+
+// the conditional jump, e.g. je
+pub fn jcc_name(cc: Cc) -> &'static str {
+    CONDITION_CODES.iter().find(|(_, c)| *c == cc).unwrap().0
+}
+
+pub fn jcc_named(name: &str) -> Option<Cc> {
+    CONDITION_CODES.iter().find(|(n, _)| *n == name).map(|(_, cc)| *cc)
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", reg_name(*self))
+    }
+}
+
+fn deref(f: &mut fmt::Formatter, reg: Reg, offset: i64) -> fmt::Result {
+    if offset < 0 {
+        write!(f, "[{}-{}]", reg, offset.unsigned_abs())
+    } else if offset > 0 {
+        write!(f, "[{}+{}]", reg, offset)
+    } else {
+        write!(f, "[{}]", reg)
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Arg::Var(name) => write!(f, "{}", name),
+            Arg::Imm(n) => write!(f, "{}", n),
+            Arg::Reg(reg) => write!(f, "{}", reg),
+            Arg::Deref(reg, offset) => deref(f, *reg, *offset),
+        }
+    }
+}
+
+impl fmt::Display for VarLoc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VarLoc::Reg(reg) => write!(f, "{}", reg),
+            // an offset below rbp, the same as x64_print
+            VarLoc::Rbp(offset) => deref(f, Reg::Rbp, -offset),
+            VarLoc::Undefined => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Home {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.loc {
+            VarLoc::Undefined => write!(f, "{}", self.name),
+            loc => write!(f, "{} -> {}", self.name, loc),
+        }
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instr::Add64(a, b) => write!(f, "add {}, {}", a, b),
+            Instr::Sub64(a, b) => write!(f, "sub {}, {}", a, b),
+            Instr::Mov64(a, b) => write!(f, "mov {}, {}", a, b),
+            Instr::Neg64(a) => write!(f, "neg {}", a),
+            Instr::Call(func, 0) => write!(f, "call {}", func),
+            Instr::Call(func, arity) => write!(f, "call {}, {}", func, arity),
+            Instr::Ret => write!(f, "ret"),
+            Instr::Push(a) => write!(f, "push {}", a),
+            Instr::Pop(a) => write!(f, "pop {}", a),
+            Instr::Jmp(label) => write!(f, "jmp {}", label),
+            Instr::Cmp64(a, b) => write!(f, "cmp {}, {}", a, b),
+
+            Instr::JmpIf(cc, label) => write!(f, "{} {}", jcc_name(*cc), label),
+        }
+    }
+}
+
+impl fmt::Display for X64Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "externals:")?;
+
+        for external in &self.external {
+            write!(f, " {}", external)?;
+        }
+
+        let vars: Vec<String> = self.vars.iter().map(|home| home.to_string()).collect();
+
+        write!(f, "\nvars:")?;
+
+        if !vars.is_empty() {
+            write!(f, " {}", vars.join(", "))?;
+        }
+
+        let start = crate::idstr!("start");
+
+        let blocks =
+            self.blocks.get_key_value(&start)
+            .into_iter()
+            .chain(self.blocks.iter().filter(|(label, _)| **label != start));
+
+        for (label, block) in blocks {
+            write!(f, "\n{}:", label)?;
+
+            for instr in &block.instr {
+                write!(f, "\n    {}", instr)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// [reg], [reg+n] or [reg-n], the '[' has been read
+fn parse_deref(s: &mut Scanner) -> Result<(Reg, i64), String> {
+    let inside = match s.next() {
+        Some(Tok::Word(word)) => word,
+        _ => return s.error("expected a register after '['".to_owned()),
+    };
+
+    let (name, offset) = match inside.find(['+', '-']) {
+        Some(i) => {
+            let offset = match inside[i + 1..].parse::<i64>() {
+                Ok(n) => n,
+                Err(_) => return s.error(format!("'{}' isn't an offset", &inside[i + 1..])),
+            };
+
+            (&inside[..i], if inside[i..].starts_with('-') { -offset } else { offset })
+        },
+
+        None => (inside.as_str(), 0),
+    };
+
+    let reg = match reg_named(name) {
+        Some(reg) => reg,
+        None => return s.error(format!("'{}' isn't a register", name)),
+    };
+
+    s.expect_punct(']')?;
+
+    Ok((reg, offset))
+}
+
+fn parse_arg(s: &mut Scanner) -> Result<Arg, String> {
+    match s.peek().cloned() {
+        Some(Tok::Int(n)) => {
+            s.next();
+            Ok(Arg::Imm(n))
+        },
+
+        Some(Tok::Word(word)) => {
+            s.next();
+
+            match reg_named(&word) {
+                Some(reg) => Ok(Arg::Reg(reg)),
+                None => Ok(Arg::Var(crate::idstr!(&word))),
+            }
+        },
+
+        Some(Tok::Punct('[')) => {
+            s.next();
+
+            let (reg, offset) = parse_deref(s)?;
+
+            Ok(Arg::Deref(reg, offset))
+        },
+
+        _ => s.error(format!("expected an operand, found {}", s.found())),
+    }
+}
+
+fn parse_two_args(s: &mut Scanner) -> Result<(Arg, Arg), String> {
+    let a = parse_arg(s)?;
+    s.expect_punct(',')?;
+    let b = parse_arg(s)?;
+
+    Ok((a, b))
+}
+
+fn parse_instr(s: &mut Scanner, mnemonic: &str) -> Result<Instr, String> {
+    let instr = match mnemonic {
+        "add" => { let (a, b) = parse_two_args(s)?; Instr::Add64(a, b) },
+        "sub" => { let (a, b) = parse_two_args(s)?; Instr::Sub64(a, b) },
+        "mov" => { let (a, b) = parse_two_args(s)?; Instr::Mov64(a, b) },
+        "cmp" => { let (a, b) = parse_two_args(s)?; Instr::Cmp64(a, b) },
+        "neg" => Instr::Neg64(parse_arg(s)?),
+        "push" => Instr::Push(parse_arg(s)?),
+        "pop" => Instr::Pop(parse_arg(s)?),
+        "ret" => Instr::Ret,
+        "jmp" => Instr::Jmp(crate::idstr!(&s.expect_word("a label")?)),
+
+        "call" => {
+            let func = crate::idstr!(&s.expect_word("a function")?);
+
+            let arity =
+                if s.peek() == Some(&Tok::Punct(',')) {
+                    s.next();
+
+                    match s.next() {
+                        Some(Tok::Int(n)) => n,
+                        _ => return s.error("expected the number of arguments".to_owned()),
+                    }
+                } else {
+                    0
+                };
+
+            Instr::Call(func, arity)
+        },
+
+        _ => {
+            match jcc_named(mnemonic) {
+                Some(cc) => Instr::JmpIf(cc, crate::idstr!(&s.expect_word("a label")?)),
+                None => return s.error(format!("unknown instruction '{}'", mnemonic)),
+            }
+        },
+    };
+
+    Ok(instr)
+}
+
+// the home of a variable on the vars line
+fn parse_home(s: &mut Scanner) -> Result<Home, String> {
+    let name = crate::idstr!(&s.expect_word("a variable")?);
+
+    if s.peek() != Some(&Tok::Word("->".to_owned())) {
+        return Ok(Home { name: name, loc: VarLoc::Undefined });
+    }
+
+    s.next();
+
+    let loc = match parse_arg(s)? {
+        Arg::Reg(reg) => VarLoc::Reg(reg),
+        Arg::Deref(Reg::Rbp, offset) if offset < 0 => VarLoc::Rbp(-offset),
+        _ => return s.error(format!("'{}' needs a register or [rbp-n] as its home", name)),
+    };
+
+    Ok(Home { name: name, loc: loc })
+}
+
+pub fn parse_x64(text: &str) -> Result<X64Program, String> {
+    let mut s = Scanner::new(text);
+
+    s.skip_newlines();
+    s.expect_keyword("externals")?;
+    s.expect_punct(':')?;
+
+    let mut external = BTreeSet::new();
+
+    while let Some(Tok::Word(name)) = s.peek().cloned() {
+        s.next();
+        external.insert(crate::idstr!(&name));
+    }
+
+    s.expect_newline()?;
+
+    s.expect_keyword("vars")?;
+    s.expect_punct(':')?;
+
+    let mut vars = vec!();
+
+    if s.peek() != Some(&Tok::Newline) && !s.at_end() {
+        vars.push(parse_home(&mut s)?);
+
+        while s.peek() == Some(&Tok::Punct(',')) {
+            s.next();
+            vars.push(parse_home(&mut s)?);
+        }
+    }
+
+    s.expect_newline()?;
+
+    let mut blocks: BTreeMap<IdString, Block> = BTreeMap::new();
+    let mut current: Option<IdString> = None;
+
+    while !s.at_end() {
+        let word = s.expect_word("a label or an instruction")?;
+
+        if s.peek() == Some(&Tok::Punct(':')) {
+            s.next();
+
+            let label = crate::idstr!(&word);
+
+            if blocks.contains_key(&label) {
+                return s.error(format!("label '{}' is defined more than once", label));
+            }
+
+            blocks.insert(label, Block { info: (), instr: vec!() });
+            current = Some(label);
+        } else {
+            let instr = parse_instr(&mut s, &word)?;
+
+            match current {
+                Some(label) => blocks.get_mut(&label).unwrap().instr.push(instr),
+                None => return s.error(format!("'{}' isn't in a block", word)),
+            }
+        }
+
+        s.expect_newline()?;
+    }
+
+    Ok(X64Program {
+        external: external,
+        vars: vars,
+        blocks: blocks,
+    })
+}
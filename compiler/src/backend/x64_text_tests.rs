@@ -0,0 +1,152 @@
+use crate::types::{OptLevel};
+use crate::fuzz::{Generator, GenConfig};
+use crate::pipeline::{PassManager, ProgramForm, IrKind};
+use crate::utility::{test_x64_helper};
+
+use super::x64_def::*;
+use super::x64_emulator::{emulate};
+use super::x64_text::*;
+
+fn run_pass(pass: &str, p: X64Program) -> X64Program {
+    PassManager::custom(&[pass]).unwrap().run(ProgramForm::X64(p)).into_x64()
+}
+
+#[test]
+fn x64_text_print() {
+    let p = X64Program {
+        external: crate::set!(crate::idstr!("read_int")),
+        vars: vec!(
+            Home { name: crate::idstr!("x.1"), loc: VarLoc::Rbp(8) },
+            Home { name: crate::idstr!("y"), loc: VarLoc::Reg(Reg::Rbx) },
+            Home { name: crate::idstr!("z"), loc: VarLoc::Undefined },
+        ),
+        blocks: crate::map!(
+            crate::idstr!("start") => Block {
+                info: (),
+                instr: vec!(
+                    Instr::Call(crate::idstr!("read_int"), 0),
+                    Instr::Mov64(Arg::Var(crate::idstr!("x.1")), Arg::Reg(Reg::Rax)),
+                    Instr::Add64(Arg::Deref(Reg::Rbp, -16), Arg::Imm(-3)),
+                    Instr::Sub64(Arg::Deref(Reg::Rsp, 8), Arg::Deref(Reg::R15, 0)),
+                    Instr::Cmp64(Arg::Var(crate::idstr!("y")), Arg::Imm(0)),
+                    Instr::JmpIf(Cc::Ne, crate::idstr!("done")),
+                    Instr::Jmp(crate::idstr!("start")),
+                ),
+            },
+            crate::idstr!("done") => Block {
+                info: (),
+                instr: vec!(Instr::Neg64(Arg::Reg(Reg::Rax)), Instr::Ret),
+            }
+        ),
+    };
+
+    let expected = [
+        "externals: read_int",
+        "vars: x.1 -> [rbp-8], y -> rbx, z",
+        "start:",
+        "    call read_int",
+        "    mov x.1, rax",
+        "    add [rbp-16], -3",
+        "    sub [rsp+8], [r15]",
+        "    cmp y, 0",
+        "    jne done",
+        "    jmp start",
+        "done:",
+        "    neg rax",
+        "    ret",
+    ];
+
+    assert_eq!(p.to_string(), expected.join("\n"));
+
+    assert_eq!(parse_x64(&p.to_string()), Ok(p));
+}
+
+#[test]
+fn x64_text_round_trip() {
+    let passes = ["select-instructions", "assign-homes", "patch-instructions", "peephole", "prologue-epilogue"];
+
+    for seed in 0..100 {
+        let p = Generator::new(seed, GenConfig::default()).generate();
+
+        let mut program = PassManager::for_level(OptLevel::O1).run_to(ProgramForm::Ast(p), IrKind::Ir);
+
+        // every stage the program goes through in the backend
+        for pass in passes {
+            program = PassManager::custom(&[pass]).unwrap().run(program);
+
+            let p = program.clone().into_x64();
+            let text = p.to_string();
+
+            assert_eq!(parse_x64(&text), Ok(p), "{}\n{}", pass, text);
+        }
+    }
+}
+
+#[test]
+fn x64_text_test_a_pass() {
+    let input = parse_x64("
+        externals: read_int
+        vars: a, b
+        start:
+            call read_int
+            mov a, rax
+            mov b, a
+            neg b
+            mov rax, b
+            ret
+    ").unwrap();
+
+    let homed = run_pass("assign-homes", input);
+
+    let expected = parse_x64("
+        externals: read_int
+        vars: a -> [rbp-8], b -> [rbp-16]
+        start:
+            call read_int
+            mov a, rax
+            mov b, a
+            neg b
+            mov rax, b
+            ret
+    ").unwrap();
+
+    assert_eq!(homed, expected);
+
+    // memory to memory isn't an instruction
+    let patched = run_pass("patch-instructions", homed);
+
+    assert!(patched.to_string().contains("    mov r15, a\n    mov b, r15\n"), "{}", patched);
+}
+
+#[test]
+fn x64_text_runs() {
+    let p = parse_x64(&test_x64_helper("(let ([x (read)]) (+ x (- 10)))").to_string()).unwrap();
+
+    assert_eq!(emulate(&p, vec!("52")), Ok(42));
+}
+
+#[test]
+fn x64_text_errors() {
+    let error = |text: &str| parse_x64(text).unwrap_err();
+
+    assert_eq!(error("vars:\nstart:\n ret"), "line 1: expected 'externals', found 'vars'");
+    assert_eq!(error("externals:\nvars:\nstart:\n mul rax, 2"), "line 4: unknown instruction 'mul'");
+    assert_eq!(error("externals:\nvars:\n ret"), "line 3: 'ret' isn't in a block");
+    assert_eq!(error("externals:\nvars: x -> 8\nstart:\n ret"), "line 2: 'x' needs a register or [rbp-n] as its home");
+    assert_eq!(error("externals:\nvars:\nstart:\n mov rax [rbp-8]"), "line 4: expected ',', found '['");
+    assert_eq!(error("externals:\nvars:\nstart:\n mov rax, [rip-8]"), "line 4: 'rip' isn't a register");
+    assert_eq!(error("externals:\nvars:\nstart:\n ret rax"), "line 4: expected the end of the line, found 'rax'");
+}
+
+#[test]
+fn x64_text_checked_overflow() {
+    let text = "externals: overflow_error read_int\nvars:\nstart:\n    call read_int\n    neg rax\n    jo overflow\n    ret\noverflow:\n    call overflow_error\n    ret";
+
+    let p = parse_x64(text).unwrap();
+
+    assert_eq!(p.blocks[&crate::idstr!("start")].instr[2], Instr::JmpIf(Cc::O, crate::idstr!("overflow")));
+    assert_eq!(p.to_string(), text);
+
+    assert_eq!(emulate(&p, vec!("-42")), Ok(42));
+    assert!(emulate(&p, vec!("-9223372036854775808")).unwrap_err().contains("integer overflow"));
+}
@@ -0,0 +1,141 @@
+/*
+    check that an x64 program is well formed
+
+    after instruction selection:
+    - there is a 'start' block and every jump goes to a block that exists
+    - calls only go to external functions
+    - every variable has exactly one home
+    - every block ends by jumping somewhere or returning
+
+    after patching, what's left has to be something the assembler accepts:
+    - every home has been given a location
+    - no instruction takes two memory operands
+    - the first operand of cmp isn't an immediate
+    - an immediate that doesn't fit in 32 bits is only moved into a register
+*/
+
+use std::collections::HashSet;
+
+use crate::types::{IdString};
+
+use super::x64_def::*;
+use super::x64_peephole::{fits_in_imm32};
+
+fn operands(instr: &Instr) -> Vec<&Arg> {
+    match instr {
+        Instr::Add64(a, b) | Instr::Sub64(a, b) |
+        Instr::Mov64(a, b) | Instr::Cmp64(a, b) => vec!(a, b),
+        Instr::Neg64(a) | Instr::Push(a) | Instr::Pop(a) => vec!(a),
+        Instr::Call(..) | Instr::Ret | Instr::Jmp(_) | Instr::JmpIf(..) => vec!(),
+    }
+}
+
+fn is_memory(arg: &Arg) -> bool {
+    matches!(arg, Arg::Var(_) | Arg::Deref(..))
+}
+
+fn result(errors: Vec<String>) -> Result<(), Vec<String>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn check_x64(p: &X64Program, errors: &mut Vec<String>) {
+    let start = crate::idstr!("start");
+
+    if !p.blocks.contains_key(&start) {
+        errors.push("entry point 'start' not found".to_owned());
+    }
+
+    let mut homes: HashSet<IdString> = HashSet::new();
+
+    for home in &p.vars {
+        if !homes.insert(home.name) {
+            errors.push(format!("'{}' has more than one home", home.name));
+        }
+    }
+
+    for (label, block) in &p.blocks {
+        for instr in &block.instr {
+            match instr {
+                Instr::Jmp(target) | Instr::JmpIf(_, target) if !p.blocks.contains_key(target) => {
+                    errors.push(format!("{}: jump to unknown label '{}'", label, target));
+                },
+
+                Instr::Call(name, _) if !p.external.contains(name) => {
+                    errors.push(format!("{}: call to '{}' which isn't external", label, name));
+                },
+
+                _ => {},
+            }
+
+            for arg in operands(instr) {
+                if let Arg::Var(name) = arg {
+                    if !homes.contains(name) {
+                        errors.push(format!("{}: '{}' has no home", label, name));
+                    }
+                }
+            }
+        }
+
+        match block.instr.last() {
+            Some(Instr::Jmp(_)) | Some(Instr::Ret) => {},
+            _ => {
+                errors.push(format!("{}: block doesn't end with a jmp or ret", label));
+            }
+        }
+    }
+}
+
+// the output of instruction selection
+pub fn verify_x64(p: &X64Program) -> Result<(), Vec<String>> {
+    let mut errors = vec!();
+
+    check_x64(p, &mut errors);
+
+    result(errors)
+}
+
+// the output of patch instructions and everything after it
+pub fn verify_x64_patched(p: &X64Program) -> Result<(), Vec<String>> {
+    let mut errors = vec!();
+
+    check_x64(p, &mut errors);
+
+    for home in &p.vars {
+        if home.loc == VarLoc::Undefined {
+            errors.push(format!("'{}' was never given a location", home.name));
+        }
+    }
+
+    for (label, block) in &p.blocks {
+        for instr in &block.instr {
+            match instr {
+                Instr::Add64(a, b) | Instr::Sub64(a, b) |
+                Instr::Mov64(a, b) | Instr::Cmp64(a, b) => {
+                    if is_memory(a) && is_memory(b) {
+                        errors.push(format!("{}: two memory operands in {:?}", label, instr));
+                    }
+
+                    if let (Instr::Cmp64(..), Arg::Imm(_)) = (instr, a) {
+                        errors.push(format!("{}: immediate as the first operand of {:?}", label, instr));
+                    }
+
+                    let into_register = matches!((instr, a), (Instr::Mov64(..), Arg::Reg(_)));
+
+                    if let Arg::Imm(n) = b {
+                        if !fits_in_imm32(*n) && !into_register {
+                            errors.push(format!("{}: 64 bit immediate in {:?}", label, instr));
+                        }
+                    }
+                },
+
+                _ => {},
+            }
+        }
+    }
+
+    result(errors)
+}
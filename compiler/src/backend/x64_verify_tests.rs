@@ -0,0 +1,69 @@
+use crate::utility::{test_x64_helper};
+
+use super::x64_def::*;
+use super::x64_verify::*;
+
+fn start_only(vars: Vec<Home>, instr: Vec<Instr>) -> X64Program {
+    X64Program {
+        external: crate::set!(),
+        vars: vars,
+        blocks: crate::map!(crate::idstr!("start") => Block { info: (), instr: instr }),
+    }
+}
+
+fn home(name: &'static str, loc: VarLoc) -> Home {
+    Home { name: crate::idstr!(name), loc: loc }
+}
+
+#[test]
+fn verify_x64_transform_output() {
+    let p = test_x64_helper("(let ([x (read)]) (let ([y (read)]) (let ([z (+ x y)]) (+ z (- y)))))");
+
+    assert_eq!(verify_x64_patched(&p), Ok(()));
+}
+
+#[test]
+fn verify_x64_missing_home() {
+    let p = start_only(vec!(), vec!(Instr::Mov64(Arg::Var(crate::idstr!("x")), Arg::Imm(1)), Instr::Ret));
+
+    assert_eq!(verify_x64(&p), Err(vec!("start: 'x' has no home".to_owned())));
+}
+
+#[test]
+fn verify_x64_unknown_call() {
+    let p = start_only(vec!(), vec!(Instr::Call(crate::idstr!("read_int"), 0), Instr::Ret));
+
+    assert_eq!(verify_x64(&p), Err(vec!("start: call to 'read_int' which isn't external".to_owned())));
+}
+
+#[test]
+fn verify_x64_block_falls_through() {
+    let p = start_only(vec!(), vec!(Instr::Mov64(Arg::Reg(Reg::Rax), Arg::Imm(1))));
+
+    assert_eq!(verify_x64(&p), Err(vec!("start: block doesn't end with a jmp or ret".to_owned())));
+}
+
+#[test]
+fn verify_x64_two_memory_operands() {
+    let x = crate::idstr!("x");
+    let y = crate::idstr!("y");
+
+    let p = start_only(
+        vec!(home("x", VarLoc::Rbp(8)), home("y", VarLoc::Rbp(16))),
+        vec!(Instr::Add64(Arg::Var(x), Arg::Var(y)), Instr::Ret)
+    );
+
+    // fine before patching, not after
+    assert_eq!(verify_x64(&p), Ok(()));
+    assert_eq!(verify_x64_patched(&p).unwrap_err().len(), 1);
+}
+
+#[test]
+fn verify_x64_undefined_home() {
+    let p = start_only(
+        vec!(home("x", VarLoc::Undefined)),
+        vec!(Instr::Mov64(Arg::Var(crate::idstr!("x")), Arg::Imm(1)), Instr::Ret)
+    );
+
+    assert_eq!(verify_x64_patched(&p), Err(vec!("'x' was never given a location".to_owned())));
+}
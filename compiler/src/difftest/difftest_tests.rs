@@ -0,0 +1,380 @@
+use crate::types::{OptLevel, Overflow, OVERFLOW_ERROR};
+use crate::backend::x64_build::{X64Builder, native_toolchain};
+
+use super::*;
+use crate::interpreter::{Limits};
+
+fn harness(opt_level: OptLevel) -> DiffHarness {
+    DiffHarness::new(
+        PipelineOptions {
+            opt_level: opt_level,
+            verify: true,
+            ..PipelineOptions::default()
+        }
+    )
+}
+
+fn outcome(value: Option<i64>, reads: Vec<i64>) -> Outcome {
+    Outcome {
+        value: value,
+        reads: reads,
+        errors: vec!(),
+        out_of_fuel: false,
+        too_deep: false,
+        stdout: None,
+    }
+}
+
+#[test]
+fn difftest_stages_agree() {
+    let programs: &[(&str, &[&str])] = &[
+        ("(2)", &[]),
+        ("(+ (read) (- (read)))", &["10", "3"]),
+        ("(let ([x (read)]) (let ([y (read)]) (+ y (- x))))", &["1", "2"]),
+        ("(let ([x 1]) (+ (let ([x 2]) x) x))", &[]),
+        ("(+ (let ([x (read)]) x) (let ([x (read)]) (+ x x)))", &["5", "7"]),
+        ("(let ([a (read)]) (let ([b a]) (let ([c b]) (+ c (- (read))))))", &["-4", "9"]),
+    ];
+
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        for (program, input) in programs {
+            let report = harness(level).run(program, input).unwrap();
+
+            assert!(report.agrees(), "{:?} {}\n{}", level, program, report);
+        }
+    }
+}
+
+#[test]
+fn difftest_runs_every_stage() {
+    let report = harness(OptLevel::O1).set_native(false).run("(+ (read) 1)", &["41"]).unwrap();
+
+    let stages: Vec<&str> = report.stages.iter().map(|s| s.stage).collect();
+
+    assert_eq!(stages, vec!(
+        "parse", "uniquify", "partial-eval", "dead-bindings", "decomplify",
+        "explicate-control", "value-numbering", "select-instructions", "assign-homes",
+        "patch-instructions", "peephole", "prologue-epilogue",
+    ));
+
+    assert_eq!(report.reference(), &outcome(Some(42), vec!(41)));
+    assert_eq!(report.native_skipped, Some("turned off".to_owned()));
+}
+
+#[test]
+fn difftest_shadowing_in_parsed_program() {
+    // the parsed program still has both x's, the ast interpreter has to scope them
+    let report = harness(OptLevel::O0).set_native(false).run("(let ([x (read)]) (+ (let ([x 2]) x) x))", &["1"]).unwrap();
+
+    assert_eq!(report.reference(), &outcome(Some(3), vec!(1)));
+    assert!(report.agrees());
+}
+
+#[test]
+fn difftest_out_of_input() {
+    // (read) fails everywhere, but it fails the same way
+    let report = harness(OptLevel::O0).run("(+ (read) (read))", &["1"]).unwrap();
+
+    assert_eq!(report.reference().value, None);
+    assert!(report.agrees(), "{}", report);
+}
+
+#[test]
+fn difftest_reports_first_divergence() {
+    let report = DiffReport {
+        stages: vec!(
+            StageOutcome { stage: "parse", outcome: outcome(Some(3), vec!(1, 2)) },
+            StageOutcome { stage: "uniquify", outcome: outcome(Some(3), vec!(1, 2)) },
+            StageOutcome { stage: "partial-eval", outcome: outcome(Some(3), vec!(2, 1)) },
+            StageOutcome { stage: "decomplify", outcome: outcome(Some(4), vec!(1, 2)) },
+        ),
+        native_skipped: None,
+    };
+
+    // same value, but the input was consumed in a different order
+    assert_eq!(report.first_divergence().unwrap().stage, "partial-eval");
+    assert!(format!("{}", report).ends_with("results diverge at 'partial-eval'"));
+}
+
+#[test]
+fn difftest_parse_error() {
+    assert!(harness(OptLevel::O0).run("(+ 1", &[]).is_err());
+}
+
+#[test]
+fn difftest_native() {
+    if native_toolchain().is_err() {
+        return;
+    }
+
+    let report = harness(OptLevel::O1).run("(let ([x (read)]) (+ x (- (read))))", &["100", "58"]).unwrap();
+
+    let native = report.stages.last().unwrap();
+
+    assert_eq!(native.stage, "native");
+    assert_eq!(native.outcome.stdout, Some("42\n".to_owned()));
+    assert_eq!(Outcome { stdout: None, ..native.outcome.clone() }, outcome(Some(42), vec!(100, 58)));
+}
+
+#[test]
+fn difftest_native_build_is_removed() {
+    if native_toolchain().is_err() {
+        return;
+    }
+
+    let asm = X64Printer::new(crate::utility::test_x64_helper("(+ (read) 1)")).print();
+
+    let first = X64Builder::new("program".to_owned(), asm.clone()).build().unwrap();
+    let second = X64Builder::new("program".to_owned(), asm).build().unwrap();
+
+    // each build has a folder of its own, the same name doesn't clash
+    assert_ne!(first.path().parent(), second.path().parent());
+    assert!(first.path().is_file());
+
+    let folder = first.path().parent().unwrap().to_owned();
+
+    drop(first);
+
+    // the .asm, .o and runtime.c are gone together with the executable
+    assert!(!folder.exists());
+    assert!(second.path().is_file());
+}
+
+#[test]
+fn difftest_report_as_json() {
+    let report = DiffReport {
+        stages: vec!(
+            StageOutcome { stage: "parse", outcome: outcome(Some(3), vec!(1, 2)) },
+            StageOutcome { stage: "decomplify", outcome: out_of_fuel() },
+            StageOutcome { stage: "native", outcome: Outcome { stdout: Some("4\n".to_owned()), ..outcome(Some(4), vec!(1, 2)) } },
+        ),
+        native_skipped: None,
+    };
+
+    let expected = Json::parse(r#"{
+        "stages": [
+            { "stage": "parse", "outcome": { "value": 3, "reads": [1, 2], "errors": [], "stopped": null, "stdout": null } },
+            { "stage": "decomplify", "outcome": { "value": null, "reads": [], "errors": ["ran out of fuel after 10 steps"], "stopped": "out of fuel", "stdout": null } },
+            { "stage": "native", "outcome": { "value": 4, "reads": [1, 2], "errors": [], "stopped": null, "stdout": "4\n" } }
+        ],
+        "native_skipped": null,
+        "first_divergence": "native",
+        "stopped": "decomplify"
+    }"#).unwrap();
+
+    assert_eq!(report.to_json(), expected);
+    assert_eq!(Json::parse(&report.to_json().pretty()), Ok(expected));
+}
+
+fn checked(opt_level: OptLevel) -> DiffHarness {
+    DiffHarness::new(
+        PipelineOptions {
+            opt_level: opt_level,
+            verify: true,
+            overflow: Overflow::Checked,
+            ..PipelineOptions::default()
+        }
+    )
+}
+
+#[test]
+fn difftest_overflow_wraps() {
+    let programs: &[(&str, &[&str], i64)] = &[
+        ("(+ (read) 1)", &["9223372036854775807"], i64::MIN),
+        ("(- (read))", &["-9223372036854775808"], i64::MIN),
+        ("(+ 9223372036854775807 (+ 9223372036854775807 2))", &[], 0),
+        ("(let ([x (read)]) (+ (+ x 9223372036854775807) 1))", &["1"], i64::MIN + 1),
+    ];
+
+    for level in [OptLevel::O0, OptLevel::O1] {
+        for (program, input, value) in programs {
+            let report = harness(level).run(program, input).unwrap();
+
+            assert_eq!(report.reference().value, Some(*value), "{}", program);
+            assert!(report.agrees(), "{:?} {}\n{}", level, program, report);
+        }
+    }
+}
+
+#[test]
+fn difftest_overflow_checked() {
+    let programs: &[(&str, &[&str], Option<i64>)] = &[
+        ("(+ (read) 1)", &["9223372036854775807"], None),
+        ("(+ (read) 1)", &["41"], Some(42)),
+        ("(- (read))", &["-9223372036854775808"], None),
+        ("(+ 9223372036854775807 1)", &[], None),
+        ("(- (- 9223372036854775807))", &[], Some(i64::MAX)),
+        // folding the constants first would hide the overflow
+        ("(let ([x (read)]) (+ (+ x 9223372036854775807) (- 1)))", &["1"], None),
+        ("(let ([x (- (read))]) 1)", &["-9223372036854775808"], None),
+    ];
+
+    for level in [OptLevel::O0, OptLevel::O1] {
+        for (program, input, value) in programs {
+            let report = checked(level).run(program, input).unwrap();
+
+            assert_eq!(report.reference().value, *value, "{}", program);
+            assert!(report.agrees(), "{:?} {}\n{}", level, program, report);
+
+            if value.is_none() {
+                for stage in &report.stages {
+                    assert!(stage.outcome.errors.iter().any(|e| e.contains(OVERFLOW_ERROR)), "{}\n{}", program, report);
+                }
+            }
+        }
+    }
+}
+
+fn limited(opt_level: OptLevel, fuel: usize, max_depth: usize) -> DiffHarness {
+    DiffHarness::new(
+        PipelineOptions {
+            opt_level: opt_level,
+            verify: true,
+            limits: Limits { fuel: fuel, max_depth: max_depth },
+            ..PipelineOptions::default()
+        }
+    )
+}
+
+fn out_of_fuel() -> Outcome {
+    Outcome {
+        value: None,
+        reads: vec!(),
+        errors: vec!("ran out of fuel after 10 steps".to_owned()),
+        out_of_fuel: true,
+        too_deep: false,
+        stdout: None,
+    }
+}
+
+fn too_deep() -> Outcome {
+    Outcome {
+        value: None,
+        reads: vec!(),
+        errors: vec!("nested deeper than 10 expressions".to_owned()),
+        out_of_fuel: false,
+        too_deep: true,
+        stdout: None,
+    }
+}
+
+#[test]
+fn difftest_out_of_fuel_is_inconclusive() {
+    let report = DiffReport {
+        stages: vec!(
+            StageOutcome { stage: "parse", outcome: out_of_fuel() },
+            StageOutcome { stage: "uniquify", outcome: outcome(Some(3), vec!(1, 2)) },
+            StageOutcome { stage: "decomplify", outcome: outcome(Some(4), vec!(1, 2)) },
+        ),
+        native_skipped: None,
+    };
+
+    // the stages that finished are compared with the first of them
+    assert_eq!(report.first_divergence().unwrap().stage, "decomplify");
+    assert!(!report.inconclusive());
+
+    let report = DiffReport {
+        stages: vec!(
+            StageOutcome { stage: "parse", outcome: out_of_fuel() },
+            StageOutcome { stage: "uniquify", outcome: outcome(Some(3), vec!(1, 2)) },
+            StageOutcome { stage: "decomplify", outcome: outcome(Some(3), vec!(1, 2)) },
+        ),
+        native_skipped: None,
+    };
+
+    assert!(report.agrees());
+    assert!(report.inconclusive());
+    assert!(format!("{}", report).ends_with("inconclusive, 'parse' ran out of fuel"), "{}", report);
+
+    let report = DiffReport {
+        stages: vec!(
+            StageOutcome { stage: "parse", outcome: outcome(Some(3), vec!(1, 2)) },
+            StageOutcome { stage: "uniquify", outcome: out_of_fuel() },
+            StageOutcome { stage: "decomplify", outcome: outcome(Some(4), vec!(1, 2)) },
+        ),
+        native_skipped: None,
+    };
+
+    // the stages that finished are still compared
+    assert_eq!(report.first_divergence().unwrap().stage, "decomplify");
+    assert!(!report.inconclusive());
+
+    let report = DiffReport {
+        stages: vec!(
+            StageOutcome { stage: "parse", outcome: too_deep() },
+            StageOutcome { stage: "uniquify", outcome: out_of_fuel() },
+        ),
+        native_skipped: None,
+    };
+
+    assert_eq!(report.stopped().unwrap().stage, "parse");
+    assert!(format!("{}", report).contains("parse: too deep"), "{}", report);
+    assert!(format!("{}", report).ends_with("inconclusive, 'parse' nested too deep"), "{}", report);
+}
+
+#[test]
+fn difftest_fuel() {
+    for level in [OptLevel::O0, OptLevel::O1] {
+        let report = limited(level, 3, 100).run("(+ (read) (- (read)))", &["1", "2"]).unwrap();
+
+        assert!(report.reference().out_of_fuel, "{}", report);
+        assert!(report.reference().errors.iter().any(|e| e.starts_with("ran out of fuel")), "{}", report);
+        assert!(report.inconclusive(), "{}", report);
+        assert!(format!("{}", report).contains("parse: out of fuel"), "{}", report);
+
+        // enough for every stage
+        let report = limited(level, 100, 100).run("(+ (read) (- (read)))", &["1", "2"]).unwrap();
+
+        assert_eq!(report.reference().value, Some(-1));
+        assert!(report.agrees() && !report.inconclusive(), "{}", report);
+    }
+}
+
+#[test]
+fn difftest_max_depth() {
+    let program = format!("{}(read){}", "(- ".repeat(20), ")".repeat(20));
+
+    let report = limited(OptLevel::O0, 1000, 10).run(&program, &["1"]).unwrap();
+
+    assert!(report.reference().errors.iter().any(|e| e == "nested deeper than 10 expressions"), "{}", report);
+    assert!(report.reference().too_deep && !report.reference().out_of_fuel, "{}", report);
+    assert!(report.inconclusive(), "{}", report);
+
+    let report = limited(OptLevel::O0, 1000, 100).run(&program, &["1"]).unwrap();
+
+    assert_eq!(report.reference().value, Some(1));
+    assert!(!report.inconclusive(), "{}", report);
+}
+
+#[test]
+fn difftest_ir_depth_counts_jumps() {
+    let text = "
+        locals: x
+        start:
+            x = 1;
+            x = (+ x 1);
+            x = (+ x 1);
+            x = (+ x 1);
+            goto a;
+        a:
+            goto b;
+        b:
+            return x;
+    ";
+
+    let program = ProgramForm::Ir(crate::ir::parser::parse_ir(text).unwrap());
+
+    let run = |max_depth: usize| {
+        let options = PipelineOptions { limits: Limits { fuel: 100, max_depth: max_depth }, ..PipelineOptions::default() };
+
+        execute(&program, CachedRuntimeCall::new(), true, &options)
+    };
+
+    // a long block doesn't nest, the jumps do
+    assert_eq!(run(2).value, Some(4));
+
+    let outcome = run(1);
+
+    assert!(outcome.too_deep && !outcome.out_of_fuel);
+    assert_eq!(outcome.errors, vec!("nested deeper than 1 jumps".to_owned()));
+}
@@ -0,0 +1,410 @@
+/*
+    differential testing
+
+    a program is run at every stage of the compiler with the same input for (read):
+
+        parse -> uniquify -> partial-eval -> dead-bindings -> decomplify   (ast interpreter)
+              -> explicate-control -> value-numbering                      (ir interpreter)
+              -> select-instructions -> ... -> prologue-epilogue           (x64 emulator)
+              -> native                                                    (the executable)
+
+    the parsed program is the reference, every later stage has to produce the same
+    value and consume the same input in the same order. the first stage that doesn't
+    is where the bug was introduced. a stage that ran out of fuel or nested too deep
+    (see Limits) can't be compared and is left out, the first stage that finished is
+    the reference then. if nothing diverges the report is inconclusive rather than
+    a match, not every stage was compared
+
+    which passes run depends on the pipeline options. native code is only run on
+    linux with nasm and a c compiler installed, otherwise that stage is skipped.
+    the executable is the only stage that prints anything, what it printed is
+    kept as it was
+
+    to_json gives the report to other programs, 'compiler --run --dump-json'
+    prints it for the golden tests
+*/
+
+#![allow(dead_code)]
+
+#[cfg(test)]
+mod difftest_tests;
+
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use runtime::types::{RuntimeI64, RuntimeValue};
+
+use crate::frontend::lexer::{Lexer};
+use crate::frontend::parser::{Parser};
+use crate::interpreter::{Interpreter, InterpretResult, CachedRuntimeCall};
+use crate::interpreter::interp_ast::{AstInterpreter};
+use crate::interpreter::interp_ir::{IrInterpreter};
+use crate::backend::x64_emulator::{X64Emulator};
+use crate::backend::x64_print::{X64Printer};
+use crate::backend::x64_build::{X64Builder, native_toolchain};
+use crate::pipeline::{PassManager, PipelineOptions, ProgramForm};
+use crate::json::{Json};
+
+// what running a program at some stage did
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outcome {
+    pub value: Option<RuntimeI64>,
+    pub reads: Vec<RuntimeI64>, // the values read, in order
+    pub errors: Vec<String>,
+    pub out_of_fuel: bool, // stopped before it finished, so there's nothing to compare
+    pub too_deep: bool, // the same, but stopped by the depth limit
+    pub stdout: Option<String>, // only the executable has one
+}
+
+impl Outcome {
+
+    fn failed(errors: Vec<String>) -> Outcome {
+        Outcome {
+            value: None,
+            reads: vec!(),
+            errors: errors,
+            out_of_fuel: false,
+            too_deep: false,
+            stdout: None,
+        }
+    }
+
+    fn from_interpreter(result: InterpretResult, crc: &CachedRuntimeCall) -> Outcome {
+        let reads =
+            crc.cached_results_of(crate::symbol::READ)
+            .into_iter()
+            .map(|value| match value { RuntimeValue::RuntimeI64(n) => n })
+            .collect();
+
+        Outcome {
+            value: if result.had_error { None } else { result.value },
+            reads: reads,
+            errors: result.errors,
+            out_of_fuel: result.out_of_fuel,
+            too_deep: result.too_deep,
+            stdout: None,
+        }
+    }
+
+    // false if the limits stopped it
+    pub fn finished(&self) -> bool {
+        !self.out_of_fuel && !self.too_deep
+    }
+
+    // the error messages don't have to match, the interpreters word them differently
+    pub fn agrees_with(&self, other: &Outcome) -> bool {
+        self.value == other.value && self.reads == other.reads
+    }
+
+    pub fn to_json(&self) -> Json {
+        let stopped = match (self.out_of_fuel, self.too_deep) {
+            (true, _) => Json::str("out of fuel"),
+            (_, true) => Json::str("too deep"),
+            _ => Json::Null,
+        };
+
+        Json::object(vec!(
+            ("value", self.value.map_or(Json::Null, Json::Int)),
+            ("reads", Json::Array(self.reads.iter().map(|n| Json::Int(*n)).collect())),
+            ("errors", Json::Array(self.errors.iter().map(|e| Json::str(e)).collect())),
+            ("stopped", stopped),
+            ("stdout", self.stdout.as_deref().map_or(Json::Null, Json::str)),
+        ))
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.value {
+            Some(n) => write!(f, "{}", n)?,
+            None if self.out_of_fuel => write!(f, "out of fuel")?,
+            None if self.too_deep => write!(f, "too deep")?,
+            None => write!(f, "error")?,
+        }
+
+        write!(f, ", read {:?}", self.reads)?;
+
+        if !self.errors.is_empty() {
+            write!(f, ", {}", self.errors.join("; "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StageOutcome {
+    pub stage: &'static str, // "parse", the name of a pass, or "native"
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffReport {
+    pub stages: Vec<StageOutcome>,
+    pub native_skipped: Option<String>, // why the executable wasn't run
+}
+
+impl DiffReport {
+
+    pub fn reference(&self) -> &Outcome {
+        &self.stages[0].outcome
+    }
+
+    // the first stage that doesn't agree with the first stage that finished,
+    // usually the parsed program
+    pub fn first_divergence(&self) -> Option<&StageOutcome> {
+        let mut finished = self.stages.iter().filter(|stage| stage.outcome.finished());
+
+        let reference = finished.next()?;
+
+        finished.find(|stage| !stage.outcome.agrees_with(&reference.outcome))
+    }
+
+    pub fn agrees(&self) -> bool {
+        self.first_divergence().is_none()
+    }
+
+    // the first stage the limits stopped, it wasn't compared with the others
+    pub fn stopped(&self) -> Option<&StageOutcome> {
+        self.stages.iter().find(|stage| !stage.outcome.finished())
+    }
+
+    // nothing diverged, but not every stage could be compared
+    pub fn inconclusive(&self) -> bool {
+        self.agrees() && self.stopped().is_some()
+    }
+
+    // the stages in order, and what the last line of the Display impl says
+    pub fn to_json(&self) -> Json {
+        let stages =
+            self.stages
+            .iter()
+            .map(|stage| Json::object(vec!(
+                ("stage", Json::str(stage.stage)),
+                ("outcome", stage.outcome.to_json()),
+            )))
+            .collect();
+
+        Json::object(vec!(
+            ("stages", Json::Array(stages)),
+            ("native_skipped", self.native_skipped.as_deref().map_or(Json::Null, Json::str)),
+            ("first_divergence", self.first_divergence().map_or(Json::Null, |stage| Json::str(stage.stage))),
+            ("stopped", self.stopped().map_or(Json::Null, |stage| Json::str(stage.stage))),
+        ))
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stage in &self.stages {
+            writeln!(f, "{:>20}: {}", stage.stage, stage.outcome)?;
+        }
+
+        if let Some(reason) = &self.native_skipped {
+            writeln!(f, "{:>20}: skipped, {}", "native", reason)?;
+        }
+
+        match (self.first_divergence(), self.stopped()) {
+            (Some(stage), _) => write!(f, "results diverge at '{}'", stage.stage),
+            (None, Some(stage)) if stage.outcome.out_of_fuel => write!(f, "inconclusive, '{}' ran out of fuel", stage.stage),
+            (None, Some(stage)) => write!(f, "inconclusive, '{}' nested too deep", stage.stage),
+            (None, None) => write!(f, "all stages agree"),
+        }
+    }
+}
+
+// run the program in whatever form it's in, reading from 'crc'
+// x64 is 'complete' once prologue-epilogue has run, before that the registers
+// the calling convention wants preserved aren't saved yet. the x64 code checks
+// for overflow itself if it was compiled that way. the options give the overflow
+// mode and the limits
+pub fn execute(program: &ProgramForm, crc: CachedRuntimeCall, complete: bool, options: &PipelineOptions) -> Outcome {
+    let mut crc = crc;
+
+    let result = match program {
+        ProgramForm::Ast(p) => {
+            let mut ast_interpreter =
+                AstInterpreter::new(p, &mut crc)
+                .set_overflow(options.overflow)
+                .set_limits(options.limits);
+
+            Interpreter::new(&mut ast_interpreter).run()
+        },
+
+        ProgramForm::Ir(p) => {
+            let mut ir_interpreter =
+                IrInterpreter::new(p, &mut crc)
+                .set_overflow(options.overflow)
+                .set_limits(options.limits);
+
+            Interpreter::new(&mut ir_interpreter).run()
+        },
+
+        ProgramForm::X64(p) => {
+            let mut emulator =
+                X64Emulator::new(p, &mut crc)
+                .set_abi_checks(complete)
+                .set_limits(options.limits);
+
+            Interpreter::new(&mut emulator).run()
+        },
+    };
+
+    Outcome::from_interpreter(result, &crc)
+}
+
+fn execute_with(program: &ProgramForm, input: &[&str], complete: bool, options: &PipelineOptions) -> Outcome {
+    execute(program, CachedRuntimeCall::new().set_input(input.to_vec()), complete, options)
+}
+
+// the runtime prints the result on stdout, and every value read on stderr
+pub fn run_native(asm: String, input: &[&str]) -> Outcome {
+    let executable = match X64Builder::new("difftest".to_owned(), asm).build() {
+        Ok(executable) => executable,
+        Err(msg) => return Outcome::failed(vec!(msg)),
+    };
+
+    let child =
+        Command::new(executable.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => return Outcome::failed(vec!(e.to_string())),
+    };
+
+    {
+        let mut stdin = child.stdin.take().unwrap();
+
+        for line in input {
+            // the program might exit before reading everything
+            let _ = writeln!(stdin, "{}", line);
+        }
+    }
+
+    let output = match child.wait_with_output() {
+        Ok(output) => output,
+        Err(e) => return Outcome::failed(vec!(e.to_string())),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    let reads =
+        stderr
+        .lines()
+        .filter_map(|line| line.strip_prefix("read "))
+        .filter_map(|n| n.parse().ok())
+        .collect();
+
+    let errors: Vec<String> =
+        stderr
+        .lines()
+        .filter(|line| !line.starts_with("read "))
+        .map(|line| line.to_owned())
+        .collect();
+
+    let value =
+        if output.status.success() {
+            stdout.lines().last().and_then(|line| line.parse().ok())
+        } else {
+            None
+        };
+
+    Outcome {
+        value: value,
+        reads: reads,
+        errors: errors,
+        out_of_fuel: false,
+        too_deep: false,
+        stdout: Some(stdout.into_owned()),
+    }
+}
+
+pub struct DiffHarness {
+    options: PipelineOptions,
+    native: bool,
+}
+
+impl DiffHarness {
+
+    pub fn new(options: PipelineOptions) -> Self {
+        DiffHarness {
+            options: options,
+            native: true,
+        }
+    }
+
+    pub fn set_native(mut self, native: bool) -> Self {
+        self.native = native;
+        self
+    }
+
+    // Err if the program doesn't parse, there's nothing to compare then
+    pub fn run(&self, source: &str, input: &[&str]) -> Result<DiffReport, String> {
+        let mut parser = Parser::new(Lexer::new(source).lex());
+
+        let parsed = parser.parse();
+
+        if !parser.parse_success() {
+            let errors: Vec<String> = parser.errors().iter().map(|e| format!("{:?}", e)).collect();
+
+            return Err(errors.join("\n"));
+        }
+
+        let mut program = ProgramForm::Ast(parsed);
+
+        let mut stages = vec!(
+            StageOutcome {
+                stage: "parse",
+                outcome: execute_with(&program, input, false, &self.options),
+            }
+        );
+
+        let pass_names = PassManager::new(self.options.clone()).pass_names();
+
+        // the passes run one at a time, so the program can be run after each of them
+        for name in &pass_names {
+            let mut pm =
+                PassManager::custom(&[name])
+                .unwrap()
+                .set_verify(self.options.verify)
+                .set_overflow(self.options.overflow);
+
+            program = match pm.try_run(program) {
+                Ok(program) => program,
+                Err(e) => {
+                    stages.push(StageOutcome { stage: name, outcome: Outcome::failed(vec!(e.to_string())) });
+
+                    return Ok(DiffReport { stages: stages, native_skipped: Some("an earlier pass failed".to_owned()) });
+                }
+            };
+
+            let outcome = execute_with(&program, input, name == pass_names.last().unwrap(), &self.options);
+
+            stages.push(StageOutcome { stage: name, outcome: outcome });
+        }
+
+        let native_skipped =
+            if !self.native {
+                Some("turned off".to_owned())
+            } else if let Err(msg) = native_toolchain() {
+                Some(msg)
+            } else {
+                let outcome = run_native(X64Printer::new(program.into_x64()).print(), input);
+
+                stages.push(StageOutcome { stage: "native", outcome: outcome });
+
+                None
+            };
+
+        Ok(DiffReport {
+            stages: stages,
+            native_skipped: native_skipped,
+        })
+    }
+}
@@ -1,30 +1,264 @@
+/*
+    the nodes of a program live in an arena, a node refers to its children by
+    their index in it:
+
+        (+ 1 (read))    0: Int(1)
+                        1: Prim { op: read, args: [] }
+                        2: Prim { op: +, args: [0, 1] }     exp: 2
+
+    a pass takes the program and adds the nodes it makes to the same arena. the
+    parts it leaves as they were keep their index, nothing is cloned. the nodes it
+    replaced stay behind unused, only what can be reached from 'exp' is the program
+
+    the passes recurse over the tree, the parser turns away programs nested deeper
+    than MAX_NESTING so they all fit in STACK_SIZE. a program compares and prints as
+    the tree it holds, however its nodes are laid out
+*/
+
+#![allow(dead_code)]
+
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
 use runtime::types::{RuntimeI64};
 
 use crate::types::{IdString};
 use super::token::{Token};
 
-#[derive(Clone, Debug, PartialEq)]
+// deeper than anyone writes by hand
+pub const MAX_NESTING: usize = 4000;
+
+// what the compiler runs on, a program nested MAX_NESTING deep takes about 8 MB
+// in a debug build, more than the 2 MB a thread gets by default
+pub const STACK_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct LetBinding {
     pub identifier: IdString,
-    pub expr: AstNode
+    pub expr: NodeId
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum AstNode {
     Int(RuntimeI64),
-    Prim {op: IdString, args: Vec<AstNode>},
+    Prim {op: IdString, args: Vec<NodeId>},
 
     Let {
         bindings: Vec<LetBinding>,
-        body: Box<AstNode>
+        body: NodeId
     },
 
     Var { name: IdString },
     Error { msg: IdString, token: Token },
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Default)]
+pub struct Ast {
+    nodes: Vec<AstNode>,
+}
+
+impl Ast {
+
+    pub fn new() -> Self {
+        Ast {
+            nodes: vec!(),
+        }
+    }
+
+    pub fn add(&mut self, node: AstNode) -> NodeId {
+        self.nodes.push(node);
+
+        NodeId((self.nodes.len() - 1) as u32)
+    }
+
+    // the children of a node, the bindings of a let come before its body
+    pub fn children(&self, id: NodeId) -> Vec<NodeId> {
+        match &self[id] {
+            AstNode::Prim { args, .. } => args.clone(),
+
+            AstNode::Let { bindings, body } => {
+                bindings.iter().map(|b| b.expr).chain(std::iter::once(*body)).collect()
+            },
+
+            _ => vec!(),
+        }
+    }
+
+    // the i-th argument of the primitive at 'id', so a pass can go through them
+    // while it adds to the arena
+    pub fn arg(&self, id: NodeId, i: usize) -> NodeId {
+        match &self[id] {
+            AstNode::Prim { args, .. } => args[i],
+            node => panic!("not a primitive: {:?}", node),
+        }
+    }
+
+    pub fn arg_mut(&mut self, id: NodeId, i: usize) -> &mut NodeId {
+        match &mut self[id] {
+            AstNode::Prim { args, .. } => &mut args[i],
+            node => panic!("not a primitive: {:?}", node),
+        }
+    }
+
+    // the i-th binding of the let at 'id'
+    pub fn binding(&self, id: NodeId, i: usize) -> LetBinding {
+        match &self[id] {
+            AstNode::Let { bindings, .. } => bindings[i],
+            node => panic!("not a let: {:?}", node),
+        }
+    }
+
+    pub fn binding_mut(&mut self, id: NodeId, i: usize) -> &mut LetBinding {
+        match &mut self[id] {
+            AstNode::Let { bindings, .. } => &mut bindings[i],
+            node => panic!("not a let: {:?}", node),
+        }
+    }
+
+    // how many nodes deep the tree under 'id' is, a leaf is 1
+    pub fn depth(&self, id: NodeId) -> usize {
+        let mut deepest = 0;
+        let mut todo = vec!((id, 1));
+
+        while let Some((id, depth)) = todo.pop() {
+            deepest = deepest.max(depth);
+
+            todo.extend(self.children(id).into_iter().map(|child| (child, depth + 1)));
+        }
+
+        deepest
+    }
+
+    // the tree under 'a' is the same as the one under 'b' in 'other'
+    pub fn same_tree(&self, a: NodeId, other: &Ast, b: NodeId) -> bool {
+        let mut todo = vec!((a, b));
+
+        while let Some((a, b)) = todo.pop() {
+            let same = match (&self[a], &other[b]) {
+                (AstNode::Prim { op: op_a, args: args_a }, AstNode::Prim { op: op_b, args: args_b }) => {
+                    op_a == op_b && args_a.len() == args_b.len()
+                },
+
+                (AstNode::Let { bindings: bindings_a, .. }, AstNode::Let { bindings: bindings_b, .. }) => {
+                    bindings_a.len() == bindings_b.len() &&
+                    bindings_a.iter().zip(bindings_b).all(|(x, y)| x.identifier == y.identifier)
+                },
+
+                (node_a, node_b) => node_a == node_b,
+            };
+
+            if !same {
+                return false;
+            }
+
+            todo.extend(self.children(a).into_iter().zip(other.children(b)));
+        }
+
+        true
+    }
+}
+
+impl Index<NodeId> for Ast {
+    type Output = AstNode;
+
+    fn index(&self, id: NodeId) -> &AstNode {
+        &self.nodes[id.0 as usize]
+    }
+}
+
+impl IndexMut<NodeId> for Ast {
+    fn index_mut(&mut self, id: NodeId) -> &mut AstNode {
+        &mut self.nodes[id.0 as usize]
+    }
+}
+
+#[derive(Clone)]
 pub struct Program {
     pub info: (),
-    pub exp: AstNode,
-}
\ No newline at end of file
+    pub ast: Ast,
+    pub exp: NodeId,
+}
+
+impl Program {
+
+    // a program of just 'node'
+    pub fn leaf(node: AstNode) -> Program {
+        let mut ast = Ast::new();
+
+        let exp = ast.add(node);
+
+        Program {
+            info: (),
+            ast: ast,
+            exp: exp,
+        }
+    }
+
+    pub fn root(&self) -> &AstNode {
+        &self.ast[self.exp]
+    }
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Program) -> bool {
+        self.ast.same_tree(self.exp, &other.ast, other.exp)
+    }
+}
+
+// the tree under a node, printed the way it would be if the children were in the nodes
+struct Tree<'a> {
+    ast: &'a Ast,
+    id: NodeId,
+}
+
+struct BindingTree<'a> {
+    ast: &'a Ast,
+    binding: &'a LetBinding,
+}
+
+impl<'a> fmt::Debug for Tree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tree = |id: &NodeId| Tree { ast: self.ast, id: *id };
+
+        match &self.ast[self.id] {
+            AstNode::Prim { op, args } => {
+                f.debug_struct("Prim")
+                    .field("op", op)
+                    .field("args", &args.iter().map(tree).collect::<Vec<Tree>>())
+                    .finish()
+            },
+
+            AstNode::Let { bindings, body } => {
+                let bindings: Vec<BindingTree> = bindings.iter().map(|b| BindingTree { ast: self.ast, binding: b }).collect();
+
+                f.debug_struct("Let")
+                    .field("bindings", &bindings)
+                    .field("body", &tree(body))
+                    .finish()
+            },
+
+            leaf => leaf.fmt(f),
+        }
+    }
+}
+
+impl<'a> fmt::Debug for BindingTree<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LetBinding")
+            .field("identifier", &self.binding.identifier)
+            .field("expr", &Tree { ast: self.ast, id: self.binding.expr })
+            .finish()
+    }
+}
+
+impl fmt::Debug for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Program")
+            .field("info", &self.info)
+            .field("exp", &Tree { ast: &self.ast, id: self.exp })
+            .finish()
+    }
+}
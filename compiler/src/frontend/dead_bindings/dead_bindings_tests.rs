@@ -0,0 +1,159 @@
+use runtime::types::{RuntimeValue};
+
+use crate::types::{Overflow};
+use crate::frontend::ast::{Ast, AstNode, Program, LetBinding};
+use crate::utility::{test_ast_helper, test_interp_ast_helper, AstStep};
+
+use super::{eliminate_dead_bindings};
+
+fn helper(prog: &'static str) -> Program {
+    test_ast_helper(
+        prog,
+        vec!(AstStep::Uniquify, AstStep::PartialEvaluation, AstStep::DeadBindings)
+    )
+}
+
+// the pass must not change the result or what was read, and in which order
+fn check_same_behaviour(prog: &'static str, input: Vec<&str>) {
+    let before = test_ast_helper(prog, vec!(AstStep::Uniquify, AstStep::PartialEvaluation));
+    let after = eliminate_dead_bindings(before.clone(), Overflow::Wrap);
+
+    let (before_result, before_reads) = test_interp_ast_helper(before, input.clone());
+    let (after_result, after_reads) = test_interp_ast_helper(after, input);
+
+    assert!(!before_result.had_error);
+    assert!(!after_result.had_error);
+
+    assert_eq!(before_result.value, after_result.value);
+    assert_eq!(before_reads, after_reads);
+}
+
+#[test]
+fn dead_bindings_remove_unused() {
+    let program = helper("(let ([x (read)] [y (- x)]) x)");
+
+    let x = crate::idstr!("x.1");
+
+    let mut ast = Ast::new();
+
+    let read = ast.add(AstNode::Prim { op: crate::idstr!("read"), args: vec!() });
+    let body = ast.add(AstNode::Var { name: x });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x,
+                expr: read
+            }
+        ),
+        body: body
+    });
+
+    let expected = Program {
+        info: (),
+        ast: ast,
+        exp: exp
+    };
+
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn dead_bindings_remove_whole_let() {
+    let program = helper("(+ 1 (let ([x (read)]) (let ([y (+ x 1)]) x)))");
+
+    let x = crate::idstr!("x.1");
+
+    let mut ast = Ast::new();
+
+    let one = ast.add(AstNode::Int(1));
+    let read = ast.add(AstNode::Prim { op: crate::idstr!("read"), args: vec!() });
+    let body = ast.add(AstNode::Var { name: x });
+
+    let inner = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x,
+                expr: read
+            }
+        ),
+        body: body
+    });
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(one, inner)
+    });
+
+    let expected = Program {
+        info: (),
+        ast: ast,
+        exp: exp
+    };
+
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn dead_bindings_keep_unused_read() {
+    let program = helper("(let ([x (read)]) (read))");
+
+    match program.root() {
+        AstNode::Let { bindings, .. } => {
+            assert_eq!(bindings.len(), 1);
+        },
+
+        _ => panic!("the binding to read was removed: {:?}", program),
+    }
+
+    check_same_behaviour("(let ([x (read)]) (read))", vec!("1", "2"));
+}
+
+#[test]
+fn dead_bindings_keep_read_inside_unused_expression() {
+    let program = helper("(let ([x (+ 1 (- (read)))] [y (read)]) y)");
+
+    match program.root() {
+        AstNode::Let { bindings, .. } => {
+            assert_eq!(bindings.len(), 2);
+        },
+
+        _ => panic!("a binding containing read was removed: {:?}", program),
+    }
+
+    check_same_behaviour("(let ([x (+ 1 (- (read)))] [y (read)]) y)", vec!("10", "20"));
+}
+
+#[test]
+fn dead_bindings_read_sequence() {
+    check_same_behaviour(
+        "(let ([a (read)] [b (+ a 1)] [c (read)]) (let ([d (+ c b)]) (+ c (read))))",
+        vec!("1", "2", "3")
+    );
+
+    let (_, reads) = test_interp_ast_helper(
+        helper("(let ([a (read)] [b (+ a 1)] [c (read)]) (let ([d (+ c b)]) (+ c (read))))"),
+        vec!("1", "2", "3")
+    );
+
+    assert_eq!(
+        reads,
+        vec!(RuntimeValue::RuntimeI64(1), RuntimeValue::RuntimeI64(2), RuntimeValue::RuntimeI64(3))
+    );
+}
+
+#[test]
+fn dead_bindings_keep_checked_arithmetic() {
+    let run = |overflow: Overflow| {
+        crate::pipeline::PassManager::custom(&["uniquify", "dead-bindings"])
+            .unwrap()
+            .set_overflow(overflow)
+            .run(crate::pipeline::ProgramForm::Ast(crate::utility::test_parse_helper("(let ([x (+ 9223372036854775807 1)]) 2)")))
+            .into_ast()
+    };
+
+    assert_eq!(*run(Overflow::Wrap).root(), AstNode::Int(2));
+
+    // the overflow would go unnoticed without the binding
+    assert!(matches!(run(Overflow::Checked).root(), AstNode::Let { .. }));
+}
@@ -0,0 +1,136 @@
+/*
+    remove let bindings whose value is never used
+
+    i.e. (let ([x 10] [y (read)]) y) will be changed to
+
+        (let ([y (read)]) y)
+
+    a binding is only removed if evaluating it can't be observed, anything containing
+    an effectful primitive such as read has to stay, otherwise the input would
+    be consumed in a different order. when overflow is checked an addition or a
+    negation can fail at run time, so they count as effectful as well
+*/
+
+#[cfg(test)]
+mod dead_bindings_tests;
+
+use std::collections::HashSet;
+
+use crate::types::{IdString, Overflow};
+use crate::symbol::{Symbol, PLUS, MINUS, READ};
+
+use super::ast::{Ast, AstNode, NodeId, LetBinding, Program};
+
+// primitives whose evaluation can be observed from the outside
+pub const EFFECTFUL_PRIMS: &[Symbol] = &[READ];
+
+// primitives that report an overflow when it's checked
+pub const CHECKED_PRIMS: &[Symbol] = &[PLUS, MINUS];
+
+pub fn has_effect(ast: &Ast, e: NodeId, overflow: Overflow) -> bool {
+    match &ast[e] {
+        AstNode::Int(_) | AstNode::Var { .. } => false,
+
+        AstNode::Prim { op, args } => {
+            EFFECTFUL_PRIMS.contains(op) ||
+            (overflow == Overflow::Checked && CHECKED_PRIMS.contains(op)) ||
+            args.iter().any(|arg| has_effect(ast, *arg, overflow))
+        },
+
+        AstNode::Let { bindings, body } => {
+            bindings.iter().any(|b| has_effect(ast, b.expr, overflow)) || has_effect(ast, *body, overflow)
+        },
+
+        // keep errors around so they are still reported
+        AstNode::Error { .. } => true,
+    }
+}
+
+// returns the new expression and the variables it uses, a let or primitive is
+// changed where it is. the variables are only looked up, so they don't need an order
+fn eliminate_exp(ast: &mut Ast, e: NodeId, overflow: Overflow) -> (NodeId, HashSet<IdString>) {
+    match &ast[e] {
+        AstNode::Int(_) | AstNode::Error { .. } => {
+            (e, HashSet::new())
+        },
+
+        AstNode::Var { name } => {
+            let used = HashSet::from([*name]);
+
+            (e, used)
+        },
+
+        AstNode::Prim { args, .. } => {
+            let mut used = HashSet::new();
+
+            for i in 0..args.len() {
+                let (new_arg, arg_used) = eliminate_exp(ast, ast.arg(e, i), overflow);
+
+                used.extend(arg_used);
+                *ast.arg_mut(e, i) = new_arg;
+            }
+
+            (e, used)
+        },
+
+        AstNode::Let { bindings, body } => {
+
+            let (count, body) = (bindings.len(), *body);
+
+            let (new_body, mut used) = eliminate_exp(ast, body, overflow);
+
+            // go backwards, so that we know whether a binding is used by
+            // the body or any of the bindings after it
+            let mut kept_bindings: Vec<LetBinding> = vec!();
+
+            for i in (0..count).rev() {
+                let binding = ast.binding(e, i);
+
+                let is_used = used.contains(&binding.identifier);
+
+                if !is_used && !has_effect(ast, binding.expr, overflow) {
+                    continue;
+                }
+
+                used.remove(&binding.identifier);
+
+                let (new_expr, expr_used) = eliminate_exp(ast, binding.expr, overflow);
+
+                used.extend(expr_used);
+
+                kept_bindings.push(
+                    LetBinding {
+                        identifier: binding.identifier,
+                        expr: new_expr
+                    }
+                );
+            }
+
+            if kept_bindings.is_empty() {
+                return (new_body, used);
+            }
+
+            kept_bindings.reverse();
+
+            ast[e] =
+                AstNode::Let {
+                    bindings: kept_bindings,
+                    body: new_body
+                };
+
+            (e, used)
+        },
+    }
+}
+
+pub fn eliminate_dead_bindings(p: Program, overflow: Overflow) -> Program {
+    let mut ast = p.ast;
+
+    let (exp, _) = eliminate_exp(&mut ast, p.exp, overflow);
+
+    Program {
+        info: p.info,
+        ast: ast,
+        exp: exp,
+    }
+}
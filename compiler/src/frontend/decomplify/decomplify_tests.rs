@@ -1,4 +1,4 @@
-use crate::frontend::ast::{AstNode, Program, LetBinding};
+use crate::frontend::ast::{Ast, AstNode, Program, LetBinding};
 use crate::utility::{test_ast_helper, AstStep};
 
 
@@ -18,30 +18,37 @@ fn decomplify_addition() {
     let x = crate::idstr!("x");
     let y = crate::idstr!("y");
 
+    let mut ast = Ast::new();
+
+    let x_node = ast.add(AstNode::Var { name: x });
+    let y_node = ast.add(AstNode::Var { name: y });
+    let tmp_node = ast.add(AstNode::Var { name: tmp });
+
+    let inner = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(x_node, y_node)
+    });
+
+    let outer = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(x_node, tmp_node)
+    });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: tmp,
+                expr: inner
+            }
+        ),
+
+        body: outer
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: tmp.clone(),
-                    expr: AstNode::Prim {
-                        op: crate::idstr!("+"),
-                        args: vec!(
-                            AstNode::Var { name: x.clone() },
-                            AstNode::Var { name: y.clone() },
-                        )
-                    }
-                }
-            ),
-
-            body: Box::new(AstNode::Prim {
-                op: crate::idstr!("+"),
-                args: vec!(
-                    AstNode::Var { name: x.clone() },
-                    AstNode::Var { name: tmp }
-                )
-            })
-        }
+        ast: ast,
+        exp: exp
     };
 
     assert_eq!(decomplified, expected);
@@ -49,44 +56,187 @@ fn decomplify_addition() {
 
 #[test]
 fn decomplify_let_read() {
+    // x is known at compile time, so the partial evaluator replaces it with 42
     let decomplified = helper("(let ([x 42]) (+ x (read)))");
 
+    let tmp = crate::idstr!("tmp.0");
+
+    let mut ast = Ast::new();
+
+    let read = ast.add(AstNode::Prim {
+        op: crate::idstr!("read"),
+        args: vec!()
+    });
+
+    let forty_two = ast.add(AstNode::Int(42));
+    let tmp_node = ast.add(AstNode::Var { name: tmp });
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(forty_two, tmp_node)
+    });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: tmp,
+                expr: read
+            },
+        ),
+
+        body: sum
+    });
+
+    let expected = Program {
+        info: (),
+        ast: ast,
+        exp: exp
+    };
+
+    assert_eq!(decomplified, expected);
+}
+
+#[test]
+fn decomplify_let_read_binding() {
+    let decomplified = helper("(let ([x (read)]) (+ x (- x)))");
+
     let tmp = crate::idstr!("tmp.0");
     let x_var = crate::idstr!("x.1");
 
+    let mut ast = Ast::new();
+
+    let read = ast.add(AstNode::Prim {
+        op: crate::idstr!("read"),
+        args: vec!()
+    });
+
+    let x_node = ast.add(AstNode::Var { name: x_var });
+    let tmp_node = ast.add(AstNode::Var { name: tmp });
+
+    let negation = ast.add(AstNode::Prim {
+        op: crate::idstr!("-"),
+        args: vec!(x_node)
+    });
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(x_node, tmp_node)
+    });
+
+    let inner = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: tmp,
+                expr: negation
+            },
+        ),
+
+        body: sum
+    });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x_var,
+                expr: read
+            }
+        ),
+
+        body: inner
+    });
+
+    let expected = Program {
+        info: (),
+        ast: ast,
+        exp: exp
+    };
+
+    assert_eq!(decomplified, expected);
+}
+
+#[test]
+fn decomplify_negate_read_argument() {
+    // the read inside the negation has to get its own tmp as well
+    let decomplified = helper("(+ (read) (- (read)))");
+
+    let tmp0 = crate::idstr!("tmp.0");
+    let tmp1 = crate::idstr!("tmp.1");
+    let tmp2 = crate::idstr!("tmp.2");
+
+    let mut ast = Ast::new();
+
+    let read = ast.add(AstNode::Prim { op: crate::idstr!("read"), args: vec!() });
+    let tmp0_node = ast.add(AstNode::Var { name: tmp0 });
+    let tmp1_node = ast.add(AstNode::Var { name: tmp1 });
+    let tmp2_node = ast.add(AstNode::Var { name: tmp2 });
+
+    let negation = ast.add(AstNode::Prim {
+        op: crate::idstr!("-"),
+        args: vec!(tmp2_node)
+    });
+
+    let inner = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: tmp2,
+                expr: read
+            }
+        ),
+        body: negation
+    });
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(tmp0_node, tmp1_node)
+    });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: tmp0,
+                expr: read
+            },
+            LetBinding {
+                identifier: tmp1,
+                expr: inner
+            },
+        ),
+
+        body: sum
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: x_var.clone(),
-                    expr: AstNode::Int(42)
-                }
-            ),
-
-            body: Box::new(
-                AstNode::Let {
-                    bindings: vec!(
-                        LetBinding {
-                            identifier: tmp.clone(),
-                            expr: AstNode::Prim {
-                                op: crate::idstr!("read"),
-                                args: vec!()
-                            }
-                        },
-                    ),
-
-                    body: 
-                        Box::new(AstNode::Prim {
-                            op: crate::idstr!("+"),
-                            args: vec!(
-                                AstNode::Var { name: x_var},
-                                AstNode::Var { name: tmp },
-                            )
-                        })
-                    })
-                }
+        ast: ast,
+        exp: exp
     };
 
     assert_eq!(decomplified, expected);
-}
\ No newline at end of file
+
+    let (result, _) = crate::utility::test_interp_ir_helper(crate::utility::test_ir_helper("(+ (read) (- (read)))"), vec!("10", "3"));
+
+    assert_eq!(result.value, Some(7));
+}
+
+#[test]
+fn decomplify_keeps_binding_order() {
+    // a's tmp binding has to stay in front of c, the reads happen in that order
+    let decomplified = test_ast_helper(
+        "(let ([a (+ (read) 1)] [c (read)]) c)",
+        vec!(AstStep::Uniquify, AstStep::Decomplify)
+    );
+
+    let names: Vec<String> = match decomplified.root() {
+        AstNode::Let { bindings, .. } => bindings.iter().map(|b| b.identifier.to_string()).collect(),
+        other => panic!("expected a let, got {:?}", other),
+    };
+
+    assert_eq!(names, vec!("tmp.0", "a.1", "c.1"));
+
+    let (result, _) = crate::utility::test_interp_ir_helper(
+        crate::utility::test_ir_helper("(let ([a (+ (read) 1)] [c (read)]) c)"),
+        vec!("1", "2")
+    );
+
+    assert_eq!(result.value, Some(2));
+}
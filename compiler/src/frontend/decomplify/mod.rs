@@ -12,20 +12,27 @@
 #[cfg(test)]
 mod decomplify_tests;
 
+use std::collections::HashMap;
+
 use crate::types::{IdString};
+use crate::symbol::{PLUS, MINUS, READ};
 
-use super::ast::{AstNode, LetBinding, Program};
+use super::ast::{Ast, AstNode, NodeId, LetBinding, Program};
 
 struct Rco {
     num: i64,
-    env: Vec<(IdString, AstNode)>,
+    // expressions that were replaced by a tmp variable, each one is taken out
+    // again exactly once, when its binding is created
+    env: HashMap<IdString, NodeId>,
+    ast: Ast,
 }
 
 impl Rco {
-    pub fn new() -> Rco {
+    pub fn new(ast: Ast) -> Rco {
         Rco {
             num: 0,
-            env: vec!()
+            env: HashMap::new(),
+            ast: ast,
         }
     }
 
@@ -39,24 +46,22 @@ impl Rco {
         crate::idstr!(new_tmp_var)
     }
 
-    fn env_get(&self, find: &String) -> Option<AstNode> {
-        for binding in &self.env {
-            if **binding.0 == *find {
-                return Some(binding.1.clone())
-            }
-        }
+    fn env_take(&mut self, find: IdString) -> Option<NodeId> {
+        self.env.remove(&find)
+    }
 
-        return None
+    fn env_set(&mut self, name: IdString, expr: NodeId) {
+        self.env.insert(name, expr);
     }
 
-    fn env_set(&mut self, name: IdString, expr: AstNode) {
-        self.env.push((name, expr));
+    fn var(&mut self, name: IdString) -> NodeId {
+        self.ast.add(AstNode::Var { name: name })
     }
 
-    // returns (true, AstNode) if whatever was passed in had to be atomized
-    fn rco_atom(&mut self, e: AstNode) -> (bool, AstNode) {
+    // returns (true, node) if whatever was passed in had to be atomized
+    fn rco_atom(&mut self, e: NodeId) -> (bool, NodeId) {
 
-        match &e {
+        match &self.ast[e] {
             // already an atom
             AstNode::Int(_) => {
                 (false, e)
@@ -72,35 +77,30 @@ impl Rco {
                 let new_tmp = self.tmp();
                 let expr = self.rco_expr(e);
 
-                self.env_set(new_tmp.clone(), expr);
+                self.env_set(new_tmp, expr);
 
-                (true, AstNode::Var {
-                    name: new_tmp
-                })
+                (true, self.var(new_tmp))
             },
 
             AstNode::Prim { op, .. } => {
 
-                match &op[..] {
-                    "+" => {
+                match *op {
+                    // the arguments might need a tmp variable themselves
+                    PLUS | MINUS => {
                         let new_tmp = self.tmp();
                         let expr = self.rco_expr(e);
 
-                        self.env_set(new_tmp.clone(), expr);
+                        self.env_set(new_tmp, expr);
 
-                        (true, AstNode::Var {
-                            name: new_tmp
-                        })
+                        (true, self.var(new_tmp))
                     },
 
-                    "read" | "-" => {
+                    READ => {
                         let new_tmp = self.tmp();
 
-                        self.env_set(new_tmp.clone(), e);
+                        self.env_set(new_tmp, e);
 
-                        (true, AstNode::Var {
-                            name: new_tmp,
-                        })
+                        (true, self.var(new_tmp))
 
                     },
 
@@ -116,9 +116,25 @@ impl Rco {
         }
     }
 
-    fn rco_expr(&mut self, e: AstNode) -> AstNode {
+    // the binding for a tmp variable created by rco_atom
+    fn tmp_binding(&mut self, name: IdString) -> LetBinding {
+        match self.env_take(name) {
+            Some(expr) => {
+                LetBinding {
+                    identifier: name,
+                    expr: expr
+                }
+            },
+
+            None => {
+                panic!("rco_expr:{}: tmp var '{}' binding not found.", line!(), name);
+            }
+        }
+    }
+
+    fn rco_expr(&mut self, e: NodeId) -> NodeId {
 
-        match &e {
+        match &self.ast[e] {
             AstNode::Int(_) => {
                 e
             }
@@ -129,39 +145,36 @@ impl Rco {
 
             AstNode::Let { bindings, body } => {
 
-                let original_bindings = bindings.clone();
-
-                let mut untouched_bindings = vec!();
-
                 /*
-                    this will contain new bindings that are created when
-                    the exp in the let binding was atomized
-
-                    the original expression will be turned into a new let expression
+                    when the exp in a let binding was atomized, the tmp bindings it needs
+                    are put in front of it
 
                     i.e. let [x (+ 2 (-10))] will be turned into
 
                     let [tmp.0 (-10)] [x (+ 2 tmp.0)]
 
+                    the bindings stay in their original order, as evaluating them
+                    might read input
                 */
-                let mut changed_bindings: Vec<LetBinding> = Vec::new();
+                let (count, body) = (bindings.len(), *body);
+
+                let mut new_bindings: Vec<LetBinding> = Vec::new();
 
-                // check if any of the existing bindings need to be atomized
-                for i in 0..original_bindings.len() {
+                for i in 0..count {
 
-                    let current_binding = original_bindings[i].clone();
+                    let current_binding = self.ast.binding(e, i);
+
+                    let var_name = current_binding.identifier;
 
                     let maybe_new_binding = self.rco_expr(current_binding.expr);
 
-                    match maybe_new_binding {
+                    match &self.ast[maybe_new_binding] {
 
                         // a tmp binding was needed because of atomization
-                        AstNode::Let { mut bindings, body } => {
-
-                            let var_name = current_binding.identifier;
+                        AstNode::Let { bindings, body } => {
 
-                            changed_bindings.append(&mut bindings);
-                            changed_bindings.push(
+                            new_bindings.extend_from_slice(bindings);
+                            new_bindings.push(
                                 LetBinding {
                                     identifier: var_name,
                                     expr: *body
@@ -171,41 +184,45 @@ impl Rco {
 
                         // nothing needed to be done, keep the old binding as it was
                         _ => {
-                            untouched_bindings.push(original_bindings[i].clone());
+                            new_bindings.push(
+                                LetBinding {
+                                    identifier: var_name,
+                                    expr: maybe_new_binding
+                                }
+                            );
                         }
                     }
                 }
 
-                let new_body = self.rco_expr(*body.clone());
+                let new_body = self.rco_expr(body);
 
-                untouched_bindings.append(&mut changed_bindings);
-
-                let new_node =
+                self.ast.add(
                     AstNode::Let {
-                        bindings: untouched_bindings,
-                        body: Box::new(new_body),
-                    };
-
-                new_node
+                        bindings: new_bindings,
+                        body: new_body,
+                    }
+                )
             },
 
-            AstNode::Prim { op, args } => {
+            AstNode::Prim { op, .. } => {
+
+                let op = *op;
 
-                match &op[..] {
-                    "read" => {
+                match op {
+                    READ => {
                         e
                     },
 
                     // potentially need to atomize args[0]
-                    "-" => {
-                        let arg = self.rco_atom(args[0].clone());
+                    MINUS => {
+                        let arg = self.rco_atom(self.ast.arg(e, 0));
 
-                        if arg.0 == true {
+                        if arg.0 {
 
                             let var_name = 
-                                match &arg.1 {
+                                match &self.ast[arg.1] {
                                     AstNode::Var { name } => {
-                                        name.clone()
+                                        *name
                                     }
                                     
                                     _ => {
@@ -214,60 +231,42 @@ impl Rco {
                                 };
 
                             let let_binding: Vec<LetBinding> = vec!(
-                                LetBinding {
-                                    identifier: var_name.clone(),
-                                    expr: self.env_get(&*var_name).unwrap()
-                                }
+                                self.tmp_binding(var_name)
                             );
 
-                            AstNode::Let {
-                                bindings: let_binding,
-                                body: Box::new(
+                            let body =
+                                self.ast.add(
                                     AstNode::Prim {
-                                        op: op.clone(),
+                                        op: op,
                                         args: vec!(arg.1)
                                     }
-                                )
-                            }
+                                );
+
+                            self.ast.add(
+                                AstNode::Let {
+                                    bindings: let_binding,
+                                    body: body
+                                }
+                            )
                         } else {
                             e
                         }
                     },
 
-                    "+" => {
+                    PLUS => {
 
                         let mut let_bindings: Vec<LetBinding> = vec!();
 
-                        let lhand = self.rco_atom(args[0].clone());
-                        let rhand = self.rco_atom(args[1].clone());
-
-                        let results = vec!(&lhand, &rhand);
-
-                        let mut was_atomized = false;
-
-                        for node in &results {
-                            match node {
-                                (_, AstNode::Int(_)) => {},
-
-                                (atm, AstNode::Var { name }) => {
+                        let lhand = self.rco_atom(self.ast.arg(e, 0));
+                        let rhand = self.rco_atom(self.ast.arg(e, 1));
 
-                                    if *atm {
-                                        match self.env_get(&(**name)) {
-                                            Some(expr) => {
-                                                let_bindings.push(
-                                                    LetBinding {
-                                                        identifier: name.clone(),
-                                                        expr: expr
-                                                    }
-                                                );
+                        for (atm, node) in [lhand, rhand] {
+                            match self.ast[node] {
+                                AstNode::Int(_) => {},
 
-                                                was_atomized = true;
-                                            },
-
-                                            _ => {
-                                                panic!("rco_expr:{}: tmp var '{}' binding not found.", line!(), name);
-                                            }
-                                        }
+                                AstNode::Var { name } => {
+                                    if atm {
+                                        let_bindings.push(self.tmp_binding(name));
                                     }
                                 },
 
@@ -277,18 +276,23 @@ impl Rco {
                             }
                         }
 
-                        if was_atomized {
-                            AstNode::Let {
-                                bindings: let_bindings,
-                                body: Box::new(
-                                    AstNode::Prim {
-                                        op: op.clone(),
-                                        args: vec!(lhand.1, rhand.1)
-                                    }
-                                )
-                            }
+                        let new_prim =
+                            self.ast.add(
+                                AstNode::Prim {
+                                    op: op,
+                                    args: vec!(lhand.1, rhand.1)
+                                }
+                            );
+
+                        if let_bindings.is_empty() {
+                            new_prim
                         } else {
-                            e
+                            self.ast.add(
+                                AstNode::Let {
+                                    bindings: let_bindings,
+                                    body: new_prim
+                                }
+                            )
                         }
                     },
 
@@ -304,18 +308,21 @@ impl Rco {
         }
     }
 
-    pub fn decomplify(&mut self, p: Program) -> AstNode {
-        self.rco_expr(p.exp)
+    pub fn decomplify(&mut self, exp: NodeId) -> NodeId {
+        self.rco_expr(exp)
     }
 }
 
 
 pub fn decomplify_program(program: Program) -> Program {
 
-    let mut rco = Rco::new();
+    let mut rco = Rco::new(program.ast);
+
+    let exp = rco.decomplify(program.exp);
 
     Program {
         info: (),
-        exp: rco.decomplify(program),
+        ast: rco.ast,
+        exp: exp,
     }
-}
\ No newline at end of file
+}
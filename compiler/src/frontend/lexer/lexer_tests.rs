@@ -76,4 +76,24 @@ fn error_unknown_caret() {
     );
 
     assert_eq!(tokens, expected_tokens);
-}
\ No newline at end of file
+}
+
+#[test]
+fn lines_and_columns() {
+
+    let input = "(+ 1\n  2)";
+
+    let mut lexer = Lexer::new(input);
+
+    let tokens = lexer.lex();
+
+    let expected_tokens: Vec<Token> = vec!(
+        Token { ttype: TokenType::Lparen, lexeme: "(".to_owned(), line: 1, col: 1 },
+        Token { ttype: TokenType::Add, lexeme: "+".to_owned(), line: 1, col: 2 },
+        Token { ttype: TokenType::Number, lexeme: "1".to_owned(), line: 1, col: 4 },
+        Token { ttype: TokenType::Number, lexeme: "2".to_owned(), line: 2, col: 3 },
+        Token { ttype: TokenType::Rparen, lexeme: ")".to_owned(), line: 2, col: 4 },
+    );
+
+    assert_eq!(tokens, expected_tokens);
+}
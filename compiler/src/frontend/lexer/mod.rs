@@ -21,17 +21,16 @@ trait VecChar {
 
 impl VecChar for Lexer {
     fn to_string(&self, start:usize, end:usize) -> String {
-        self.source_code[start..end].into_iter().collect::<String>()
+        self.source_code[start..end].iter().collect::<String>()
     }
 }
 
 fn is_alpha(c: char) -> bool {
-    c >= 'a' && c <= 'z' ||
-    c >= 'A' && c <= 'Z'
+    c.is_ascii_alphabetic()
 }
 
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
 fn is_id_start(c: char) -> bool {
@@ -108,7 +107,7 @@ impl Lexer {
 
         loop {
             match c {
-                '\n' => { self.line += 1; self.column = 1;},
+                '\n' => { self.line += 1; self.column = 0;}, // advance moves it to 1
                 ' ' => (),
                 '\r' => (),
                 '\t' => (),
@@ -161,30 +160,25 @@ impl Lexer {
 
         self.skip_whitespace();
 
-        let token = {
-
-            let c = self.peek();
-
-            match c {
-                '+'         => self.make_token(TokenType::Add, 1),
-                '-'         => self.make_token(TokenType::Negate, 1),
-                '('         => self.make_token(TokenType::Lparen, 1),
-                ')'         => self.make_token(TokenType::Rparen, 1),
-                '['         => self.make_token(TokenType::Lbracket, 1),
-                ']'         => self.make_token(TokenType::Rbracket, 1),
-                '0'..='9'   => self.number(),
-                '\0'        => self.make_token(TokenType::EndOfFile, 1),
-                _           => {
-                    if is_id_start(c) {
-                        self.identifier()
-                    } else {
-                        self.make_token(TokenType::Error, 1)
-                    }
-                },
-            }
-        };
-
-        token
+        let c = self.peek();
+
+        match c {
+            '+'         => self.make_token(TokenType::Add, 1),
+            '-'         => self.make_token(TokenType::Negate, 1),
+            '('         => self.make_token(TokenType::Lparen, 1),
+            ')'         => self.make_token(TokenType::Rparen, 1),
+            '['         => self.make_token(TokenType::Lbracket, 1),
+            ']'         => self.make_token(TokenType::Rbracket, 1),
+            '0'..='9'   => self.number(),
+            '\0'        => self.make_token(TokenType::EndOfFile, 1),
+            _           => {
+                if is_id_start(c) {
+                    self.identifier()
+                } else {
+                    self.make_token(TokenType::Error, 1)
+                }
+            },
+        }
     }
 
     pub fn lex(&mut self) -> Vec<Token> {
@@ -199,7 +193,7 @@ impl Lexer {
             t = self.next_token();
         }
 
-        return token_vec;
+        token_vec
     }
 
     pub fn new(source: & str) -> Self {
@@ -4,4 +4,7 @@ pub mod ast;
 pub mod parser;
 pub mod uniquify;
 pub mod partial_eval;
-pub mod token;
\ No newline at end of file
+pub mod dead_bindings;
+pub mod token;
+pub mod verify;
+pub mod printer;
\ No newline at end of file
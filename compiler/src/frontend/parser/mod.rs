@@ -3,18 +3,28 @@
 #[cfg(test)]
 mod parser_tests;
 
-use std::rc::Rc;
 
 use super::token::{Token, TokenType};
-use super::ast::{AstNode, LetBinding, Program};
+use super::ast::{Ast, AstNode, NodeId, LetBinding, Program, MAX_NESTING};
+use crate::types::{IdString};
 
 //use std::collections::HashMap;
 
+// what can be typed at the repl prompt
+#[derive(Clone, Debug, PartialEq)]
+pub enum TopLevel {
+    Define(IdString, Program), // (define var exp), the program is exp
+    Program(Program),
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     parse_success: bool,
     errors: Vec<AstNode>,
+    ast: Ast, // the nodes parsed so far
+    depth: usize, // how many expressions the one being parsed is inside of
+    too_deep: Option<NodeId>, // the error once MAX_NESTING was reached
 
     previous: Token,
 }
@@ -32,7 +42,7 @@ impl Parser {
             idx = self.current as i64;
         }
 
-        let token = self.tokens[(idx as usize)].clone();
+        let token = self.tokens[idx as usize].clone();
         self.current = idx as usize;
 
         token
@@ -42,9 +52,7 @@ impl Parser {
         self.previous = self.peek(0);
         self.current += 1;
 
-        let token = self.peek(0);
-
-        token
+        self.peek(0)
     }
 
     fn peek(&self, n: i64) -> Token {
@@ -88,6 +96,10 @@ impl Parser {
         self.parse_success
     }
 
+    pub fn errors(&self) -> &[AstNode] {
+        &self.errors
+    }
+
     pub fn print_errors(&self) {
         for error in &self.errors {
             println!("{:?}", error);
@@ -98,18 +110,38 @@ impl Parser {
         self.parse_success = false;
     }
 
-    fn make_error_node(&mut self, msg: String, offset: i64) -> AstNode {
+    fn make_error_node(&mut self, msg: String, offset: i64) -> NodeId {
         self.error();
 
         let error = 
             AstNode::Error {
-                msg: Rc::new(msg),
+                msg: crate::idstr!(msg),
                 token: self.peek(offset)
             };
 
         self.errors.push(error.clone());
 
-        error
+        self.ast.add(error)
+    }
+
+    // an error for the current token, it isn't the 'what' that was expected
+    fn expected(&mut self, what: &str) -> NodeId {
+        let msg = format!("Expected '{}', found '{}'", what, self.current().lexeme);
+
+        self.make_error_node(msg, 0)
+    }
+
+    fn is_error(&self, node: NodeId) -> bool {
+        matches!(self.ast[node], AstNode::Error { .. })
+    }
+
+    // the program made of what was parsed, 'exp' is its root
+    fn program(&mut self, exp: NodeId) -> Program {
+        Program {
+            info: (),
+            ast: std::mem::take(&mut self.ast),
+            exp: exp,
+        }
     }
 
     pub fn new(tokens: Vec<Token>) -> Parser {
@@ -118,6 +150,9 @@ impl Parser {
             current: 0,
             parse_success: true,
             errors: vec!(),
+            ast: Ast::new(),
+            depth: 0,
+            too_deep: None,
             previous: Token {
                 ttype: TokenType::Error,
                 lexeme: "".to_string(),
@@ -127,147 +162,189 @@ impl Parser {
         }
     }
 
-    fn parse_number(&mut self) -> AstNode {
+    fn parse_number(&mut self) -> NodeId {
         let token = self.current();
-        let node = AstNode::Int(token.lexeme.parse::<i64>().unwrap());
+
+        // the lexer only lets digits through, so this can only fail when the literal is too big
+        let node = match token.lexeme.parse::<i64>() {
+            Ok(n) => self.ast.add(AstNode::Int(n)),
+            Err(_) => self.make_error_node(format!("Integer literal '{}' doesn't fit in 64 bits", token.lexeme), 0),
+        };
 
         self.next();
 
         node
     }
 
-    fn parse_identifier(&mut self) -> AstNode {
+    fn parse_identifier(&mut self) -> NodeId {
         let token = self.current();
         self.next();
 
         match &token.lexeme[..] {
             "read" => {
-                AstNode::Prim{ op: Rc::new(token.lexeme), args: vec!() }
+                self.ast.add(AstNode::Prim{ op: crate::idstr!(token.lexeme), args: vec!() })
             },
 
-            // (let ([var exp]) (exp))
-            "let" => {
+            "let" => self.parse_let(),
 
-                if !self.expect(TokenType::Lparen) {
-                    return self.make_error_node(
-                        format!("Expected '(', found '{}'", self.current().lexeme),
-                        0)
-                }
-
-                if !self.expect(TokenType::Lbracket) {
-                    return self.make_error_node(
-                        format!("Expected '[', found '{}'", self.current().lexeme),
-                        0)
-                }
+            _ => {
+                self.ast.add(
+                    AstNode::Var {
+                        name: crate::idstr!(token.lexeme)
+                    }
+                )
+            }
+        }
+    }
 
-                let mut binding_vec: Vec<LetBinding> = Vec::new();
+    // (let ([var exp]) (exp))
+    fn parse_let(&mut self) -> NodeId {
+        if !self.expect(TokenType::Lparen) {
+            return self.expected("(")
+        }
 
-                let mut keep_parsing = true;
+        if !self.expect(TokenType::Lbracket) {
+            return self.expected("[")
+        }
 
-                while keep_parsing {
-                    // this should be the variable name e.g. "x"
-                    let token = self.current();
-                    let var = token.lexeme;
+        let mut binding_vec: Vec<LetBinding> = Vec::new();
 
-                    self.next();
+        let mut keep_parsing = true;
 
-                    let value = self.parse_expr(); // an expression that is bound to x
+        while keep_parsing {
+            // this should be the variable name e.g. "x"
+            let token = self.current();
+            let var = token.lexeme;
 
-                    if self.next_is(TokenType::Lbracket) {
-                        // we're at ']'
-                        self.next(); // '['
-                        self.next(); // begining of loop expects a variable
-                    } else {
-                        //self.next(); // skip ']'
+            self.next();
 
-                        if !self.expect(TokenType::Rbracket) {
-                            return self.make_error_node(
-                                format!("Expected ']', found '{}'", self.current().lexeme),
-                                0)
-                        }
+            let value = self.parse_expr(); // an expression that is bound to x
 
-                        keep_parsing = false;
-                    }
+            if self.next_is(TokenType::Lbracket) {
+                // we're at ']'
+                self.next(); // '['
+                self.next(); // begining of loop expects a variable
+            } else {
+                //self.next(); // skip ']'
 
-                    binding_vec.push(
-                        LetBinding {
-                            identifier: Rc::new(var),
-                            expr: value
-                        }
-                    );
+                if !self.expect(TokenType::Rbracket) {
+                    return self.expected("]")
                 }
 
-                if !self.expect(TokenType::Rparen) {
-                    return self.make_error_node(
-                        format!("Expected ')', found '{}'", self.current().lexeme),
-                        0)
+                keep_parsing = false;
+            }
+
+            binding_vec.push(
+                LetBinding {
+                    identifier: crate::idstr!(var),
+                    expr: value
                 }
+            );
+        }
+
+        if !self.expect(TokenType::Rparen) {
+            return self.expected(")")
+        }
 
 
-                let body = Box::new(self.parse_expr());
+        let body = self.parse_expr();
 
-                AstNode::Let {
-                    bindings: binding_vec,
-                    body: body
-                }
-            },
-            _ => {
-                AstNode::Var {
-                    name: Rc::new(token.lexeme)
-                }
+        self.ast.add(
+            AstNode::Let {
+                bindings: binding_vec,
+                body: body
             }
-        }
+        )
     }
 
-    fn parse_operator(&mut self) -> AstNode {
+    fn parse_operator(&mut self) -> NodeId {
         let token = self.current();
         self.next();
 
         match token.ttype {
             TokenType::Add => {
-                AstNode::Prim{
-                    op: Rc::new(token.lexeme),
-                    args: vec![self.parse_expr(), self.parse_expr()]
-                }
+                let args = vec![self.parse_expr(), self.parse_expr()];
+
+                self.ast.add(
+                    AstNode::Prim{
+                        op: crate::idstr!(token.lexeme),
+                        args: args
+                    }
+                )
             },
             TokenType::Negate => {
-                AstNode::Prim{
-                    op: Rc::new(token.lexeme),
-                    args: vec![self.parse_expr()]
-                }
+                let args = vec![self.parse_expr()];
+
+                self.ast.add(
+                    AstNode::Prim{
+                        op: crate::idstr!(token.lexeme),
+                        args: args
+                    }
+                )
             },
 
             _ => self.make_error_node("Unknown operator in parse_operator: ".to_owned(), -1)
         }
     }
 
-    fn parse_expr(&mut self) -> AstNode {
+    // the parser and every pass after it recurse once per level of nesting, so
+    // expressions aren't parsed deeper than MAX_NESTING. the brackets around an
+    // expression are counted instead of recursed into, ((exp)) is just as deep as exp
+    fn parse_expr(&mut self) -> NodeId {
+        if let Some(error) = self.too_deep {
+            return error;
+        }
 
-        let token = self.current();
+        let mut parens = 0;
 
-        match token.ttype {
+        while self.is(TokenType::Lparen) {
+            self.next();
+            parens += 1;
+        }
 
-            TokenType::Lparen => {
-                self.next();
+        if self.depth >= MAX_NESTING {
+            return self.nested_too_deep();
+        }
 
-                let mut node = self.parse_expr();
+        self.depth += 1;
 
-                if !self.is(TokenType::Rparen) {
-                    match node {
-                        AstNode::Error {..} => { // don't there's already an error message
-                        },
-                        _ => {
-                            node = self.make_error_node(
-                                    format!("Expected ')', found '{}'", self.current().lexeme),
-                                    0)
-                        }
-                    }
+        let mut node = self.parse_node();
+
+        self.depth -= 1;
+
+        // the expressions around the one that was too deep are the error as well,
+        // the rest of the input isn't looked at
+        if let Some(error) = self.too_deep {
+            return error;
+        }
+
+        for _ in 0..parens {
+            if !self.is(TokenType::Rparen) {
+                // don't if there's already an error message
+                if !self.is_error(node) {
+                    node = self.expected(")")
                 }
+            }
 
-                self.next();
+            self.next();
+        }
 
-                node
-            },
+        node
+    }
+
+    fn nested_too_deep(&mut self) -> NodeId {
+        let error = self.make_error_node(format!("Nested deeper than {} expressions", MAX_NESTING), 0);
+
+        self.too_deep = Some(error);
+
+        error
+    }
+
+    fn parse_node(&mut self) -> NodeId {
+
+        let token = self.current();
+
+        match token.ttype {
 
             TokenType::Number => {
                 self.parse_number()
@@ -289,36 +366,84 @@ impl Parser {
     }
 
     fn parse_program(&mut self) -> Program {
-        Program {
-            info: (),
-            exp: {
-                if !self.is(TokenType::Lparen) {
-                    self.make_error_node(
-                        format!("Expected '(', found '{}'", self.current().lexeme),
-                        0)
-                } else {
+        let exp = {
+            if !self.is(TokenType::Lparen) {
+                self.expected("(")
+            } else {
 
-                    self.next();
-                    let mut node = self.parse_expr();
-                    if !self.is(TokenType::Rparen) {
-                        match node {
-                            AstNode::Error {..} => { // don't there's already an error message
-                            },
-                            _ => {
-                                node = self.make_error_node(
-                                            format!("Expected ')', found '{}'", self.current().lexeme),
-                                        0)
-                            }
-                        }
+                self.next();
+                let mut node = self.parse_expr();
+                if !self.is(TokenType::Rparen) {
+                    // don't if there's already an error message
+                    if !self.is_error(node) {
+                        node = self.expected(")")
                     }
-
-                    node
                 }
+
+                node
             }
-        }
+        };
+
+        self.program(exp)
     }
 
     pub fn parse(&mut self) -> Program {
         self.parse_program()
     }
+
+    // (define var exp) or a program
+    pub fn parse_top_level(&mut self) -> TopLevel {
+        let is_define =
+            self.tokens.len() > 1 &&
+            self.is(TokenType::Lparen) &&
+            self.peek(1).ttype == TokenType::Identifier &&
+            self.peek(1).lexeme == "define";
+
+        if !is_define {
+            return TopLevel::Program(self.parse_program());
+        }
+
+        self.next(); // '('
+        self.next(); // define
+
+        if !self.is(TokenType::Identifier) {
+            let error = self.make_error_node(
+                format!("Expected a variable, found '{}'", self.current().lexeme),
+                0);
+
+            return TopLevel::Program(self.program(error));
+        }
+
+        let name = crate::idstr!(self.current().lexeme);
+
+        self.next();
+
+        let value = self.parse_expr();
+
+        // peek keeps returning the last token once the input runs out
+        let at_end = self.current >= self.tokens.len();
+
+        if at_end || !self.is(TokenType::Rparen) {
+            let error =
+                if self.is_error(value) {
+                    value // there's already an error message
+                } else if at_end {
+                    self.make_error_node("Expected ')', found the end of the input".to_owned(), 0)
+                } else {
+                    self.expected(")")
+                };
+
+            return TopLevel::Program(self.program(error));
+        }
+
+        if self.current + 1 < self.tokens.len() {
+            let error = self.make_error_node(
+                format!("Unexpected '{}' after the definition", self.peek(1).lexeme),
+                1);
+
+            return TopLevel::Program(self.program(error));
+        }
+
+        TopLevel::Define(name, self.program(value))
+    }
 }
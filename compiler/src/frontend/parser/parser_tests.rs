@@ -1,9 +1,12 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
-use crate::frontend::ast::{AstNode, Program, LetBinding};
+use crate::frontend::ast::{Ast, AstNode, Program, LetBinding, MAX_NESTING};
 
 use crate::utility::{test_ast_helper};
+use crate::frontend::lexer::{Lexer};
+
+use super::{Parser, TopLevel};
 
 pub fn helper(prog: &'static str) -> Program {
     test_ast_helper(prog, vec!())
@@ -13,10 +16,7 @@ pub fn helper(prog: &'static str) -> Program {
 fn parse_constant() {
     let ast = helper("(2)");
 
-    let expected = Program {
-        info: (),
-        exp: AstNode::Int(2)
-    };
+    let expected = Program::leaf(AstNode::Int(2));
 
     assert_eq!(ast, expected);
 }
@@ -25,18 +25,25 @@ fn parse_constant() {
 fn parse_add_with_negate() {
     let ast = helper("(+ 2 (-1))");
 
+    let mut tree = Ast::new();
+
+    let two = tree.add(AstNode::Int(2));
+    let one = tree.add(AstNode::Int(1));
+
+    let negation = tree.add(AstNode::Prim {
+        op: crate::idstr!("-"),
+        args: vec!(one)
+    });
+
+    let exp = tree.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(two, negation)
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Prim {
-            op: crate::idstr!("+"),
-            args: vec!(
-                AstNode::Int(2),
-                AstNode::Prim {
-                    op: crate::idstr!("-"),
-                    args: vec!(AstNode::Int(1))
-                }
-            )
-        }
+        ast: tree,
+        exp: exp
     };
 
     assert_eq!(ast, expected);
@@ -46,19 +53,28 @@ fn parse_add_with_negate() {
 fn parse_let() {
     let ast = helper("(let ([x 10]) x)");
 
+    let mut tree = Ast::new();
+
+    let ten = tree.add(AstNode::Int(10));
+
+    let x = tree.add(AstNode::Var {
+        name: crate::idstr!("x")
+    });
+
+    let exp = tree.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: crate::idstr!("x"),
+                expr: ten
+            }
+        ),
+        body: x
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: crate::idstr!("x"),
-                    expr: AstNode::Int(10)
-                }
-            ),
-            body: Box::new(AstNode::Var {
-                name: crate::idstr!("x")
-            })
-        }
+        ast: tree,
+        exp: exp
     };
 
     assert_eq!(ast, expected);
@@ -71,27 +87,39 @@ fn parse_nested_let() {
     let var_x = crate::idstr!("x");
     let var_y = crate::idstr!("y");
 
+    let mut tree = Ast::new();
+
+    let forty_two = tree.add(AstNode::Int(42));
+    let y = tree.add(AstNode::Var { name: var_y });
+
+    let inner = tree.add(AstNode::Let {
+        bindings: vec! (
+            LetBinding {
+                identifier: var_y,
+                expr: forty_two
+            }
+        ),
+        body: y
+    });
+
+    let x = tree.add(AstNode::Var {
+        name: crate::idstr!("x")
+    });
+
+    let exp = tree.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: var_x,
+                expr: inner
+            }
+        ),
+        body: x
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: var_x.clone(),
-                    expr: AstNode::Let {
-                        bindings: vec! (
-                            LetBinding {
-                                identifier: var_y.clone(),
-                                expr: AstNode::Int(42)
-                            }
-                        ),
-                        body: Box::new(AstNode::Var { name: var_y.clone() })
-                    }
-                }
-            ),
-            body: Box::new(AstNode::Var {
-                name: crate::idstr!("x")
-            })
-        }
+        ast: tree,
+        exp: exp
     };
 
     assert_eq!(ast, expected);
@@ -101,11 +129,11 @@ fn parse_nested_let() {
 fn parse_fail_expect_leftparen () {
     let ast = helper("2");
 
-    match ast.exp {
+    match ast.root() {
         AstNode::Error { msg, .. } => {
             assert_eq!(
                 "Expected '(', found '2'".to_owned(),
-                *msg
+                **msg
             )
         },
 
@@ -113,18 +141,122 @@ fn parse_fail_expect_leftparen () {
     }
 }
 
+#[test]
+fn parse_fail_number_too_big () {
+    let ast = helper("(+ 1 9223372036854775808)");
+
+    match &ast.ast[ast.ast.arg(ast.exp, 1)] {
+        AstNode::Error { msg, token } => {
+            assert_eq!(
+                "Integer literal '9223372036854775808' doesn't fit in 64 bits".to_owned(),
+                **msg
+            );
+
+            assert_eq!((token.line, token.col), (1, 6));
+        },
+
+        other => panic!("{:?}", other)
+    }
+
+    // the largest one still does
+    assert_eq!(helper("(9223372036854775807)").root(), &AstNode::Int(i64::MAX));
+}
+
 #[test]
 fn parse_fail_expect_rightbracket () {
     let ast = helper("(let ([x 10) x)");
 
-    match ast.exp {
+    match ast.root() {
         AstNode::Error { msg, .. } => {
             assert_eq!(
                 "Expected ']', found ')'".to_owned(),
-                *msg
+                **msg
             )
         },
 
         _ => panic!()
     }
-}
\ No newline at end of file
+}
+fn top_level(source: &str) -> (TopLevel, bool) {
+    let mut parser = Parser::new(Lexer::new(source).lex());
+
+    let entry = parser.parse_top_level();
+
+    (entry, parser.parse_success())
+}
+
+#[test]
+fn parse_define() {
+    let mut tree = Ast::new();
+
+    let one = tree.add(AstNode::Int(1));
+    let read = tree.add(AstNode::Prim { op: crate::idstr!("read"), args: vec!() });
+
+    let exp = tree.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(one, read)
+    });
+
+    assert_eq!(
+        top_level("(define x (+ 1 (read)))"),
+        (
+            TopLevel::Define(
+                crate::idstr!("x"),
+                Program { info: (), ast: tree, exp: exp }
+            ),
+            true
+        )
+    );
+
+    assert_eq!(top_level("(define y 2)"), (TopLevel::Define(crate::idstr!("y"), Program::leaf(AstNode::Int(2))), true));
+
+    // anything else is a program
+    assert_eq!(top_level("(+ 1 2)").0, TopLevel::Program(helper("(+ 1 2)")));
+
+    assert!(!top_level("(define 1 2)").1);
+    assert!(!top_level("(define x 1 2)").1);
+    assert!(!top_level("(define x (+ 1 2)").1);
+    assert!(!top_level("(define x 1) 2").1);
+}
+
+#[test]
+fn parse_nesting_limit() {
+    // as deep as the parser allows takes more than the stack of a test thread
+    crate::utility::with_compiler_stack(|| {
+        // (- (- ... (- 1))) with 'depth' expressions in all
+        let negations = |depth: usize, brackets: bool| {
+            let (open, close) = if brackets { ("(- ", ")") } else { ("- ", "") };
+
+            format!("({}1{})", open.repeat(depth - 1), close.repeat(depth - 1))
+        };
+
+        for brackets in [true, false] {
+            let mut parser = Parser::new(Lexer::new(&negations(MAX_NESTING, brackets)).lex());
+
+            let p = parser.parse();
+
+            assert!(parser.parse_success());
+            assert_eq!(p.ast.depth(p.exp), MAX_NESTING);
+
+            // one more is turned away instead of running out of stack in a later pass
+            let mut parser = Parser::new(Lexer::new(&negations(MAX_NESTING + 1, brackets)).lex());
+
+            parser.parse();
+
+            assert!(!parser.parse_success());
+
+            match &parser.errors()[0] {
+                AstNode::Error { msg, .. } => assert_eq!(msg.to_string(), format!("Nested deeper than {} expressions", MAX_NESTING)),
+                other => panic!("{:?}", other),
+            }
+
+            assert_eq!(parser.errors().len(), 1);
+        }
+
+        // brackets around an expression don't make it deeper
+        let mut parser = Parser::new(Lexer::new(&format!("({}1{})", "(".repeat(5 * MAX_NESTING), ")".repeat(5 * MAX_NESTING))).lex());
+
+        assert_eq!(parser.parse(), Program::leaf(AstNode::Int(1)));
+        assert!(parser.parse_success());
+    });
+}
@@ -1,3 +1,24 @@
+/*
+    evaluate as much of the program as possible at compile time
+
+    every expression is brought into the form
+
+        constant + residual
+
+    where the residual is the part that can only be known at run time (e.g. a (read)).
+    sums are reassociated so all the constants end up together:
+
+        (+ 1 (+ (read) 1)) becomes (+ 2 (read))
+
+    the residual terms are kept in their original order, so the calls to read
+    happen in the same order as in the original program
+
+    the constants wrap around like the compiled code. when overflow is checked
+    reassociating could hide or introduce an overflow, so only sums and negations
+    of constants are folded, and only when they don't overflow. the rest is left
+    for run time, where the overflow is reported
+*/
+
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
@@ -6,199 +27,309 @@ mod partial_eval_tests;
 
 use runtime::types::{RuntimeI64, RuntimeValue};
 
-use crate::types::{IdString, Environment};
+use crate::types::{IdString, Environment, Overflow};
+use crate::symbol::{PLUS, MINUS};
 use crate::frontend::ast::*;
 
-struct PartialEvaluator {
-    prog: Program,
-    env: Environment,
+// constant + (term_0 + term_1 + ... + term_n)
+#[derive(Debug)]
+struct Residual {
+    constant: RuntimeI64,
+    terms: Vec<NodeId>,
 }
 
-impl PartialEvaluator {
+impl Residual {
 
-    fn new(prog: Program) -> Self {
-        PartialEvaluator {
-            prog: prog,
-            env: Environment::new(), 
+    fn constant(n: RuntimeI64) -> Self {
+        Residual {
+            constant: n,
+            terms: vec!(),
         }
     }
 
-    fn evaluate(&mut self) -> AstNode {
-
-        let p = &self.prog.exp.clone();
-        let result = self.partial_eval_exp(p);
+    fn term(node: NodeId) -> Self {
+        Residual {
+            constant: 0,
+            terms: vec!(node),
+        }
+    }
 
-        result
+    fn add(mut self, mut other: Residual) -> Self {
+        self.constant = self.constant.wrapping_add(other.constant);
+        self.terms.append(&mut other.terms);
 
+        self
     }
 
-    fn partial_eval_negate(&mut self, r: &AstNode) -> AstNode {
+    fn negate(self, ast: &mut Ast) -> Self {
+        Residual {
+            constant: self.constant.wrapping_neg(),
+            terms: self.terms.into_iter().map(|term| negate_term(ast, term)).collect(),
+        }
+    }
 
-        let right = self.partial_eval_exp(r);
+    // (+ constant (+ term_0 (+ term_1 ...)))
+    fn into_node(self, ast: &mut Ast) -> NodeId {
+        let mut terms = self.terms;
 
-        match &right {
-            AstNode::Int(n) => {
-                return AstNode::Int(0 - n);
+        let residual = match terms.pop() {
+            Some(last) => {
+                terms
+                    .into_iter()
+                    .rev()
+                    .fold(last, |acc, term| add_node(ast, term, acc))
             },
 
-            AstNode::Var { name } => {
-                let value = self.env.get_value_of(name.clone());
+            None => {
+                return ast.add(AstNode::Int(self.constant));
+            }
+        };
 
-                if let Some(&AstNode::Int(n)) = value {
-                    return AstNode::Int(0 - n);
-                }
-            },
+        if self.constant == 0 {
+            residual
+        } else {
+            let constant = ast.add(AstNode::Int(self.constant));
 
-            _ => {
-            },
+            add_node(ast, constant, residual)
         }
+    }
+}
 
+fn add_node(ast: &mut Ast, l: NodeId, r: NodeId) -> NodeId {
+    ast.add(
         AstNode::Prim {
-            op: crate::idstr!("-"),
-            args: vec!(right.clone())
+            op: PLUS,
+            args: vec!(l, r)
+        }
+    )
+}
+
+// (- (- x)) is just x
+fn negate_term(ast: &mut Ast, term: NodeId) -> NodeId {
+    match &ast[term] {
+        AstNode::Prim { op: MINUS, args } if args.len() == 1 => {
+            args[0]
+        },
+
+        _ => {
+            ast.add(
+                AstNode::Prim {
+                    op: MINUS,
+                    args: vec!(term)
+                }
+            )
         }
     }
+}
 
-    fn partial_eval_add(&mut self, l: &AstNode, r: &AstNode) -> AstNode {
+struct PartialEvaluator {
+    env: Environment,
+    overflow: Overflow,
+    ast: Ast,
+}
 
-        let left = self.partial_eval_exp(l);
-        let right = self.partial_eval_exp(r);
+impl PartialEvaluator {
 
-        match (&left, &right) {
-            (AstNode::Int(n), AstNode::Int(m)) => {
-                return AstNode::Int(n + m);
-            },
+    fn new(overflow: Overflow, ast: Ast) -> Self {
+        PartialEvaluator {
+            env: Environment::new(),
+            overflow: overflow,
+            ast: ast,
+        }
+    }
 
-            (AstNode::Var { name: left_name }, AstNode::Var { name: right_name }) => {
-                let lvv = self.env.get_value_of(left_name.clone());
-                let rvv = self.env.get_value_of(right_name.clone());
+    fn is_constant(residual: &Residual) -> bool {
+        residual.terms.is_empty()
+    }
 
-                match (lvv, rvv) {
-                    (Some(&AstNode::Int(n)), Some(&AstNode::Int(m))) => {
-                        return AstNode::Int(n + m);
-                    },
+    fn add(&mut self, left: Residual, right: Residual) -> Residual {
+        if self.overflow == Overflow::Wrap {
+            return left.add(right);
+        }
 
-                    _ => {}
-                }
+        // adding 0 can't overflow
+        if Self::is_constant(&left) && left.constant == 0 {
+            return right;
+        }
+
+        if Self::is_constant(&right) && right.constant == 0 {
+            return left;
+        }
 
+        if Self::is_constant(&left) && Self::is_constant(&right) {
+            if let Some(n) = self.overflow.add(left.constant, right.constant) {
+                return Residual::constant(n);
             }
+        }
 
-            (AstNode::Var { ref name }, AstNode::Int(n)) | 
-            (AstNode::Int(n), AstNode::Var { ref name })
-            => {
-                let vv = self.env.get_value_of(name.clone());
+        let left = left.into_node(&mut self.ast);
+        let right = right.into_node(&mut self.ast);
 
-                match vv {
-                    Some(&AstNode::Int(m)) => {
-                        return AstNode::Int(n + m);
-                    },
+        Residual::term(add_node(&mut self.ast, left, right))
+    }
 
-                    _ => {}
-                }
-            },
+    fn negate(&mut self, residual: Residual) -> Residual {
+        if self.overflow == Overflow::Wrap {
+            return residual.negate(&mut self.ast);
+        }
 
-            _ => {
+        if Self::is_constant(&residual) {
+            if let Some(n) = self.overflow.negate(residual.constant) {
+                return Residual::constant(n);
             }
         }
 
-        AstNode::Prim {
-            op: crate::idstr!("+"),
-            args: vec!(left, right)
+        // (- (- x)) overflows when x is the smallest number, so it stays
+        let arg = residual.into_node(&mut self.ast);
+
+        Residual::term(self.ast.add(AstNode::Prim { op: MINUS, args: vec!(arg) }))
+    }
+
+    fn evaluate(&mut self, exp: NodeId) -> NodeId {
+        self.partial_eval_exp(exp).into_node(&mut self.ast)
+    }
+
+    fn partial_eval_var(&mut self, e: NodeId, name: IdString) -> Residual {
+        // only variables bound to a constant are known, everything else
+        // (including free variables) stays as is
+        match self.env.get(name) {
+            Some(&AstNode::Int(n)) => {
+                Residual::constant(n)
+            },
+
+            _ => {
+                Residual::term(e)
+            }
         }
     }
 
-    fn partial_eval_prim(&mut self, exp: &AstNode) -> AstNode {
-        match exp {
-            AstNode::Prim { op, args } => {
-                match &op[..] {
+    fn partial_eval_prim(&mut self, e: NodeId, op: IdString, arity: usize) -> Residual {
+        match (op, arity) {
 
-                    "+" => {
-                        self.partial_eval_add(&args[0], &args[1])
-                    },
+            (PLUS, 2) => {
+                let left = self.partial_eval_exp(self.ast.arg(e, 0));
+                let right = self.partial_eval_exp(self.ast.arg(e, 1));
 
-                    "-" => {
-                        self.partial_eval_negate(&args[0])
-                    },
+                self.add(left, right)
+            },
 
-                    _ => {
-                        exp.clone()
-                    },
-                }
+            (MINUS, 1) => {
+                let arg = self.partial_eval_exp(self.ast.arg(e, 0));
+
+                self.negate(arg)
             },
 
+            // (read) and anything we don't know about is left to run time
             _ => {
-                unreachable!();
+                Residual::term(e)
+            },
+        }
+    }
+
+    fn partial_eval_let(&mut self, e: NodeId, count: usize, body: NodeId) -> Residual {
+
+        // bindings that evaluate to a constant are substituted into the body and
+        // disappear, the others (they depend on a read) have to stay
+        let mut residual_bindings = vec!();
+
+        for i in 0..count {
+            let binding = self.ast.binding(e, i);
+            let value = self.evaluate(binding.expr);
+
+            if let AstNode::Int(n) = self.ast[value] {
+                self.env.insert(binding.identifier, AstNode::Int(n));
+                continue;
             }
+
+            // the variable is only known at run time, this also hides a constant
+            // bound to the same name by an earlier let
+            self.env.insert(binding.identifier, AstNode::Var { name: binding.identifier });
+
+            residual_bindings.push(
+                LetBinding {
+                    identifier: binding.identifier,
+                    expr: value
+                }
+            );
+        }
+
+        let new_body = self.partial_eval_exp(body);
+
+        if residual_bindings.is_empty() {
+            return new_body;
+        }
+
+        // the constant part of the body doesn't depend on the bindings,
+        // so it can be moved out of the let
+        if new_body.terms.is_empty() {
+            let body = new_body.into_node(&mut self.ast);
+
+            Residual::term(
+                self.ast.add(
+                    AstNode::Let {
+                        bindings: residual_bindings,
+                        body: body
+                    }
+                )
+            )
+        } else {
+            let constant = new_body.constant;
+
+            let inner_body = Residual {
+                constant: 0,
+                terms: new_body.terms,
+            };
+
+            let body = inner_body.into_node(&mut self.ast);
+
+            let new_let =
+                self.ast.add(
+                    AstNode::Let {
+                        bindings: residual_bindings,
+                        body: body
+                    }
+                );
+
+            self.add(
+                Residual::constant(constant),
+                Residual::term(new_let)
+            )
         }
     }
 
-    fn partial_eval_exp(&mut self, exp: &AstNode) -> AstNode {
+    fn partial_eval_exp(&mut self, exp: NodeId) -> Residual {
 
-        match exp {
-            AstNode::Var { .. } => {
-                exp.clone()
+        match &self.ast[exp] {
+            AstNode::Var { name } => {
+                self.partial_eval_var(exp, *name)
             },
 
-            AstNode::Int(_) => {
-                exp.clone()
+            AstNode::Int(n) => {
+                Residual::constant(*n)
             }
 
-            AstNode::Prim { .. } => {
-                self.partial_eval_prim(&exp)
+            AstNode::Prim { op, args } => {
+                self.partial_eval_prim(exp, *op, args.len())
             },
 
             AstNode::Let { bindings, body } => {
-                
-                let new_bindings = 
-                    bindings
-                        .iter()
-                        .map(
-                            | b |
-                            {
-                                let new_expr = self.partial_eval_exp(&b.expr);
-                                self.env.insert(b.identifier.clone(), new_expr.clone());
-                                LetBinding {
-                                    identifier: b.identifier.clone(),
-                                    expr: new_expr 
-                                }
-                            }
-                        )
-                        .collect();
-
-                let new_body = self.partial_eval_exp(*&body);
-                match new_body {
-                    // we were able to evaluate everything to a single integer
-                    AstNode::Int(_) => {
-                        new_body
-                    },
-
-                    AstNode::Var { name } => {
-                        self.env.get_value_of(name.clone()).unwrap().clone()
-                    },
-
-                    _ => {
-                        AstNode::Let {
-                            bindings: new_bindings,
-                            body: Box::new(self.partial_eval_exp(*&body))
-                        }
-                    }
-                }
-
+                self.partial_eval_let(exp, bindings.len(), *body)
             },
 
-            _ => {
-                exp.clone()
+            AstNode::Error { .. } => {
+                Residual::term(exp)
             }
         }
     }
 }
 
-pub fn partially_evaluate(prog: Program) -> Program {
-    let mut pe = PartialEvaluator::new(prog);
-    let result = pe.evaluate();
+pub fn partially_evaluate(prog: Program, overflow: Overflow) -> Program {
+    let mut pe = PartialEvaluator::new(overflow, prog.ast);
+    let result = pe.evaluate(prog.exp);
 
     Program {
         info: (),
+        ast: pe.ast,
         exp: result
     }
-}
\ No newline at end of file
+}
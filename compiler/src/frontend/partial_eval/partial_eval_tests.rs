@@ -1,7 +1,7 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
-use crate::frontend::ast::{AstNode, Program};
+use crate::frontend::ast::{Ast, AstNode, NodeId, Program, LetBinding};
 
 use crate::utility::{test_ast_helper, AstStep};
 
@@ -16,11 +16,7 @@ fn helper(prog: &'static str) -> Program {
 fn partial_evalute_add_constants() {
     let program = helper("(+ 2 2)");
 
-    let expected = 
-        Program {
-            info: (),
-            exp: AstNode::Int(4)
-        };
+    let expected = Program::leaf(AstNode::Int(4));
 
     assert_eq!(
         program,
@@ -32,11 +28,7 @@ fn partial_evalute_add_constants() {
 fn partial_eval_add_constant_add_constants() {
     let program = helper("(+ 2 (+ 2 2))");
 
-    let expected = 
-        Program {
-            info: (),
-            exp: AstNode::Int(6)
-        };
+    let expected = Program::leaf(AstNode::Int(6));
 
     assert_eq!(
         program,
@@ -48,11 +40,7 @@ fn partial_eval_add_constant_add_constants() {
 fn partial_eval_negate_add_constant_negate_constant() {
     let program = helper("(- (+ 3 (- 5))))))");
 
-    let expected = 
-        Program {
-            info: (),
-            exp: AstNode::Int(2)
-        };
+    let expected = Program::leaf(AstNode::Int(2));
 
     assert_eq!(
         program,
@@ -64,19 +52,21 @@ fn partial_eval_negate_add_constant_negate_constant() {
 fn partial_eval_add_constant_read() {
     let program = helper("(+ 2 (read))");
 
+    let mut ast = Ast::new();
+
+    let two = ast.add(AstNode::Int(2));
+    let read = read(&mut ast);
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(two, read)
+    });
+
     let expected = 
         Program {
             info: (),
-            exp: AstNode::Prim {
-                op: crate::idstr!("+"),
-                args: vec!(
-                    AstNode::Int(2),
-                    AstNode::Prim {
-                        op: crate::idstr!("read"),
-                        args: vec!(),
-                    }
-                )
-            }
+            ast: ast,
+            exp: exp
         };
 
     assert_eq!(
@@ -89,19 +79,21 @@ fn partial_eval_add_constant_read() {
 fn partial_eval_add_read_negate_add_two_constants() {
     let program = helper("(+ (read) (- (+ 5 3)))");
 
+    let mut ast = Ast::new();
+
+    let minus_eight = ast.add(AstNode::Int(-8));
+    let read = read(&mut ast);
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(minus_eight, read)
+    });
+
     let expected = 
         Program {
             info: (),
-            exp: AstNode::Prim {
-                op: crate::idstr!("+"),
-                args: vec!(
-                    AstNode::Prim {
-                        op: crate::idstr!("read"),
-                        args: vec!(),
-                    },
-                    AstNode::Int(-8)
-                )
-            }
+            ast: ast,
+            exp: exp
         };
 
     assert_eq!(
@@ -114,10 +106,64 @@ fn partial_eval_add_read_negate_add_two_constants() {
 fn partial_eval_let_add_constants_in_add_constant() {
     let program = helper("(let ([x (+ 10 23)]) (+ x 100))");
 
+    let expected = Program::leaf(AstNode::Int(133));
+
+    assert_eq!(
+        program,
+        expected
+    )
+}
+
+#[test]
+fn partial_eval_let_constant_let_var_in_add_vars() {
+    let program = helper("(let ([x 10][y x]) (+ x y)");
+
+    let expected = Program::leaf(AstNode::Int(20));
+
+    assert_eq!(
+        program,
+        expected
+    )
+}
+
+#[test]
+fn partial_eval_nested_let() {
+    let program = helper("(let ([x (let ([x (+ 123 (- 23))]) (x))]) (x))");
+
+    let expected = Program::leaf(AstNode::Int(100));
+
+    assert_eq!(
+        program,
+        expected
+    )
+}
+
+fn read(ast: &mut Ast) -> NodeId {
+    ast.add(AstNode::Prim {
+        op: crate::idstr!("read"),
+        args: vec!(),
+    })
+}
+
+#[test]
+fn partial_eval_reassociate_constants() {
+    let program = helper("(+ 1 (+ (read) 1))");
+
+    let mut ast = Ast::new();
+
+    let two = ast.add(AstNode::Int(2));
+    let read = read(&mut ast);
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(two, read)
+    });
+
     let expected = 
         Program {
             info: (),
-            exp: AstNode::Int(133)
+            ast: ast,
+            exp: exp
         };
 
     assert_eq!(
@@ -127,13 +173,35 @@ fn partial_eval_let_add_constants_in_add_constant() {
 }
 
 #[test]
-fn partial_eval_let_constant_let_var_in_add_vars() {
-    let program = helper("(let ([x 10][y x]) (+ x y)");
+fn partial_eval_negate_sum_keeps_read_order() {
+    let program = helper("(- (+ (read) (+ 3 (- (read)))))");
+
+    let mut ast = Ast::new();
+
+    let minus_three = ast.add(AstNode::Int(-3));
+    let first = read(&mut ast);
+    let second = read(&mut ast);
+
+    let negation = ast.add(AstNode::Prim {
+        op: crate::idstr!("-"),
+        args: vec!(first)
+    });
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(negation, second)
+    });
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(minus_three, sum)
+    });
 
     let expected = 
         Program {
             info: (),
-            exp: AstNode::Int(20)
+            ast: ast,
+            exp: exp
         };
 
     assert_eq!(
@@ -143,17 +211,152 @@ fn partial_eval_let_constant_let_var_in_add_vars() {
 }
 
 #[test]
-fn partial_eval_nested_let() {
-    let program = helper("(let ([x (let ([x (+ 123 (- 23))]) (x))]) (x))");
+fn partial_eval_let_bound_to_read() {
+    let program = helper("(let ([x (read)]) x)");
+
+    let x = crate::idstr!("x.1");
+
+    let mut ast = Ast::new();
+
+    let read = read(&mut ast);
+    let body = ast.add(AstNode::Var { name: x });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x,
+                expr: read
+            }
+        ),
+        body: body
+    });
 
     let expected = 
         Program {
             info: (),
-            exp: AstNode::Int(100)
+            ast: ast,
+            exp: exp
         };
 
     assert_eq!(
         program,
         expected
     )
-}
\ No newline at end of file
+}
+
+#[test]
+fn partial_eval_unused_read_is_kept() {
+    let program = helper("(let ([x (read)]) 5)");
+
+    let mut ast = Ast::new();
+
+    let read = read(&mut ast);
+    let five = ast.add(AstNode::Int(5));
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: crate::idstr!("x.1"),
+                expr: read
+            }
+        ),
+        body: five
+    });
+
+    let expected = 
+        Program {
+            info: (),
+            ast: ast,
+            exp: exp
+        };
+
+    assert_eq!(
+        program,
+        expected
+    )
+}
+
+#[test]
+fn partial_eval_fold_through_nested_let() {
+    let program = helper("(+ 1 (let ([x (+ 2 (read))]) (let ([y 3]) (+ x (+ y 4)))))");
+
+    let x = crate::idstr!("x.1");
+
+    let mut ast = Ast::new();
+
+    let eight = ast.add(AstNode::Int(8));
+    let two = ast.add(AstNode::Int(2));
+    let read = read(&mut ast);
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(two, read)
+    });
+
+    let body = ast.add(AstNode::Var { name: x });
+
+    let inner = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x,
+                expr: sum
+            }
+        ),
+        body: body
+    });
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(eight, inner)
+    });
+
+    let expected = 
+        Program {
+            info: (),
+            ast: ast,
+            exp: exp
+        };
+
+    assert_eq!(
+        program,
+        expected
+    )
+}
+
+fn checked_helper(prog: &'static str) -> Program {
+    crate::pipeline::PassManager::custom(&["uniquify", "partial-eval"])
+        .unwrap()
+        .set_overflow(crate::types::Overflow::Checked)
+        .run(crate::pipeline::ProgramForm::Ast(crate::utility::test_parse_helper(prog)))
+        .into_ast()
+}
+
+#[test]
+fn partial_eval_overflow_wraps() {
+    assert_eq!(*helper("(+ 9223372036854775807 1)").root(), AstNode::Int(i64::MIN));
+    assert_eq!(*helper("(- (+ (- 9223372036854775807) (- 1)))").root(), AstNode::Int(i64::MIN));
+}
+
+#[test]
+fn partial_eval_overflow_checked() {
+    // what fits is still folded
+    assert_eq!(*checked_helper("(+ 2 (- 3))").root(), AstNode::Int(-1));
+    assert_eq!(checked_helper("(let ([x 1]) (+ x (read)))").to_string(), "(+ 1 (read))");
+    assert_eq!(checked_helper("(+ 0 (read))").to_string(), "(read)");
+
+    // an overflow is left for run time to report
+    assert_eq!(checked_helper("(+ 9223372036854775807 1)").to_string(), "(+ 9223372036854775807 1)");
+    let mut ast = Ast::new();
+
+    let min = ast.add(AstNode::Int(i64::MIN));
+    let exp = ast.add(AstNode::Prim { op: crate::idstr!("-"), args: vec!(min) });
+
+    assert_eq!(
+        checked_helper("(- (+ (- 9223372036854775807) (- 1)))"),
+        Program { info: (), ast: ast, exp: exp }
+    );
+
+    // the sums aren't reassociated, and (- (- x)) stays
+    assert_eq!(checked_helper("(+ 1 (+ (read) 1))").to_string(), "(+ 1 (+ (read) 1))");
+    assert_eq!(checked_helper("(- (- (read)))").to_string(), "(- (- (read)))");
+}
@@ -0,0 +1,158 @@
+/*
+    print an ast as source the parser accepts
+
+    short expressions stay on one line, a let or primitive that doesn't fit in
+    the width is broken up:
+
+        (let ([x (+ (read) 10)]
+              [y (- (read))])
+          (+ x y))
+
+    for the parser's output parse(print(ast)) == ast. later passes make things
+    the parser can't read back, names like x.1 and negative numbers. a negative
+    number is printed as (- n), so the text still means the same thing
+*/
+
+#[cfg(test)]
+mod printer_tests;
+
+use std::fmt;
+
+use super::ast::{Ast, AstNode, NodeId, Program};
+
+pub const DEFAULT_WIDTH: usize = 80;
+
+pub struct AstPrinter {
+    width: usize,
+}
+
+impl AstPrinter {
+
+    pub fn new() -> Self {
+        AstPrinter {
+            width: DEFAULT_WIDTH,
+        }
+    }
+
+    // usize::MAX puts everything on one line
+    pub fn set_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn print(&self, p: &Program) -> String {
+        match *p.root() {
+            // a program always starts with a '('
+            AstNode::Int(n) if n >= 0 => format!("({})", n),
+            AstNode::Var { name } => format!("({})", name),
+            _ => self.print_exp(&p.ast, p.exp),
+        }
+    }
+
+    pub fn print_exp(&self, ast: &Ast, e: NodeId) -> String {
+        self.exp(ast, e, 0, 0)
+    }
+
+    // 'trailing' is the closing brackets that follow on the same line
+    fn fits(&self, column: usize, text: &str, trailing: usize) -> bool {
+        text.len() + trailing <= self.width.saturating_sub(column)
+    }
+
+    // 'column' is where the expression starts on its line
+    fn exp(&self, ast: &Ast, e: NodeId, column: usize, trailing: usize) -> String {
+        let text = flat(ast, e);
+
+        if self.fits(column, &text, trailing) {
+            return text;
+        }
+
+        match &ast[e] {
+            AstNode::Prim { op, args } if !args.is_empty() => {
+                // the arguments line up under the first one
+                let arg_column = column + op.len() + 2;
+
+                let args: Vec<String> =
+                    args
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| {
+                        let closing = if i + 1 == args.len() { trailing + 1 } else { 0 };
+
+                        self.exp(ast, *arg, arg_column, closing)
+                    })
+                    .collect();
+
+                format!("({} {})", op, args.join(&newline(arg_column)))
+            },
+
+            AstNode::Let { bindings, body } => {
+                let binding_column = column + "(let (".len();
+
+                let bindings: Vec<String> =
+                    bindings
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| {
+                        let expr_column = binding_column + b.identifier.len() + 2;
+                        let closing = if i + 1 == bindings.len() { 2 } else { 1 };
+
+                        format!("[{} {}]", b.identifier, self.exp(ast, b.expr, expr_column, closing))
+                    })
+                    .collect();
+
+                format!(
+                    "(let ({}){}{})",
+                    bindings.join(&newline(binding_column)),
+                    newline(column + 2),
+                    self.exp(ast, *body, column + 2, trailing + 1)
+                )
+            },
+
+            _ => text,
+        }
+    }
+}
+
+fn newline(column: usize) -> String {
+    format!("\n{}", " ".repeat(column))
+}
+
+fn flat(ast: &Ast, e: NodeId) -> String {
+    match &ast[e] {
+        AstNode::Int(n) if *n < 0 => format!("(- {})", n.unsigned_abs()),
+
+        AstNode::Int(n) => n.to_string(),
+
+        AstNode::Var { name } => name.to_string(),
+
+        AstNode::Prim { op, args } => {
+            let mut text = format!("({}", op);
+
+            for arg in args {
+                text += " ";
+                text += &flat(ast, *arg);
+            }
+
+            text + ")"
+        },
+
+        AstNode::Let { bindings, body } => {
+            let bindings: Vec<String> =
+                bindings
+                .iter()
+                .map(|b| format!("[{} {}]", b.identifier, flat(ast, b.expr)))
+                .collect();
+
+            format!("(let ({}) {})", bindings.join(" "), flat(ast, *body))
+        },
+
+        AstNode::Error { msg, .. } => format!("<error: {}>", msg),
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", AstPrinter::new().print(self))
+    }
+}
+
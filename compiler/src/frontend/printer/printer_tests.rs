@@ -0,0 +1,96 @@
+use crate::frontend::ast::{AstNode, Program};
+use crate::fuzz::{Generator, GenConfig};
+use crate::utility::{test_parse_helper, test_interp_ast_helper};
+
+use super::*;
+
+fn round_trip(source: &str, printer: &AstPrinter) {
+    let p = test_parse_helper(source);
+
+    let printed = printer.print(&p);
+
+    assert_eq!(test_parse_helper(&printed), p, "{}", printed);
+
+    // printing what was printed doesn't change it
+    assert_eq!(printer.print(&test_parse_helper(&printed)), printed);
+}
+
+#[test]
+fn print_ast_flat() {
+    let p = test_parse_helper("(let ([x  (read)] [y 2])\n   (+ x (- y)))");
+
+    assert_eq!(p.to_string(), "(let ([x (read)] [y 2]) (+ x (- y)))");
+    assert_eq!(test_parse_helper("(42)").to_string(), "(42)");
+    assert_eq!(test_parse_helper("(x)").to_string(), "(x)");
+    assert_eq!(test_parse_helper("(read)").to_string(), "(read)");
+}
+
+#[test]
+fn print_ast_breaks_long_lines() {
+    let p = test_parse_helper("(let ([x (+ (read) 10)] [y (- (read))]) (+ x y))");
+
+    let printed = AstPrinter::new().set_width(22).print(&p);
+
+    let expected = [
+        "(let ([x (+ (read)",
+        "            10)]",
+        "      [y (- (read))])",
+        "  (+ x y))",
+    ];
+
+    assert_eq!(printed, expected.join("\n"));
+
+    assert!(printed.lines().all(|line| line.len() <= 22), "{}", printed);
+}
+
+#[test]
+fn print_ast_negative_numbers() {
+    // partial evaluation makes these, the parser can't
+    let p = Program::leaf(AstNode::Int(-5));
+
+    assert_eq!(p.to_string(), "(- 5)");
+
+    let (result, _) = test_interp_ast_helper(test_parse_helper(&p.to_string()), vec!());
+
+    assert_eq!(result.value, Some(-5));
+}
+
+#[test]
+fn print_ast_round_trip() {
+    let sources = [
+        "(1)",
+        "(read)",
+        "(- (- (read)))",
+        "(+ (+ 1 2) (+ (read) (- 3)))",
+        "(let ([x 1]) x)",
+        "(let ([x 1] [y x] [z (+ x y)]) (let ([x (+ z (read))]) (+ x (- z))))",
+        "(let ([long-name (let ([a (read)] [b (read)]) (+ a b))]) (+ long-name (let ([c 1]) c)))",
+    ];
+
+    for width in [10, 20, 40, DEFAULT_WIDTH, usize::MAX] {
+        let printer = AstPrinter::new().set_width(width);
+
+        for source in sources {
+            round_trip(source, &printer);
+        }
+    }
+}
+
+#[test]
+fn print_ast_round_trip_generated() {
+    let configs = [GenConfig::default(), GenConfig::shadowing(), GenConfig::reads()];
+
+    for width in [16, 30, DEFAULT_WIDTH, usize::MAX] {
+        let printer = AstPrinter::new().set_width(width);
+
+        for config in &configs {
+            for seed in 0..200 {
+                let p = Generator::new(seed, config.clone()).generate();
+
+                let printed = printer.print(&p);
+
+                assert_eq!(test_parse_helper(&printed), p, "{}", printed);
+            }
+        }
+    }
+}
@@ -1,102 +1,117 @@
 /*
     make it so that variables are numbered depending on the scope they're defined in
     this allows variable shadowing
+
+    every binder gets a name of its own, so if x.1 is already taken (e.g. by a let
+    next to this one) the next free number is used instead
 */
 
 #[cfg(test)]
 mod uniquify_tests;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::types::{IdString};
 
-use super::ast::{AstNode, LetBinding, Program};
+use super::ast::{Ast, AstNode, NodeId, Program};
+
+struct Uniquifier {
+    environments: Vec<HashMap<IdString, IdString>>,
+    taken: HashSet<IdString>,
+}
+
+fn fresh_name(taken: &mut HashSet<IdString>, var: IdString, depth: usize) -> IdString {
+    let mut n = depth;
+
+    loop {
+        let name = IdString::derive(var, ".", n);
+
+        if taken.insert(name) {
+            return name;
+        }
+
+        n += 1;
+    }
+}
 
-fn uniquify_exp(environments: &mut Vec<HashMap<IdString, IdString>>, e: AstNode) -> AstNode {
-    match e {
-        AstNode::Int(n) => AstNode::Int(n),
+// the names are changed where they are, the tree keeps its shape
+fn uniquify_exp(u: &mut Uniquifier, ast: &mut Ast, e: NodeId) {
+    match &ast[e] {
+        AstNode::Int(_) => {},
 
         AstNode::Var { name } => {
 
-            let mut new_name = name.clone();
+            let name = *name;
+            let mut new_name = name;
 
-            let last = environments.len();
+            let last = u.environments.len();
 
             for i in (0..last).rev() {
-                if environments[i].contains_key(&name) {
-                    new_name = environments[i][&name].clone();
+                if u.environments[i].contains_key(&name) {
+                    new_name = u.environments[i][&name];
                     break;
                 }
             }
 
-            AstNode::Var {
+            ast[e] = AstNode::Var {
                 name: new_name
-            }
+            };
         },
 
-        AstNode::Prim { op, mut args } => {
+        AstNode::Prim { args, .. } => {
             for i in 0..args.len() {
-                let new_arg_expr = uniquify_exp(environments, args[i].clone());
-                args[i] = new_arg_expr;
-            }
+                let arg = ast.arg(e, i);
 
-            AstNode::Prim {
-                op: op,
-                args: args
+                uniquify_exp(u, ast, arg);
             }
         },
 
         AstNode::Let { bindings, body, } => {
 
-            environments.push(HashMap::new());
+            let (count, body) = (bindings.len(), *body);
 
-            let last = environments.len()-1;
+            u.environments.push(HashMap::new());
 
-            let mut unique_bindings: Vec<LetBinding> = Vec::new();
+            let last = u.environments.len()-1;
 
-            for binding in bindings {
+            for i in 0..count {
 
-                let the_var = binding.identifier;
-                let the_expression = binding.expr;
+                let the_var = ast.binding(e, i).identifier;
+                let the_expression = ast.binding(e, i).expr;
 
-                let new_name = crate::idstr!((*the_var).clone() + "." + &(last+1).to_string());
+                let new_name = fresh_name(&mut u.taken, the_var, last + 1);
 
-                let unq_value = uniquify_exp(environments, the_expression);
+                uniquify_exp(u, ast, the_expression);
 
-                let current_env = environments.get_mut(last).unwrap();
+                let current_env = u.environments.get_mut(last).unwrap();
 
-                current_env.insert(the_var, new_name.clone());
+                current_env.insert(the_var, new_name);
 
-                unique_bindings.push(
-                    LetBinding {
-                        identifier: new_name,
-                        expr: unq_value
-                    }
-                );
+                ast.binding_mut(e, i).identifier = new_name;
             }
 
-            let unq_body = uniquify_exp(environments, *body);
+            uniquify_exp(u, ast, body);
 
-            environments.pop();
-
-            AstNode::Let {
-                bindings: unique_bindings,
-                body: Box::new(unq_body)
-            }
+            u.environments.pop();
         },
 
-        AstNode::Error { msg, token } => {
-            AstNode::Error { msg: msg, token: token }
-        },
+        AstNode::Error { .. } => {},
     }
 }
 
 pub fn uniquify_program(p: Program) -> Program {
 
-    let env =
-        &mut Vec::<HashMap<IdString, IdString>>::new();
+    let mut u = Uniquifier {
+        environments: vec!(),
+        taken: HashSet::new(),
+    };
+
+    let mut ast = p.ast;
+
+    uniquify_exp(&mut u, &mut ast, p.exp);
 
     Program {
         info: p.info,
-        exp: uniquify_exp(env, p.exp),
+        ast: ast,
+        exp: p.exp,
     }
-}
\ No newline at end of file
+}
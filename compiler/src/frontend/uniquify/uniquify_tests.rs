@@ -1,4 +1,4 @@
-use crate::frontend::ast::{AstNode, Program, LetBinding};
+use crate::frontend::ast::{Ast, AstNode, Program, LetBinding};
 
 use crate::utility::{test_ast_helper, AstStep};
 
@@ -15,18 +15,26 @@ fn uniquify_let() {
 
     let x_var_unq = crate::idstr!("x.1");
 
+    let mut ast = Ast::new();
+
+    let forty_two = ast.add(AstNode::Int(42));
+    let x = ast.add(AstNode::Var { name: x_var_unq });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x_var_unq,
+                expr: forty_two
+            }
+        ),
+
+        body: x
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: x_var_unq.clone(),
-                    expr: AstNode::Int(42)
-                }
-            ),
-
-            body: Box::new(AstNode::Var { name: x_var_unq })
-        }
+        ast: ast,
+        exp: exp
     };
 
     assert_eq!(unique_program, expected);
@@ -39,30 +47,37 @@ fn uniquify_let_addition() {
     let x_var_unq = crate::idstr!("x.1");
     let y_var_unq = crate::idstr!("y.1");
 
+    let mut ast = Ast::new();
+
+    let forty_two = ast.add(AstNode::Int(42));
+    let ten = ast.add(AstNode::Int(10));
+    let x = ast.add(AstNode::Var { name: x_var_unq });
+    let y = ast.add(AstNode::Var { name: y_var_unq });
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(x, y)
+    });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x_var_unq,
+                expr: forty_two
+            },
+            LetBinding {
+                identifier: y_var_unq,
+                expr: ten
+            }
+        ),
+
+        body: sum
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: x_var_unq.clone(),
-                    expr: AstNode::Int(42)
-                },
-                LetBinding {
-                    identifier: y_var_unq.clone(),
-                    expr: AstNode::Int(10)
-                }
-            ),
-
-            body: Box::new(
-                AstNode::Prim {
-                    op: crate::idstr!("+"),
-                    args: vec!(
-                        AstNode::Var { name: x_var_unq },
-                        AstNode::Var { name: y_var_unq },
-                    )
-                }
-            )
-        }
+        ast: ast,
+        exp: exp
     };
 
     assert_eq!(unique_program, expected);
@@ -75,28 +90,38 @@ fn uniquify_nested_let() {
     let x_var_unq = crate::idstr!("x.1");
     let y_var_unq = crate::idstr!("y.2");
 
+    let mut ast = Ast::new();
+
+    let forty_two = ast.add(AstNode::Int(42));
+    let y = ast.add(AstNode::Var { name: y_var_unq });
+
+    let inner = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: y_var_unq,
+                expr: forty_two
+            },
+        ),
+        body: y
+    });
+
+    let x = ast.add(AstNode::Var { name: x_var_unq });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x_var_unq,
+                expr: inner
+            }
+        ),
+
+        body: x
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: x_var_unq.clone(),
-                    expr: AstNode::Let {
-                        bindings: vec!(
-                            LetBinding {
-                                identifier: y_var_unq.clone(),
-                                expr: AstNode::Int(42)
-                            },
-                        ),
-                        body: Box::new( AstNode::Var { name: y_var_unq } )
-                    }
-                }
-            ),
-
-            body: Box::new(
-                AstNode::Var { name: x_var_unq },
-            )
-        }
+        ast: ast,
+        exp: exp
     };
 
     assert_eq!(unique_program, expected);
@@ -109,36 +134,66 @@ fn uniquify_nested_let_shadowing() {
     let x1_var = crate::idstr!("x.1");
     let x2_var = crate::idstr!("x.2");
 
+    let mut ast = Ast::new();
+
+    let ten = ast.add(AstNode::Int(10));
+    let x1 = ast.add(AstNode::Var { name: x1_var });
+    let one = ast.add(AstNode::Int(1));
+
+    let sum = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(x1, one)
+    });
+
+    let x2 = ast.add(AstNode::Var { name: x2_var });
+
+    let inner = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x2_var,
+                expr: sum
+            }
+        ),
+        body: x2
+    });
+
+    let exp = ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: x1_var,
+                expr: ten,
+            },
+        ),
+        body: inner
+    });
+
     let expected = Program {
         info: (),
-        exp: AstNode::Let {
-            bindings: vec!(
-                LetBinding {
-                    identifier: x1_var.clone(),
-                    expr: AstNode::Int(10),
-                },
-            ),
-            body: Box::new(
-                AstNode::Let {
-                    bindings: vec!(
-                        LetBinding {
-                            identifier: x2_var.clone(),
-                            expr: AstNode::Prim {
-                                op: crate::idstr!("+"),
-                                args: vec!(
-                                    AstNode::Var { name: x1_var },
-                                    AstNode::Int(1)
-                                )
-                            }
-                        }
-                    ),
-                    body: Box::new(
-                        AstNode::Var { name: x2_var }
-                    )
-                }
-            )
-        }
+        ast: ast,
+        exp: exp
     };
 
     assert_eq!(unique_program, expected);
-}
\ No newline at end of file
+}
+
+#[test]
+fn uniquify_sibling_lets() {
+    // both lets are at the same depth, the second one can't reuse x.1
+    let unique_program = helper("(+ (let ([x 1]) x) (let ([x 2]) x))");
+
+    let ast = &unique_program.ast;
+
+    let binder = |e| match &ast[e] {
+        AstNode::Let { bindings, body } => (bindings[0].identifier, ast[*body].clone()),
+        other => panic!("expected a let, got {:?}", other),
+    };
+
+    match unique_program.root() {
+        AstNode::Prim { args, .. } => {
+            assert_eq!(binder(args[0]), (crate::idstr!("x.1"), AstNode::Var { name: crate::idstr!("x.1") }));
+            assert_eq!(binder(args[1]), (crate::idstr!("x.2"), AstNode::Var { name: crate::idstr!("x.2") }));
+        },
+
+        other => panic!("expected a prim, got {:?}", other),
+    }
+}
@@ -0,0 +1,198 @@
+/*
+    check that an ast has the shape the next pass expects
+
+    after uniquify every let binder has a name of its own:
+
+        (let ([x.1 1]) (let ([x.2 x.1]) x.2))
+
+    after decomplify the program is in monadic normal form, the arguments of
+    the primitives are atoms:
+
+        atm ::= int | var
+        exp ::= atm | (read) | (- atm) | (+ atm atm) | (let ([var exp]+) exp)
+
+    a program read from a file has only been parsed, every variable in it has
+    to be bound by an enclosing let:
+
+        (let ([x 1] [y x]) (+ x y))
+
+    and it can't be nested deeper than the parser allows, the passes recurse
+
+    the verifiers return a description of every problem found
+*/
+
+#[cfg(test)]
+mod verify_tests;
+
+use std::collections::HashSet;
+
+use crate::types::{IdString};
+use crate::symbol::{Symbol, PLUS, MINUS, READ};
+
+use super::ast::{Ast, AstNode, NodeId, Program, MAX_NESTING};
+use super::printer::{AstPrinter};
+
+// the primitives and how many arguments they take
+pub const KNOWN_PRIMS: &[(Symbol, usize)] = &[(READ, 0), (MINUS, 1), (PLUS, 2)];
+
+fn check_prim(op: &IdString, args: &[NodeId], errors: &mut Vec<String>) {
+    match KNOWN_PRIMS.iter().find(|(name, _)| *op == *name) {
+        Some((_, arity)) => {
+            if args.len() != *arity {
+                errors.push(format!("'{}' takes {} argument(s) but was given {}", op, arity, args.len()));
+            }
+        },
+
+        None => {
+            errors.push(format!("unknown primitive '{}'", op));
+        }
+    }
+}
+
+fn check_binders(ast: &Ast, e: NodeId, binders: &mut HashSet<IdString>, errors: &mut Vec<String>) {
+    match &ast[e] {
+        AstNode::Int(_) | AstNode::Var { .. } => {},
+
+        AstNode::Prim { op, args } => {
+            check_prim(op, args, errors);
+
+            for arg in args {
+                check_binders(ast, *arg, binders, errors);
+            }
+        },
+
+        AstNode::Let { bindings, body } => {
+            for binding in bindings {
+                if !binders.insert(binding.identifier) {
+                    errors.push(format!("'{}' is bound more than once", binding.identifier));
+                }
+
+                check_binders(ast, binding.expr, binders, errors);
+            }
+
+            check_binders(ast, *body, binders, errors);
+        },
+
+        AstNode::Error { msg, .. } => {
+            errors.push(format!("error node left in the program: {}", msg));
+        },
+    }
+}
+
+// every variable is bound by an enclosing let, a let binding sees the ones before it
+fn check_scopes(ast: &Ast, e: NodeId, scope: &mut Vec<IdString>, errors: &mut Vec<String>) {
+    match &ast[e] {
+        AstNode::Int(_) => {},
+
+        AstNode::Var { name } => {
+            if !scope.contains(name) {
+                errors.push(format!("'{}' is not defined", name));
+            }
+        },
+
+        AstNode::Prim { op, args } => {
+            check_prim(op, args, errors);
+
+            for arg in args {
+                check_scopes(ast, *arg, scope, errors);
+            }
+        },
+
+        AstNode::Let { bindings, body } => {
+            let depth = scope.len();
+
+            for binding in bindings {
+                if scope[depth..].contains(&binding.identifier) {
+                    errors.push(format!("'{}' is bound more than once in the same let", binding.identifier));
+                }
+
+                check_scopes(ast, binding.expr, scope, errors);
+
+                scope.push(binding.identifier);
+            }
+
+            check_scopes(ast, *body, scope, errors);
+
+            scope.truncate(depth);
+        },
+
+        AstNode::Error { msg, .. } => {
+            errors.push(format!("error node left in the program: {}", msg));
+        },
+    }
+}
+
+fn is_atom(e: &AstNode) -> bool {
+    matches!(e, AstNode::Int(_) | AstNode::Var { .. })
+}
+
+fn check_monadic(ast: &Ast, e: NodeId, errors: &mut Vec<String>) {
+    match &ast[e] {
+        AstNode::Int(_) | AstNode::Var { .. } => {},
+
+        AstNode::Prim { op, args } => {
+            for arg in args {
+                if !is_atom(&ast[*arg]) {
+                    let text = AstPrinter::new().set_width(usize::MAX).print_exp(ast, *arg);
+
+                    errors.push(format!("argument of '{}' is not an atom: {}", op, text));
+                }
+            }
+        },
+
+        AstNode::Let { bindings, body } => {
+            for binding in bindings {
+                check_monadic(ast, binding.expr, errors);
+            }
+
+            check_monadic(ast, *body, errors);
+        },
+
+        AstNode::Error { .. } => {},
+    }
+}
+
+fn result(errors: Vec<String>) -> Result<(), Vec<String>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// a program that didn't come from the parser, e.g. one read from json: it's no
+// deeper than the parser allows, only known primitives are used and every
+// variable is bound
+pub fn verify_ast(p: &Program) -> Result<(), Vec<String>> {
+    // checked first, the checks below recurse as deep as the program goes
+    let depth = p.ast.depth(p.exp);
+
+    if depth > MAX_NESTING {
+        return Err(vec!(format!("nested {} expressions deep, more than {}", depth, MAX_NESTING)));
+    }
+
+    let mut errors = vec!();
+
+    check_scopes(&p.ast, p.exp, &mut vec!(), &mut errors);
+
+    result(errors)
+}
+
+// no let binder appears twice, only known primitives are used
+pub fn verify_uniquified(p: &Program) -> Result<(), Vec<String>> {
+    let mut errors = vec!();
+
+    check_binders(&p.ast, p.exp, &mut HashSet::new(), &mut errors);
+
+    result(errors)
+}
+
+// uniquified, and every primitive only takes atoms
+pub fn verify_monadic(p: &Program) -> Result<(), Vec<String>> {
+    let mut errors = vec!();
+
+    check_binders(&p.ast, p.exp, &mut HashSet::new(), &mut errors);
+    check_monadic(&p.ast, p.exp, &mut errors);
+
+    result(errors)
+}
@@ -0,0 +1,142 @@
+use crate::frontend::ast::{Ast, AstNode, NodeId, Program, LetBinding, MAX_NESTING};
+use crate::utility::{test_ast_helper, test_parse_helper, AstStep};
+
+use super::*;
+
+// (let ([name value]) name)
+fn int_let(ast: &mut Ast, name: &str, value: i64) -> NodeId {
+    let expr = ast.add(AstNode::Int(value));
+    let body = ast.add(AstNode::Var { name: crate::idstr!(name) });
+
+    ast.add(AstNode::Let {
+        bindings: vec!(
+            LetBinding {
+                identifier: crate::idstr!(name),
+                expr: expr
+            }
+        ),
+        body: body
+    })
+}
+
+fn program(ast: Ast, exp: NodeId) -> Program {
+    Program {
+        info: (),
+        ast: ast,
+        exp: exp,
+    }
+}
+
+// (op args...) of ints
+fn prim(op: &str, args: &[i64]) -> Program {
+    let mut ast = Ast::new();
+
+    let args = args.iter().map(|n| ast.add(AstNode::Int(*n))).collect();
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!(op),
+        args: args
+    });
+
+    program(ast, exp)
+}
+
+#[test]
+fn verify_uniquify_output() {
+    let p = test_ast_helper(
+        "(+ (let ([x 1]) x) (let ([x (let ([x 2]) x)]) (let ([x 3]) x)))",
+        vec!(AstStep::Uniquify)
+    );
+
+    assert_eq!(verify_uniquified(&p), Ok(()));
+}
+
+#[test]
+fn verify_duplicate_binder() {
+    let mut ast = Ast::new();
+
+    let left = int_let(&mut ast, "x.1", 1);
+    let right = int_let(&mut ast, "x.1", 2);
+
+    let exp = ast.add(AstNode::Prim {
+        op: crate::idstr!("+"),
+        args: vec!(left, right)
+    });
+
+    let p = program(ast, exp);
+
+    assert_eq!(verify_uniquified(&p), Err(vec!("'x.1' is bound more than once".to_owned())));
+}
+
+#[test]
+fn verify_unknown_primitive() {
+    let p = prim("*", &[1, 2]);
+
+    assert_eq!(verify_uniquified(&p), Err(vec!("unknown primitive '*'".to_owned())));
+}
+
+#[test]
+fn verify_wrong_arity() {
+    let p = prim("-", &[1, 2]);
+
+    assert_eq!(verify_uniquified(&p), Err(vec!("'-' takes 1 argument(s) but was given 2".to_owned())));
+}
+
+#[test]
+fn verify_decomplify_output() {
+    let p = test_ast_helper(
+        "(let ([x (+ (read) (- (read)))]) (+ x (let ([y (- x)]) (+ y (read)))))",
+        vec!(AstStep::Uniquify, AstStep::Decomplify)
+    );
+
+    assert_eq!(verify_monadic(&p), Ok(()));
+}
+
+#[test]
+fn verify_not_monadic() {
+    let p = test_ast_helper("(+ (read) (- 2))", vec!(AstStep::Uniquify));
+
+    let errors = verify_monadic(&p).unwrap_err();
+
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].starts_with("argument of '+' is not an atom"));
+}
+
+#[test]
+fn verify_scopes() {
+    let p = test_parse_helper("(let ([x 1] [y x]) (+ (let ([x 2]) x) y))");
+
+    assert_eq!(verify_ast(&p), Ok(()));
+
+    // y is only bound in the body of the first let
+    let p = test_parse_helper("(+ (let ([y 1]) y) y)");
+
+    assert_eq!(verify_ast(&p), Err(vec!("'y' is not defined".to_owned())));
+
+    let p = test_parse_helper("(let ([x 1] [x 2]) x)");
+
+    assert_eq!(verify_ast(&p), Err(vec!("'x' is bound more than once in the same let".to_owned())));
+}
+
+#[test]
+fn verify_nesting() {
+    // (- (- ... (- 1))) with 'depth' nodes in all
+    let negations = |depth: usize| {
+        let mut ast = Ast::new();
+
+        let mut exp = ast.add(AstNode::Int(1));
+
+        for _ in 1..depth {
+            exp = ast.add(AstNode::Prim { op: crate::idstr!("-"), args: vec!(exp) });
+        }
+
+        program(ast, exp)
+    };
+
+    assert_eq!(verify_ast(&negations(MAX_NESTING)), Ok(()));
+
+    assert_eq!(
+        verify_ast(&negations(MAX_NESTING + 1)),
+        Err(vec!(format!("nested {} expressions deep, more than {}", MAX_NESTING + 1, MAX_NESTING)))
+    );
+}
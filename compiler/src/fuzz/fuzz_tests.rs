@@ -0,0 +1,197 @@
+use crate::types::{OptLevel, Overflow};
+use crate::utility::{test_interp_ast_helper};
+use crate::frontend::ast::{Program};
+use crate::frontend::verify::{verify_uniquified};
+use crate::frontend::uniquify::{uniquify_program};
+
+use super::*;
+
+fn presets() -> Vec<GenConfig> {
+    vec!(GenConfig::default(), GenConfig::shadowing(), GenConfig::reads(), GenConfig::checked(), GenConfig::checked_reads())
+}
+
+fn options(opt_level: OptLevel) -> PipelineOptions {
+    PipelineOptions {
+        opt_level: opt_level,
+        verify: true,
+        ..PipelineOptions::default()
+    }
+}
+
+const LEVELS: [OptLevel; 3] = [OptLevel::O0, OptLevel::O1, OptLevel::O2];
+
+fn report(failures: &[FuzzFailure]) -> String {
+    failures.iter().map(|f| f.to_string()).collect::<Vec<String>>().join("\n\n")
+}
+
+#[test]
+fn fuzz_same_seed_same_program() {
+    for config in presets() {
+        let a = Generator::new(1234, config.clone()).generate();
+        let b = Generator::new(1234, config.clone()).generate();
+        let c = Generator::new(1235, config).generate();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+}
+
+#[test]
+fn fuzz_programs_are_well_scoped() {
+    for config in presets() {
+        for seed in 0..200 {
+            let mut generator = Generator::new(seed, config.clone());
+
+            let p = generator.generate();
+            let input = generator.gen_input(&p);
+
+            let source = to_source(&p);
+
+            // the text parses back to the same program
+            assert_eq!(crate::utility::test_parse_helper(&source), p, "{}", source);
+
+            let (result, reads) = test_interp_ast_helper(p.clone(), input.iter().map(|l| l.as_str()).collect());
+
+            assert!(!result.had_error, "{}: {:?}", source, result.errors);
+            assert_eq!(reads.len(), input.len());
+
+            assert_eq!(verify_uniquified(&uniquify_program(p)), Ok(()));
+        }
+    }
+}
+
+#[test]
+fn fuzz_respects_limits() {
+    let config = GenConfig::default().set_max_depth(3).set_max_size(10);
+
+    for seed in 0..200 {
+        let p = Generator::new(seed, config.clone()).generate();
+
+        // a let or prim at the depth limit still has leaves below it
+        assert!(depth_of(&p) <= 4, "{}", to_source(&p));
+    }
+}
+
+#[test]
+fn fuzz_presets_have_their_shape() {
+    let total = |config: GenConfig, count: fn(&Program) -> usize| -> usize {
+        (0..100).map(|seed| count(&Generator::new(seed, config.clone()).generate())).sum()
+    };
+
+    assert!(total(GenConfig::reads(), count_reads) > total(GenConfig::default(), count_reads));
+
+    // with a single name every let inside another one shadows it
+    let shadowing = (0..100)
+        .map(|seed| to_source(&Generator::new(seed, GenConfig::shadowing()).generate()))
+        .filter(|source| source.matches("(let").count() > 1)
+        .count();
+
+    assert!(shadowing > 10);
+
+    // checked mode stops a good part of the programs, and never the wrapping presets
+    let overflows = |config: GenConfig| {
+        (0..100)
+            .filter(|seed| {
+                let mut generator = Generator::new(*seed, config.clone());
+
+                let source = to_source(&generator.generate());
+                let input = generator.gen_input(&crate::utility::test_parse_helper(&source));
+
+                let lines: Vec<&str> = input.iter().map(|line| line.as_str()).collect();
+
+                let options = PipelineOptions { overflow: config.overflow, ..options(OptLevel::O0) };
+                let report = DiffHarness::new(options).set_native(false).run(&source, &lines).unwrap();
+
+                report.reference().value.is_none()
+            })
+            .count()
+    };
+
+    assert!(overflows(GenConfig::checked()) > 10);
+    assert!(overflows(GenConfig::checked_reads()) > 10);
+    assert_eq!(overflows(GenConfig::default()), 0);
+}
+
+#[test]
+fn fuzz_pipeline() {
+    for level in LEVELS {
+        for config in presets() {
+            let failures = fuzz(0..300, &config, &options(level));
+
+            assert!(failures.is_empty(), "{:?}\n{}", level, report(&failures));
+        }
+    }
+}
+
+#[test]
+fn fuzz_regressions() {
+    let programs = parse_regressions(include_str!("regressions.txt")).unwrap();
+
+    assert!(!programs.is_empty());
+
+    // the file doesn't say which preset found a program, so each one is checked both ways
+    for level in LEVELS {
+        for overflow in [Overflow::Wrap, Overflow::Checked] {
+            for (source, input) in &programs {
+                let options = PipelineOptions { overflow: overflow, ..options(level) };
+
+                let result = check_program(source, input, &options);
+
+                assert_eq!(result, Ok(()), "{:?} {:?}\n{}\n{:?}", level, overflow, source, input);
+            }
+        }
+    }
+}
+
+#[test]
+fn fuzz_parse_regressions() {
+    let programs = parse_regressions("# comment\n\n(+ (read) 1) | 41\n(2) |   # no input\n").unwrap();
+
+    assert_eq!(programs, vec!(
+        ("(+ (read) 1)".to_owned(), vec!("41".to_owned())),
+        ("(2)".to_owned(), vec!()),
+    ));
+
+    assert!(parse_regressions("(+ (read) 1)").is_err());
+    assert!(parse_regressions(" | 1 2").is_err());
+}
+
+#[test]
+fn fuzz_failure_is_a_regression_line() {
+    let failure = FuzzFailure {
+        seed: 3,
+        preset: "default",
+        source: "(+ (read) 1)".to_owned(),
+        input: vec!("41".to_owned()),
+        reason: "'decomplify' disagrees".to_owned(),
+    };
+
+    let line = format!("{}", failure).lines().last().unwrap().to_owned();
+
+    assert!(line.ends_with("add '(+ (read) 1) | 41' to src/fuzz/regressions.txt"), "{}", line);
+
+    assert_eq!(
+        parse_regressions("(+ (read) 1) | 41"),
+        Ok(vec!((failure.source, failure.input)))
+    );
+}
+
+// run a lot more seeds than the regular tests, e.g. after changing a pass
+// FUZZ_SEEDS=100000 cargo test fuzz_many -- --ignored --nocapture
+#[test]
+#[ignore]
+fn fuzz_many() {
+    let count = std::env::var("FUZZ_SEEDS").ok().and_then(|n| n.parse().ok()).unwrap_or(10000);
+
+    let mut failures = vec!();
+
+    for level in LEVELS {
+        for config in presets() {
+            failures.extend(fuzz(0..count, &config, &options(level)));
+        }
+    }
+
+    println!("{}", report(&failures));
+
+    assert!(failures.is_empty());
+}
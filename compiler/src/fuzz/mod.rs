@@ -0,0 +1,475 @@
+/*
+    random programs for fuzzing the pipeline
+
+    the generator only produces well scoped programs of the current grammar:
+
+        exp ::= int | (read) | (- exp) | (+ exp exp) | var | (let ([var exp]+) exp)
+
+    a variable is only used where a let binds it. the expression of a binding never
+    refers to a name bound earlier in the same let, so it means the same thing
+    whether the bindings are evaluated in sequence or all at once
+
+    everything is derived from the seed, so a failing seed reproduces the failure.
+    the presets make shapes that tend to break passes: lots of shadowing with a
+    single name, or lots of (read) whose order matters. the checked presets use
+    ints and input close to the 64 bit limits and are compiled with checked
+    overflow, so a program often stops at an overflow and every stage has to stop
+    at the same one
+
+    check_seed runs a program through every stage with the differential harness and
+    reports the seed if a stage disagrees or a pass panics. a program may only fail
+    with an overflow, and only in checked mode. programs that failed once
+    are kept in regressions.txt together with their input and rechecked by the tests.
+    the program is kept rather than the seed, a change to the generator would turn
+    the seed into a different program
+*/
+
+#![allow(dead_code)]
+
+#[cfg(test)]
+mod fuzz_tests;
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::types::{IdString, Overflow, OVERFLOW_ERROR};
+use crate::symbol::{PLUS, MINUS, READ};
+use crate::frontend::ast::{Ast, AstNode, LetBinding, NodeId, Program};
+use crate::frontend::printer::{AstPrinter};
+use crate::difftest::{DiffHarness};
+use crate::pipeline::{PipelineOptions};
+
+// xorshift64*, small and good enough to pick shapes
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+
+    pub fn new(seed: u64) -> Self {
+        // the state must never be zero, and nearby seeds should start far apart
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ 0xD1B5_4A32_D192_ED03;
+
+        if state == 0 {
+            state = 1;
+        }
+
+        Rng {
+            state: state,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // in 0..n
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    // true percent% of the time
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenConfig {
+    pub name: &'static str,
+    pub max_depth: usize,
+    pub max_size: usize, // roughly the number of nodes
+    pub max_bindings: usize, // per let
+    pub names: &'static [&'static str], // the variable names to pick from
+    pub max_int: i64,
+    pub read_percent: u64, // how often a leaf is a (read)
+    pub let_percent: u64, // how often an inner node is a let
+    pub huge_percent: u64, // how often an int or an input is within max_int of the 64 bit limits
+    pub overflow: Overflow, // how the programs are compiled and run
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        GenConfig {
+            name: "default",
+            max_depth: 6,
+            max_size: 40,
+            max_bindings: 3,
+            names: &["a", "b", "c", "d"],
+            max_int: 100,
+            read_percent: 25,
+            let_percent: 35,
+            huge_percent: 0,
+            overflow: Overflow::Wrap,
+        }
+    }
+}
+
+impl GenConfig {
+
+    // one name, so almost every let shadows another one
+    pub fn shadowing() -> Self {
+        GenConfig {
+            name: "shadowing",
+            names: &["x"],
+            let_percent: 60,
+            ..GenConfig::default()
+        }
+    }
+
+    // mostly (read), the order input is consumed in has to be kept
+    pub fn reads() -> Self {
+        GenConfig {
+            name: "reads",
+            read_percent: 70,
+            ..GenConfig::default()
+        }
+    }
+
+    // sums close to the limits, about half of the programs overflow
+    pub fn checked() -> Self {
+        GenConfig {
+            name: "checked",
+            huge_percent: 30,
+            overflow: Overflow::Checked,
+            ..GenConfig::default()
+        }
+    }
+
+    // mostly huge input, the reads after an overflow must not happen in any stage
+    pub fn checked_reads() -> Self {
+        GenConfig {
+            name: "checked-reads",
+            huge_percent: 30,
+            overflow: Overflow::Checked,
+            ..GenConfig::reads()
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<GenConfig> {
+        match name {
+            "default" => Some(GenConfig::default()),
+            "shadowing" => Some(GenConfig::shadowing()),
+            "reads" => Some(GenConfig::reads()),
+            "checked" => Some(GenConfig::checked()),
+            "checked-reads" => Some(GenConfig::checked_reads()),
+            _ => None,
+        }
+    }
+
+    pub fn set_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn set_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+}
+
+pub struct Generator {
+    rng: Rng,
+    config: GenConfig,
+    size: usize,
+    ast: Ast,
+}
+
+impl Generator {
+
+    pub fn new(seed: u64, config: GenConfig) -> Self {
+        Generator {
+            rng: Rng::new(seed),
+            config: config,
+            size: 0,
+            ast: Ast::new(),
+        }
+    }
+
+    pub fn generate(&mut self) -> Program {
+        self.size = 0;
+        self.ast = Ast::new();
+
+        let exp = self.gen_exp(0, &[]);
+
+        Program {
+            info: (),
+            ast: std::mem::take(&mut self.ast),
+            exp: exp,
+        }
+    }
+
+    fn gen_int(&mut self) -> NodeId {
+        let n = self.rng.below(self.config.max_int as u64 + 1) as i64;
+
+        // a literal can't be negative, so only the upper limit
+        let n = if self.rng.chance(self.config.huge_percent) { i64::MAX - n } else { n };
+
+        self.ast.add(AstNode::Int(n))
+    }
+
+    fn gen_leaf(&mut self, scope: &[IdString]) -> NodeId {
+        if self.rng.chance(self.config.read_percent) {
+            return self.ast.add(AstNode::Prim { op: READ, args: vec!() });
+        }
+
+        // prefer variables when there are some, that's where the passes have work to do
+        if !scope.is_empty() && self.rng.chance(60) {
+            let var = scope[self.rng.below(scope.len() as u64) as usize];
+
+            return self.ast.add(AstNode::Var { name: var });
+        }
+
+        self.gen_int()
+    }
+
+    fn gen_exp(&mut self, depth: usize, scope: &[IdString]) -> NodeId {
+        self.size += 1;
+
+        if depth >= self.config.max_depth || self.size >= self.config.max_size || self.rng.chance(20) {
+            return self.gen_leaf(scope);
+        }
+
+        if self.rng.chance(self.config.let_percent) {
+            return self.gen_let(depth, scope);
+        }
+
+        if self.rng.chance(30) {
+            let arg = self.gen_exp(depth + 1, scope);
+
+            self.ast.add(AstNode::Prim {
+                op: MINUS,
+                args: vec!(arg),
+            })
+        } else {
+            let left = self.gen_exp(depth + 1, scope);
+            let right = self.gen_exp(depth + 1, scope);
+
+            self.ast.add(AstNode::Prim {
+                op: PLUS,
+                args: vec!(left, right),
+            })
+        }
+    }
+
+    fn gen_let(&mut self, depth: usize, scope: &[IdString]) -> NodeId {
+        let count = 1 + self.rng.below(self.config.max_bindings as u64) as usize;
+
+        let mut bindings: Vec<LetBinding> = vec!();
+
+        for _ in 0..count {
+            let unused: Vec<&str> =
+                self.config.names
+                .iter()
+                .filter(|name| !bindings.iter().any(|b| b.identifier == **name))
+                .cloned()
+                .collect();
+
+            if unused.is_empty() {
+                break;
+            }
+
+            let name = crate::idstr!(unused[self.rng.below(unused.len() as u64) as usize]);
+
+            // only what was visible before the let, minus what this let rebinds
+            let outer: Vec<IdString> =
+                scope
+                .iter()
+                .filter(|var| !bindings.iter().any(|b| b.identifier == **var) && **var != name)
+                .cloned()
+                .collect();
+
+            bindings.push(
+                LetBinding {
+                    identifier: name,
+                    expr: self.gen_exp(depth + 1, &outer),
+                }
+            );
+        }
+
+        let mut inner: Vec<IdString> =
+            scope
+            .iter()
+            .filter(|var| !bindings.iter().any(|b| b.identifier == **var))
+            .cloned()
+            .collect();
+
+        inner.extend(bindings.iter().map(|b| b.identifier));
+
+        let body = self.gen_exp(depth + 1, &inner);
+
+        self.ast.add(AstNode::Let {
+            bindings: bindings,
+            body: body,
+        })
+    }
+
+    // values for every (read) in the program, they all get evaluated exactly once
+    pub fn gen_input(&mut self, p: &Program) -> Vec<String> {
+        (0..count_reads(p))
+            .map(|_| {
+                let n = self.rng.below(2 * self.config.max_int as u64 + 1) as i64 - self.config.max_int;
+
+                let n = match (self.rng.chance(self.config.huge_percent), n < 0) {
+                    (false, _) => n,
+                    (true, true) => i64::MIN - n - 1,
+                    (true, false) => i64::MAX - n,
+                };
+
+                n.to_string()
+            })
+            .collect()
+    }
+}
+
+pub fn count_reads(p: &Program) -> usize {
+    let mut reads = 0;
+    let mut todo = vec!(p.exp);
+
+    while let Some(e) = todo.pop() {
+        if let AstNode::Prim { op, .. } = &p.ast[e] {
+            if *op == READ {
+                reads += 1;
+            }
+        }
+
+        todo.extend(p.ast.children(e));
+    }
+
+    reads
+}
+
+// a leaf is 0 deep
+pub fn depth_of(p: &Program) -> usize {
+    p.ast.depth(p.exp) - 1
+}
+
+// the program as text the parser accepts, on one line so a failure reads well
+pub fn to_source(p: &Program) -> String {
+    AstPrinter::new().set_width(usize::MAX).print(p)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuzzFailure {
+    pub seed: u64,
+    pub preset: &'static str,
+    pub source: String,
+    pub input: Vec<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "seed {} ({}) failed: {}", self.seed, self.preset, self.reason)?;
+        writeln!(f, "    program: {}", self.source)?;
+        writeln!(f, "    input: {:?}", self.input)?;
+        write!(f, "    to keep it as a regression test, add '{} | {}' to src/fuzz/regressions.txt", self.source, self.input.join(" "))
+    }
+}
+
+// the preset decides the overflow mode, the rest comes from the options
+pub fn check_seed(seed: u64, config: &GenConfig, options: &PipelineOptions) -> Result<(), FuzzFailure> {
+    let mut generator = Generator::new(seed, config.clone());
+    let options = PipelineOptions { overflow: config.overflow, ..options.clone() };
+
+    let program = generator.generate();
+    let input = generator.gen_input(&program);
+
+    let source = to_source(&program);
+
+    match check_program(&source, &input, &options) {
+        Ok(()) => Ok(()),
+        Err(reason) => Err(FuzzFailure {
+            seed: seed,
+            preset: config.name,
+            source: source,
+            input: input,
+            reason: reason,
+        }),
+    }
+}
+
+// Err says why the program failed
+pub fn check_program(source: &str, input: &[String], options: &PipelineOptions) -> Result<(), String> {
+    let harness = DiffHarness::new(options.clone()).set_native(false);
+    let lines: Vec<&str> = input.iter().map(|line| line.as_str()).collect();
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| harness.run(source, &lines)));
+
+    match result {
+        Ok(Ok(report)) => {
+            if let Some(stage) = report.first_divergence() {
+                return Err(format!("'{}' disagrees\n{}", stage.stage, report));
+            }
+
+            // nothing to go on, a generated program doesn't have to finish in time
+            if report.inconclusive() {
+                return Ok(());
+            }
+
+            let reference = report.reference();
+
+            let overflowed =
+                options.overflow == Overflow::Checked
+                && !reference.errors.is_empty()
+                && reference.errors.iter().all(|error| error.starts_with(OVERFLOW_ERROR));
+
+            if reference.value.is_none() && !overflowed {
+                return Err(format!("the program failed to run\n{}", report));
+            }
+
+            Ok(())
+        },
+
+        Ok(Err(msg)) => Err(format!("the program doesn't parse: {}", msg)),
+
+        Err(panic) => {
+            let msg =
+                panic.downcast_ref::<String>().cloned()
+                .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+
+            Err(format!("panicked: {}", msg))
+        },
+    }
+}
+
+// every failing seed in the range
+pub fn fuzz(seeds: std::ops::Range<u64>, config: &GenConfig, options: &PipelineOptions) -> Vec<FuzzFailure> {
+    seeds
+        .filter_map(|seed| check_seed(seed, config, options).err())
+        .collect()
+}
+
+// the lines of regressions.txt: "<program> | <input>", the input is the values
+// for (read) separated by spaces. # starts a comment
+pub fn parse_regressions(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut programs = vec!();
+
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap().trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (source, input) = match line.split_once('|') {
+            Some((source, input)) => (source.trim(), input),
+            None => return Err(format!("'{}': expected '<program> | <input>'", line)),
+        };
+
+        if source.is_empty() {
+            return Err(format!("'{}': the program is missing", line));
+        }
+
+        let input = input.split_whitespace().map(|value| value.to_owned()).collect();
+
+        programs.push((source.to_owned(), input));
+    }
+
+    Ok(programs)
+}
@@ -0,0 +1,337 @@
+/*
+    a debugger for the ir interpreter
+
+    the interpreter stops before every statement and every tail and asks for a
+    command. it starts out stepping:
+
+        start: x.1 = (read);
+        (debug) watch tmp.2
+        (debug) continue
+        start: tmp.2 = (- x.1);     stopped at the watch on tmp.2
+        (debug) print
+        x.1 = 5
+
+    'break' stops when a block is entered, 'watch' before a variable is assigned.
+    a variable can be named as in the source, 'watch x' also stops at x.1 and x.2
+    after uniquify. at the end of the input it lets the program run to the end
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use crate::ir::explicate::{Block, Tail, Stmt, Atm};
+use crate::types::{IdString};
+
+pub const DEBUG_PROMPT: &str = "(debug) ";
+
+// the error of a program the debugger stopped
+pub const STOPPED_ERROR: &str = "stopped by the debugger";
+
+// what the interpreter is about to do
+#[derive(Clone, Copy, Debug)]
+pub enum Point<'p> {
+    Stmt(&'p Stmt),
+    Tail(&'p Tail),
+}
+
+pub struct Stop<'p> {
+    pub label: IdString,
+    pub block: &'p Block,
+    pub index: usize, // of the statement in the block, the tail comes after all of them
+    pub point: Point<'p>,
+    pub vars: &'p HashMap<IdString, Atm>,
+    pub blocks: &'p BTreeMap<IdString, Block>, // all of the program's, for checking a label
+}
+
+impl<'p> Stop<'p> {
+
+    fn assigns(&self) -> Option<IdString> {
+        match self.point {
+            Point::Stmt(Stmt::Assign(Atm::Var { name }, _)) => Some(*name),
+            _ => None,
+        }
+    }
+}
+
+// x.1 is what uniquify made of x
+fn same_var(name: IdString, var: IdString) -> bool {
+    let suffix =
+        var.to_string()
+        .strip_prefix(&name.to_string())
+        .and_then(|rest| rest.strip_prefix('.'))
+        .map(|n| n.to_owned());
+
+    name == var || suffix.is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    Label(IdString), // when the block is entered
+    Var(IdString),   // before the variable is assigned
+}
+
+impl Breakpoint {
+
+    fn name(&self) -> IdString {
+        match self {
+            Breakpoint::Label(name) | Breakpoint::Var(name) => *name,
+        }
+    }
+
+    fn hit(&self, stop: &Stop) -> bool {
+        match self {
+            Breakpoint::Label(label) => *label == stop.label && stop.index == 0,
+            Breakpoint::Var(var) => stop.assigns().is_some_and(|assigned| same_var(*var, assigned)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resume {
+    Stay, // ask for the next command
+    Step,
+    Continue,
+    Quit,
+}
+
+#[derive(Copy, Clone)]
+struct DebugCommand {
+    pub cmd: &'static str,
+    pub short: &'static str,
+    pub help: &'static str,
+    pub action: fn (&mut Debugger, &str, &Stop) -> Resume, // gets what follows the command
+}
+
+const DEBUG_COMMANDS: &[DebugCommand] = &[
+    DebugCommand { cmd: "step", short: "s", help: "run the statement or tail, an empty line does the same", action: step },
+    DebugCommand { cmd: "continue", short: "c", help: "run until a breakpoint", action: resume },
+    DebugCommand { cmd: "break", short: "b", help: "break <label> stops when the block is entered, without a label lists the breakpoints", action: break_at },
+    DebugCommand { cmd: "watch", short: "w", help: "watch <var> stops before the variable is assigned", action: watch },
+    DebugCommand { cmd: "delete", short: "d", help: "delete <name> removes its breakpoints, without a name all of them", action: delete },
+    DebugCommand { cmd: "print", short: "p", help: "print [var] shows the variables", action: print },
+    DebugCommand { cmd: "list", short: "l", help: "show the block", action: list },
+    DebugCommand { cmd: "quit", short: "q", help: "stop the program", action: |_, _, _| Resume::Quit },
+    DebugCommand { cmd: "help", short: "h", help: "show the commands", action: help },
+];
+
+// gets the prompt to show and gives None at the end of the input
+pub type NextLine<'d> = Box<dyn FnMut(&str) -> Option<String> + 'd>;
+
+pub struct Debugger<'d> {
+    breakpoints: Vec<Breakpoint>,
+    stepping: bool,
+    detached: bool, // the input ended, the program runs to the end
+    next_line: NextLine<'d>,
+    out: Box<dyn Write + 'd>,
+}
+
+impl<'d> Debugger<'d> {
+
+    pub fn new(next_line: impl FnMut(&str) -> Option<String> + 'd, out: impl Write + 'd) -> Self {
+        Debugger {
+            breakpoints: vec!(),
+            stepping: true,
+            detached: false,
+            next_line: Box::new(next_line),
+            out: Box::new(out),
+        }
+    }
+
+    // without stepping it only stops at the breakpoints
+    pub fn set_stepping(mut self, stepping: bool) -> Self {
+        self.stepping = stepping;
+        self
+    }
+
+    pub fn set_breakpoints(mut self, breakpoints: Vec<Breakpoint>) -> Self {
+        self.breakpoints = breakpoints;
+        self
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // the output is only for whoever is debugging, so a failed write is ignored
+    fn say(&mut self, text: String) {
+        let _ = writeln!(self.out, "{}", text);
+    }
+
+    // false if the program should be stopped
+    pub fn pause(&mut self, stop: &Stop) -> bool {
+        if self.detached {
+            return true;
+        }
+
+        let hit: Vec<Breakpoint> = self.breakpoints.iter().filter(|b| b.hit(stop)).cloned().collect();
+
+        if !self.stepping && hit.is_empty() {
+            return true;
+        }
+
+        let mut at = format!("{}: {}", stop.label, describe(stop.point));
+
+        for breakpoint in hit {
+            match breakpoint {
+                Breakpoint::Label(label) => at += &format!("     stopped at the break on {}", label),
+                Breakpoint::Var(var) => at += &format!("     stopped at the watch on {}", var),
+            }
+        }
+
+        self.say(at);
+
+        loop {
+            let line = match (self.next_line)(DEBUG_PROMPT) {
+                Some(line) => line,
+                None => {
+                    self.detached = true;
+                    return true;
+                }
+            };
+
+            let line = line.trim();
+
+            let (command, args) = match line.find(char::is_whitespace) {
+                Some(i) => (&line[..i], line[i..].trim()),
+                None => (line, ""),
+            };
+
+            let resume = match DEBUG_COMMANDS.iter().find(|c| c.cmd == command || c.short == command) {
+                Some(c) => (c.action)(self, args, stop),
+                None if command.is_empty() => step(self, "", stop),
+                None => {
+                    self.say(format!("unknown command '{}', 'help' lists them", command));
+                    Resume::Stay
+                },
+            };
+
+            match resume {
+                Resume::Stay => (),
+                Resume::Step | Resume::Continue => return true,
+                Resume::Quit => return false,
+            }
+        }
+    }
+
+    fn add(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    fn list_breakpoints(&mut self) {
+        if self.breakpoints.is_empty() {
+            self.say("no breakpoints".to_owned());
+        }
+
+        for breakpoint in self.breakpoints.clone() {
+            match breakpoint {
+                Breakpoint::Label(label) => self.say(format!("break {}", label)),
+                Breakpoint::Var(var) => self.say(format!("watch {}", var)),
+            }
+        }
+    }
+}
+
+// the commands, what they print goes to the debugger's output
+fn step(debugger: &mut Debugger, _: &str, _: &Stop) -> Resume {
+    debugger.stepping = true;
+    Resume::Step
+}
+
+fn resume(debugger: &mut Debugger, _: &str, _: &Stop) -> Resume {
+    debugger.stepping = false;
+    Resume::Continue
+}
+
+fn break_at(debugger: &mut Debugger, args: &str, stop: &Stop) -> Resume {
+    match args {
+        "" => debugger.list_breakpoints(),
+
+        // a break on a block that doesn't exist would never stop
+        label if !stop.blocks.contains_key(&crate::idstr!(label)) => {
+            let labels: Vec<String> = stop.blocks.keys().map(|l| l.to_string()).collect();
+
+            debugger.say(format!("unknown label '{}', the blocks are {}", label, labels.join(", ")));
+        },
+
+        label => debugger.add(Breakpoint::Label(crate::idstr!(label))),
+    }
+
+    Resume::Stay
+}
+
+fn watch(debugger: &mut Debugger, args: &str, _: &Stop) -> Resume {
+    match args {
+        "" => debugger.list_breakpoints(),
+        var => debugger.add(Breakpoint::Var(crate::idstr!(var))),
+    }
+
+    Resume::Stay
+}
+
+fn delete(debugger: &mut Debugger, args: &str, _: &Stop) -> Resume {
+    match args {
+        "" => debugger.breakpoints.clear(),
+        name => {
+            let name = crate::idstr!(name);
+            debugger.breakpoints.retain(|breakpoint| breakpoint.name() != name);
+        },
+    }
+
+    Resume::Stay
+}
+
+fn print(debugger: &mut Debugger, args: &str, stop: &Stop) -> Resume {
+    // in the same order every time
+    let mut vars: Vec<(&IdString, &Atm)> =
+        stop.vars
+        .iter()
+        .filter(|(var, _)| args.is_empty() || same_var(crate::idstr!(args), **var))
+        .collect();
+
+    vars.sort_by_key(|(var, _)| var.to_string());
+
+    if vars.is_empty() {
+        match args {
+            "" => debugger.say("no variables yet".to_owned()),
+            name => debugger.say(format!("{} isn't assigned yet", name)),
+        }
+    }
+
+    for (var, value) in vars {
+        debugger.say(format!("{} = {}", var, value));
+    }
+
+    Resume::Stay
+}
+
+// the block, with an arrow at what runs next
+
+fn list(debugger: &mut Debugger, _: &str, stop: &Stop) -> Resume {
+    debugger.say(format!("{}:", stop.label));
+
+    for (i, line) in format!("{}", stop.block).lines().enumerate() {
+        let marker = if i == stop.index { "=>" } else { "  " };
+
+        debugger.say(format!("{}{}", marker, line));
+    }
+
+    Resume::Stay
+}
+
+fn help(debugger: &mut Debugger, _: &str, _: &Stop) -> Resume {
+    for c in DEBUG_COMMANDS {
+        debugger.say(format!("{} ({}) - {}", c.cmd, c.short, c.help));
+    }
+
+    Resume::Stay
+}
+
+// a tail is printed indented like in a block
+fn describe(point: Point) -> String {
+    match point {
+        Point::Stmt(stmt) => format!("{}", stmt),
+        Point::Tail(tail) => format!("{}", tail).trim().to_owned(),
+    }
+}
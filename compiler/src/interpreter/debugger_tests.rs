@@ -0,0 +1,159 @@
+use crate::ir::parser::{parse_ir};
+
+use super::*;
+use super::interp_ir::{IrInterpreter};
+use super::debugger::*;
+
+const COUNTDOWN: &str = "
+    locals: x
+    start:
+        x = (read);
+        goto loop;
+    loop:
+        if x goto body; else goto done;
+    body:
+        x = (+ x -1);
+        goto loop;
+    done:
+        return 42;
+";
+
+// runs the program with the commands as the debugger's input, and gives back what it printed
+fn debug(text: &str, input: Vec<&str>, commands: &[&str], setup: fn(Debugger) -> Debugger) -> (InterpretResult, String) {
+    let program = parse_ir(text).unwrap();
+
+    let mut commands = commands.iter();
+    let mut out: Vec<u8> = vec!();
+
+    let result = {
+        let debugger = setup(Debugger::new(|_| commands.next().map(|c| c.to_string()), &mut out));
+
+        let mut crc = CachedRuntimeCall::new().set_input(input);
+        let mut ir_interpreter = IrInterpreter::new(&program, &mut crc).set_debugger(debugger);
+
+        Interpreter::new(&mut ir_interpreter).run()
+    };
+
+    (result, String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn debugger_steps() {
+    let (result, out) = debug(COUNTDOWN, vec!("1"), &["s", "", "step", "s", "s", "s", "s", "s"], |d| d);
+
+    assert_eq!(result.value, Some(42));
+    assert_eq!(
+        out.lines().collect::<Vec<&str>>(),
+        vec!(
+            "start: x = (read);",
+            "start: goto loop;",
+            "loop: if x goto body; else goto done;",
+            "body: x = (+ x -1);",
+            "body: goto loop;",
+            "loop: if x goto body; else goto done;",
+            "done: return 42;",
+        )
+    );
+}
+
+#[test]
+fn debugger_breakpoints() {
+    // only stops at the breakpoints
+    let (result, out) = debug(COUNTDOWN, vec!("2"), &["c", "c", "c"], |d| {
+        d.set_stepping(false).set_breakpoints(vec!(Breakpoint::Var(crate::idstr!("x")), Breakpoint::Label(crate::idstr!("done"))))
+    });
+
+    assert_eq!(result.value, Some(42));
+    assert_eq!(
+        out.lines().collect::<Vec<&str>>(),
+        vec!(
+            "start: x = (read);     stopped at the watch on x",
+            "body: x = (+ x -1);     stopped at the watch on x",
+            "body: x = (+ x -1);     stopped at the watch on x",
+            "done: return 42;     stopped at the break on done",
+        )
+    );
+
+    // set and removed at a stop
+    let (_, out) = debug(COUNTDOWN, vec!("2"), &["break body", "watch x", "b", "delete x", "c", "d", "c"], |d| d);
+
+    assert_eq!(
+        out.lines().collect::<Vec<&str>>(),
+        vec!(
+            "start: x = (read);",
+            "break body",
+            "watch x",
+            "body: x = (+ x -1);     stopped at the break on body",
+        )
+    );
+
+    // only a label of one of the blocks is taken
+    let (result, out) = debug(COUNTDOWN, vec!("1"), &["break nosuch", "b", "c"], |d| d);
+
+    assert_eq!(result.value, Some(42));
+    assert_eq!(
+        out.lines().collect::<Vec<&str>>(),
+        vec!(
+            "start: x = (read);",
+            "unknown label 'nosuch', the blocks are body, done, loop, start",
+            "no breakpoints",
+        )
+    );
+}
+
+#[test]
+fn debugger_print_and_list() {
+    let (_, out) = debug(COUNTDOWN, vec!("7"), &["p", "s", "s", "print", "p x", "p y", "l"], |d| d);
+
+    assert_eq!(
+        out.lines().collect::<Vec<&str>>(),
+        vec!(
+            "start: x = (read);",
+            "no variables yet",
+            "start: goto loop;",
+            "loop: if x goto body; else goto done;",
+            "x = 7",
+            "x = 7",
+            "y isn't assigned yet",
+            "loop:",
+            "=>    if x goto body; else goto done;",
+        )
+    );
+}
+
+#[test]
+fn debugger_source_names() {
+    let text = "
+        locals: x.1 x.2
+        start:
+            x.1 = 1;
+            x.2 = (+ x.1 1);
+            return x.2;
+    ";
+
+    let (_, out) = debug(text, vec!(), &["c", "p x", "c"], |d| d.set_stepping(false).set_breakpoints(vec!(Breakpoint::Var(crate::idstr!("x")))));
+
+    assert_eq!(
+        out.lines().collect::<Vec<&str>>(),
+        vec!(
+            "start: x.1 = 1;     stopped at the watch on x",
+            "start: x.2 = (+ x.1 1);     stopped at the watch on x",
+            "x.1 = 1",
+        )
+    );
+}
+
+#[test]
+fn debugger_quit() {
+    let (result, _) = debug(COUNTDOWN, vec!("1"), &["s", "q"], |d| d);
+
+    assert!(result.had_error);
+    assert_eq!(result.value, None);
+    assert_eq!(result.errors, vec!(STOPPED_ERROR.to_owned()));
+
+    // at the end of the input the program runs to the end
+    let (result, out) = debug(COUNTDOWN, vec!("3"), &["s"], |d| d);
+
+    assert_eq!(result.value, Some(42));
+    assert_eq!(out.lines().count(), 2);
+}
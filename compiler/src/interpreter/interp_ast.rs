@@ -3,31 +3,70 @@
 
 use runtime::types::{RuntimeI64};
 
-use crate::frontend::ast::{Program, AstNode};
-use crate::io::{get_line};
-use crate::types::{Environment};
-use crate::interpreter::{Interpretable, InterpretResult, RuntimeValue, CachedRuntimeCall};
+use crate::frontend::ast::{Program, AstNode, LetBinding, NodeId};
+use crate::types::{Environment, IdString, Overflow, OVERFLOW_ERROR};
+use crate::symbol::{PLUS, MINUS, READ};
+use crate::interpreter::{Interpretable, InterpretResult, RuntimeValue, CachedRuntimeCall, Limits};
+use crate::interpreter::trace::{TraceEntry};
 
 // AstInterpreter -> exp ::= int | (read) | (- exp) | (+ exp exp)
 //               | var | (let ([var exp]) exp)
 pub struct AstInterpreter<'a> {
-    program: Program,
+    program: &'a Program,
     interpretation_error: bool,
     errors: Vec<String>,
     crc: &'a mut CachedRuntimeCall,
+    overflow: Overflow,
+    limits: Limits,
+    steps: usize,
+    depth: usize,
+    out_of_fuel: bool,
+    too_deep: bool,
+    tracing: bool,
+    trace: Vec<TraceEntry>,
+    last_read: Option<String>, // for the trace of a (read)
 }
 
 impl<'a> AstInterpreter<'a> {
 
-    pub fn new(p: Program, crc: &mut CachedRuntimeCall) -> AstInterpreter {
+    pub fn new(p: &'a Program, crc: &'a mut CachedRuntimeCall) -> AstInterpreter<'a> {
         AstInterpreter {
             program: p,
             interpretation_error: false,
             errors: vec!(),
             crc: crc,
+            overflow: Overflow::Wrap,
+            limits: Limits::default(),
+            steps: 0,
+            depth: 0,
+            out_of_fuel: false,
+            too_deep: false,
+            tracing: false,
+            trace: vec!(),
+            last_read: None,
         }
     }
 
+    pub fn set_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn set_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // keep a TraceEntry for every expression evaluated, see trace
+    pub fn set_trace(mut self, tracing: bool) -> Self {
+        self.tracing = tracing;
+        self
+    }
+
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+
     pub fn interpret_success(&self) -> bool {
         !self.interpretation_error
     }
@@ -50,108 +89,182 @@ impl<'a> AstInterpreter<'a> {
         None
     }
 
-    fn interp_exp(&mut self, env: &mut Environment, e: &AstNode) -> Option<RuntimeI64> {
-        match e {
+    // every expression is a step, and nesting them is as deep as the interpreter recurses
+    fn interp_exp(&mut self, env: &mut Environment, e: NodeId) -> Option<RuntimeI64> {
+        if self.out_of_fuel || self.too_deep {
+            return None;
+        }
 
-            AstNode::Int(n) => Some(*n),
+        if self.steps >= self.limits.fuel {
+            self.out_of_fuel = true;
+            return self.add_error(format!("ran out of fuel after {} steps", self.steps));
+        }
 
-            AstNode::Prim {op, args} => {
-                match &op[..] {
-                    "+" => {
-                        let arg1 = self.interp_exp(env, &args[0]);
-                        let arg2 = self.interp_exp(env, &args[1]);
+        if self.depth >= self.limits.max_depth {
+            self.too_deep = true;
+            return self.add_error(format!("nested deeper than {} expressions", self.limits.max_depth));
+        }
 
-                        if arg1.is_none() {
-                            return None;
-                        }
+        let entry = if self.tracing {
+            self.trace.push(TraceEntry::new(self.depth, &self.program.ast[e], env));
+            Some(self.trace.len() - 1)
+        } else {
+            None
+        };
 
-                        if arg2.is_none() {
-                            return None;
-                        }
+        self.steps += 1;
+        self.depth += 1;
 
-                        Some(arg1.unwrap() + arg2.unwrap())
-                    },
-                    "-" => {
-                        let arg1 = self.interp_exp(env, &args[0]);
+        let value = self.interp_node(env, e);
 
-                        Some(-arg1.unwrap())
-                    },
-                    "read" => {
+        self.depth -= 1;
 
-                        // either we're using cached runtime calls (unlikely as this is the first interpreter being run)
-                        // or we're caching calls to the runtime
+        // a (read) doesn't have arguments, so what it read is its own
+        if let Some(i) = entry {
+            self.trace[i].value = value;
+            self.trace[i].read = self.last_read.take();
+        }
 
-                        let fn_name = crate::idstr!("read");
+        value
+    }
 
-                        if !self.crc.write {
+    // the work for each kind of node is in a function of its own, this one is on the
+    // stack once for every level of nesting and is kept small
+    fn interp_node(&mut self, env: &mut Environment, e: NodeId) -> Option<RuntimeI64> {
+        // the program outlives self, so its nodes can be read while self changes
+        let program = self.program;
 
-                            let runtime_val = self.crc.get_cached_result_of(fn_name);
+        match &program.ast[e] {
 
-                            match runtime_val {
-                                RuntimeValue::RuntimeI64(n) => {
-                                    return Some(n);
-                                },
-                            }
-                        } else {
-                            let input = get_line();
+            AstNode::Int(n) => Some(*n),
 
-                            match input.parse::<RuntimeI64>() {
-                                Ok(n) => {
+            AstNode::Prim {op, args} => self.interp_prim(env, *op, args),
 
-                                    self.crc.set_cached_result_of(fn_name, RuntimeValue::RuntimeI64(n));
+            AstNode::Let { bindings, body } => self.interp_let(env, bindings, *body),
 
-                                    return Some(n);
-                                },
+            AstNode::Var { name } => {
 
-                                Err(error) => {
-                                    return self.add_error(format!("{}", error));
-                                }
-                            }
-                        }
-                    },
-                    _ => {  
-                            return self.add_error(format!("Unrecognized operator in interp_exp: {}", op));
+                match env.get_value_of(*name) {
+                    Some(&AstNode::Int(n)) => Some(n),
+                    _ => {
+                        self.add_error(format!("{} is not defined!", name));
+                        None
                     }
                 }
             },
 
-            AstNode::Let { bindings, body } => {
-
-                for binding in bindings {
-                    let the_var = binding.identifier.clone();
+            AstNode::Error {msg, token} => {
+                self.add_error(format!("{}{:?}", msg, token));
 
-                    let already_exists = env.exists(the_var.clone());
+                None
+            },
+        }
+    }
 
-                    if already_exists {
-                        return self.add_error(format!("{} is already defined!", the_var));
+    fn interp_prim(&mut self, env: &mut Environment, op: IdString, args: &[NodeId]) -> Option<RuntimeI64> {
+        match op {
+            PLUS => {
+                // left to right, and a failing argument stops the sum before the next one runs
+                let arg1 = self.interp_exp(env, args[0])?;
+                let arg2 = self.interp_exp(env, args[1])?;
 
-                    } else {
-                        let the_value = &binding.expr;
-                        let result = self.interp_exp(env, the_value).unwrap();
-                        let _ = env.insert(the_var, AstNode::Int(result));
-                    }
+                match self.overflow.add(arg1, arg2) {
+                    Some(n) => Some(n),
+                    None => self.add_error(format!("{} in (+ {} {})", OVERFLOW_ERROR, arg1, arg2)),
                 }
+            },
+            MINUS => {
+                let arg1 = self.interp_exp(env, args[0])?;
 
-                self.interp_exp(env, body)
+                match self.overflow.negate(arg1) {
+                    Some(n) => Some(n),
+                    None => self.add_error(format!("{} in (- {})", OVERFLOW_ERROR, arg1)),
+                }
             },
+            READ => self.interp_read(),
+            _ => {  
+                    self.add_error(format!("Unrecognized operator in interp_exp: {}", op))
+            }
+        }
+    }
 
-            AstNode::Var { name } => {
+    fn interp_read(&mut self) -> Option<RuntimeI64> {
 
-                match env.get_value_of(name.clone()) {
-                    Some(&AstNode::Int(n)) => Some(n),
-                    _ => {
-                        self.add_error(format!("{} is not defined!", name));
-                        return None;
-                    }
+        // either we're using cached runtime calls (unlikely as this is the first interpreter being run)
+        // or we're caching calls to the runtime
+
+        let fn_name = READ;
+
+        if !self.crc.write {
+
+            let runtime_val = self.crc.get_cached_result_of(fn_name);
+
+            match runtime_val {
+                RuntimeValue::RuntimeI64(n) => {
+                    self.last_read = Some(n.to_string());
+
+                    Some(n)
+                },
+            }
+        } else {
+            let input = self.crc.read_line();
+
+            self.last_read = Some(input.clone());
+
+            match input.parse::<RuntimeI64>() {
+                Ok(n) => {
+
+                    self.crc.set_cached_result_of(fn_name, RuntimeValue::RuntimeI64(n));
+
+                    Some(n)
+                },
+
+                Err(error) => {
+                    self.add_error(format!("{}", error))
                 }
-            },
+            }
+        }
+    }
 
-            AstNode::Error {msg, token} => {
-                self.add_error(format!("{}{:?}", msg, token));
+    fn interp_let(&mut self, env: &mut Environment, bindings: &[LetBinding], body: NodeId) -> Option<RuntimeI64> {
 
-                return None;
-            },
+        // the bindings are only visible in the let, so remember what they shadow
+        // and put it back afterwards. this way the parsed program can be run
+        // before uniquify has given every binder a name of its own
+        let mut shadowed: Vec<(IdString, Option<AstNode>)> = vec!();
+
+        let mut value = None;
+
+        for binding in bindings {
+            let the_var = binding.identifier;
+
+            if shadowed.iter().any(|(var, _)| *var == the_var) {
+                self.add_error(format!("{} is already defined!", the_var));
+                break;
+            }
+
+            let result = self.interp_exp(env, binding.expr);
+
+            shadowed.push((the_var, env.get(the_var).cloned()));
+
+            match result {
+                Some(n) => env.insert(the_var, AstNode::Int(n)),
+                None => break,
+            }
+        }
+
+        if !self.interpretation_error {
+            value = self.interp_exp(env, body);
         }
+
+        for (var, previous) in shadowed.into_iter().rev() {
+            match previous {
+                Some(node) => env.insert(var, node),
+                None => env.remove(var),
+            }
+        }
+
+        value
     }
 }
 
@@ -159,12 +272,16 @@ impl<'a> Interpretable for AstInterpreter<'a> {
     fn interpret(&mut self) -> InterpretResult {
         let mut envir = Environment::new();
 
-        let value = self.interp_exp(&mut envir, &self.program.exp.clone());
+        let program = self.program;
+
+        let value = self.interp_exp(&mut envir, program.exp);
 
         InterpretResult {
             value: value,
             had_error: self.interpretation_error,
             errors: self.errors.clone(),
+            out_of_fuel: self.out_of_fuel,
+            too_deep: self.too_deep,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,40 @@
+use crate::utility::{test_ast_helper, test_interp_ast_helper};
+
+fn helper(prog: &'static str, input: Vec<&str>) -> Option<i64> {
+    // straight from the parser, nothing has been renamed yet
+    let (result, _) = test_interp_ast_helper(test_ast_helper(prog, vec!()), input);
+
+    assert!(!result.had_error, "{:?}", result.errors);
+
+    result.value
+}
+
+#[test]
+fn interp_ast_sibling_lets() {
+    // the first x is gone once its let is done
+    assert_eq!(helper("(+ (let ([x 1]) x) (let ([x 2]) x))", vec!()), Some(3));
+}
+
+#[test]
+fn interp_ast_shadowing_is_undone() {
+    assert_eq!(helper("(let ([x 1]) (+ (let ([x 2]) x) x))", vec!()), Some(3));
+    assert_eq!(helper("(let ([x (read)]) (+ (let ([x (- x)]) x) x))", vec!("5")), Some(0));
+}
+
+#[test]
+fn interp_ast_duplicate_binding() {
+    let (result, _) = test_interp_ast_helper(test_ast_helper("(let ([x 1] [x 2]) x)", vec!()), vec!());
+
+    assert!(result.had_error);
+    assert_eq!(result.value, None);
+}
+
+#[test]
+fn interp_ast_sum_stops_at_the_first_error() {
+    let (result, reads) = test_interp_ast_helper(test_ast_helper("(+ (read) (read))", vec!()), vec!("abc", "5"));
+
+    // the second (read) never runs, so 5 isn't read
+    assert!(result.had_error);
+    assert_eq!(result.errors.len(), 1, "{:?}", result.errors);
+    assert!(reads.is_empty(), "{:?}", reads);
+}
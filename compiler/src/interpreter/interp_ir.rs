@@ -5,18 +5,28 @@ use runtime::types::{RuntimeI64};
 
 use std::collections::HashMap;
 
-use crate::io::{get_line};
-use crate::ir::explicate::{IRProgram, Tail, Stmt, Exp, Atm};
+use crate::ir::explicate::{IRProgram, Block, Tail, Stmt, Exp, Atm};
 
-use crate::types::{IdString};
-use crate::interpreter::{Interpretable, InterpretResult, RuntimeValue, CachedRuntimeCall};
+use crate::types::{IdString, Overflow, OVERFLOW_ERROR};
+use crate::symbol::{PLUS, MINUS, READ};
+use crate::interpreter::{Interpretable, InterpretResult, RuntimeValue, CachedRuntimeCall, Limits};
+use crate::interpreter::debugger::{Debugger, Stop, Point, STOPPED_ERROR};
 
 pub struct IrInterpreter<'a> {
     interpretation_error: bool,
     errors: Vec<String>,
-    cprog: IRProgram,
+    cprog: &'a IRProgram,
     vars: HashMap<IdString, Atm>,
     crc: &'a mut CachedRuntimeCall,
+    overflow: Overflow,
+    limits: Limits,
+    steps: usize,
+    depth: usize,
+    out_of_fuel: bool,
+    too_deep: bool,
+    debugger: Option<Debugger<'a>>,
+    label: IdString, // where the program is, for the debugger
+    index: usize,
 }
 
 #[derive(Debug)]
@@ -98,7 +108,7 @@ impl<'a> IrInterpreter<'a> {
                     Atm::Var { .. } => {
                         let maybe_val = self.get_var_value(&larg);
 
-                        self.extract_i64(&maybe_val.unwrap()).unwrap()
+                        self.extract_i64(&maybe_val?)?
                     },
 
                     Atm::Int(n) => {
@@ -110,7 +120,7 @@ impl<'a> IrInterpreter<'a> {
                     Atm::Var { .. } => {
                         let maybe_val = self.get_var_value(&rarg);
 
-                        self.extract_i64(&maybe_val.unwrap()).unwrap()
+                        self.extract_i64(&maybe_val?)?
                     },
 
                     Atm::Int(n) => {
@@ -120,7 +130,10 @@ impl<'a> IrInterpreter<'a> {
 
                 match kind {
                     ArithmeticKind::Add => {
-                        Some(Atm::Int(larg_value + rarg_value))
+                        match self.overflow.add(larg_value, rarg_value) {
+                            Some(n) => Some(Atm::Int(n)),
+                            None => self.add_error(format!("{} in (+ {} {})", OVERFLOW_ERROR, larg_value, rarg_value)),
+                        }
                     },
 
                     _ => {
@@ -138,7 +151,7 @@ impl<'a> IrInterpreter<'a> {
                     Atm::Var { .. } => {
                         let maybe_val = self.get_var_value(&arg);
 
-                        self.extract_i64(&maybe_val.unwrap()).unwrap()
+                        self.extract_i64(&maybe_val?)?
                     },
 
                     Atm::Int(n) => {
@@ -148,7 +161,10 @@ impl<'a> IrInterpreter<'a> {
 
                 match kind {
                     ArithmeticKind::Negate => {
-                        Some(Atm::Int(0 - arg_value))
+                        match self.overflow.negate(arg_value) {
+                            Some(n) => Some(Atm::Int(n)),
+                            None => self.add_error(format!("{} in (- {})", OVERFLOW_ERROR, arg_value)),
+                        }
                     },
 
                     _ => {
@@ -197,7 +213,7 @@ impl<'a> IrInterpreter<'a> {
     fn extract_var(&self, atm: &Atm) -> Option<IdString> {
         match atm {
             Atm::Var { name } => {
-                Some(name.clone())
+                Some(*name)
             },
 
             _ => {
@@ -221,23 +237,23 @@ impl<'a> IrInterpreter<'a> {
             },
 
             Exp::Prim { op, args } => {
-                match &op[..] {
-                    "+" => {
+                match *op {
+                    PLUS => {
                         let operation = self.op_to_arithm(ArithmeticKind::Add, args.clone());
                         self.atm_aritmetic(operation)
                     },
 
-                    "-" => {
+                    MINUS => {
                         let operation = self.op_to_arithm(ArithmeticKind::Negate, args.clone());
                         self.atm_aritmetic(operation)
                     },
 
-                    "read" => {
+                    READ => {
 
                         // either we're using cached runtime calls (unlikely as this is the first interpreter being run)
                         // or we're caching calls to the runtime
 
-                        let fn_name = crate::idstr!("read");
+                        let fn_name = READ;
 
                         if !self.crc.write {
 
@@ -245,22 +261,22 @@ impl<'a> IrInterpreter<'a> {
 
                             match runtime_val {
                                 RuntimeValue::RuntimeI64(n) => {
-                                    return Some(Atm::Int(n));
+                                    Some(Atm::Int(n))
                                 },
                             }
                         } else {
-                            let input = get_line();
+                            let input = self.crc.read_line();
 
                             match input.parse::<RuntimeI64>() {
                                 Ok(n) => {
 
                                     self.crc.set_cached_result_of(fn_name, RuntimeValue::RuntimeI64(n));
 
-                                    return Some(Atm::Int(n));
+                                    Some(Atm::Int(n))
                                 },
 
                                 Err(error) => {
-                                    return self.add_error(format!("{}", error));
+                                    self.add_error(format!("{}", error))
                                 }
                             }
                         }
@@ -301,22 +317,127 @@ impl<'a> IrInterpreter<'a> {
                 };
 
                 self.vars.insert(
-                    var.clone(),
+                    var,
                     expr
                 );
             }
         }
     }
 
-    fn handle_tail(&mut self, tail: &Tail) -> Option<Atm> {
-        match tail {
-            Tail::Seq (stmt, tail) => {
-                self.handle_stmt(stmt);
-                self.handle_tail(tail)
-            },
+    // None once the limits are reached, every statement and tail is a step
+    fn step(&mut self) -> Option<()> {
+        if self.steps >= self.limits.fuel {
+            self.out_of_fuel = true;
+            self.add_error(format!("ran out of fuel after {} steps", self.steps));
+            return None;
+        }
+
+        self.steps += 1;
+
+        Some(())
+    }
+
+    // None if the debugger stopped the program
+    fn pause(&mut self, point: Point) -> Option<()> {
+        let cprog = self.cprog;
+
+        let debugger = match &mut self.debugger {
+            Some(debugger) => debugger,
+            None => return Some(()),
+        };
+
+        let stop = Stop {
+            label: self.label,
+            block: &cprog.labels[&self.label],
+            index: self.index,
+            point: point,
+            vars: &self.vars,
+            blocks: &cprog.labels,
+        };
+
+        if !debugger.pause(&stop) {
+            self.add_error(STOPPED_ERROR.to_owned());
+            return None;
+        }
+
+        Some(())
+    }
+
+    fn handle_block(&mut self, block: &Block) -> Option<Atm> {
+        // a long block doesn't use up the stack, only a goto recurses
+        for stmt in &block.stmts {
+            self.step()?;
+            self.pause(Point::Stmt(stmt))?;
+
+            self.handle_stmt(stmt);
+
+            // e.g. a failed (read), the rest would only pile up more errors
+            if self.interpretation_error {
+                return None;
+            }
+
+            self.index += 1;
+        }
+
+        let tail = &block.tail;
 
+        self.step()?;
+        self.pause(Point::Tail(tail))?;
+
+        match tail {
             Tail::Return (exp) => {
                 self.handle_exp(exp)
+            },
+
+            Tail::Goto (label) => {
+                self.goto(label)
+            },
+
+            Tail::If (cnd, thn, els) => {
+                let cnd_value = self.handle_exp(&Exp::Atm(cnd.clone()));
+
+                match cnd_value {
+                    Some(Atm::Int(0)) => {
+                        self.goto(els)
+                    },
+
+                    Some(Atm::Int(_)) => {
+                        self.goto(thn)
+                    },
+
+                    _ => {
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn goto(&mut self, label: &IdString) -> Option<Atm> {
+        let cprog = self.cprog;
+
+        if self.depth >= self.limits.max_depth {
+            self.too_deep = true;
+            return self.add_error(format!("nested deeper than {} jumps", self.limits.max_depth));
+        }
+
+        match cprog.labels.get(label) {
+            Some(block) => {
+                self.label = *label;
+                self.index = 0;
+                self.depth += 1;
+
+                let value = self.handle_block(block);
+
+                self.depth -= 1;
+
+                value
+            },
+
+            None => {
+                self.add_error(
+                    format!("Attempted to jump to unknown label: '{}'", label)
+                )
             }
         }
     }
@@ -331,36 +452,61 @@ impl<'a> IrInterpreter<'a> {
         }
     }
 
-    pub fn new(cprog: IRProgram, crc: &mut CachedRuntimeCall) -> IrInterpreter {
+    pub fn new(cprog: &'a IRProgram, crc: &'a mut CachedRuntimeCall) -> IrInterpreter<'a> {
         IrInterpreter {
             cprog: cprog,
             interpretation_error: false,
             errors: vec!(),
             vars: HashMap::new(),
             crc: crc,
+            overflow: Overflow::Wrap,
+            limits: Limits::default(),
+            steps: 0,
+            depth: 0,
+            out_of_fuel: false,
+            too_deep: false,
+            debugger: None,
+            label: crate::idstr!("start"),
+            index: 0,
         }
     }
+
+    pub fn set_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn set_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // stops before every statement and tail to ask the debugger what to do
+    pub fn set_debugger(mut self, debugger: Debugger<'a>) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
 }
 
 impl<'a> Interpretable for IrInterpreter<'a> {
     fn interpret(&mut self) -> InterpretResult {
 
         let r = {
-            let start_label =
-                if let Some(tail) = self.cprog.labels.get(&crate::idstr!("start")) {
-                    Some(tail.clone())
-                } else {
-                    None
-                };
-            
-            if let Some(tail) = start_label {
-                let maybe_atm = self.handle_tail(&tail);
+            let cprog = self.cprog;
+
+            if let Some(block) = cprog.labels.get(&crate::idstr!("start")) {
+                let maybe_atm = self.handle_block(block);
 
                 match maybe_atm {
                     Some(Atm::Int(n)) => {
                         Some(n)
                     },
 
+                    // already reported, e.g. running out of fuel
+                    None if self.interpretation_error => {
+                        None
+                    },
+
                     _ => {
                         self.add_error(
                             format!("{}:{}:Expected the result of executing the IR to be an i64",
@@ -386,6 +532,8 @@ impl<'a> Interpretable for IrInterpreter<'a> {
             value: r,
             had_error: self.has_error(),
             errors: self.errors.clone(),
+            out_of_fuel: self.out_of_fuel,
+            too_deep: self.too_deep,
         }
     }
 }
\ No newline at end of file
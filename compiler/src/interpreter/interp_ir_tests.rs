@@ -0,0 +1,11 @@
+use crate::utility::{test_ir_helper, test_interp_ir_helper};
+
+#[test]
+fn interp_ir_stops_at_failed_read() {
+    // nothing to read, so x never gets a value
+    let (result, reads) = test_interp_ir_helper(test_ir_helper("(let ([x (read)]) (+ x (read)))"), vec!());
+
+    assert!(result.had_error);
+    assert_eq!(result.value, None);
+    assert_eq!(reads.len(), 0);
+}
@@ -3,14 +3,29 @@
 
 pub mod interp_ast;
 pub mod interp_ir;
+pub mod debugger;
+pub mod trace;
+
+#[cfg(test)]
+mod debugger_tests;
+#[cfg(test)]
+mod trace_tests;
+
+#[cfg(test)]
+mod interp_ast_tests;
+#[cfg(test)]
+mod interp_ir_tests;
 
 use runtime::types::{RuntimeI64, RuntimeValue};
 
 use std::collections::{HashMap, VecDeque};
 
 use crate::types::{IdString};
+use crate::io::{get_line};
 
 pub type CachedFunctionResult = VecDeque<RuntimeValue>;
+// short for CachedRuntimeCall, the same as the crc parameters
+#[allow(clippy::upper_case_acronyms)]
 pub type CRC = HashMap<IdString, CachedFunctionResult>;
 
 pub trait Interpretable {
@@ -21,11 +36,37 @@ pub struct InterpretResult {
     pub value: Option<RuntimeI64>,
     pub had_error: bool,
     pub errors: Vec<String>,
+    pub out_of_fuel: bool, // stopped by the limits, the program itself might be fine
+    pub too_deep: bool, // the same, but it was the depth limit
+}
+
+pub const DEFAULT_FUEL: usize = 1_000_000;
+// a program the parser accepts is never too deep for the ast interpreter
+pub const DEFAULT_MAX_DEPTH: usize = crate::frontend::ast::MAX_NESTING;
+
+// how far a program may run before it's stopped, so one that never finishes,
+// or nests deeper than the stack can take, still comes back. a step is an
+// expression for the ast interpreter, a statement or tail for the ir interpreter
+// and an instruction for the emulator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub fuel: usize,
+    pub max_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            fuel: DEFAULT_FUEL,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
 }
 
 pub struct CachedRuntimeCall {
     result: CRC,
     write: bool,
+    input: Option<VecDeque<String>>, // when set, read from here instead of stdin
 }
 
 impl CachedRuntimeCall {
@@ -34,6 +75,34 @@ impl CachedRuntimeCall {
         Self {
             result: CRC::new(),
             write: true,
+            input: None,
+        }
+    }
+
+    pub fn set_input(mut self, lines: Vec<&str>) -> Self {
+        self.input = Some(lines.iter().map(|l| l.to_string()).collect());
+
+        self
+    }
+
+    // the next line of input for a runtime call such as read
+    pub fn read_line(&mut self) -> String {
+        match &mut self.input {
+            Some(lines) => {
+                lines.pop_front().unwrap_or_default()
+            },
+
+            None => {
+                get_line()
+            }
+        }
+    }
+
+    // the results recorded so far for a runtime function, in the order they were produced
+    pub fn cached_results_of(&self, fn_name: IdString) -> Vec<RuntimeValue> {
+        match self.result.get(&fn_name) {
+            Some(results) => results.iter().cloned().collect(),
+            None => vec!(),
         }
     }
 
@@ -49,7 +118,7 @@ impl CachedRuntimeCall {
     }
 
     pub fn get_cached_runtime_fn(&mut self, fn_name: IdString) -> Option<&mut CachedFunctionResult> {
-        return self.result.get_mut(&fn_name);
+        self.result.get_mut(&fn_name)
     }
 
     pub fn get_cached_result_of(&mut self, fn_name: IdString) -> RuntimeValue {
@@ -57,13 +126,12 @@ impl CachedRuntimeCall {
 
         match maybe_read_calls {
             Some(read_calls) => {
-                return read_calls.pop_front().unwrap();
+                read_calls.pop_front().unwrap()
             },
 
             _ => {
                 println!(
-                    "{}: was told to look for a cached call for the 'read' function; could not find it.",
-                    "ast-interpreter",
+                    "ast-interpreter: was told to look for a cached call for the 'read' function; could not find it.",
                 );
                 unreachable!();
             }
@@ -71,7 +139,7 @@ impl CachedRuntimeCall {
     }
 
     pub fn set_cached_result_of(&mut self, fn_name: IdString, val: RuntimeValue) {
-        let maybe_cached_runtime_fn = self.get_cached_runtime_fn(fn_name.clone());
+        let maybe_cached_runtime_fn = self.get_cached_runtime_fn(fn_name);
 
         match maybe_cached_runtime_fn {
             Some(cached_runtime_fn) => {
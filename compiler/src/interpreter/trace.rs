@@ -0,0 +1,101 @@
+/*
+    a trace of the ast interpreter, one line for every expression it evaluated
+
+        (let ([x _]) _) => 6
+          (read) => 5    read "5"
+          (+ _ _) => 6    [x = 5]
+            x => 5    [x = 5]
+            1 => 1    [x = 5]
+
+    a line is indented by how deep the expression is, the arguments of a let or
+    primitive are the lines under it. the value is 'error' when it failed, the
+    bindings in scope come next and a (read) shows the input it took. the lines
+    only depend on the program and its input, so the traces before and after a
+    pass can be diffed line by line to see which expressions it changed
+*/
+
+use std::fmt;
+
+use runtime::types::{RuntimeI64};
+
+use crate::frontend::ast::{AstNode};
+use crate::types::{Environment, IdString};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub depth: usize,
+    pub node: String, // the expression, with '_' for its arguments
+    pub bindings: Vec<(IdString, RuntimeI64)>,
+    pub value: Option<RuntimeI64>,
+    pub read: Option<String>, // the line of input a (read) took
+}
+
+impl TraceEntry {
+
+    pub fn new(depth: usize, e: &AstNode, env: &Environment) -> Self {
+        let bindings =
+            env.bindings()
+            .into_iter()
+            .filter_map(|(id, val)| match val {
+                AstNode::Int(n) => Some((id, *n)),
+                _ => None,
+            })
+            .collect();
+
+        TraceEntry {
+            depth: depth,
+            node: head(e),
+            bindings: bindings,
+            value: None,
+            read: None,
+        }
+    }
+}
+
+// the expression without its arguments, they have lines of their own
+fn head(e: &AstNode) -> String {
+    match e {
+        AstNode::Int(n) => format!("{}", n),
+
+        AstNode::Var { name } => format!("{}", name),
+
+        AstNode::Prim { op, args } => format!("({}{})", op, " _".repeat(args.len())),
+
+        AstNode::Let { bindings, .. } => {
+            let bindings: Vec<String> = bindings.iter().map(|b| format!("[{} _]", b.identifier)).collect();
+
+            format!("(let ({}) _)", bindings.join(" "))
+        },
+
+        AstNode::Error { msg, .. } => format!("error: {}", msg),
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{} => ", "  ".repeat(self.depth), self.node)?;
+
+        match self.value {
+            Some(n) => write!(f, "{}", n)?,
+            None => write!(f, "error")?,
+        }
+
+        if !self.bindings.is_empty() {
+            let bindings: Vec<String> = self.bindings.iter().map(|(id, n)| format!("{} = {}", id, n)).collect();
+
+            write!(f, "    [{}]", bindings.join(", "))?;
+        }
+
+        if let Some(line) = &self.read {
+            write!(f, "    read {:?}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn format_trace(trace: &[TraceEntry]) -> String {
+    let lines: Vec<String> = trace.iter().map(|entry| entry.to_string()).collect();
+
+    lines.join("\n")
+}
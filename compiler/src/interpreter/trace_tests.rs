@@ -0,0 +1,104 @@
+use crate::utility::{test_parse_helper, test_ast_helper, AstStep};
+use crate::frontend::ast::{Program};
+use crate::snapshot::{diff};
+
+use super::*;
+use super::interp_ast::{AstInterpreter};
+use super::trace::*;
+
+fn trace(p: &Program, input: Vec<&str>) -> String {
+    let mut crc = CachedRuntimeCall::new().set_input(input);
+    let mut ast_interpreter = AstInterpreter::new(p, &mut crc).set_trace(true);
+
+    Interpreter::new(&mut ast_interpreter).run();
+
+    format_trace(ast_interpreter.trace())
+}
+
+#[test]
+fn trace_lines() {
+    let p = test_parse_helper("(let ([x (read)]) (+ x 1))");
+
+    assert_eq!(
+        trace(&p, vec!("5")),
+        [
+            "(let ([x _]) _) => 6",
+            "  (read) => 5    read \"5\"",
+            "  (+ _ _) => 6    [x = 5]",
+            "    x => 5    [x = 5]",
+            "    1 => 1    [x = 5]",
+        ].join("\n")
+    );
+
+    // the bindings in scope, by name
+    let p = test_parse_helper("(let ([y 1] [x 2]) (- y))");
+
+    assert_eq!(
+        trace(&p, vec!()),
+        [
+            "(let ([y _] [x _]) _) => -1",
+            "  1 => 1",
+            "  2 => 2    [y = 1]",
+            "  (- _) => -1    [x = 2, y = 1]",
+            "    y => 1    [x = 2, y = 1]",
+        ].join("\n")
+    );
+}
+
+#[test]
+fn trace_errors() {
+    let p = test_parse_helper("(+ (read) 1)");
+
+    // the sum stops at the failing read, the 1 is never evaluated
+    assert_eq!(
+        trace(&p, vec!("one")),
+        [
+            "(+ _ _) => error",
+            "  (read) => error    read \"one\"",
+        ].join("\n")
+    );
+
+    // nothing is traced once the fuel runs out
+    let mut crc = CachedRuntimeCall::new();
+    let mut ast_interpreter =
+        AstInterpreter::new(&p, &mut crc)
+        .set_limits(Limits { fuel: 1, ..Limits::default() })
+        .set_trace(true);
+
+    Interpreter::new(&mut ast_interpreter).run();
+
+    assert_eq!(format_trace(ast_interpreter.trace()), "(+ _ _) => error");
+}
+
+#[test]
+fn trace_off_by_default() {
+    let p = test_parse_helper("(+ 1 2)");
+
+    let mut crc = CachedRuntimeCall::new();
+    let mut ast_interpreter = AstInterpreter::new(&p, &mut crc);
+
+    Interpreter::new(&mut ast_interpreter).run();
+
+    assert!(ast_interpreter.trace().is_empty());
+}
+
+#[test]
+fn trace_diff_shows_what_a_pass_changed() {
+    let source = "(let ([x (read)]) (let ([y 1]) x))";
+
+    let before = test_ast_helper(source, vec!(AstStep::Uniquify));
+    let after = test_ast_helper(source, vec!(AstStep::Uniquify, AstStep::DeadBindings));
+
+    assert_eq!(
+        diff(&trace(&before, vec!("5")), &trace(&after, vec!("5"))),
+        [
+            "  (let ([x.1 _]) _) => 5",
+            "    (read) => 5    read \"5\"",
+            "-   (let ([y.2 _]) _) => 5    [x.1 = 5]",
+            "-     1 => 1    [x.1 = 5]",
+            "-     x.1 => 5    [x.1 = 5, y.2 = 1]",
+            "+   x.1 => 5    [x.1 = 5]",
+            "",
+        ].join("\n")
+    );
+}
@@ -1,9 +1,10 @@
-
 use std::io::{self};
 
-pub fn get_line() -> String {
+// None at the end of the input
+pub fn read_line() -> Option<String> {
     let mut the_input = String::new();
     match io::stdin().read_line(&mut the_input) {
+        Ok(0) => None,
         Ok(_n) => {
             if the_input.ends_with("\n") {
                 the_input.pop();
@@ -11,11 +12,13 @@ pub fn get_line() -> String {
             if the_input.ends_with("\r") {
                 the_input.pop();
             }
+
+            Some(the_input)
         },
-        Err(_error) => {
-            the_input = "".to_owned();
-        }
+        Err(_error) => None,
     }
+}
 
-    the_input
-}
\ No newline at end of file
+pub fn get_line() -> String {
+    read_line().unwrap_or_default()
+}
@@ -0,0 +1,298 @@
+/*
+    the control flow graph of the ir labels and its dominator tree
+
+    a label d dominates a label n if every path from the entry to n goes through d,
+    the immediate dominator of n is the closest such label (other than n itself).
+    the dominance frontier of d are the labels where the dominance of d ends, that
+    is where control flow from d meets control flow that didn't go through d
+*/
+
+#![allow(dead_code)]
+
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{IdString};
+
+use super::explicate::{Tail};
+
+// the labels a tail can jump to, in the order they appear
+pub fn successors(tail: &Tail) -> Vec<IdString> {
+    match tail {
+        Tail::Return(_) => vec!(),
+        Tail::Goto(label) => vec!(*label),
+        Tail::If(_, thn, els) => {
+            if thn == els {
+                vec!(*thn)
+            } else {
+                vec!(*thn, *els)
+            }
+        },
+    }
+}
+
+pub struct Cfg {
+    pub entry: IdString,
+    // the labels reachable from the entry, in reverse postorder
+    pub labels: Vec<IdString>,
+    pub successors: HashMap<IdString, Vec<IdString>>,
+    pub predecessors: HashMap<IdString, Vec<IdString>>,
+}
+
+impl Cfg {
+
+    // 'tails' can be anything that maps a label to its code
+    pub fn new<'a, F>(entry: IdString, tail_of: F) -> Cfg
+    where F: Fn(&IdString) -> Option<&'a Tail> {
+
+        let mut successor_map: HashMap<IdString, Vec<IdString>> = HashMap::new();
+        let mut postorder: Vec<IdString> = vec!();
+        let mut visited: HashSet<IdString> = HashSet::new();
+
+        // iterative depth first search, so deep graphs can't overflow the stack
+        // every entry is a label and the index of the next successor to visit
+        let mut stack: Vec<(IdString, usize)> = vec!();
+
+        if tail_of(&entry).is_some() {
+            visited.insert(entry);
+            stack.push((entry, 0));
+        }
+
+        while let Some((label, next)) = stack.pop() {
+            let succs = match successor_map.get(&label) {
+                Some(succs) => succs.clone(),
+                None => {
+                    // jumps to labels that don't exist are left out of the graph
+                    let succs: Vec<IdString> =
+                        successors(tail_of(&label).unwrap())
+                        .into_iter()
+                        .filter(|s| tail_of(s).is_some())
+                        .collect();
+
+                    successor_map.insert(label, succs.clone());
+                    succs
+                }
+            };
+
+            if next < succs.len() {
+                stack.push((label, next + 1));
+
+                let succ = &succs[next];
+
+                if !visited.contains(succ) {
+                    visited.insert(*succ);
+                    stack.push((*succ, 0));
+                }
+            } else {
+                postorder.push(label);
+            }
+        }
+
+        postorder.reverse();
+
+        let mut predecessor_map: HashMap<IdString, Vec<IdString>> = HashMap::new();
+
+        for label in &postorder {
+            predecessor_map.entry(*label).or_default();
+        }
+
+        for label in &postorder {
+            for succ in &successor_map[label] {
+                predecessor_map.get_mut(succ).unwrap().push(*label);
+            }
+        }
+
+        Cfg {
+            entry: entry,
+            labels: postorder,
+            successors: successor_map,
+            predecessors: predecessor_map,
+        }
+    }
+
+    pub fn successors_of(&self, label: &IdString) -> &[IdString] {
+        match self.successors.get(label) {
+            Some(succs) => succs,
+            None => &[],
+        }
+    }
+
+    pub fn predecessors_of(&self, label: &IdString) -> &[IdString] {
+        match self.predecessors.get(label) {
+            Some(preds) => preds,
+            None => &[],
+        }
+    }
+
+    pub fn is_reachable(&self, label: &IdString) -> bool {
+        self.successors.contains_key(label)
+    }
+}
+
+pub struct DominatorTree {
+    entry: IdString,
+    idom: HashMap<IdString, IdString>,
+    children: HashMap<IdString, Vec<IdString>>,
+    frontiers: HashMap<IdString, Vec<IdString>>,
+}
+
+impl DominatorTree {
+
+    // "A Simple, Fast Dominance Algorithm" by Cooper, Harvey and Kennedy
+    pub fn new(cfg: &Cfg) -> DominatorTree {
+
+        let order: HashMap<IdString, usize> =
+            cfg.labels
+            .iter()
+            .enumerate()
+            .map(|(i, l)| (*l, i))
+            .collect();
+
+        // indices into cfg.labels, the entry is its own dominator while computing
+        let mut idom: Vec<Option<usize>> = vec!(None; cfg.labels.len());
+
+        if !cfg.labels.is_empty() {
+            idom[0] = Some(0);
+        }
+
+        let intersect = |idom: &Vec<Option<usize>>, mut a: usize, mut b: usize| -> usize {
+            while a != b {
+                while a > b {
+                    a = idom[a].unwrap();
+                }
+                while b > a {
+                    b = idom[b].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+
+        while changed {
+            changed = false;
+
+            for i in 1..cfg.labels.len() {
+                let mut new_idom: Option<usize> = None;
+
+                for pred in cfg.predecessors_of(&cfg.labels[i]) {
+                    let p = order[pred];
+
+                    if idom[p].is_none() {
+                        continue;
+                    }
+
+                    new_idom = match new_idom {
+                        None => Some(p),
+                        Some(current) => Some(intersect(&idom, p, current)),
+                    };
+                }
+
+                if new_idom != idom[i] {
+                    idom[i] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut idom_map: HashMap<IdString, IdString> = HashMap::new();
+        let mut children: HashMap<IdString, Vec<IdString>> = HashMap::new();
+
+        for label in &cfg.labels {
+            children.insert(*label, vec!());
+        }
+
+        // the entry is at 0 and has no immediate dominator
+        for (i, dom) in idom.iter().enumerate().skip(1) {
+            if let Some(d) = *dom {
+                idom_map.insert(cfg.labels[i], cfg.labels[d]);
+                children.get_mut(&cfg.labels[d]).unwrap().push(cfg.labels[i]);
+            }
+        }
+
+        let mut tree = DominatorTree {
+            entry: cfg.entry,
+            idom: idom_map,
+            children: children,
+            frontiers: HashMap::new(),
+        };
+
+        tree.frontiers = tree.compute_frontiers(cfg);
+
+        tree
+    }
+
+    fn compute_frontiers(&self, cfg: &Cfg) -> HashMap<IdString, Vec<IdString>> {
+        let mut frontiers: HashMap<IdString, Vec<IdString>> = HashMap::new();
+
+        for label in &cfg.labels {
+            frontiers.insert(*label, vec!());
+        }
+
+        for label in &cfg.labels {
+            let preds = cfg.predecessors_of(label);
+
+            if preds.len() < 2 {
+                continue;
+            }
+
+            let label_idom = self.idom.get(label);
+
+            for pred in preds {
+                let mut runner = Some(*pred);
+
+                while let Some(r) = runner {
+                    if Some(&r) == label_idom {
+                        break;
+                    }
+
+                    let frontier = frontiers.get_mut(&r).unwrap();
+
+                    if !frontier.contains(label) {
+                        frontier.push(*label);
+                    }
+
+                    runner = self.idom.get(&r).cloned();
+                }
+            }
+        }
+
+        frontiers
+    }
+
+    pub fn entry(&self) -> &IdString {
+        &self.entry
+    }
+
+    pub fn idom(&self, label: &IdString) -> Option<&IdString> {
+        self.idom.get(label)
+    }
+
+    pub fn children(&self, label: &IdString) -> &[IdString] {
+        match self.children.get(label) {
+            Some(children) => children,
+            None => &[],
+        }
+    }
+
+    pub fn frontier(&self, label: &IdString) -> &[IdString] {
+        match self.frontiers.get(label) {
+            Some(frontier) => frontier,
+            None => &[],
+        }
+    }
+
+    // does 'a' dominate 'b', every label dominates itself
+    pub fn dominates(&self, a: &IdString, b: &IdString) -> bool {
+        let mut current = Some(b);
+
+        while let Some(label) = current {
+            if label == a {
+                return true;
+            }
+
+            current = self.idom.get(label);
+        }
+
+        false
+    }
+}
@@ -0,0 +1,133 @@
+/*
+    copy propagation
+
+    in ssa form a variable that is assigned an atom holds that atom everywhere it's
+    used, so every use can be replaced with the atom and the assignment dropped.
+    value numbering leaves copies like this behind:
+
+        x = (read)                  x_1 = (read)
+        y = x                       return (+ x_1 1)
+        z = y           becomes
+        return (+ z 1)
+
+    the program goes into ssa form first, that's what makes it work across labels,
+    and back out of it at the end. a variable that is assigned more than once isn't
+    a problem then, every assignment is a variable of its own
+*/
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use crate::types::{IdString};
+
+use super::explicate::{IRProgram, Block, Tail, Stmt, Exp, Atm};
+use super::ssa::{Phi, SsaBlock, to_ssa, from_ssa};
+
+struct Copies {
+    atoms: HashMap<IdString, Atm>, // the atom every copy was assigned
+    removed: usize,
+}
+
+impl Copies {
+
+    // a chain of copies ends at the atom the first one copied
+    fn resolve(&self, atm: &Atm) -> Atm {
+        let mut atm = atm.clone();
+
+        while let Atm::Var { name } = &atm {
+            match self.atoms.get(name) {
+                Some(copied) => atm = copied.clone(),
+                None => break,
+            }
+        }
+
+        atm
+    }
+
+    fn resolve_exp(&self, exp: &Exp) -> Exp {
+        match exp {
+            Exp::Atm(atm) => Exp::Atm(self.resolve(atm)),
+            Exp::Prim { op, args } => Exp::Prim {
+                op: *op,
+                args: args.iter().map(|a| self.resolve(a)).collect(),
+            },
+        }
+    }
+
+    fn find(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            if let Stmt::Assign(Atm::Var { name }, Exp::Atm(atm)) = stmt {
+                self.atoms.insert(*name, atm.clone());
+            }
+        }
+    }
+
+    fn rewrite(&mut self, block: Block) -> Block {
+        let mut stmts = vec!();
+
+        for Stmt::Assign(assignee, exp) in block.stmts {
+            match assignee {
+                // every use is replaced, nothing reads it any more
+                Atm::Var { name } if self.atoms.contains_key(&name) => {
+                    self.removed += 1;
+                },
+
+                _ => {
+                    stmts.push(Stmt::Assign(assignee, self.resolve_exp(&exp)));
+                }
+            }
+        }
+
+        Block::new(stmts, self.rewrite_tail(block.tail))
+    }
+
+    fn rewrite_tail(&self, tail: Tail) -> Tail {
+        match tail {
+            Tail::Return(exp) => Tail::Return(self.resolve_exp(&exp)),
+
+            Tail::Goto(label) => Tail::Goto(label),
+
+            Tail::If(cnd, thn, els) => Tail::If(self.resolve(&cnd), thn, els),
+        }
+    }
+}
+
+// also returns how many copies were removed
+pub fn propagate_copies_counted(program: &IRProgram) -> (IRProgram, usize) {
+    let mut ssa = to_ssa(program);
+
+    let mut copies = Copies {
+        atoms: HashMap::new(),
+        removed: 0,
+    };
+
+    for block in ssa.blocks.values() {
+        copies.find(&block.body);
+    }
+
+    ssa.blocks =
+        std::mem::take(&mut ssa.blocks)
+        .into_iter()
+        .map(|(label, block)| {
+            let phis =
+                block.phis
+                .into_iter()
+                .map(|phi| Phi {
+                    dest: phi.dest,
+                    args: phi.args.iter().map(|(from, value)| (*from, copies.resolve(value))).collect(),
+                })
+                .collect();
+
+            (label, SsaBlock { phis: phis, body: copies.rewrite(block.body) })
+        })
+        .collect();
+
+    let removed = copies.removed;
+
+    (from_ssa(ssa), removed)
+}
+
+pub fn propagate_copies(program: &IRProgram) -> IRProgram {
+    propagate_copies_counted(program).0
+}
@@ -0,0 +1,113 @@
+use crate::utility::{test_ir_helper, test_interp_ir_helper};
+
+use super::explicate::*;
+use super::copy_propagation::*;
+use super::value_numbering::{number_values};
+use super::parser::{parse_ir};
+use super::verify::{verify_ir};
+
+// the program has to compute the same value and read the same input afterwards
+fn check_same_behaviour(before: &IRProgram, after: &IRProgram, input: Vec<&str>) {
+    let (before_result, before_reads) = test_interp_ir_helper(before.clone(), input.clone());
+    let (after_result, after_reads) = test_interp_ir_helper(after.clone(), input);
+
+    assert!(!before_result.had_error);
+    assert!(!after_result.had_error);
+
+    assert_eq!(before_result.value, after_result.value);
+    assert_eq!(before_reads, after_reads);
+}
+
+#[test]
+fn copy_propagation_after_value_numbering() {
+    // value numbering turns the second (- x) into a copy of the first
+    let ir = number_values(test_ir_helper("(let ([x (read)]) (+ (- x) (- x)))"));
+
+    let (after, removed) = propagate_copies_counted(&ir);
+
+    assert_eq!(removed, 1);
+
+    let expected = parse_ir("
+        locals: tmp.0_1 x.1_1
+        start:
+            x.1_1 = (read);
+            tmp.0_1 = (- x.1_1);
+            return (+ tmp.0_1 tmp.0_1);
+    ").unwrap();
+
+    assert_eq!(after, expected);
+
+    check_same_behaviour(&ir, &after, vec!("7"));
+}
+
+#[test]
+fn copy_propagation_chains_and_constants() {
+    let ir = parse_ir("
+        locals: a b c d
+        start:
+            a = (read);
+            b = a;
+            c = b;
+            d = 5;
+            return (+ c d);
+    ").unwrap();
+
+    let (after, removed) = propagate_copies_counted(&ir);
+
+    assert_eq!(removed, 3);
+    assert_eq!(after.labels[&crate::idstr!("start")], parse_ir("locals: a_1\nstart: a_1 = (read); return (+ a_1 5);").unwrap().labels[&crate::idstr!("start")]);
+
+    check_same_behaviour(&ir, &after, vec!("3"));
+}
+
+#[test]
+fn copy_propagation_across_labels() {
+    // y only passes values on, in the loop as well
+    let ir = parse_ir("
+        locals: n x y
+        start:
+            n = (read);
+            y = n;
+            x = y;
+            goto loop;
+        loop:
+            if n goto body; else goto done;
+        body:
+            n = (+ n -1);
+            y = x;
+            x = (+ y 1);
+            goto loop;
+        done:
+            return x;
+    ").unwrap();
+
+    let after = propagate_copies(&ir);
+
+    assert_eq!(verify_ir(&after), Ok(()));
+
+    for input in ["0", "1", "4"] {
+        check_same_behaviour(&ir, &after, vec!(input));
+    }
+
+    // y is gone, the copies left are the ones that take the program out of ssa form
+    let expected = parse_ir("
+        locals: n_1 n_2 n_3 x_2 x_3
+        start:
+            n_1 = (read);
+            n_2 = n_1;
+            x_2 = n_1;
+            goto loop;
+        loop:
+            if n_2 goto body; else goto done;
+        body:
+            n_3 = (+ n_2 -1);
+            x_3 = (+ x_2 1);
+            n_2 = n_3;
+            x_2 = x_3;
+            goto loop;
+        done:
+            return x_2;
+    ").unwrap();
+
+    assert_eq!(after, expected);
+}
@@ -7,18 +7,26 @@
 
 use natord;
 
-use crate::frontend::ast::{AstNode, Program};
+use crate::frontend::ast::{Ast, AstNode, NodeId, Program};
 use crate::types::{IdString};
+use crate::symbol::{PLUS, MINUS, READ};
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /*
 
 Atm   ::= (Int int) | (Var var)
 Exp   ::= atm | (Prim read ()) |(Prim - (atm)) |(Prim + (atm atm))
 Stmt  ::= (Assign (Var var) exp)
-Tail  ::= (Return exp) | (Seq stmt tail)
-Clang ::= (IRProgram info ((label . tail) ...))
+Tail  ::= (Return exp) | (Goto label) | (If atm label label)
+Block ::= (Block (stmt ...) tail)
+Clang ::= (IRProgram info ((label . block) ...))
+
+a block is its statements in order and the tail that ends it, they're kept in a
+vector rather than a chain of (Seq stmt tail), so a long block is walked with a
+loop and not with recursion
+
+(If atm then else) jumps to 'then' when atm is not zero, otherwise to 'else'
 
 info will be a list of local variables
 
@@ -44,17 +52,43 @@ pub enum Stmt {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Tail {
     Return(Exp),
-    Seq(Stmt, Box<Tail>),
+    Goto(IdString),
+    If(Atm, IdString, IdString),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub tail: Tail,
+}
+
+impl Block {
+
+    pub fn new(stmts: Vec<Stmt>, tail: Tail) -> Self {
+        Block {
+            stmts: stmts,
+            tail: tail,
+        }
+    }
+}
+
+// a block with nothing before its tail
+impl From<Tail> for Block {
+    fn from(tail: Tail) -> Self {
+        Block::new(vec!(), tail)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct IRProgram {
     pub locals: Vec<IdString>, // local variables
-    pub labels: HashMap<IdString, Tail>,
+    pub labels: BTreeMap<IdString, Block>, // ordered, so every pass sees the labels in the same order
 }
 
-struct Explicator {
+struct Explicator<'a> {
     local_vars: Vec<IdString>,
+    ast: &'a Ast,
+    stmts: Vec<Stmt>, // of the block being built
 }
 
 #[derive(Debug, Clone)]
@@ -63,18 +97,20 @@ enum Arithmetic {
     Sub,
 }
 
-fn prim_args_to_ir_atm_vec(vec: Vec<AstNode>) -> Vec<Atm> {
+fn prim_args_to_ir_atm_vec(ast: &Ast, vec: &[NodeId]) -> Vec<Atm> {
 
     let mut v: Vec<Atm> = vec!();
 
-    for node in vec {
-        match node {
+    for id in vec {
+        let node = &ast[*id];
+
+        match *node {
             AstNode::Int(n) => {
                 v.push(Atm::Int(n));
             },
 
             AstNode::Var { name } => {
-                v.push(Atm::Var { name: name.clone() });
+                v.push(Atm::Var { name: name });
             },
 
             _ => {
@@ -91,70 +127,57 @@ fn prim_args_to_ir_atm_vec(vec: Vec<AstNode>) -> Vec<Atm> {
     v
 }
 
-impl Explicator {
+impl<'a> Explicator<'a> {
 
-    pub fn new() -> Explicator {
+    pub fn new(ast: &'a Ast) -> Explicator<'a> {
         Explicator {
             local_vars: vec!(),
+            ast: ast,
+            stmts: vec!(),
         }
     }
 
-    fn explicate_tail(&mut self, exp: AstNode) -> Tail {
-        match exp {
+    // the statements before 'exp' are in self.stmts, what's returned ends the block
+    fn explicate_tail(&mut self, exp: NodeId) -> Tail {
+        let ast = self.ast;
 
-            AstNode::Var { name } => {
-                let return_node = 
-                    Tail::Return (
-                        Exp::Atm (
-                            Atm::Var {
-                                name: name.clone()
-                            }
-                        )
-                    );
+        match &ast[exp] {
 
-                return_node
+            AstNode::Var { name } => {
+                Tail::Return (
+                    Exp::Atm (
+                        Atm::Var {
+                            name: *name
+                        }
+                    )
+                )
             },
 
             AstNode::Int(n) => {
-                let return_node = 
-                    Tail::Return (
-                        Exp::Atm (
-                            Atm::Int(n)
-                        )
-                    );
-
-                return_node
+                Tail::Return (
+                    Exp::Atm (
+                        Atm::Int(*n)
+                    )
+                )
             },
 
             AstNode::Let { bindings, body } => {
 
-                let last_tail = self.explicate_tail(*body.clone());
-
-                let assignments = 
-                    bindings
-                    .iter()
-                    .rev()
-                    .fold(
-                        last_tail,
-                        | tail, assign |
-                        self.explicate_assign(
-                            assign.expr.clone(),
-                            assign.identifier.clone(),
-                            tail
-                        )
-                    );
+                for binding in bindings {
+                    self.explicate_assign(binding.expr, binding.identifier);
+                }
 
-                assignments
+                self.explicate_tail(*body)
             },
 
             AstNode::Prim {op, args} => {
 
-                match &op[..] {
-                    "+" | "-" | "read" => {
+                match *op {
+                    PLUS | MINUS | READ => {
                         Tail::Return (
                             Exp::Prim {
-                                op: op.clone(),
-                                args: prim_args_to_ir_atm_vec(args)
+                                op: *op,
+                                args: prim_args_to_ir_atm_vec(ast, args)
                             }
                         )
                     },
@@ -177,80 +200,67 @@ impl Explicator {
         }
     }
 
-    fn explicate_assign(&mut self, exp: AstNode, var: IdString, acc: Tail) -> Tail {
-        match exp {
+    // add the statements that assign 'exp' to 'var'
+    fn explicate_assign(&mut self, exp: NodeId, var: IdString) {
+        let ast = self.ast;
+
+        match &ast[exp] {
 
             AstNode::Var { name } => {
                 
-                self.local_vars.push(var.clone());
+                self.local_vars.push(var);
 
-                Tail::Seq(
+                self.stmts.push(
                     Stmt::Assign(
                         Atm::Var {name: var},
                         Exp::Atm (
                             Atm::Var {
-                                name: name.clone()
+                                name: *name
                             }
                         )
-                    ),
-                    Box::new(acc)
+                    )
                 )
 
             },
 
             AstNode::Int(n) => {
 
-                self.local_vars.push(var.clone());
+                self.local_vars.push(var);
 
-                Tail::Seq(
+                self.stmts.push(
                     Stmt::Assign(
                         Atm::Var{ name: var },
                         Exp::Atm (
-                            Atm::Int(n)
+                            Atm::Int(*n)
                         )
-                    ),
-                    Box::new(acc)
+                    )
                 )
             },
 
             AstNode::Let { bindings, body } => {
 
-                let body_assign = self.explicate_assign(*body.clone(), var, acc);
-
-                let assignments = 
-                    bindings
-                    .iter()
-                    .rev()
-                    .fold(
-                        body_assign,
-                        | tail, assign |
-                        self.explicate_assign(
-                            assign.expr.clone(),
-                            assign.identifier.clone(),
-                            tail
-                        )
-                    );
-
-                assignments
+                for binding in bindings {
+                    self.explicate_assign(binding.expr, binding.identifier);
+                }
 
+                self.explicate_assign(*body, var)
             },
 
             AstNode::Prim { op, args } => {
 
-                match &op[..] {
+                match *op {
 
-                    "read" | "+" | "-" => {
-                        self.local_vars.push(var.clone());
+                    READ | PLUS | MINUS => {
+                        self.local_vars.push(var);
 
-                        Tail::Seq(
+                        self.stmts.push(
                             Stmt::Assign(
                                 Atm::Var { name: var },
                                 Exp::Prim {
-                                    op: op.clone(),
-                                    args: prim_args_to_ir_atm_vec(args)
+                                    op: *op,
+                                    args: prim_args_to_ir_atm_vec(ast, args)
                                 }
-                            ),
-                            Box::new(acc)
+                            )
                         )
                     },
                     _ => {
@@ -264,11 +274,11 @@ impl Explicator {
                 }
             },
 
-            _ => {
+            node => {
                 println!("{}:{}: unrecognized expression: '{:?}'",
                     crate::function!(),
                     line!(),
-                    exp
+                    node
                 );
                 unreachable!();
             }
@@ -277,12 +287,12 @@ impl Explicator {
 }
 
 pub fn explicate_control(program: Program) -> IRProgram {
-    let mut explicator = Explicator::new();
+    let mut explicator = Explicator::new(&program.ast);
 
-    let instructions = explicator.explicate_tail(program.exp);
+    let tail = explicator.explicate_tail(program.exp);
 
-    let mut labels = HashMap::new();
-    labels.insert(crate::idstr!("start"), instructions);
+    let mut labels = BTreeMap::new();
+    labels.insert(crate::idstr!("start"), Block::new(std::mem::take(&mut explicator.stmts), tail));
 
     let mut locals = explicator.local_vars;
 
@@ -299,7 +309,7 @@ pub fn explicate_control(program: Program) -> IRProgram {
     */
     locals.sort_by(
         |a, b|
-        natord::compare(&*a, &*b)
+        natord::compare(a, b)
     );
 
     // start is the entry point in clang
@@ -307,4 +317,4 @@ pub fn explicate_control(program: Program) -> IRProgram {
         locals: locals,
         labels: labels,
     }
-}
\ No newline at end of file
+}
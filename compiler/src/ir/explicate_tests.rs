@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::utility::{test_ir_helper};
 
@@ -12,13 +12,15 @@ fn helper(prog: &'static str) -> IRProgram {
 fn explicate_constant() {
     let ir = helper("(123)");
 
-    let mut labels = HashMap::new();
+    let mut labels = BTreeMap::new();
 
     labels.insert(
         crate::idstr!("start"),
-        Tail::Return(
-            Exp::Atm(
-                Atm::Int(123)
+        Block::from(
+            Tail::Return(
+                Exp::Atm(
+                    Atm::Int(123)
+                )
             )
         )
     );
@@ -35,13 +37,15 @@ fn explicate_constant() {
 fn explicate_add_constants() {
     let ir = helper("(+ 2 2)");
 
-    let mut labels = HashMap::new();
+    let mut labels = BTreeMap::new();
 
     labels.insert(
         crate::idstr!("start"),
-        Tail::Return(
-            Exp::Atm(
-                Atm::Int(4)
+        Block::from(
+            Tail::Return(
+                Exp::Atm(
+                    Atm::Int(4)
+                )
             )
         )
     );
@@ -58,13 +62,15 @@ fn explicate_add_constants() {
 fn explicate_let_constant() {
     let ir = helper("(let ([x 10]) x)");
 
-    let mut labels = HashMap::new();
+    let mut labels = BTreeMap::new();
 
     labels.insert(
         crate::idstr!("start"),
-        Tail::Return (
-            Exp::Atm(
-                Atm::Int(10)
+        Block::from(
+            Tail::Return (
+                Exp::Atm(
+                    Atm::Int(10)
+                )
             )
         )
     );
@@ -81,13 +87,15 @@ fn explicate_let_constant() {
 fn explicate_let_nested_const() {
     let ir = helper("(let ([x (let ([y 42]) y)]) x)");
 
-    let mut labels = HashMap::new();
+    let mut labels = BTreeMap::new();
 
     labels.insert(
         crate::idstr!("start"),
-        Tail::Return(
-            Exp::Atm(
-                Atm::Int(42)
+        Block::from(
+            Tail::Return(
+                Exp::Atm(
+                    Atm::Int(42)
+                )
             )
         )
     );
@@ -104,41 +112,68 @@ fn explicate_let_nested_const() {
 fn explicate_add_read() {
     let ir = helper("(+ (read) (read))");
 
-    let mut labels = HashMap::new();
+    let mut labels = BTreeMap::new();
 
     let tmp = crate::idstr!("tmp.0");
     let tmp1 = crate::idstr!("tmp.1");
 
     labels.insert(
         crate::idstr!("start"),
-        Tail::Seq(
-            Stmt::Assign(
-                Atm::Var { name: tmp.clone() },
-                Exp::Prim { op: crate::idstr!("read"), args: vec!() },
-            ),
-            Box::new(
-                Tail::Seq(
-                    Stmt::Assign(
-                        Atm::Var { name: tmp1.clone() },
-                        Exp::Prim { op: crate::idstr!("read"), args: vec!() },
-                    ),
-                    Box::new(
-                        Tail::Return(
-                            Exp::Prim {
-                                op: crate::idstr!("+"),
-                                args: vec!(Atm::Var {name: tmp.clone()}, Atm::Var {name: tmp1.clone()}),
-                            }
-                        )
-                    )
+        Block::new(
+            vec!(
+                Stmt::Assign(
+                    Atm::Var { name: tmp },
+                    Exp::Prim { op: crate::idstr!("read"), args: vec!() },
+                ),
+                Stmt::Assign(
+                    Atm::Var { name: tmp1 },
+                    Exp::Prim { op: crate::idstr!("read"), args: vec!() },
                 ),
+            ),
+            Tail::Return(
+                Exp::Prim {
+                    op: crate::idstr!("+"),
+                    args: vec!(Atm::Var {name: tmp}, Atm::Var {name: tmp1}),
+                }
             )
         )
     );
 
     let expected = IRProgram {
-        locals: vec!(tmp.clone(), tmp1.clone()),
+        locals: vec!(tmp, tmp1),
         labels: labels
     };
 
     assert_eq!(ir, expected);
-}
\ No newline at end of file
+}
+
+#[test]
+fn explicate_if_and_goto_interpret() {
+    // start: x = (read); if x then yes else no
+    let x = crate::idstr!("x");
+
+    let mut labels = BTreeMap::new();
+
+    labels.insert(
+        crate::idstr!("start"),
+        Block::new(
+            vec!(Stmt::Assign(Atm::Var { name: x }, Exp::Prim { op: crate::idstr!("read"), args: vec!() })),
+            Tail::If(Atm::Var { name: x }, crate::idstr!("yes"), crate::idstr!("no"))
+        )
+    );
+
+    labels.insert(crate::idstr!("yes"), Block::from(Tail::Goto(crate::idstr!("done"))));
+    labels.insert(crate::idstr!("no"), Block::from(Tail::Return(Exp::Atm(Atm::Int(2)))));
+    labels.insert(crate::idstr!("done"), Block::from(Tail::Return(Exp::Atm(Atm::Int(1)))));
+
+    let ir = IRProgram {
+        locals: vec!(x),
+        labels: labels
+    };
+
+    let (taken, _) = crate::utility::test_interp_ir_helper(ir.clone(), vec!("7"));
+    let (not_taken, _) = crate::utility::test_interp_ir_helper(ir, vec!("0"));
+
+    assert_eq!(taken.value, Some(1));
+    assert_eq!(not_taken.value, Some(2));
+}
@@ -1,4 +1,23 @@
 pub mod explicate;
+pub mod value_numbering;
+pub mod copy_propagation;
+pub mod cfg;
+pub mod ssa;
+pub mod verify;
+pub mod printer;
+pub mod parser;
 
 #[cfg(test)]
-mod explicate_tests;
\ No newline at end of file
+mod explicate_tests;
+#[cfg(test)]
+mod value_numbering_tests;
+#[cfg(test)]
+mod copy_propagation_tests;
+#[cfg(test)]
+mod ssa_tests;
+#[cfg(test)]
+mod verify_tests;
+#[cfg(test)]
+mod printer_tests;
+#[cfg(test)]
+mod parser_tests;
@@ -0,0 +1,368 @@
+/*
+    read the ir back from the text the printer makes
+
+        locals: x.1 tmp.2
+        start:
+            x.1 = (read);
+            tmp.2 = (- x.1);
+            if tmp.2 goto block.1; else goto block.2;
+        block.1:
+            return (+ x.1 tmp.2);
+        block.2:
+            goto start;
+
+    statements end with a ';', line breaks don't matter. # starts a comment
+
+    the scanner is shared with the pseudo-x86 parser, there line breaks do matter
+*/
+
+use std::collections::BTreeMap;
+
+use crate::types::{IdString};
+use crate::frontend::verify::{KNOWN_PRIMS};
+
+use super::explicate::{Atm, Exp, Stmt, Tail, Block, IRProgram};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tok {
+    Word(String), // names, labels, keywords and operators
+    Int(i64),
+    Punct(char),
+    Newline,
+}
+
+const PUNCTUATION: &str = "()[];:=,";
+
+pub struct Scanner {
+    tokens: Vec<(usize, Tok)>, // with the line they're on
+    position: usize,
+}
+
+impl Scanner {
+
+    pub fn new(text: &str) -> Self {
+        let mut tokens = vec!();
+
+        for (n, line) in text.lines().enumerate() {
+            let line_number = n + 1;
+            let line = line.split('#').next().unwrap();
+
+            let mut word = String::new();
+
+            let end_word = |word: &mut String, tokens: &mut Vec<(usize, Tok)>| {
+                if !word.is_empty() {
+                    let tok = match word.parse::<i64>() {
+                        Ok(n) => Tok::Int(n),
+                        Err(_) => Tok::Word(word.clone()),
+                    };
+
+                    tokens.push((line_number, tok));
+                    word.clear();
+                }
+            };
+
+            for c in line.chars() {
+                if c.is_whitespace() {
+                    end_word(&mut word, &mut tokens);
+                } else if PUNCTUATION.contains(c) {
+                    end_word(&mut word, &mut tokens);
+                    tokens.push((line_number, Tok::Punct(c)));
+                } else {
+                    word.push(c);
+                }
+            }
+
+            end_word(&mut word, &mut tokens);
+
+            tokens.push((line_number, Tok::Newline));
+        }
+
+        Scanner {
+            tokens: tokens,
+            position: 0,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&Tok> {
+        self.peek_at(0)
+    }
+
+    pub fn peek_at(&self, n: usize) -> Option<&Tok> {
+        self.tokens.get(self.position + n).map(|(_, tok)| tok)
+    }
+
+    pub fn next(&mut self) -> Option<Tok> {
+        let tok = self.peek().cloned();
+
+        if tok.is_some() {
+            self.position += 1;
+        }
+
+        tok
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    pub fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Tok::Newline) {
+            self.position += 1;
+        }
+    }
+
+    pub fn error<T>(&self, msg: String) -> Result<T, String> {
+        let line = match self.tokens.get(self.position) {
+            Some((line, _)) => *line,
+            None => self.tokens.last().map(|(line, _)| *line).unwrap_or(1),
+        };
+
+        Err(format!("line {}: {}", line, msg))
+    }
+
+    pub fn found(&self) -> String {
+        match self.peek() {
+            Some(Tok::Word(word)) => format!("'{}'", word),
+            Some(Tok::Int(n)) => format!("'{}'", n),
+            Some(Tok::Punct(c)) => format!("'{}'", c),
+            Some(Tok::Newline) => "the end of the line".to_owned(),
+            None => "the end of the input".to_owned(),
+        }
+    }
+
+    pub fn expect_punct(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(&Tok::Punct(c)) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(format!("expected '{}', found {}", c, self.found()))
+        }
+    }
+
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        match self.peek() {
+            Some(Tok::Word(word)) if word == keyword => {
+                self.position += 1;
+                Ok(())
+            },
+
+            _ => self.error(format!("expected '{}', found {}", keyword, self.found())),
+        }
+    }
+
+    // 'what' is what the word is for, e.g. "a label"
+    pub fn expect_word(&mut self, what: &str) -> Result<String, String> {
+        match self.peek().cloned() {
+            Some(Tok::Word(word)) => {
+                self.position += 1;
+                Ok(word)
+            },
+
+            _ => self.error(format!("expected {}, found {}", what, self.found())),
+        }
+    }
+
+    pub fn expect_newline(&mut self) -> Result<(), String> {
+        match self.peek() {
+            Some(Tok::Newline) | None => {
+                self.skip_newlines();
+                Ok(())
+            },
+
+            _ => self.error(format!("expected the end of the line, found {}", self.found())),
+        }
+    }
+}
+
+fn parse_atm(s: &mut Scanner) -> Result<Atm, String> {
+    match s.peek().cloned() {
+        Some(Tok::Int(n)) => {
+            s.next();
+            Ok(Atm::Int(n))
+        },
+
+        Some(Tok::Word(name)) => {
+            s.next();
+            Ok(Atm::Var { name: crate::idstr!(&name) })
+        },
+
+        _ => s.error(format!("expected a number or a variable, found {}", s.found())),
+    }
+}
+
+fn parse_exp(s: &mut Scanner) -> Result<Exp, String> {
+    s.skip_newlines();
+
+    if s.peek() != Some(&Tok::Punct('(')) {
+        return Ok(Exp::Atm(parse_atm(s)?));
+    }
+
+    s.next();
+
+    let op = s.expect_word("an operator")?;
+
+    let arity = match KNOWN_PRIMS.iter().find(|(name, _)| *name == op.as_str()) {
+        Some((_, arity)) => *arity,
+        None => return s.error(format!("unknown primitive '{}'", op)),
+    };
+
+    let mut args = vec!();
+
+    loop {
+        s.skip_newlines();
+
+        if s.peek() == Some(&Tok::Punct(')')) {
+            s.next();
+            break;
+        }
+
+        args.push(parse_atm(s)?);
+    }
+
+    if args.len() != arity {
+        return s.error(format!("'{}' takes {} argument(s) but was given {}", op, arity, args.len()));
+    }
+
+    Ok(Exp::Prim { op: crate::idstr!(&op), args: args })
+}
+
+fn parse_label(s: &mut Scanner) -> Result<IdString, String> {
+    s.skip_newlines();
+
+    let label = s.expect_word("a label")?;
+
+    Ok(crate::idstr!(&label))
+}
+
+fn parse_end_of_statement(s: &mut Scanner) -> Result<(), String> {
+    s.skip_newlines();
+    s.expect_punct(';')
+}
+
+// None, without taking anything, if the next statement is an assignment
+fn parse_tail(s: &mut Scanner) -> Result<Option<Tail>, String> {
+    s.skip_newlines();
+
+    let keyword = match s.peek() {
+        Some(Tok::Word(word)) => word.clone(),
+        _ => return s.error(format!("expected a statement, found {}", s.found())),
+    };
+
+    // 'x = ...' is an assignment even if x is a keyword
+    if s.peek_at(1) != Some(&Tok::Punct('=')) {
+        match keyword.as_str() {
+            "return" => {
+                s.next();
+
+                let exp = parse_exp(s)?;
+                parse_end_of_statement(s)?;
+
+                return Ok(Some(Tail::Return(exp)));
+            },
+
+            "goto" => {
+                s.next();
+
+                let label = parse_label(s)?;
+                parse_end_of_statement(s)?;
+
+                return Ok(Some(Tail::Goto(label)));
+            },
+
+            "if" => {
+                s.next();
+
+                let atm = parse_atm(s)?;
+
+                s.skip_newlines();
+                s.expect_keyword("goto")?;
+                let thn = parse_label(s)?;
+                parse_end_of_statement(s)?;
+
+                s.skip_newlines();
+                s.expect_keyword("else")?;
+                s.skip_newlines();
+                s.expect_keyword("goto")?;
+                let els = parse_label(s)?;
+                parse_end_of_statement(s)?;
+
+                return Ok(Some(Tail::If(atm, thn, els)));
+            },
+
+            _ => {},
+        }
+    }
+
+    Ok(None)
+}
+
+fn parse_stmt(s: &mut Scanner) -> Result<Stmt, String> {
+    let name = match s.next() {
+        Some(Tok::Word(word)) => word,
+        // parse_tail has already seen the name
+        _ => unreachable!(),
+    };
+
+    let var = Atm::Var { name: crate::idstr!(&name) };
+
+    s.skip_newlines();
+    s.expect_punct('=')?;
+
+    let exp = parse_exp(s)?;
+    parse_end_of_statement(s)?;
+
+    Ok(Stmt::Assign(var, exp))
+}
+
+// the block goes on until a return, goto or if
+fn parse_block(s: &mut Scanner) -> Result<Block, String> {
+    let mut stmts = vec!();
+
+    loop {
+        if let Some(tail) = parse_tail(s)? {
+            return Ok(Block::new(stmts, tail));
+        }
+
+        stmts.push(parse_stmt(s)?);
+    }
+}
+
+pub fn parse_ir(text: &str) -> Result<IRProgram, String> {
+    let mut s = Scanner::new(text);
+
+    s.skip_newlines();
+    s.expect_keyword("locals")?;
+    s.expect_punct(':')?;
+
+    let mut locals = vec!();
+
+    while let Some(Tok::Word(name)) = s.peek().cloned() {
+        s.next();
+        locals.push(crate::idstr!(&name));
+    }
+
+    s.expect_newline()?;
+
+    let mut labels = BTreeMap::new();
+
+    while !s.at_end() {
+        let label = parse_label(&mut s)?;
+
+        s.expect_punct(':')?;
+
+        if labels.contains_key(&label) {
+            return s.error(format!("label '{}' is defined more than once", label));
+        }
+
+        let block = parse_block(&mut s)?;
+
+        labels.insert(label, block);
+
+        s.expect_newline()?;
+    }
+
+    Ok(IRProgram {
+        locals: locals,
+        labels: labels,
+    })
+}
@@ -0,0 +1,114 @@
+use crate::types::{OptLevel};
+use crate::utility::{test_interp_ir_helper};
+use crate::fuzz::{Generator, GenConfig};
+use crate::pipeline::{PassManager, ProgramForm, IrKind};
+
+use super::explicate::*;
+use super::parser::*;
+
+fn var(name: &'static str) -> Atm {
+    Atm::Var { name: crate::idstr!(name) }
+}
+
+fn run_pass(pass: &str, p: IRProgram) -> IRProgram {
+    PassManager::custom(&[pass]).unwrap().run(ProgramForm::Ir(p)).into_ir()
+}
+
+#[test]
+fn parse_ir_hand_written() {
+    let text = "
+        # counts down from what was read
+        locals: x
+        start:
+            x = (read);
+            goto loop;
+        loop:
+            if x goto body; else goto done;
+        body:
+            x = (+ x -1);
+            goto loop;
+        done:
+            return 42;
+    ";
+
+    let p = parse_ir(text).unwrap();
+
+    assert_eq!(p.locals, vec!(crate::idstr!("x")));
+    assert_eq!(p.labels.len(), 4);
+
+    assert_eq!(
+        p.labels[&crate::idstr!("body")],
+        Block::new(
+            vec!(Stmt::Assign(var("x"), Exp::Prim { op: crate::idstr!("+"), args: vec!(var("x"), Atm::Int(-1)) })),
+            Tail::Goto(crate::idstr!("loop"))
+        )
+    );
+
+    let (result, reads) = test_interp_ir_helper(p, vec!("3"));
+
+    assert_eq!(result.value, Some(42));
+    assert_eq!(reads.len(), 1);
+}
+
+#[test]
+fn parse_ir_line_breaks_dont_matter() {
+    let one_line = parse_ir("locals: a\nstart: a = (read); return (- a);").unwrap();
+    let spread = parse_ir("locals: a\nstart:\n a\n =\n (read)\n ;\n return\n (-\n a)\n ;").unwrap();
+
+    assert_eq!(one_line, spread);
+}
+
+#[test]
+fn parse_ir_round_trip() {
+    for level in [OptLevel::O0, OptLevel::O1] {
+        for seed in 0..200 {
+            let program = Generator::new(seed, GenConfig::default()).generate();
+
+            let p =
+                PassManager::for_level(level)
+                .run_to(ProgramForm::Ast(program), IrKind::Ir)
+                .into_ir();
+
+            let text = p.to_string();
+
+            assert_eq!(parse_ir(&text), Ok(p), "{}", text);
+        }
+    }
+}
+
+#[test]
+fn parse_ir_test_a_pass() {
+    let input = parse_ir("
+        locals: a b c
+        start:
+            a = (read);
+            b = (- a);
+            c = (- a);
+            return (+ b c);
+    ").unwrap();
+
+    let expected = parse_ir("
+        locals: a b c
+        start:
+            a = (read);
+            b = (- a);
+            c = b;
+            return (+ b c);
+    ").unwrap();
+
+    assert_eq!(run_pass("value-numbering", input), expected);
+}
+
+#[test]
+fn parse_ir_errors() {
+    let error = |text: &str| parse_ir(text).unwrap_err();
+
+    assert_eq!(error("start:\n return 1;"), "line 1: expected 'locals', found 'start'");
+    assert_eq!(error("locals:\nstart:\n x = 1\n return x;"), "line 4: expected ';', found 'return'");
+    assert_eq!(error("locals:\nstart:\n 1 = 2;\n return 1;"), "line 3: expected a statement, found '1'");
+    assert_eq!(error("locals:\nstart:\n return 1;\nstart:\n return 2;"), "line 4: label 'start' is defined more than once");
+    assert_eq!(error("locals:\nstart:\n x = (+ 1;"), "line 3: expected a number or a variable, found ';'");
+    assert_eq!(error("locals:\nstart:\n x = 1;"), "line 3: expected a statement, found the end of the input");
+    assert_eq!(error("locals: x\nstart:\n x = (+ 1);\n return x;"), "line 3: '+' takes 2 argument(s) but was given 1");
+    assert_eq!(error("locals: x\nstart:\n x = (* 2 3);\n return x;"), "line 3: unknown primitive '*'");
+}
@@ -0,0 +1,96 @@
+/*
+    print the ir in the book's c like notation
+
+        locals: x.1 tmp.2
+        start:
+            x.1 = (read);
+            tmp.2 = (- x.1);
+            return (+ x.1 tmp.2);
+
+    start comes first, the other labels follow in order
+*/
+
+use std::fmt;
+
+use super::explicate::{Atm, Exp, Stmt, Tail, Block, IRProgram};
+
+impl fmt::Display for Atm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Atm::Int(n) => write!(f, "{}", n),
+            Atm::Var { name } => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for Exp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Exp::Atm(atm) => write!(f, "{}", atm),
+
+            Exp::Prim { op, args } => {
+                write!(f, "({}", op)?;
+
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+
+                write!(f, ")")
+            },
+        }
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stmt::Assign(var, exp) => write!(f, "{} = {};", var, exp),
+        }
+    }
+}
+
+impl fmt::Display for Tail {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tail::Return(exp) => write!(f, "    return {};", exp),
+
+            Tail::Goto(label) => write!(f, "    goto {};", label),
+
+            Tail::If(atm, thn, els) => write!(f, "    if {} goto {}; else goto {};", atm, thn, els),
+        }
+    }
+}
+
+// one statement per line, indented
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stmt in &self.stmts {
+            writeln!(f, "    {}", stmt)?;
+        }
+
+        write!(f, "{}", self.tail)
+    }
+}
+
+impl fmt::Display for IRProgram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "locals:")?;
+
+        for local in &self.locals {
+            write!(f, " {}", local)?;
+        }
+
+        let start = crate::idstr!("start");
+
+        let labels =
+            self.labels.get_key_value(&start)
+            .into_iter()
+            .chain(self.labels.iter().filter(|(label, _)| **label != start));
+
+        for (label, block) in labels {
+            write!(f, "\n{}:\n{}", label, block)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use crate::utility::{test_ir_helper};
+
+use super::explicate::*;
+
+fn var(name: &'static str) -> Atm {
+    Atm::Var { name: crate::idstr!(name) }
+}
+
+#[test]
+fn print_ir_explicated() {
+    let p = test_ir_helper("(let ([x (read)]) (+ x (- (read))))");
+
+    let expected = [
+        "locals: tmp.0 tmp.1 x.1",
+        "start:",
+        "    x.1 = (read);",
+        "    tmp.1 = (read);",
+        "    tmp.0 = (- tmp.1);",
+        "    return (+ x.1 tmp.0);",
+    ];
+
+    assert_eq!(p.to_string(), expected.join("\n"));
+}
+
+#[test]
+fn print_ir_start_comes_first() {
+    let p = IRProgram {
+        locals: vec!(crate::idstr!("x")),
+        labels: vec!(
+            (crate::idstr!("block.1"), Block::from(Tail::Return(Exp::Atm(Atm::Int(-1))))),
+            (crate::idstr!("block.2"), Block::from(Tail::Return(Exp::Atm(var("x"))))),
+            (
+                crate::idstr!("start"),
+                Block::new(
+                    vec!(Stmt::Assign(var("x"), Exp::Prim { op: crate::idstr!("read"), args: vec!() })),
+                    Tail::If(var("x"), crate::idstr!("block.2"), crate::idstr!("block.1"))
+                )
+            ),
+            (crate::idstr!("loop"), Block::from(Tail::Goto(crate::idstr!("loop")))),
+        ).into_iter().collect(),
+    };
+
+    let expected = [
+        "locals: x",
+        "start:",
+        "    x = (read);",
+        "    if x goto block.2; else goto block.1;",
+        "block.1:",
+        "    return -1;",
+        "block.2:",
+        "    return x;",
+        "loop:",
+        "    goto loop;",
+    ];
+
+    assert_eq!(p.to_string(), expected.join("\n"));
+}
@@ -0,0 +1,740 @@
+/*
+    static single assignment form for the ir
+
+    in ssa form every variable is assigned exactly once, and where control flow joins
+    a phi node picks the version of a variable coming from the predecessor we came from
+
+        start:                              start:
+            c = (read)                          c_1 = (read)
+            if c then else                      if c_1 then else
+        then:                               then:
+            x = 1                               x_1 = 1
+            goto join                           goto join
+        else:               becomes         else:
+            x = 2                               x_2 = 2
+            goto join                           goto join
+        join:                               join:
+            return x                            x_3 = phi(then: x_1, else: x_2)
+                                                return x_3
+
+    to_ssa places the phi nodes on the dominance frontiers of the assignments
+    (only for variables that are live across labels) and renames every definition
+    while walking the dominator tree. nothing jumps into start from outside the
+    program, so a phi there would miss the value coming in. when start is the target
+    of a jump its body is moved to a label of its own first, and start goes there.
+    from_ssa turns every phi back into copies at the end of the predecessors,
+    splitting an edge when the predecessor has other successors.
+
+    the new versions, temporaries and labels never take a name the program already uses
+
+    labels that can't be reached from start are dropped
+*/
+
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use natord;
+
+use crate::types::{IdString};
+
+use super::cfg::{Cfg, DominatorTree, successors};
+use super::explicate::{IRProgram, Block, Tail, Stmt, Exp, Atm};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Phi {
+    pub dest: IdString,
+    pub args: Vec<(IdString, Atm)>, // (predecessor label, value coming from it)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SsaBlock {
+    pub phis: Vec<Phi>,
+    pub body: Block,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SsaProgram {
+    pub locals: Vec<IdString>,
+    pub blocks: BTreeMap<IdString, SsaBlock>,
+}
+
+impl SsaProgram {
+    pub fn cfg(&self) -> Cfg {
+        Cfg::new(crate::idstr!("start"), |l| self.blocks.get(l).map(|b| &b.body.tail))
+    }
+}
+
+fn sorted(names: HashSet<IdString>) -> Vec<IdString> {
+    let mut names: Vec<IdString> = names.into_iter().collect();
+
+    names.sort_by(
+        |a, b|
+        natord::compare(a, b)
+    );
+
+    names
+}
+
+fn atm_uses(atm: &Atm, uses: &mut Vec<IdString>) {
+    if let Atm::Var { name } = atm {
+        uses.push(*name);
+    }
+}
+
+fn exp_uses(exp: &Exp, uses: &mut Vec<IdString>) {
+    match exp {
+        Exp::Atm(atm) => atm_uses(atm, uses),
+        Exp::Prim { args, .. } => {
+            for arg in args {
+                atm_uses(arg, uses);
+            }
+        }
+    }
+}
+
+// variables used by the jump or return that ends a block
+fn terminator_uses(tail: &Tail) -> Vec<IdString> {
+    let mut uses = vec!();
+
+    match tail {
+        Tail::Return(exp) => exp_uses(exp, &mut uses),
+        Tail::If(cnd, _, _) => atm_uses(cnd, &mut uses),
+        _ => {},
+    }
+
+    uses
+}
+
+fn assigned_var(stmt: &Stmt) -> Option<&IdString> {
+    match stmt {
+        Stmt::Assign(Atm::Var { name }, _) => Some(name),
+        _ => None,
+    }
+}
+
+// every variable the block assigns or uses
+fn add_names(block: &Block, names: &mut HashSet<IdString>) {
+    for stmt in &block.stmts {
+        let mut uses = vec!();
+
+        match stmt {
+            Stmt::Assign(assignee, exp) => {
+                atm_uses(assignee, &mut uses);
+                exp_uses(exp, &mut uses);
+            }
+        }
+
+        names.extend(uses);
+    }
+
+    names.extend(terminator_uses(&block.tail));
+}
+
+// 'name' if it isn't taken yet, otherwise the first of name.1, name.2, ... that isn't
+fn fresh_name(taken: &mut HashSet<IdString>, name: IdString) -> IdString {
+    if taken.insert(name) {
+        return name;
+    }
+
+    let mut n = 1;
+
+    loop {
+        let candidate = IdString::derive(name, ".", n);
+
+        if taken.insert(candidate) {
+            return candidate;
+        }
+
+        n += 1;
+    }
+}
+
+struct SsaBuilder<'a> {
+    labels: &'a BTreeMap<IdString, Block>,
+    cfg: Cfg,
+    dom: DominatorTree,
+    phis: HashMap<IdString, Vec<Phi>>,
+    phi_vars: HashMap<IdString, Vec<IdString>>, // the original variable of every phi
+    stacks: HashMap<IdString, Vec<IdString>>,
+    versions: HashMap<IdString, usize>,
+    new_bodies: HashMap<IdString, Block>,
+    defined: HashSet<IdString>,
+    taken: HashSet<IdString>, // every name in the program, so a version can't clash with one
+}
+
+impl<'a> SsaBuilder<'a> {
+
+    fn new(program: &'a IRProgram) -> Self {
+        let cfg = Cfg::new(crate::idstr!("start"), |l| program.labels.get(l).map(|b| &b.tail));
+        let dom = DominatorTree::new(&cfg);
+
+        let mut taken: HashSet<IdString> = program.locals.iter().cloned().collect();
+
+        for block in program.labels.values() {
+            add_names(block, &mut taken);
+        }
+
+        SsaBuilder {
+            labels: &program.labels,
+            cfg: cfg,
+            dom: dom,
+            phis: HashMap::new(),
+            phi_vars: HashMap::new(),
+            stacks: HashMap::new(),
+            versions: HashMap::new(),
+            new_bodies: HashMap::new(),
+            defined: HashSet::new(),
+            taken: taken,
+        }
+    }
+
+    // variables that are used in a label before being assigned in it, only those
+    // can need a phi node
+    fn live_across_labels(&self) -> HashSet<IdString> {
+        let mut globals = HashSet::new();
+
+        for label in &self.cfg.labels {
+            let block = &self.labels[label];
+            let mut killed: HashSet<IdString> = HashSet::new();
+
+            for stmt in &block.stmts {
+                let mut uses = vec!();
+
+                match stmt {
+                    Stmt::Assign(_, exp) => exp_uses(exp, &mut uses),
+                }
+
+                for var in uses {
+                    if !killed.contains(&var) {
+                        globals.insert(var);
+                    }
+                }
+
+                if let Some(var) = assigned_var(stmt) {
+                    killed.insert(*var);
+                }
+            }
+
+            for var in terminator_uses(&block.tail) {
+                if !killed.contains(&var) {
+                    globals.insert(var);
+                }
+            }
+        }
+
+        globals
+    }
+
+    fn place_phis(&mut self) {
+        let mut def_sites: HashMap<IdString, Vec<IdString>> = HashMap::new();
+
+        for label in &self.cfg.labels {
+            for stmt in &self.labels[label].stmts {
+                if let Some(var) = assigned_var(stmt) {
+                    let sites = def_sites.entry(*var).or_default();
+
+                    if !sites.contains(label) {
+                        sites.push(*label);
+                    }
+                }
+            }
+        }
+
+        for label in &self.cfg.labels {
+            self.phis.insert(*label, vec!());
+            self.phi_vars.insert(*label, vec!());
+        }
+
+        // sorted so phi nodes always come out in the same order
+        let globals = sorted(self.live_across_labels());
+
+        for var in globals {
+            let mut worklist = match def_sites.get(&var) {
+                Some(sites) => sites.clone(),
+                None => continue,
+            };
+
+            let mut has_phi: HashSet<IdString> = HashSet::new();
+            let mut ever_on_worklist: HashSet<IdString> = worklist.iter().cloned().collect();
+
+            while let Some(label) = worklist.pop() {
+                for frontier in self.dom.frontier(&label).to_vec() {
+                    if has_phi.contains(&frontier) {
+                        continue;
+                    }
+
+                    has_phi.insert(frontier);
+
+                    self.phis.get_mut(&frontier).unwrap().push(
+                        Phi {
+                            dest: var,
+                            args: vec!(),
+                        }
+                    );
+                    self.phi_vars.get_mut(&frontier).unwrap().push(var);
+
+                    if !ever_on_worklist.contains(&frontier) {
+                        ever_on_worklist.insert(frontier);
+                        worklist.push(frontier);
+                    }
+                }
+            }
+        }
+    }
+
+    fn new_version(&mut self, var: &IdString) -> IdString {
+        let version = self.versions.entry(*var).or_insert(0);
+
+        // x_1 might be a variable of the program already
+        let name = loop {
+            *version += 1;
+
+            let name = IdString::derive(*var, "_", *version);
+
+            if self.taken.insert(name) {
+                break name;
+            }
+        };
+
+        self.stacks.entry(*var).or_default().push(name);
+        self.defined.insert(name);
+
+        name
+    }
+
+    fn current_version(&self, var: &IdString) -> IdString {
+        match self.stacks.get(var).and_then(|s| s.last()) {
+            Some(name) => *name,
+            // nothing reaches this use, the variable is free
+            None => *var,
+        }
+    }
+
+    fn rename_atm(&self, atm: &Atm) -> Atm {
+        match atm {
+            Atm::Var { name } => Atm::Var { name: self.current_version(name) },
+            Atm::Int(n) => Atm::Int(*n),
+        }
+    }
+
+    fn rename_exp(&self, exp: &Exp) -> Exp {
+        match exp {
+            Exp::Atm(atm) => Exp::Atm(self.rename_atm(atm)),
+            Exp::Prim { op, args } => Exp::Prim {
+                op: *op,
+                args: args.iter().map(|a| self.rename_atm(a)).collect(),
+            },
+        }
+    }
+
+    // returns the renamed block, the variables that got a new version are added to 'pushed'
+    fn rename_block(&mut self, block: &Block, pushed: &mut Vec<IdString>) -> Block {
+        let mut stmts = vec!();
+
+        for Stmt::Assign(assignee, exp) in &block.stmts {
+            let new_exp = self.rename_exp(exp);
+
+            let new_assignee = match assignee {
+                Atm::Var { name } => {
+                    pushed.push(*name);
+                    Atm::Var { name: self.new_version(name) }
+                },
+                Atm::Int(n) => Atm::Int(*n),
+            };
+
+            stmts.push(Stmt::Assign(new_assignee, new_exp));
+        }
+
+        Block::new(stmts, self.rename_tail(&block.tail))
+    }
+
+    fn rename_tail(&self, tail: &Tail) -> Tail {
+        match tail {
+            Tail::Return(exp) => Tail::Return(self.rename_exp(exp)),
+
+            Tail::Goto(label) => Tail::Goto(*label),
+
+            Tail::If(cnd, thn, els) => Tail::If(self.rename_atm(cnd), *thn, *els),
+        }
+    }
+
+    fn rename(&mut self, label: &IdString) {
+        let mut pushed: Vec<IdString> = vec!();
+
+        let phi_vars = self.phi_vars[label].clone();
+
+        for (i, var) in phi_vars.iter().enumerate() {
+            pushed.push(*var);
+
+            let dest = self.new_version(var);
+            self.phis.get_mut(label).unwrap()[i].dest = dest;
+        }
+
+        let new_body = self.rename_block(&self.labels[label], &mut pushed);
+        self.new_bodies.insert(*label, new_body);
+
+        for succ in self.cfg.successors_of(label).to_vec() {
+            let succ_vars = self.phi_vars[&succ].clone();
+
+            for (i, var) in succ_vars.iter().enumerate() {
+                let value = Atm::Var { name: self.current_version(var) };
+
+                self.phis.get_mut(&succ).unwrap()[i].args.push((*label, value));
+            }
+        }
+
+        for child in self.dom.children(label).to_vec() {
+            self.rename(&child);
+        }
+
+        for var in pushed {
+            self.stacks.get_mut(&var).unwrap().pop();
+        }
+    }
+
+    fn build(mut self) -> SsaProgram {
+        self.place_phis();
+
+        let entry = self.cfg.entry;
+
+        if self.cfg.is_reachable(&entry) {
+            self.rename(&entry);
+        }
+
+        let mut blocks = BTreeMap::new();
+
+        for label in &self.cfg.labels {
+            blocks.insert(
+                *label,
+                SsaBlock {
+                    phis: self.phis.remove(label).unwrap(),
+                    body: self.new_bodies.remove(label).unwrap(),
+                }
+            );
+        }
+
+        SsaProgram {
+            locals: sorted(self.defined),
+            blocks: blocks,
+        }
+    }
+}
+
+// a copy of the program where nothing jumps to start, or None if that's already so
+fn separate_entry(program: &IRProgram) -> Option<IRProgram> {
+    let start = crate::idstr!("start");
+
+    let cfg = Cfg::new(start, |l| program.labels.get(l).map(|b| &b.tail));
+
+    if cfg.predecessors_of(&start).is_empty() {
+        return None;
+    }
+
+    let mut taken: HashSet<IdString> = program.labels.keys().cloned().collect();
+
+    let body = fresh_name(&mut taken, crate::idstr!("start.body"));
+
+    let mut labels: BTreeMap<IdString, Block> =
+        program.labels
+        .iter()
+        .map(|(label, block)| {
+            let label = if *label == start { body } else { *label };
+
+            (label, retarget(block.clone(), &start, &body))
+        })
+        .collect();
+
+    labels.insert(start, Block::from(Tail::Goto(body)));
+
+    Some(IRProgram {
+        locals: program.locals.clone(),
+        labels: labels,
+    })
+}
+
+pub fn to_ssa(program: &IRProgram) -> SsaProgram {
+    match separate_entry(program) {
+        Some(separated) => SsaBuilder::new(&separated).build(),
+        None => SsaBuilder::new(program).build(),
+    }
+}
+
+// put 'stmts' right before the jump or return at the end of the block
+fn append_stmts(mut block: Block, stmts: Vec<Stmt>) -> Block {
+    block.stmts.extend(stmts);
+
+    block
+}
+
+fn retarget(block: Block, from: &IdString, to: &IdString) -> Block {
+    let replace = |l: IdString| if l == *from { *to } else { l };
+
+    let tail = match block.tail {
+        Tail::Goto(label) => Tail::Goto(replace(label)),
+        Tail::If(cnd, thn, els) => Tail::If(cnd, replace(thn), replace(els)),
+        Tail::Return(exp) => Tail::Return(exp),
+    };
+
+    Block::new(block.stmts, tail)
+}
+
+// the copies for one edge happen all at once, if one copy would overwrite the source
+// of another one every value goes through a temporary first
+pub fn sequentialize(copies: Vec<(IdString, Atm)>, taken: &mut HashSet<IdString>) -> Vec<Stmt> {
+    let dests: HashSet<&IdString> = copies.iter().map(|c| &c.0).collect();
+
+    let conflict = copies.iter().any(|(dest, src)| match src {
+        Atm::Var { name } => name != dest && dests.contains(name),
+        _ => false,
+    });
+
+    if !conflict {
+        return copies
+            .into_iter()
+            .filter(|(dest, src)| *src != Atm::Var { name: *dest })
+            .map(|(dest, src)| Stmt::Assign(Atm::Var { name: dest }, Exp::Atm(src)))
+            .collect();
+    }
+
+    let tmps: Vec<IdString> =
+        copies
+        .iter()
+        .map(|(dest, _)| fresh_name(taken, crate::idstr!(format!("{}_copy", dest))))
+        .collect();
+
+    let mut stmts = vec!();
+
+    for ((_, src), tmp) in copies.iter().zip(&tmps) {
+        stmts.push(Stmt::Assign(Atm::Var { name: *tmp }, Exp::Atm(src.clone())));
+    }
+
+    for ((dest, _), tmp) in copies.iter().zip(&tmps) {
+        stmts.push(Stmt::Assign(Atm::Var { name: *dest }, Exp::Atm(Atm::Var { name: *tmp })));
+    }
+
+    stmts
+}
+
+pub fn from_ssa(program: SsaProgram) -> IRProgram {
+    let cfg = program.cfg();
+
+    let mut labels: BTreeMap<IdString, Block> = BTreeMap::new();
+
+    // copies to put at the end of a label
+    let mut copies: HashMap<IdString, Vec<Stmt>> = HashMap::new();
+
+    for label in &cfg.labels {
+        labels.insert(*label, program.blocks[label].body.clone());
+    }
+
+    // the names the temporaries and the labels on split edges mustn't take
+    let mut taken_vars: HashSet<IdString> = program.locals.iter().cloned().collect();
+    let mut taken_labels: HashSet<IdString> = program.blocks.keys().cloned().collect();
+
+    // what the program assigns, a variable that isn't is free
+    let mut defined: HashSet<IdString> = HashSet::new();
+
+    for block in program.blocks.values() {
+        add_names(&block.body, &mut taken_vars);
+
+        for phi in &block.phis {
+            taken_vars.insert(phi.dest);
+            defined.insert(phi.dest);
+
+            for (_, value) in &phi.args {
+                if let Atm::Var { name } = value {
+                    taken_vars.insert(*name);
+                }
+            }
+        }
+
+        for stmt in &block.body.stmts {
+            if let Some(var) = assigned_var(stmt) {
+                defined.insert(*var);
+            }
+        }
+    }
+
+    for label in &cfg.labels {
+        let block = &program.blocks[label];
+
+        if block.phis.is_empty() {
+            continue;
+        }
+
+        for pred in cfg.predecessors_of(label) {
+            let edge_copies: Vec<(IdString, Atm)> =
+                block.phis
+                .iter()
+                .filter_map(|phi| {
+                    phi.args
+                        .iter()
+                        .find(|(from, _)| from == pred)
+                        .map(|(_, value)| (phi.dest, value.clone()))
+                })
+                // a free variable, e.g. coming in from start, has no value to copy. the phi
+                // stays unassigned on that edge, the same as the variable was before
+                .filter(|(_, value)| match value {
+                    Atm::Var { name } => defined.contains(name),
+                    Atm::Int(_) => true,
+                })
+                .collect();
+
+            let stmts = sequentialize(edge_copies, &mut taken_vars);
+
+            if cfg.successors_of(pred).len() > 1 {
+                // the copies can't go at the end of the predecessor, they would also
+                // happen when going to its other successors, so a new label is put on the edge
+                let split = fresh_name(&mut taken_labels, crate::idstr!(format!("{}.{}", pred, label)));
+
+                labels.insert(split, Block::new(stmts, Tail::Goto(*label)));
+
+                let pred_block = labels.remove(pred).unwrap();
+                labels.insert(*pred, retarget(pred_block, label, &split));
+            } else {
+                copies.entry(*pred).or_default().extend(stmts);
+            }
+        }
+    }
+
+    for (label, stmts) in copies {
+        let block = labels.remove(&label).unwrap();
+        labels.insert(label, append_stmts(block, stmts));
+    }
+
+    let mut locals: HashSet<IdString> = HashSet::new();
+
+    for block in labels.values() {
+        for stmt in &block.stmts {
+            if let Some(var) = assigned_var(stmt) {
+                locals.insert(*var);
+            }
+        }
+    }
+
+    IRProgram {
+        locals: sorted(locals),
+        labels: labels,
+    }
+}
+
+// check that the program really is in ssa form, returns a description of every problem found
+pub fn verify_ssa(program: &SsaProgram) -> Result<(), Vec<String>> {
+    let mut errors: Vec<String> = vec!();
+
+    let start = crate::idstr!("start");
+
+    if !program.blocks.contains_key(&start) {
+        return Err(vec!("entry point 'start' not found".to_owned()));
+    }
+
+    for (label, block) in &program.blocks {
+        for target in successors(&block.body.tail) {
+            if !program.blocks.contains_key(&target) {
+                errors.push(format!("{}: jump to unknown label '{}'", label, target));
+            }
+        }
+    }
+
+    let cfg = program.cfg();
+    let dom = DominatorTree::new(&cfg);
+
+    // where every variable is defined: the label and the position in it,
+    // phi nodes come before the first statement
+    let mut defs: HashMap<IdString, (IdString, usize)> = HashMap::new();
+
+    let mut define = |name: &IdString, label: &IdString, position: usize, errors: &mut Vec<String>| {
+        if let Some((other, _)) = defs.get(name) {
+            errors.push(format!("{}: '{}' is already assigned in '{}'", label, name, other));
+        } else {
+            defs.insert(*name, (*label, position));
+        }
+    };
+
+    // nothing says what comes into start from outside the program
+    if !program.blocks[&start].phis.is_empty() {
+        errors.push("start: phi at the entry of the program".to_owned());
+    }
+
+    for label in &cfg.labels {
+        let block = &program.blocks[label];
+
+        for phi in &block.phis {
+            define(&phi.dest, label, 0, &mut errors);
+        }
+
+        for (i, stmt) in block.body.stmts.iter().enumerate() {
+            if let Some(var) = assigned_var(stmt) {
+                define(var, label, i + 1, &mut errors);
+            }
+        }
+    }
+
+    // a use at 'position' in 'label' has to be dominated by the definition,
+    // variables that are never defined are free, and so come from outside the program
+    let check_use = |name: &IdString, label: &IdString, position: usize, errors: &mut Vec<String>| {
+        if let Some((def_label, def_position)) = defs.get(name) {
+            let dominated =
+                if def_label == label {
+                    *def_position < position
+                } else {
+                    dom.dominates(def_label, label)
+                };
+
+            if !dominated {
+                errors.push(format!("{}: use of '{}' is not dominated by its definition in '{}'", label, name, def_label));
+            }
+        }
+    };
+
+    for label in &cfg.labels {
+        let block = &program.blocks[label];
+        let preds = cfg.predecessors_of(label);
+
+        for phi in &block.phis {
+            let mut from: Vec<&IdString> = phi.args.iter().map(|a| &a.0).collect();
+            from.sort();
+            let mut expected: Vec<&IdString> = preds.iter().collect();
+            expected.sort();
+
+            if from != expected {
+                errors.push(format!(
+                    "{}: phi for '{}' has arguments for {:?}, but the predecessors are {:?}",
+                    label, phi.dest, from, expected
+                ));
+            }
+
+            // the value has to be available at the end of the predecessor
+            for (pred, value) in &phi.args {
+                if let Atm::Var { name } = value {
+                    check_use(name, pred, usize::MAX, &mut errors);
+                }
+            }
+        }
+
+        let stmts = &block.body.stmts;
+
+        for (i, stmt) in stmts.iter().enumerate() {
+            let mut uses = vec!();
+
+            match stmt {
+                Stmt::Assign(_, exp) => exp_uses(exp, &mut uses),
+            }
+
+            for name in uses {
+                check_use(&name, label, i + 1, &mut errors);
+            }
+        }
+
+        for name in terminator_uses(&block.body.tail) {
+            check_use(&name, label, stmts.len() + 1, &mut errors);
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,390 @@
+use crate::utility::{test_ir_helper, test_interp_ir_helper};
+
+use super::explicate::*;
+use super::ssa::*;
+
+fn var(name: &'static str) -> Atm {
+    Atm::Var { name: crate::idstr!(name) }
+}
+
+fn seq(stmts: Vec<Stmt>, end: Tail) -> Block {
+    Block::new(stmts, end)
+}
+
+fn set(name: &'static str, exp: Exp) -> Stmt {
+    Stmt::Assign(var(name), exp)
+}
+
+fn prim(op: &'static str, args: Vec<Atm>) -> Exp {
+    Exp::Prim { op: crate::idstr!(op), args: args }
+}
+
+fn read() -> Exp {
+    prim("read", vec!())
+}
+
+fn goto(label: &'static str) -> Tail {
+    Tail::Goto(crate::idstr!(label))
+}
+
+fn if_(cnd: Atm, thn: &'static str, els: &'static str) -> Tail {
+    Tail::If(cnd, crate::idstr!(thn), crate::idstr!(els))
+}
+
+fn program(labels: Vec<(&'static str, Block)>) -> IRProgram {
+    IRProgram {
+        locals: vec!(),
+        labels: labels.into_iter().map(|(l, t)| (crate::idstr!(l), t)).collect(),
+    }
+}
+
+// going into ssa and back out of it mustn't change what the program does
+fn check_round_trip(ir: IRProgram, input: Vec<&str>) -> SsaProgram {
+    let ssa = to_ssa(&ir);
+
+    assert_eq!(verify_ssa(&ssa), Ok(()));
+
+    let back = from_ssa(ssa.clone());
+
+    let (before_result, before_reads) = test_interp_ir_helper(ir, input.clone());
+    let (after_result, after_reads) = test_interp_ir_helper(back, input);
+
+    assert!(!before_result.had_error);
+    assert!(!after_result.had_error);
+
+    assert_eq!(before_result.value, after_result.value);
+    assert_eq!(before_reads, after_reads);
+
+    ssa
+}
+
+// x = 1 if the input is non-zero, 2 otherwise, then x + 10
+fn if_join_program() -> IRProgram {
+    program(vec!(
+        ("start", seq(vec!(set("c", read())), if_(var("c"), "then", "else"))),
+        ("then", seq(vec!(set("x", Exp::Atm(Atm::Int(1)))), goto("join"))),
+        ("else", seq(vec!(set("x", Exp::Atm(Atm::Int(2)))), goto("join"))),
+        ("join", Tail::Return(prim("+", vec!(var("x"), Atm::Int(10)))).into()),
+    ))
+}
+
+// sums i, i - 1, ..., 1 for an i that is read
+fn loop_program() -> IRProgram {
+    program(vec!(
+        ("start", seq(vec!(set("i", read()), set("sum", Exp::Atm(Atm::Int(0)))), goto("loop"))),
+        ("loop", if_(var("i"), "body", "done").into()),
+        ("body", seq(
+            vec!(
+                set("sum", prim("+", vec!(var("sum"), var("i")))),
+                set("i", prim("+", vec!(var("i"), Atm::Int(-1)))),
+            ),
+            goto("loop")
+        )),
+        ("done", Tail::Return(Exp::Atm(var("sum"))).into()),
+    ))
+}
+
+#[test]
+fn ssa_straight_line() {
+    let ir = test_ir_helper("(let ([x (read)]) (let ([y (read)]) (+ x (- y))))");
+
+    let ssa = check_round_trip(ir, vec!("5", "3"));
+
+    let start = &ssa.blocks[&crate::idstr!("start")];
+
+    assert!(start.phis.is_empty());
+    assert_eq!(ssa.blocks.len(), 1);
+}
+
+#[test]
+fn ssa_straight_line_reassigned() {
+    let ir = program(vec!(
+        ("start", seq(
+            vec!(
+                set("x", read()),
+                set("x", prim("+", vec!(var("x"), Atm::Int(1)))),
+                set("x", prim("-", vec!(var("x")))),
+            ),
+            Tail::Return(Exp::Atm(var("x")))
+        )),
+    ));
+
+    let ssa = check_round_trip(ir, vec!("4"));
+
+    let expected = seq(
+        vec!(
+            set("x_1", read()),
+            set("x_2", prim("+", vec!(var("x_1"), Atm::Int(1)))),
+            set("x_3", prim("-", vec!(var("x_2")))),
+        ),
+        Tail::Return(Exp::Atm(var("x_3")))
+    );
+
+    assert_eq!(ssa.blocks[&crate::idstr!("start")].body, expected);
+}
+
+#[test]
+fn ssa_if_join_places_phi() {
+    let ssa = check_round_trip(if_join_program(), vec!("1"));
+    check_round_trip(if_join_program(), vec!("0"));
+
+    let join = &ssa.blocks[&crate::idstr!("join")];
+
+    assert_eq!(join.phis.len(), 1);
+
+    // every argument is the version of x assigned in that predecessor
+    for (pred, value) in &join.phis[0].args {
+        match ssa.blocks[pred].body.stmts.first() {
+            Some(Stmt::Assign(assigned, _)) => assert_eq!(assigned, value),
+            _ => panic!("{} should assign x", pred),
+        }
+    }
+
+    assert_eq!(join.body, Tail::Return(prim("+", vec!(var("x_3"), Atm::Int(10)))).into());
+}
+
+#[test]
+fn ssa_no_phi_for_label_local_variable() {
+    // 'c' is only used in the label that assigns it, so there's nothing to join
+    let ssa = to_ssa(&if_join_program());
+
+    for block in ssa.blocks.values() {
+        assert!(block.phis.iter().all(|phi| !phi.dest.starts_with("c_")));
+    }
+}
+
+#[test]
+fn ssa_loop() {
+    let ssa = check_round_trip(loop_program(), vec!("4"));
+    check_round_trip(loop_program(), vec!("0"));
+
+    let (result, _) = test_interp_ir_helper(from_ssa(ssa.clone()), vec!("4"));
+    assert_eq!(result.value, Some(10));
+
+    // both i and sum change in the loop
+    assert_eq!(ssa.blocks[&crate::idstr!("loop")].phis.len(), 2);
+}
+
+#[test]
+fn ssa_swap_in_loop() {
+    // a and b are swapped every iteration, the phi copies on the back edge
+    // have to happen at the same time
+    let ir = program(vec!(
+        ("start", seq(
+            vec!(
+                set("n", read()),
+                set("a", Exp::Atm(Atm::Int(1))),
+                set("b", Exp::Atm(Atm::Int(2))),
+            ),
+            goto("loop")
+        )),
+        ("loop", if_(var("n"), "body", "done").into()),
+        ("body", seq(
+            vec!(
+                set("t", Exp::Atm(var("a"))),
+                set("a", Exp::Atm(var("b"))),
+                set("b", Exp::Atm(var("t"))),
+                set("n", prim("+", vec!(var("n"), Atm::Int(-1)))),
+            ),
+            goto("loop")
+        )),
+        ("done", Tail::Return(Exp::Atm(var("a"))).into()),
+    ));
+
+    check_round_trip(ir.clone(), vec!("0"));
+    check_round_trip(ir.clone(), vec!("1"));
+    check_round_trip(ir, vec!("3"));
+}
+
+#[test]
+fn ssa_critical_edge_is_split() {
+    // start -> join is a critical edge, start has two successors and join two predecessors
+    let ir = program(vec!(
+        ("start", seq(vec!(set("x", read())), if_(var("x"), "then", "join"))),
+        ("then", seq(vec!(set("x", Exp::Atm(Atm::Int(7)))), goto("join"))),
+        ("join", Tail::Return(Exp::Atm(var("x"))).into()),
+    ));
+
+    let ssa = check_round_trip(ir.clone(), vec!("3"));
+    check_round_trip(ir, vec!("0"));
+
+    let back = from_ssa(ssa);
+
+    assert!(back.labels.contains_key(&crate::idstr!("start.join")));
+}
+
+#[test]
+fn ssa_split_label_is_fresh() {
+    // the program already has a label called start.join
+    let ir = program(vec!(
+        ("start", seq(vec!(set("x", read())), if_(var("x"), "then", "join"))),
+        ("then", goto("start.join").into()),
+        ("start.join", seq(vec!(set("x", Exp::Atm(Atm::Int(7)))), goto("join"))),
+        ("join", Tail::Return(Exp::Atm(var("x"))).into()),
+    ));
+
+    let ssa = check_round_trip(ir.clone(), vec!("3"));
+    check_round_trip(ir, vec!("0"));
+
+    let back = from_ssa(ssa);
+
+    assert!(back.labels.contains_key(&crate::idstr!("start.join.1")));
+    assert_eq!(back.labels[&crate::idstr!("then")], goto("start.join").into());
+}
+
+#[test]
+fn ssa_versions_are_fresh() {
+    // x_1 is free, the first version of x can't be called that
+    let ir = program(vec!(
+        ("start", seq(
+            vec!(
+                set("x", Exp::Atm(Atm::Int(1))),
+                set("x", prim("+", vec!(var("x"), Atm::Int(1)))),
+            ),
+            Tail::Return(Exp::Prim { op: crate::idstr!("+"), args: vec!(var("x"), var("x_1")) })
+        )),
+    ));
+
+    let expected = seq(
+        vec!(
+            set("x_2", Exp::Atm(Atm::Int(1))),
+            set("x_3", prim("+", vec!(var("x_2"), Atm::Int(1)))),
+        ),
+        Tail::Return(Exp::Prim { op: crate::idstr!("+"), args: vec!(var("x_3"), var("x_1")) })
+    );
+
+    assert_eq!(to_ssa(&ir).blocks[&crate::idstr!("start")].body, expected);
+}
+
+#[test]
+fn ssa_copy_temporaries_are_fresh() {
+    let mut taken = ["a", "b", "a_copy"].iter().map(|name| crate::idstr!(*name)).collect();
+
+    // a swap has to go through temporaries
+    let stmts = sequentialize(
+        vec!((crate::idstr!("a"), var("b")), (crate::idstr!("b"), var("a"))),
+        &mut taken
+    );
+
+    assert_eq!(stmts, vec!(
+        set("a_copy.1", Exp::Atm(var("b"))),
+        set("b_copy", Exp::Atm(var("a"))),
+        set("a", Exp::Atm(var("a_copy.1"))),
+        set("b", Exp::Atm(var("b_copy"))),
+    ));
+}
+
+#[test]
+fn ssa_jump_to_start() {
+    // x is read again every time start is entered, the phi for it can't be at
+    // start, there'd be no value for the way into the program
+    let ir = program(vec!(
+        ("start", seq(vec!(set("x", read())), if_(var("x"), "start", "done"))),
+        ("done", Tail::Return(Exp::Atm(var("x"))).into()),
+    ));
+
+    let ssa = check_round_trip(ir.clone(), vec!("0"));
+    check_round_trip(ir, vec!("2", "1", "0"));
+
+    assert!(ssa.blocks[&crate::idstr!("start")].phis.is_empty());
+    assert_eq!(ssa.blocks[&crate::idstr!("start")].body, goto("start.body").into());
+}
+
+#[test]
+fn ssa_verify_phi_at_entry() {
+    let ssa = SsaProgram {
+        locals: vec!(crate::idstr!("x")),
+        blocks: crate::map!(
+            crate::idstr!("start") => SsaBlock {
+                phis: vec!(Phi { dest: crate::idstr!("x"), args: vec!((crate::idstr!("start"), Atm::Int(1))) }),
+                body: if_(var("x"), "start", "done").into()
+            },
+            crate::idstr!("done") => SsaBlock {
+                phis: vec!(),
+                body: Tail::Return(Exp::Atm(var("x"))).into()
+            }
+        ),
+    };
+
+    let errors = verify_ssa(&ssa).unwrap_err();
+
+    assert_eq!(errors, vec!("start: phi at the entry of the program".to_owned()));
+}
+
+#[test]
+fn ssa_unreachable_label_dropped() {
+    let ir = program(vec!(
+        ("start", Tail::Return(Exp::Atm(Atm::Int(1))).into()),
+        ("nowhere", seq(vec!(set("x", read())), goto("start"))),
+    ));
+
+    let ssa = check_round_trip(ir, vec!());
+
+    assert_eq!(ssa.blocks.len(), 1);
+}
+
+#[test]
+fn ssa_verify_double_assignment() {
+    let ssa = SsaProgram {
+        locals: vec!(),
+        blocks: crate::map!(
+            crate::idstr!("start") => SsaBlock {
+                phis: vec!(),
+                body: seq(
+                    vec!(set("x", read()), set("x", read())),
+                    Tail::Return(Exp::Atm(var("x")))
+                ),
+            }
+        ),
+    };
+
+    let errors = verify_ssa(&ssa).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("already assigned"));
+}
+
+#[test]
+fn ssa_verify_use_not_dominated() {
+    // x is only assigned on one of the paths to join
+    let mut ssa = to_ssa(&if_join_program());
+
+    let join = ssa.blocks.get_mut(&crate::idstr!("join")).unwrap();
+    join.phis.clear();
+    join.body = Tail::Return(Exp::Atm(var("x_1"))).into();
+
+    let errors = verify_ssa(&ssa).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("not dominated"));
+}
+
+#[test]
+fn ssa_verify_phi_arguments() {
+    let mut ssa = to_ssa(&if_join_program());
+
+    let join = ssa.blocks.get_mut(&crate::idstr!("join")).unwrap();
+    join.phis[0].args.pop();
+
+    let errors = verify_ssa(&ssa).unwrap_err();
+
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("predecessors"));
+}
+
+#[test]
+fn ssa_verify_unknown_label() {
+    let ssa = SsaProgram {
+        locals: vec!(),
+        blocks: crate::map!(
+            crate::idstr!("start") => SsaBlock {
+                phis: vec!(),
+                body: goto("missing").into(),
+            }
+        ),
+    };
+
+    let errors = verify_ssa(&ssa).unwrap_err();
+
+    assert!(errors[0].contains("unknown label 'missing'"));
+}
@@ -0,0 +1,242 @@
+/*
+    local value numbering
+
+    every value computed in a label gets a number, two expressions that apply the
+    same operator to the same numbered values compute the same value, so the second
+    one can reuse the variable holding the first
+
+        x = (+ a b)                 x = (+ a b)
+        y = (+ b a)     becomes     y = x
+        return (- y)                return (- y)
+
+    only pure primitives are numbered, every (read) produces a new value
+*/
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+
+use crate::types::{IdString};
+use crate::symbol::{Symbol, PLUS, MINUS};
+
+use super::explicate::{IRProgram, Block, Tail, Stmt, Exp, Atm};
+
+// primitives that always produce the same value for the same arguments
+const PURE_PRIMS: &[Symbol] = &[PLUS, MINUS];
+
+// operators where the order of the arguments doesn't matter
+const COMMUTATIVE_PRIMS: &[Symbol] = &[PLUS];
+
+type ValueNumber = usize;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct ExpKey {
+    op: IdString,
+    args: Vec<ValueNumber>,
+}
+
+struct ValueNumbering {
+    next: ValueNumber,
+    var_values: HashMap<IdString, ValueNumber>,
+    int_values: HashMap<i64, ValueNumber>,
+    // the value of an expression and the variable it was first stored in
+    exp_values: HashMap<ExpKey, (ValueNumber, IdString)>,
+    replaced: usize,
+}
+
+impl ValueNumbering {
+
+    fn new() -> Self {
+        ValueNumbering {
+            next: 0,
+            var_values: HashMap::new(),
+            int_values: HashMap::new(),
+            exp_values: HashMap::new(),
+            replaced: 0,
+        }
+    }
+
+    fn fresh(&mut self) -> ValueNumber {
+        let vn = self.next;
+        self.next += 1;
+
+        vn
+    }
+
+    fn atm_value(&mut self, atm: &Atm) -> ValueNumber {
+        match atm {
+            Atm::Int(n) => {
+                match self.int_values.get(n) {
+                    Some(vn) => *vn,
+                    None => {
+                        let vn = self.fresh();
+                        self.int_values.insert(*n, vn);
+                        vn
+                    }
+                }
+            },
+
+            Atm::Var { name } => {
+                match self.var_values.get(name) {
+                    Some(vn) => *vn,
+                    None => {
+                        // a variable we haven't seen assigned, i.e. defined elsewhere
+                        let vn = self.fresh();
+                        self.var_values.insert(*name, vn);
+                        vn
+                    }
+                }
+            }
+        }
+    }
+
+    fn exp_key(&mut self, op: &IdString, args: &[Atm]) -> Option<ExpKey> {
+        if !PURE_PRIMS.contains(op) {
+            return None;
+        }
+
+        let mut arg_values: Vec<ValueNumber> =
+            args.iter().map(|a| self.atm_value(a)).collect();
+
+        if COMMUTATIVE_PRIMS.contains(op) {
+            arg_values.sort();
+        }
+
+        Some(ExpKey {
+            op: *op,
+            args: arg_values,
+        })
+    }
+
+    // is there still a variable holding the value of this expression
+    fn available(&self, key: &ExpKey) -> Option<IdString> {
+        match self.exp_values.get(key) {
+            Some((vn, holder)) => {
+                // the holder might have been assigned something else since
+                if self.var_values.get(holder) == Some(vn) {
+                    Some(*holder)
+                } else {
+                    None
+                }
+            },
+
+            None => None,
+        }
+    }
+
+    // returns the (possibly replaced) expression and its value number
+    fn number_exp(&mut self, exp: Exp) -> (Exp, ValueNumber) {
+        match exp {
+            Exp::Atm(atm) => {
+                let vn = self.atm_value(&atm);
+
+                (Exp::Atm(atm), vn)
+            },
+
+            Exp::Prim { op, args } => {
+                match self.exp_key(&op, &args) {
+                    Some(key) => {
+                        if let Some(holder) = self.available(&key) {
+                            self.replaced += 1;
+
+                            let vn = self.var_values[&holder];
+
+                            (Exp::Atm(Atm::Var { name: holder }), vn)
+                        } else {
+                            let vn = match self.exp_values.get(&key) {
+                                Some((vn, _)) => *vn,
+                                None => self.fresh(),
+                            };
+
+                            (Exp::Prim { op: op, args: args }, vn)
+                        }
+                    },
+
+                    None => {
+                        let vn = self.fresh();
+
+                        (Exp::Prim { op: op, args: args }, vn)
+                    }
+                }
+            }
+        }
+    }
+
+    fn number_stmt(&mut self, stmt: Stmt) -> Stmt {
+        match stmt {
+            Stmt::Assign(Atm::Var { name }, exp) => {
+
+                let key = match &exp {
+                    Exp::Prim { op, args } => self.exp_key(op, args),
+                    _ => None,
+                };
+
+                let (new_exp, vn) = self.number_exp(exp);
+
+                self.var_values.insert(name, vn);
+
+                if let Some(key) = key {
+                    if self.available(&key).is_none() {
+                        self.exp_values.insert(key, (vn, name));
+                    }
+                }
+
+                Stmt::Assign(Atm::Var { name: name }, new_exp)
+            },
+
+            _ => {
+                stmt
+            }
+        }
+    }
+
+    fn number_block(&mut self, block: Block) -> Block {
+        let stmts = block.stmts.into_iter().map(|stmt| self.number_stmt(stmt)).collect();
+
+        Block::new(stmts, self.number_tail(block.tail))
+    }
+
+    fn number_tail(&mut self, tail: Tail) -> Tail {
+        match tail {
+            Tail::Return(exp) => {
+                let (new_exp, _) = self.number_exp(exp);
+
+                Tail::Return(new_exp)
+            },
+
+            Tail::Goto(_) | Tail::If(..) => {
+                tail
+            }
+        }
+    }
+}
+
+// returns the new program and how many expressions were replaced
+pub fn number_values_counted(program: IRProgram) -> (IRProgram, usize) {
+    let mut replaced = 0;
+
+    let mut labels = program.labels;
+
+    for block in labels.values_mut() {
+        // value numbering is local, every label starts from scratch
+        let mut numbering = ValueNumbering::new();
+
+        let old_block = std::mem::replace(block, Block::new(vec!(), Tail::Return(Exp::Atm(Atm::Int(0)))));
+
+        *block = numbering.number_block(old_block);
+
+        replaced += numbering.replaced;
+    }
+
+    let new_program =
+        IRProgram {
+            locals: program.locals,
+            labels: labels,
+        };
+
+    (new_program, replaced)
+}
+
+pub fn number_values(program: IRProgram) -> IRProgram {
+    number_values_counted(program).0
+}
@@ -0,0 +1,145 @@
+use crate::utility::{test_ir_helper, test_interp_ir_helper};
+
+use super::explicate::*;
+use super::value_numbering::*;
+
+fn var(name: &'static str) -> Atm {
+    Atm::Var { name: crate::idstr!(name) }
+}
+
+fn assign(name: &'static str, exp: Exp) -> Stmt {
+    Stmt::Assign(var(name), exp)
+}
+
+fn prim(op: &'static str, args: Vec<Atm>) -> Exp {
+    Exp::Prim { op: crate::idstr!(op), args: args }
+}
+
+fn program(block: Block) -> IRProgram {
+    IRProgram {
+        locals: vec!(),
+        labels: crate::map!(crate::idstr!("start") => block),
+    }
+}
+
+// the numbered program has to compute the same value and read the same input
+fn check_same_behaviour(before: IRProgram, after: IRProgram, input: Vec<&str>) {
+    let (before_result, before_reads) = test_interp_ir_helper(before, input.clone());
+    let (after_result, after_reads) = test_interp_ir_helper(after, input);
+
+    assert!(!before_result.had_error);
+    assert!(!after_result.had_error);
+
+    assert_eq!(before_result.value, after_result.value);
+    assert_eq!(before_reads, after_reads);
+}
+
+#[test]
+fn value_numbering_repeated_negate() {
+    let ir = test_ir_helper("(let ([x (read)]) (+ (- x) (- x)))");
+
+    let (numbered, replaced) = number_values_counted(ir.clone());
+
+    assert_eq!(replaced, 1);
+
+    let expected = program(
+        Block::new(
+            vec!(
+                assign("x.1", prim("read", vec!())),
+                assign("tmp.0", prim("-", vec!(var("x.1")))),
+                assign("tmp.1", Exp::Atm(var("tmp.0"))),
+            ),
+            Tail::Return(prim("+", vec!(var("tmp.0"), var("tmp.1"))))
+        )
+    );
+
+    assert_eq!(numbered.labels, expected.labels);
+
+    check_same_behaviour(ir, numbered, vec!("7"));
+}
+
+#[test]
+fn value_numbering_commutative_add() {
+    let before = program(
+        Block::new(
+            vec!(
+                assign("a", prim("read", vec!())),
+                assign("x", prim("+", vec!(var("a"), Atm::Int(1)))),
+                assign("y", prim("+", vec!(Atm::Int(1), var("a")))),
+            ),
+            Tail::Return(prim("+", vec!(var("x"), var("y"))))
+        )
+    );
+
+    let (after, replaced) = number_values_counted(before.clone());
+
+    assert_eq!(replaced, 1);
+
+    check_same_behaviour(before, after, vec!("5"));
+}
+
+#[test]
+fn value_numbering_through_copies() {
+    // b is a copy of a, so (- b) is the same as (- a)
+    let before = program(
+        Block::new(
+            vec!(
+                assign("a", prim("read", vec!())),
+                assign("n", prim("-", vec!(var("a")))),
+                assign("b", Exp::Atm(var("a"))),
+            ),
+            Tail::Return(prim("-", vec!(var("b"))))
+        )
+    );
+
+    let (after, replaced) = number_values_counted(before.clone());
+
+    assert_eq!(replaced, 1);
+    assert_eq!(
+        after.labels[&crate::idstr!("start")],
+        Block::new(
+            vec!(
+                assign("a", prim("read", vec!())),
+                assign("n", prim("-", vec!(var("a")))),
+                assign("b", Exp::Atm(var("a"))),
+            ),
+            Tail::Return(Exp::Atm(var("n")))
+        )
+    );
+
+    check_same_behaviour(before, after, vec!("3"));
+}
+
+#[test]
+fn value_numbering_never_merges_read() {
+    let ir = test_ir_helper("(+ (read) (read))");
+
+    let (numbered, replaced) = number_values_counted(ir.clone());
+
+    assert_eq!(replaced, 0);
+    assert_eq!(numbered, ir);
+
+    check_same_behaviour(ir, numbered, vec!("1", "2"));
+}
+
+#[test]
+fn value_numbering_reassigned_holder() {
+    // x is overwritten, so its old value can't be reused
+    let before = program(
+        Block::new(
+            vec!(
+                assign("a", prim("read", vec!())),
+                assign("x", prim("-", vec!(var("a")))),
+                assign("x", prim("read", vec!())),
+                assign("y", prim("-", vec!(var("a")))),
+            ),
+            Tail::Return(prim("+", vec!(var("x"), var("y"))))
+        )
+    );
+
+    let (after, replaced) = number_values_counted(before.clone());
+
+    assert_eq!(replaced, 0);
+
+    check_same_behaviour(before, after, vec!("4", "9"));
+}
@@ -0,0 +1,197 @@
+/*
+    check that an ir program is well formed
+
+    - there is a 'start' label and every jump goes to a label that exists
+    - only known primitives are used, with the right number of arguments
+    - every variable is assigned on every path to a use of it
+    - 'locals' are exactly the variables that get assigned
+*/
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::types::{IdString};
+use crate::frontend::verify::{KNOWN_PRIMS};
+
+use super::cfg::{Cfg, successors};
+use super::explicate::{IRProgram, Block, Tail, Stmt, Exp, Atm};
+
+fn atm_uses(atm: &Atm, uses: &mut Vec<IdString>) {
+    if let Atm::Var { name } = atm {
+        uses.push(*name);
+    }
+}
+
+fn exp_uses(exp: &Exp, uses: &mut Vec<IdString>) {
+    match exp {
+        Exp::Atm(atm) => atm_uses(atm, uses),
+        Exp::Prim { args, .. } => {
+            for arg in args {
+                atm_uses(arg, uses);
+            }
+        }
+    }
+}
+
+fn check_exp(label: &IdString, exp: &Exp, errors: &mut Vec<String>) {
+    if let Exp::Prim { op, args } = exp {
+        match KNOWN_PRIMS.iter().find(|(name, _)| *op == *name) {
+            Some((_, arity)) => {
+                if args.len() != *arity {
+                    errors.push(format!("{}: '{}' takes {} argument(s) but was given {}", label, op, arity, args.len()));
+                }
+            },
+
+            None => {
+                errors.push(format!("{}: unknown primitive '{}'", label, op));
+            }
+        }
+    }
+}
+
+// the variables assigned in the block
+fn assigned(block: &Block) -> HashSet<IdString> {
+    let mut vars = HashSet::new();
+
+    for stmt in &block.stmts {
+        if let Stmt::Assign(Atm::Var { name }, _) = stmt {
+            vars.insert(*name);
+        }
+    }
+
+    vars
+}
+
+// go through the block in order with the variables assigned on every path so far,
+// reporting uses of anything else
+fn check_block(label: &IdString, block: &Block, defined: &mut HashSet<IdString>, errors: &mut Vec<String>) {
+    let mut report = |uses: Vec<IdString>, defined: &HashSet<IdString>| {
+        for var in uses {
+            if !defined.contains(&var) {
+                errors.push(format!("{}: '{}' is used before it is assigned", label, var));
+            }
+        }
+    };
+
+    for Stmt::Assign(assignee, exp) in &block.stmts {
+        let mut uses = vec!();
+        exp_uses(exp, &mut uses);
+        report(uses, defined);
+
+        if let Atm::Var { name } = assignee {
+            defined.insert(*name);
+        }
+    }
+
+    match &block.tail {
+        Tail::Return(exp) => {
+            let mut uses = vec!();
+            exp_uses(exp, &mut uses);
+            report(uses, defined);
+        },
+
+        Tail::If(cnd, _, _) => {
+            let mut uses = vec!();
+            atm_uses(cnd, &mut uses);
+            report(uses, defined);
+        },
+
+        Tail::Goto(_) => {},
+    }
+}
+
+fn exps_of(block: &Block) -> Vec<&Exp> {
+    let mut exps: Vec<&Exp> = block.stmts.iter().map(|Stmt::Assign(_, exp)| exp).collect();
+
+    if let Tail::Return(exp) = &block.tail {
+        exps.push(exp);
+    }
+
+    exps
+}
+
+pub fn verify_ir(p: &IRProgram) -> Result<(), Vec<String>> {
+    let mut errors: Vec<String> = vec!();
+
+    let start = crate::idstr!("start");
+
+    if !p.labels.contains_key(&start) {
+        return Err(vec!("entry point 'start' not found".to_owned()));
+    }
+
+    let mut all_assigned: BTreeSet<IdString> = BTreeSet::new();
+
+    for (label, block) in &p.labels {
+        for target in successors(&block.tail) {
+            if !p.labels.contains_key(&target) {
+                errors.push(format!("{}: jump to unknown label '{}'", label, target));
+            }
+        }
+
+        for exp in exps_of(block) {
+            check_exp(label, exp, &mut errors);
+        }
+
+        all_assigned.extend(assigned(block));
+    }
+
+    let locals: BTreeSet<IdString> = p.locals.iter().cloned().collect();
+
+    if locals.len() != p.locals.len() {
+        errors.push("a variable appears in locals more than once".to_owned());
+    }
+
+    for var in all_assigned.difference(&locals) {
+        errors.push(format!("'{}' is assigned but isn't in locals", var));
+    }
+
+    for var in locals.difference(&all_assigned) {
+        errors.push(format!("'{}' is in locals but is never assigned", var));
+    }
+
+    // the variables assigned on every path into a label, the entry starts with nothing
+    let cfg = Cfg::new(start, |l| p.labels.get(l).map(|b| &b.tail));
+
+    // only looked up, the messages come from the blocks in order
+    let mut defined_in: HashMap<IdString, HashSet<IdString>> = HashMap::new();
+
+    defined_in.insert(start, HashSet::new());
+
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for label in &cfg.labels {
+            let incoming: Option<HashSet<IdString>> =
+                if *label == start {
+                    Some(HashSet::new())
+                } else {
+                    cfg.predecessors_of(label)
+                        .iter()
+                        .filter_map(|pred| {
+                            defined_in.get(pred).map(|d| d.union(&assigned(&p.labels[pred])).cloned().collect())
+                        })
+                        .reduce(|a: HashSet<IdString>, b| a.intersection(&b).cloned().collect())
+                };
+
+            if let Some(incoming) = incoming {
+                if defined_in.get(label) != Some(&incoming) {
+                    defined_in.insert(*label, incoming);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    for label in &cfg.labels {
+        let mut defined = defined_in.get(label).cloned().unwrap_or_default();
+
+        check_block(label, &p.labels[label], &mut defined, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
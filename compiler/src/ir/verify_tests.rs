@@ -0,0 +1,88 @@
+use crate::utility::{test_ir_helper};
+
+use super::explicate::*;
+use super::verify::*;
+
+fn var(name: &'static str) -> Atm {
+    Atm::Var { name: crate::idstr!(name) }
+}
+
+fn seq(stmts: Vec<Stmt>, end: Tail) -> Block {
+    Block::new(stmts, end)
+}
+
+fn set(name: &'static str, exp: Exp) -> Stmt {
+    Stmt::Assign(var(name), exp)
+}
+
+fn ret(name: &'static str) -> Tail {
+    Tail::Return(Exp::Atm(var(name)))
+}
+
+fn program(locals: Vec<&'static str>, labels: Vec<(&'static str, Block)>) -> IRProgram {
+    IRProgram {
+        locals: locals.into_iter().map(|l| crate::idstr!(l)).collect(),
+        labels: labels.into_iter().map(|(l, t)| (crate::idstr!(l), t)).collect(),
+    }
+}
+
+#[test]
+fn verify_ir_explicate_output() {
+    let p = test_ir_helper("(let ([x (read)]) (let ([y (+ x (- (read)))]) (+ y x)))");
+
+    assert_eq!(verify_ir(&p), Ok(()));
+}
+
+#[test]
+fn verify_ir_missing_start() {
+    let p = program(vec!(), vec!(("main", Tail::Return(Exp::Atm(Atm::Int(1))).into())));
+
+    assert_eq!(verify_ir(&p), Err(vec!("entry point 'start' not found".to_owned())));
+}
+
+#[test]
+fn verify_ir_unknown_label() {
+    let p = program(vec!(), vec!(("start", Tail::Goto(crate::idstr!("nowhere")).into())));
+
+    assert_eq!(verify_ir(&p), Err(vec!("start: jump to unknown label 'nowhere'".to_owned())));
+}
+
+#[test]
+fn verify_ir_use_before_assign() {
+    let p = program(
+        vec!("x", "y"),
+        vec!(("start", seq(vec!(set("y", Exp::Atm(var("x"))), set("x", Exp::Atm(Atm::Int(1)))), ret("y"))))
+    );
+
+    assert_eq!(verify_ir(&p), Err(vec!("start: 'x' is used before it is assigned".to_owned())));
+}
+
+#[test]
+fn verify_ir_assigned_on_one_branch_only() {
+    let p = program(
+        vec!("c", "x"),
+        vec!(
+            ("start", seq(vec!(set("c", Exp::Atm(Atm::Int(1)))), Tail::If(var("c"), crate::idstr!("then"), crate::idstr!("join")))),
+            ("then", seq(vec!(set("x", Exp::Atm(Atm::Int(2)))), Tail::Goto(crate::idstr!("join")))),
+            ("join", ret("x").into()),
+        )
+    );
+
+    assert_eq!(verify_ir(&p), Err(vec!("join: 'x' is used before it is assigned".to_owned())));
+}
+
+#[test]
+fn verify_ir_locals_mismatch() {
+    let p = program(
+        vec!("y"),
+        vec!(("start", seq(vec!(set("x", Exp::Atm(Atm::Int(1)))), ret("x"))))
+    );
+
+    assert_eq!(
+        verify_ir(&p),
+        Err(vec!(
+            "'x' is assigned but isn't in locals".to_owned(),
+            "'y' is in locals but is never assigned".to_owned(),
+        ))
+    );
+}
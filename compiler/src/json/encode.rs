@@ -0,0 +1,620 @@
+/*
+    json encodings of the token stream and of every program form, version 1
+
+    everything is wrapped in an envelope that says what it is:
+
+        { "format": "ast", "version": 1, "data": ... }
+
+    the format is one of "tokens", "ast", "ir" and "x64". a change to an
+    encoding that an older reader can't handle has to bump FORMAT_VERSION
+
+    tokens:  [ { "type": "lparen", "lexeme": "(", "line": 1, "col": 1 }, ... ]
+
+    ast:     { "exp": exp }
+        exp: { "kind": "int", "value": 1 }
+           | { "kind": "var", "name": "x" }
+           | { "kind": "prim", "op": "+", "args": [exp, ...] }
+           | { "kind": "let", "bindings": [{ "name": "x", "expr": exp }, ...], "body": exp }
+           | { "kind": "error", "msg": "...", "token": token }
+
+    ir:      { "locals": ["x", ...], "labels": { "start": block, ... } }
+        block: { "stmts": [{ "kind": "assign", "var": "x", "exp": exp }, ...], "tail": tail }
+        tail:  { "kind": "return", "exp": exp }
+             | { "kind": "goto", "label": "l" }
+             | { "kind": "if", "cond": atm, "then": "l", "else": "l" }
+        exp:   atm | { "kind": "prim", "op": "+", "args": [atm, ...] }
+        atm:   { "kind": "int", "value": 1 } | { "kind": "var", "name": "x" }
+
+    x64:     { "externals": ["read_int"], "vars": [home, ...], "blocks": { "start": [instr, ...] } }
+        home:  { "name": "x", "loc": loc }
+        loc:   { "kind": "reg", "reg": "rbx" }
+             | { "kind": "rbp", "offset": 8 }      (rbp - offset)
+             | { "kind": "undefined" }
+        instr: { "op": "mov", "args": [arg, arg] }  (also add, sub, cmp, neg, push, pop)
+             | { "op": "call", "func": "read_int", "arity": 0 }
+             | { "op": "jmp", "label": "l" } | { "op": "je", "label": "l" } | ...
+             | { "op": "ret" }
+        arg:   { "kind": "var", "name": "x" } | { "kind": "imm", "value": 1 }
+             | { "kind": "reg", "reg": "rax" } | { "kind": "deref", "reg": "rbp", "offset": -8 }
+
+    the labels of the ir and x64 are objects, ordered the same way as the
+    program's labels
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::types::{IdString};
+use crate::frontend::ast::{Ast, AstNode, LetBinding, NodeId, Program, MAX_NESTING};
+use crate::frontend::token::{Token, TokenType};
+use crate::ir::explicate::{Atm, Exp, Stmt, Tail, Block as IRBlock, IRProgram};
+use crate::backend::x64_def::*;
+use crate::backend::x64_text::{reg_name, reg_named, jcc_name, jcc_named};
+use crate::pipeline::{ProgramForm, IrKind};
+
+use super::Json;
+
+pub const FORMAT_VERSION: i64 = 1;
+
+const TOKEN_TYPES: &[(&str, TokenType)] = &[
+    ("number", TokenType::Number),
+    ("add", TokenType::Add),
+    ("negate", TokenType::Negate),
+    ("identifier", TokenType::Identifier),
+    ("end_of_file", TokenType::EndOfFile),
+    ("lparen", TokenType::Lparen),
+    ("rparen", TokenType::Rparen),
+    ("rbracket", TokenType::Rbracket),
+    ("lbracket", TokenType::Lbracket),
+    ("error", TokenType::Error),
+];
+
+const FORMATS: &[(&str, Option<IrKind>)] = &[
+    ("tokens", None),
+    ("ast", Some(IrKind::Ast)),
+    ("ir", Some(IrKind::Ir)),
+    ("x64", Some(IrKind::X64)),
+];
+
+fn name(s: IdString) -> Json {
+    Json::str(&s)
+}
+
+fn name_from(json: &Json) -> Result<IdString, String> {
+    Ok(crate::idstr!(json.as_str()?))
+}
+
+fn kind_of(json: &Json) -> Result<&str, String> {
+    json.get("kind")?.as_str()
+}
+
+fn unknown<T>(what: &str, kind: &str) -> Result<T, String> {
+    Err(format!("unknown {} '{}'", what, kind))
+}
+
+// tokens
+
+pub fn token_to_json(token: &Token) -> Json {
+    let ttype = TOKEN_TYPES.iter().find(|(_, t)| *t == token.ttype).unwrap().0;
+
+    Json::object(vec!(
+        ("type", Json::str(ttype)),
+        ("lexeme", Json::str(&token.lexeme)),
+        ("line", Json::Int(token.line as i64)),
+        ("col", Json::Int(token.col as i64)),
+    ))
+}
+
+pub fn token_from_json(json: &Json) -> Result<Token, String> {
+    let ttype = json.get("type")?.as_str()?;
+
+    let ttype = match TOKEN_TYPES.iter().find(|(name, _)| *name == ttype) {
+        Some((_, t)) => *t,
+        None => return unknown("token type", ttype),
+    };
+
+    Ok(Token {
+        ttype: ttype,
+        lexeme: json.get("lexeme")?.as_str()?.to_owned(),
+        line: json.get("line")?.as_i64()? as i32,
+        col: json.get("col")?.as_i64()? as i32,
+    })
+}
+
+pub fn tokens_to_json(tokens: &[Token]) -> Json {
+    Json::Array(tokens.iter().map(token_to_json).collect())
+}
+
+pub fn tokens_from_json(json: &Json) -> Result<Vec<Token>, String> {
+    json.as_array()?.iter().map(token_from_json).collect()
+}
+
+// ast
+
+pub fn exp_to_json(ast: &Ast, e: NodeId) -> Json {
+    match &ast[e] {
+        AstNode::Int(n) => Json::object(vec!(("kind", Json::str("int")), ("value", Json::Int(*n)))),
+
+        AstNode::Var { name: var } => Json::object(vec!(("kind", Json::str("var")), ("name", name(*var)))),
+
+        AstNode::Prim { op, args } => {
+            Json::object(vec!(
+                ("kind", Json::str("prim")),
+                ("op", name(*op)),
+                ("args", Json::Array(args.iter().map(|arg| exp_to_json(ast, *arg)).collect())),
+            ))
+        },
+
+        AstNode::Let { bindings, body } => {
+            let bindings =
+                bindings
+                .iter()
+                .map(|b| Json::object(vec!(("name", name(b.identifier)), ("expr", exp_to_json(ast, b.expr)))))
+                .collect();
+
+            Json::object(vec!(
+                ("kind", Json::str("let")),
+                ("bindings", Json::Array(bindings)),
+                ("body", exp_to_json(ast, *body)),
+            ))
+        },
+
+        AstNode::Error { msg, token } => {
+            Json::object(vec!(
+                ("kind", Json::str("error")),
+                ("msg", name(*msg)),
+                ("token", token_to_json(token)),
+            ))
+        },
+    }
+}
+
+pub fn exp_from_json(json: &Json, ast: &mut Ast) -> Result<NodeId, String> {
+    exp_from_json_at(json, ast, 1)
+}
+
+// 'depth' counts the expressions 'json' is in, itself included. the same limit
+// as for the source keeps the recursion here and in the passes bounded
+fn exp_from_json_at(json: &Json, ast: &mut Ast, depth: usize) -> Result<NodeId, String> {
+    if depth > MAX_NESTING {
+        return Err(format!("nested deeper than {} expressions", MAX_NESTING));
+    }
+
+    let node = match kind_of(json)? {
+        "int" => AstNode::Int(json.get("value")?.as_i64()?),
+
+        "var" => AstNode::Var { name: name_from(json.get("name")?)? },
+
+        "prim" => {
+            let mut args = vec!();
+
+            for arg in json.get("args")?.as_array()? {
+                args.push(exp_from_json_at(arg, ast, depth + 1)?);
+            }
+
+            AstNode::Prim {
+                op: name_from(json.get("op")?)?,
+                args: args,
+            }
+        },
+
+        "let" => {
+            let mut bindings = vec!();
+
+            for b in json.get("bindings")?.as_array()? {
+                bindings.push(
+                    LetBinding {
+                        identifier: name_from(b.get("name")?)?,
+                        expr: exp_from_json_at(b.get("expr")?, ast, depth + 1)?,
+                    }
+                );
+            }
+
+            AstNode::Let {
+                bindings: bindings,
+                body: exp_from_json_at(json.get("body")?, ast, depth + 1)?,
+            }
+        },
+
+        "error" => {
+            AstNode::Error {
+                msg: name_from(json.get("msg")?)?,
+                token: token_from_json(json.get("token")?)?,
+            }
+        },
+
+        kind => return unknown("ast node", kind),
+    };
+
+    Ok(ast.add(node))
+}
+
+pub fn ast_to_json(p: &Program) -> Json {
+    Json::object(vec!(("exp", exp_to_json(&p.ast, p.exp))))
+}
+
+pub fn ast_from_json(json: &Json) -> Result<Program, String> {
+    let mut ast = Ast::new();
+
+    let exp = exp_from_json(json.get("exp")?, &mut ast)?;
+
+    Ok(Program {
+        info: (),
+        ast: ast,
+        exp: exp,
+    })
+}
+
+// ir
+
+fn atm_to_json(atm: &Atm) -> Json {
+    match atm {
+        Atm::Int(n) => Json::object(vec!(("kind", Json::str("int")), ("value", Json::Int(*n)))),
+        Atm::Var { name: var } => Json::object(vec!(("kind", Json::str("var")), ("name", name(*var)))),
+    }
+}
+
+fn atm_from_json(json: &Json) -> Result<Atm, String> {
+    match kind_of(json)? {
+        "int" => Ok(Atm::Int(json.get("value")?.as_i64()?)),
+        "var" => Ok(Atm::Var { name: name_from(json.get("name")?)? }),
+        kind => unknown("atom", kind),
+    }
+}
+
+fn ir_exp_to_json(e: &Exp) -> Json {
+    match e {
+        Exp::Atm(atm) => atm_to_json(atm),
+
+        Exp::Prim { op, args } => {
+            Json::object(vec!(
+                ("kind", Json::str("prim")),
+                ("op", name(*op)),
+                ("args", Json::Array(args.iter().map(atm_to_json).collect())),
+            ))
+        },
+    }
+}
+
+fn ir_exp_from_json(json: &Json) -> Result<Exp, String> {
+    match kind_of(json)? {
+        "prim" => {
+            Ok(Exp::Prim {
+                op: name_from(json.get("op")?)?,
+                args: json.get("args")?.as_array()?.iter().map(atm_from_json).collect::<Result<_, _>>()?,
+            })
+        },
+
+        _ => Ok(Exp::Atm(atm_from_json(json)?)),
+    }
+}
+
+fn block_to_json(block: &IRBlock) -> Json {
+    let mut stmts = vec!();
+
+    for Stmt::Assign(var, exp) in &block.stmts {
+        let var = match var {
+            Atm::Var { name: var } => name(*var),
+            Atm::Int(n) => Json::Int(*n),
+        };
+
+        stmts.push(Json::object(vec!(("kind", Json::str("assign")), ("var", var), ("exp", ir_exp_to_json(exp)))));
+    }
+
+    let tail = match &block.tail {
+        Tail::Return(exp) => Json::object(vec!(("kind", Json::str("return")), ("exp", ir_exp_to_json(exp)))),
+        Tail::Goto(label) => Json::object(vec!(("kind", Json::str("goto")), ("label", name(*label)))),
+
+        Tail::If(atm, thn, els) => {
+            Json::object(vec!(
+                ("kind", Json::str("if")),
+                ("cond", atm_to_json(atm)),
+                ("then", name(*thn)),
+                ("else", name(*els)),
+            ))
+        },
+    };
+
+    Json::object(vec!(("stmts", Json::Array(stmts)), ("tail", tail)))
+}
+
+fn block_from_json(json: &Json) -> Result<IRBlock, String> {
+    let end = json.get("tail")?;
+
+    let tail = match kind_of(end)? {
+        "return" => Tail::Return(ir_exp_from_json(end.get("exp")?)?),
+        "goto" => Tail::Goto(name_from(end.get("label")?)?),
+
+        "if" => {
+            Tail::If(
+                atm_from_json(end.get("cond")?)?,
+                name_from(end.get("then")?)?,
+                name_from(end.get("else")?)?
+            )
+        },
+
+        kind => return unknown("tail", kind),
+    };
+
+    let mut stmts = vec!();
+
+    for stmt in json.get("stmts")?.as_array()? {
+        match kind_of(stmt)? {
+            "assign" => {
+                let var = Atm::Var { name: name_from(stmt.get("var")?)? };
+                let exp = ir_exp_from_json(stmt.get("exp")?)?;
+
+                stmts.push(Stmt::Assign(var, exp));
+            },
+
+            kind => return unknown("statement", kind),
+        }
+    }
+
+    Ok(IRBlock::new(stmts, tail))
+}
+
+pub fn ir_to_json(p: &IRProgram) -> Json {
+    let labels = p.labels.iter().map(|(label, block)| (label.to_string(), block_to_json(block))).collect();
+
+    Json::object(vec!(
+        ("locals", Json::Array(p.locals.iter().map(|l| name(*l)).collect())),
+        ("labels", Json::Object(labels)),
+    ))
+}
+
+pub fn ir_from_json(json: &Json) -> Result<IRProgram, String> {
+    let locals = json.get("locals")?.as_array()?.iter().map(name_from).collect::<Result<_, _>>()?;
+
+    let mut labels = BTreeMap::new();
+
+    for (label, block) in json.get("labels")?.as_object()? {
+        let block = block_from_json(block).map_err(|e| format!("{}: {}", label, e))?;
+
+        labels.insert(crate::idstr!(label), block);
+    }
+
+    Ok(IRProgram {
+        locals: locals,
+        labels: labels,
+    })
+}
+
+// x64
+
+fn reg_to_json(reg: Reg) -> Json {
+    Json::str(reg_name(reg))
+}
+
+fn reg_from_json(json: &Json) -> Result<Reg, String> {
+    let name = json.as_str()?;
+
+    match reg_named(name) {
+        Some(reg) => Ok(reg),
+        None => unknown("register", name),
+    }
+}
+
+fn arg_to_json(arg: &Arg) -> Json {
+    match arg {
+        Arg::Var(var) => Json::object(vec!(("kind", Json::str("var")), ("name", name(*var)))),
+        Arg::Imm(n) => Json::object(vec!(("kind", Json::str("imm")), ("value", Json::Int(*n)))),
+        Arg::Reg(reg) => Json::object(vec!(("kind", Json::str("reg")), ("reg", reg_to_json(*reg)))),
+
+        Arg::Deref(reg, offset) => {
+            Json::object(vec!(
+                ("kind", Json::str("deref")),
+                ("reg", reg_to_json(*reg)),
+                ("offset", Json::Int(*offset)),
+            ))
+        },
+    }
+}
+
+fn arg_from_json(json: &Json) -> Result<Arg, String> {
+    match kind_of(json)? {
+        "var" => Ok(Arg::Var(name_from(json.get("name")?)?)),
+        "imm" => Ok(Arg::Imm(json.get("value")?.as_i64()?)),
+        "reg" => Ok(Arg::Reg(reg_from_json(json.get("reg")?)?)),
+        "deref" => Ok(Arg::Deref(reg_from_json(json.get("reg")?)?, json.get("offset")?.as_i64()?)),
+        kind => unknown("operand", kind),
+    }
+}
+
+fn home_to_json(home: &Home) -> Json {
+    let loc = match home.loc {
+        VarLoc::Reg(reg) => Json::object(vec!(("kind", Json::str("reg")), ("reg", reg_to_json(reg)))),
+        VarLoc::Rbp(offset) => Json::object(vec!(("kind", Json::str("rbp")), ("offset", Json::Int(offset)))),
+        VarLoc::Undefined => Json::object(vec!(("kind", Json::str("undefined")))),
+    };
+
+    Json::object(vec!(("name", name(home.name)), ("loc", loc)))
+}
+
+fn home_from_json(json: &Json) -> Result<Home, String> {
+    let loc = json.get("loc")?;
+
+    let loc = match kind_of(loc)? {
+        "reg" => VarLoc::Reg(reg_from_json(loc.get("reg")?)?),
+        "rbp" => VarLoc::Rbp(loc.get("offset")?.as_i64()?),
+        "undefined" => VarLoc::Undefined,
+        kind => return unknown("location", kind),
+    };
+
+    Ok(Home {
+        name: name_from(json.get("name")?)?,
+        loc: loc,
+    })
+}
+
+fn instr_to_json(instr: &Instr) -> Json {
+    let op = |op: &str, args: Vec<&Arg>| {
+        Json::object(vec!(
+            ("op", Json::str(op)),
+            ("args", Json::Array(args.into_iter().map(arg_to_json).collect())),
+        ))
+    };
+
+    match instr {
+        Instr::Add64(a, b) => op("add", vec!(a, b)),
+        Instr::Sub64(a, b) => op("sub", vec!(a, b)),
+        Instr::Mov64(a, b) => op("mov", vec!(a, b)),
+        Instr::Cmp64(a, b) => op("cmp", vec!(a, b)),
+        Instr::Neg64(a) => op("neg", vec!(a)),
+        Instr::Push(a) => op("push", vec!(a)),
+        Instr::Pop(a) => op("pop", vec!(a)),
+        Instr::Ret => Json::object(vec!(("op", Json::str("ret")))),
+
+        Instr::Call(func, arity) => {
+            Json::object(vec!(
+                ("op", Json::str("call")),
+                ("func", name(*func)),
+                ("arity", Json::Int(*arity)),
+            ))
+        },
+
+        Instr::Jmp(label) => Json::object(vec!(("op", Json::str("jmp")), ("label", name(*label)))),
+        Instr::JmpIf(cc, label) => Json::object(vec!(("op", Json::str(jcc_name(*cc))), ("label", name(*label)))),
+    }
+}
+
+fn instr_from_json(json: &Json) -> Result<Instr, String> {
+    let op = json.get("op")?.as_str()?;
+
+    let args = || -> Result<Vec<Arg>, String> {
+        json.get("args")?.as_array()?.iter().map(arg_from_json).collect()
+    };
+
+    let operands = |count: usize| -> Result<Vec<Arg>, String> {
+        let args = args()?;
+
+        if args.len() != count {
+            return Err(format!("'{}' takes {} operands, found {}", op, count, args.len()));
+        }
+
+        Ok(args)
+    };
+
+    let instr = match op {
+        "add" => { let a = operands(2)?; Instr::Add64(a[0].clone(), a[1].clone()) },
+        "sub" => { let a = operands(2)?; Instr::Sub64(a[0].clone(), a[1].clone()) },
+        "mov" => { let a = operands(2)?; Instr::Mov64(a[0].clone(), a[1].clone()) },
+        "cmp" => { let a = operands(2)?; Instr::Cmp64(a[0].clone(), a[1].clone()) },
+        "neg" => Instr::Neg64(operands(1)?[0].clone()),
+        "push" => Instr::Push(operands(1)?[0].clone()),
+        "pop" => Instr::Pop(operands(1)?[0].clone()),
+        "ret" => Instr::Ret,
+        "call" => Instr::Call(name_from(json.get("func")?)?, json.get("arity")?.as_i64()?),
+        "jmp" => Instr::Jmp(name_from(json.get("label")?)?),
+
+        _ => {
+            match jcc_named(op) {
+                Some(cc) => Instr::JmpIf(cc, name_from(json.get("label")?)?),
+                None => return unknown("instruction", op),
+            }
+        },
+    };
+
+    Ok(instr)
+}
+
+pub fn x64_to_json(p: &X64Program) -> Json {
+    let blocks =
+        p.blocks
+        .iter()
+        .map(|(label, block)| (label.to_string(), Json::Array(block.instr.iter().map(instr_to_json).collect())))
+        .collect();
+
+    Json::object(vec!(
+        ("externals", Json::Array(p.external.iter().map(|e| name(*e)).collect())),
+        ("vars", Json::Array(p.vars.iter().map(home_to_json).collect())),
+        ("blocks", Json::Object(blocks)),
+    ))
+}
+
+pub fn x64_from_json(json: &Json) -> Result<X64Program, String> {
+    let external: BTreeSet<IdString> = json.get("externals")?.as_array()?.iter().map(name_from).collect::<Result<_, _>>()?;
+
+    let vars = json.get("vars")?.as_array()?.iter().map(home_from_json).collect::<Result<_, _>>()?;
+
+    let mut blocks = BTreeMap::new();
+
+    for (label, instrs) in json.get("blocks")?.as_object()? {
+        let instr =
+            instrs.as_array()?
+            .iter()
+            .map(instr_from_json)
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("{}: {}", label, e))?;
+
+        blocks.insert(crate::idstr!(label), Block { info: (), instr: instr });
+    }
+
+    Ok(X64Program {
+        external: external,
+        vars: vars,
+        blocks: blocks,
+    })
+}
+
+// the envelope
+
+fn envelope(format: &str, data: Json) -> String {
+    Json::object(vec!(
+        ("format", Json::str(format)),
+        ("version", Json::Int(FORMAT_VERSION)),
+        ("data", data),
+    )).pretty()
+}
+
+// the format and the data, if this version can read it
+fn open_envelope(text: &str) -> Result<(String, Json), String> {
+    let json = Json::parse(text)?;
+
+    let format = json.get("format")?.as_str()?.to_owned();
+
+    if !FORMATS.iter().any(|(name, _)| *name == format) {
+        return unknown("format", &format);
+    }
+
+    let version = json.get("version")?.as_i64()?;
+
+    if version != FORMAT_VERSION {
+        return Err(format!("version {} of '{}' isn't supported, only version {}", version, format, FORMAT_VERSION));
+    }
+
+    Ok((format, json.get("data")?.clone()))
+}
+
+pub fn encode_tokens(tokens: &[Token]) -> String {
+    envelope("tokens", tokens_to_json(tokens))
+}
+
+pub fn decode_tokens(text: &str) -> Result<Vec<Token>, String> {
+    match open_envelope(text)? {
+        (format, data) if format == "tokens" => tokens_from_json(&data),
+        (format, _) => Err(format!("expected tokens, found '{}'", format)),
+    }
+}
+
+pub fn encode(program: &ProgramForm) -> String {
+    match program {
+        ProgramForm::Ast(p) => envelope("ast", ast_to_json(p)),
+        ProgramForm::Ir(p) => envelope("ir", ir_to_json(p)),
+        ProgramForm::X64(p) => envelope("x64", x64_to_json(p)),
+    }
+}
+
+// a program at any stage, so the pipeline can carry on from there
+pub fn decode(text: &str) -> Result<ProgramForm, String> {
+    let (format, data) = open_envelope(text)?;
+
+    let kind = FORMATS.iter().find(|(name, _)| *name == format).unwrap().1;
+
+    // the same checks as a program read from its text form
+    match kind {
+        Some(IrKind::Ast) => ProgramForm::Ast(ast_from_json(&data)?).verified(),
+        Some(IrKind::Ir) => ProgramForm::Ir(ir_from_json(&data)?).verified(),
+        Some(IrKind::X64) => ProgramForm::X64(x64_from_json(&data)?).verified(),
+        None => Err(format!("expected a program, found '{}'", format)),
+    }
+}
@@ -0,0 +1,282 @@
+use crate::types::{OptLevel};
+use crate::frontend::lexer::{Lexer};
+use crate::fuzz::{Generator, GenConfig};
+use crate::pipeline::{PassManager, PipelineOptions, ProgramForm, IrKind};
+use crate::ir::parser::{parse_ir};
+use crate::backend::x64_text::{parse_x64};
+use crate::frontend::ast::{MAX_NESTING};
+use crate::utility::{test_parse_helper, with_compiler_stack};
+
+use super::*;
+use super::value::{MAX_DEPTH};
+use super::encode::*;
+
+#[test]
+fn json_print_and_parse() {
+    let value = Json::object(vec!(
+        ("null", Json::Null),
+        ("bools", Json::Array(vec!(Json::Bool(true), Json::Bool(false)))),
+        ("int", Json::Int(-9223372036854775808)),
+        ("text", Json::str("a \"quoted\" \\ line\n\ttab \u{1}")),
+        ("empty", Json::Object(vec!())),
+        ("nothing", Json::Array(vec!())),
+    ));
+
+    let compact = value.to_string();
+
+    assert_eq!(
+        compact,
+        r#"{"null":null,"bools":[true,false],"int":-9223372036854775808,"text":"a \"quoted\" \\ line\n\ttab \u0001","empty":{},"nothing":[]}"#
+    );
+
+    assert_eq!(Json::parse(&compact), Ok(value.clone()));
+    assert_eq!(Json::parse(&value.pretty()), Ok(value));
+
+    assert_eq!(Json::parse(r#" [ 1 , "é\/" ] "#), Ok(Json::Array(vec!(Json::Int(1), Json::str("é/")))));
+}
+
+#[test]
+fn json_pretty() {
+    let value = Json::object(vec!(("a", Json::Array(vec!(Json::Int(1), Json::object(vec!(("b", Json::Null)))))), ("c", Json::Array(vec!()))));
+
+    assert_eq!(value.pretty(), "{\n  \"a\": [\n    1,\n    {\n      \"b\": null\n    }\n  ],\n  \"c\": []\n}");
+}
+
+#[test]
+fn json_parse_errors() {
+    let error = |text: &str| Json::parse(text).unwrap_err();
+
+    assert_eq!(error("1.5"), "json at 1: only integers are supported");
+    assert_eq!(error("99999999999999999999"), "json at 20: '99999999999999999999' isn't a 64 bit integer");
+    assert_eq!(error("[1 2]"), "json at 3: expected ',' or ']'");
+    assert_eq!(error("{\"a\": 1, \"a\": 2}"), "json at 12: field 'a' appears more than once");
+    assert_eq!(error("\"open"), "json at 5: unterminated string");
+    assert_eq!(error("tru"), "json at 3: expected 'true'");
+    assert_eq!(error("{} {}"), "json at 3: expected the end of the input");
+}
+
+#[test]
+fn json_nesting_limit() {
+    with_compiler_stack(|| {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+
+        assert_eq!(
+            Json::parse(&nested(MAX_DEPTH + 1)),
+            Err(format!("json at {}: nested deeper than {} arrays and objects", MAX_DEPTH + 1, MAX_DEPTH))
+        );
+
+        // objects count as well, and the error comes before the rest is read
+        assert!(Json::parse(&"{\"a\":".repeat(MAX_DEPTH + 1)).unwrap_err().contains("nested deeper"));
+    });
+}
+
+#[test]
+fn json_ast_nesting_limit() {
+    with_compiler_stack(|| {
+        // (- (- ... 1)), written straight as json
+        let negations = |depth: usize| {
+            format!(
+                r#"{{"format": "ast", "version": 1, "data": {{"exp": {}{{"kind": "int", "value": 1}}{}}}}}"#,
+                r#"{"kind": "prim", "op": "-", "args": ["#.repeat(depth - 1),
+                "]}".repeat(depth - 1)
+            )
+        };
+
+        match decode(&negations(MAX_NESTING)) {
+            Ok(ProgramForm::Ast(p)) => assert_eq!(p.ast.depth(p.exp), MAX_NESTING),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+
+        assert_eq!(
+            decode(&negations(MAX_NESTING + 1)).map(|_| ()),
+            Err(format!("nested deeper than {} expressions", MAX_NESTING))
+        );
+
+        // what the source parser accepts is written out and read back, compact as
+        // the indentation of the pretty form grows with the depth
+        let p = test_parse_helper(&format!("{}1{}", "(- ".repeat(MAX_NESTING - 1), ")".repeat(MAX_NESTING - 1)));
+
+        let json = Json::parse(&ast_to_json(&p).to_string()).unwrap();
+
+        assert_eq!(ast_from_json(&json), Ok(p));
+    });
+}
+
+// the schema tests pin down every encoding, a change here needs a new FORMAT_VERSION
+
+#[test]
+fn json_schema_tokens() {
+    let tokens = Lexer::new("(+ x 1)").lex();
+
+    assert_eq!(
+        tokens_to_json(&tokens[..3]).to_string(),
+        concat!(
+            r#"[{"type":"lparen","lexeme":"(","line":1,"col":1},"#,
+            r#"{"type":"add","lexeme":"+","line":1,"col":2},"#,
+            r#"{"type":"identifier","lexeme":"x","line":1,"col":4}]"#,
+        )
+    );
+
+    assert_eq!(decode_tokens(&encode_tokens(&tokens)), Ok(tokens));
+}
+
+#[test]
+fn json_schema_ast() {
+    let p = test_parse_helper("(let ([x (read)]) (+ x (- 2)))");
+
+    assert_eq!(
+        ast_to_json(&p).to_string(),
+        concat!(
+            r#"{"exp":{"kind":"let","bindings":[{"name":"x","expr":{"kind":"prim","op":"read","args":[]}}],"#,
+            r#""body":{"kind":"prim","op":"+","args":[{"kind":"var","name":"x"},"#,
+            r#"{"kind":"prim","op":"-","args":[{"kind":"int","value":2}]}]}}}"#,
+        )
+    );
+
+    let error = test_parse_helper("(+ 1");
+    let json = ast_to_json(&error).to_string();
+
+    assert!(json.starts_with(r#"{"exp":{"kind":"error","msg":"#), "{}", json);
+    assert_eq!(ast_from_json(&Json::parse(&json).unwrap()), Ok(error));
+}
+
+#[test]
+fn json_schema_ir() {
+    let p = parse_ir("
+        locals: x
+        start:
+            x = (read);
+            if x goto done; else goto start;
+        done:
+            return (+ x -1);
+    ").unwrap();
+
+    assert_eq!(
+        ir_to_json(&p).to_string(),
+        concat!(
+            r#"{"locals":["x"],"labels":{"#,
+            r#""done":{"stmts":[],"tail":{"kind":"return","exp":{"kind":"prim","op":"+","args":[{"kind":"var","name":"x"},{"kind":"int","value":-1}]}}},"#,
+            r#""start":{"stmts":[{"kind":"assign","var":"x","exp":{"kind":"prim","op":"read","args":[]}}],"#,
+            r#""tail":{"kind":"if","cond":{"kind":"var","name":"x"},"then":"done","else":"start"}}}}"#,
+        )
+    );
+
+    assert_eq!(decode(&encode(&ProgramForm::Ir(p.clone()))), Ok(ProgramForm::Ir(p)));
+}
+
+#[test]
+fn json_schema_x64() {
+    let p = parse_x64("
+        externals: read_int
+        vars: x -> [rbp-8], y -> rbx, z
+        start:
+            call read_int
+            mov x, rax
+            add [rbp-16], -3
+            cmp y, 0
+            je start
+            jmp done
+        done:
+            neg z
+            ret
+    ").unwrap();
+
+    assert_eq!(
+        x64_to_json(&p).to_string(),
+        concat!(
+            r#"{"externals":["read_int"],"#,
+            r#""vars":[{"name":"x","loc":{"kind":"rbp","offset":8}},{"name":"y","loc":{"kind":"reg","reg":"rbx"}},{"name":"z","loc":{"kind":"undefined"}}],"#,
+            r#""blocks":{"done":[{"op":"neg","args":[{"kind":"var","name":"z"}]},{"op":"ret"}],"#,
+            r#""start":[{"op":"call","func":"read_int","arity":0},"#,
+            r#"{"op":"mov","args":[{"kind":"var","name":"x"},{"kind":"reg","reg":"rax"}]},"#,
+            r#"{"op":"add","args":[{"kind":"deref","reg":"rbp","offset":-16},{"kind":"imm","value":-3}]},"#,
+            r#"{"op":"cmp","args":[{"kind":"var","name":"y"},{"kind":"imm","value":0}]},"#,
+            r#"{"op":"je","label":"start"},{"op":"jmp","label":"done"}]}}"#,
+        )
+    );
+
+    assert_eq!(decode(&encode(&ProgramForm::X64(p.clone()))), Ok(ProgramForm::X64(p)));
+}
+
+#[test]
+fn json_envelope() {
+    let text = encode(&ProgramForm::Ast(test_parse_helper("(1)")));
+
+    assert!(text.starts_with("{\n  \"format\": \"ast\",\n  \"version\": 1,\n  \"data\": {"), "{}", text);
+
+    let newer = text.replace("\"version\": 1", "\"version\": 2");
+
+    assert_eq!(decode(&newer), Err("version 2 of 'ast' isn't supported, only version 1".to_owned()));
+    assert_eq!(decode(&text.replace("\"ast\"", "\"asm\"")), Err("unknown format 'asm'".to_owned()));
+    assert_eq!(decode_tokens(&text), Err("expected tokens, found 'ast'".to_owned()));
+    assert_eq!(decode(&encode_tokens(&[])), Err("expected a program, found 'tokens'".to_owned()));
+
+    let broken = text.replace("\"int\"", "\"float\"");
+
+    assert_eq!(decode(&broken), Err("unknown ast node 'float'".to_owned()));
+}
+
+#[test]
+fn json_round_trip_every_stage() {
+    for seed in 0..100 {
+        let mut program = ProgramForm::Ast(Generator::new(seed, GenConfig::default()).generate());
+
+        for name in PassManager::for_level(OptLevel::O1).pass_names() {
+            program = PassManager::custom(&[name]).unwrap().run(program);
+
+            assert_eq!(decode(&encode(&program)).as_ref(), Ok(&program), "after {}", name);
+        }
+    }
+}
+
+#[test]
+fn json_decoded_programs_are_verified() {
+    let ast = encode(&ProgramForm::Ast(test_parse_helper("(+ x 1)")));
+
+    assert_eq!(decode(&ast), Err("'x' is not defined".to_owned()));
+
+    let ir = encode(&ProgramForm::Ir(parse_ir("locals: x\nstart:\n    x = (- 1);\n    return x;").unwrap()));
+
+    // an arity the ir parser wouldn't accept
+    let wrong_arity = ir.replace("\"op\": \"-\"", "\"op\": \"+\"");
+
+    assert!(decode(&wrong_arity).unwrap_err().contains("'+' takes 2 argument(s)"), "{}", wrong_arity);
+
+    let x64 = encode(&ProgramForm::X64(parse_x64("externals:\nvars:\nstart:\n    jmp start").unwrap()));
+
+    let bad_jump = x64.replace("\"start\"", "\"done\"");
+
+    assert!(decode(&bad_jump).unwrap_err().contains("entry point 'start' not found"), "{}", bad_jump);
+}
+
+#[test]
+fn json_resume_the_pipeline() {
+    let source = test_parse_helper("(let ([x (read)] [y (read)]) (+ x (- y)))");
+
+    let straight = PassManager::for_level(OptLevel::O1).run(ProgramForm::Ast(source.clone()));
+
+    // stop at the ir, write it out, read it back and carry on
+    let mut pm = PassManager::for_level(OptLevel::O1);
+
+    let ir = pm.run_to(ProgramForm::Ast(source), IrKind::Ir);
+
+    let resumed = pm.run(decode(&encode(&ir)).unwrap());
+
+    assert_eq!(resumed, straight);
+}
+
+#[test]
+fn json_dumps() {
+    let options = PipelineOptions::from_args(&["--dump-after=explicate-control", "--dump-json"]).unwrap();
+
+    let mut pm = PassManager::new(options);
+
+    pm.run(ProgramForm::Ast(test_parse_helper("(+ 1 (read))")));
+
+    let dumps = pm.take_dumps();
+
+    assert_eq!(dumps.len(), 1);
+    assert!(matches!(decode(&dumps[0].text), Ok(ProgramForm::Ir(_))));
+}
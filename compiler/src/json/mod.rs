@@ -0,0 +1,18 @@
+/*
+    a small json value with a printer and a parser, enough for the encodings
+    of the program forms in encode
+
+    numbers are integers, the compiler has no other kind. the fields of an
+    object keep the order they were written in, so the same program always
+    gives the same text
+*/
+
+#![allow(dead_code)]
+
+#[cfg(test)]
+mod json_tests;
+
+pub mod value;
+pub mod encode;
+
+pub use value::{Json};
@@ -0,0 +1,440 @@
+/*
+    the json value, its printer and its parser
+
+    only the standard library is used here, tests/golden.rs includes this file
+    to read the report of 'compiler --run --dump-json'
+
+    the parser recurses once for every array or object it is in, so the input
+    can't nest them deeper than MAX_DEPTH
+*/
+
+#![allow(dead_code)]
+
+use std::fmt;
+
+// a level of the ast takes at most three levels of json, this leaves room for a
+// program nested as deep as the parser of the source accepts
+pub const MAX_DEPTH: usize = 16 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+
+    pub fn str(s: &str) -> Json {
+        Json::Str(s.to_owned())
+    }
+
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Bool(_) => "a bool",
+            Json::Int(_) => "a number",
+            Json::Str(_) => "a string",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Result<&Json, String> {
+        match self {
+            Json::Object(fields) => {
+                fields
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| v)
+                    .ok_or_else(|| format!("missing field '{}'", key))
+            },
+
+            other => Err(format!("expected an object with '{}', found {}", key, other.kind())),
+        }
+    }
+
+    pub fn as_i64(&self) -> Result<i64, String> {
+        match self {
+            Json::Int(n) => Ok(*n),
+            other => Err(format!("expected a number, found {}", other.kind())),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str, String> {
+        match self {
+            Json::Str(s) => Ok(s),
+            other => Err(format!("expected a string, found {}", other.kind())),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&[Json], String> {
+        match self {
+            Json::Array(items) => Ok(items),
+            other => Err(format!("expected an array, found {}", other.kind())),
+        }
+    }
+
+    pub fn as_object(&self) -> Result<&[(String, Json)], String> {
+        match self {
+            Json::Object(fields) => Ok(fields),
+            other => Err(format!("expected an object, found {}", other.kind())),
+        }
+    }
+
+    // two spaces per level, for people to read
+    pub fn pretty(&self) -> String {
+        let mut text = String::new();
+        self.write_pretty(&mut text, 0);
+        text
+    }
+
+    fn write_pretty(&self, text: &mut String, level: usize) {
+        let indent = |level: usize| "  ".repeat(level);
+
+        match self {
+            Json::Array(items) if !items.is_empty() => {
+                text.push_str("[\n");
+
+                for (i, item) in items.iter().enumerate() {
+                    text.push_str(&indent(level + 1));
+                    item.write_pretty(text, level + 1);
+                    text.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+
+                text.push_str(&indent(level));
+                text.push(']');
+            },
+
+            Json::Object(fields) if !fields.is_empty() => {
+                text.push_str("{\n");
+
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    text.push_str(&indent(level + 1));
+                    text.push_str(&format!("{}: ", Json::Str(key.clone())));
+                    value.write_pretty(text, level + 1);
+                    text.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+
+                text.push_str(&indent(level));
+                text.push('}');
+            },
+
+            other => text.push_str(&other.to_string()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser { chars: text.chars().collect(), position: 0, depth: 0 };
+
+        let value = parser.value()?;
+
+        parser.skip_whitespace();
+
+        if parser.position < parser.chars.len() {
+            return parser.error("expected the end of the input");
+        }
+
+        Ok(value)
+    }
+}
+
+// compact, on one line
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(n) => write!(f, "{}", n),
+
+            Json::Str(s) => {
+                write!(f, "\"")?;
+
+                for c in s.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+
+                write!(f, "\"")
+            },
+
+            Json::Array(items) => {
+                write!(f, "[")?;
+
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}", item)?;
+                }
+
+                write!(f, "]")
+            },
+
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+
+                    write!(f, "{}:{}", Json::Str(key.clone()), value)?;
+                }
+
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize, // the arrays and objects the parser is in
+}
+
+impl JsonParser {
+
+    fn error<T>(&self, msg: &str) -> Result<T, String> {
+        Err(format!("json at {}: {}", self.position, msg))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(c) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", c))
+        }
+    }
+
+    // called on the way into an array or an object
+    fn enter(&mut self) -> Result<(), String> {
+        if self.depth >= MAX_DEPTH {
+            return self.error(&format!("nested deeper than {} arrays and objects", MAX_DEPTH));
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for c in word.chars() {
+            if self.peek() != Some(c) {
+                return self.error(&format!("expected '{}'", word));
+            }
+
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => self.error(&format!("unexpected '{}'", c)),
+            None => self.error("unexpected end of the input"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+
+            self.position += 1;
+        }
+
+        if let Some('.') | Some('e') | Some('E') = self.peek() {
+            return self.error("only integers are supported");
+        }
+
+        let digits: String = self.chars[start..self.position].iter().collect();
+
+        match digits.parse::<i64>() {
+            Ok(n) => Ok(Json::Int(n)),
+            Err(_) => self.error(&format!("'{}' isn't a 64 bit integer", digits)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+
+        let mut s = String::new();
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return self.error("unterminated string"),
+            };
+
+            self.position += 1;
+
+            match c {
+                '"' => break,
+
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(c) => c,
+                        None => return self.error("unterminated string"),
+                    };
+
+                    self.position += 1;
+
+                    match escaped {
+                        '"' => s.push('"'),
+                        '\\' => s.push('\\'),
+                        '/' => s.push('/'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+
+                        'u' => {
+                            let hex: String = self.chars.iter().skip(self.position).take(4).collect();
+
+                            let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+
+                            match c {
+                                Some(c) if hex.len() == 4 => s.push(c),
+                                _ => return self.error(&format!("'\\u{}' isn't a character", hex)),
+                            }
+
+                            self.position += 4;
+                        },
+
+                        other => return self.error(&format!("unknown escape '\\{}'", other)),
+                    }
+                },
+
+                c => s.push(c),
+            }
+        }
+
+        Ok(s)
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        self.enter()?;
+
+        let mut items = vec!();
+
+        self.skip_whitespace();
+
+        if self.peek() == Some(']') {
+            self.position += 1;
+            self.depth -= 1;
+            return Ok(Json::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => { self.position += 1; break; },
+                _ => return self.error("expected ',' or ']'"),
+            }
+        }
+
+        self.depth -= 1;
+
+        Ok(Json::Array(items))
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        self.enter()?;
+
+        let mut fields: Vec<(String, Json)> = vec!();
+
+        self.skip_whitespace();
+
+        if self.peek() == Some('}') {
+            self.position += 1;
+            self.depth -= 1;
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+
+            let key = self.string()?;
+
+            if fields.iter().any(|(k, _)| *k == key) {
+                return self.error(&format!("field '{}' appears more than once", key));
+            }
+
+            self.expect(':')?;
+
+            let value = self.value()?;
+
+            fields.push((key, value));
+
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => { self.position += 1; break; },
+                _ => return self.error("expected ',' or '}'"),
+            }
+        }
+
+        self.depth -= 1;
+
+        Ok(Json::Object(fields))
+    }
+}
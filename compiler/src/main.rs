@@ -1,3 +1,7 @@
+// the code base spells out struct fields even when the variable has the same
+// name, e.g. Home { name: name, loc: loc }
+#![allow(clippy::redundant_field_names)]
+
 mod repl;
 mod io;
 mod backend;
@@ -5,15 +9,123 @@ mod frontend;
 mod interpreter;
 mod ir;
 mod types;
+mod symbol;
+mod pipeline;
+mod difftest;
+mod fuzz;
+mod json;
+mod snapshot;
 
 #[macro_use]
 mod utility;
 
 use repl::{Repl};
+use pipeline::{PassManager, PipelineOptions, ProgramForm};
+use backend::x64_print::{X64Printer};
+use difftest::{DiffHarness, DiffReport};
+
+// compile a source, .ir, .x86v or .json file and print the assembly
+fn compile_file(path: &str, options: PipelineOptions) -> Result<String, String> {
+    let program = ProgramForm::load(path)?;
+
+    let mut pm = PassManager::new(options);
+
+    let result = pm.try_run(program);
+
+    for dump in pm.take_dumps() {
+        println!("{}", dump);
+    }
+
+    let x64 = result.map_err(|e| e.to_string())?.into_x64();
+
+    if pm.options().time_passes {
+        println!("{}\n", pm.timings());
+    }
+
+    Ok(X64Printer::new(x64).print())
+}
+
+// run a source file at every stage of the compiler, the input for (read) comes from stdin
+fn run_file(path: &str, options: PipelineOptions) -> Result<DiffReport, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("can't read '{}': {}", path, e))?;
+
+    let input: Vec<String> = std::iter::from_fn(io::read_line).collect();
+    let input: Vec<&str> = input.iter().map(|line| line.as_str()).collect();
+
+    DiffHarness::new(options).run(&source, &input)
+}
 
 fn main() {
+    utility::with_compiler_stack(compiler_main);
+}
+
+fn compiler_main() {
+
+    // e.g. -O1 --disable-pass=partial-eval --dump-after=decomplify --dump-json --time-passes --verify --overflow=checked --fuel=1000 --max-depth=100
+    // followed by a file to compile instead of starting the repl,
+    // or --run and a source file to run it at every stage instead of compiling it,
+    // with --dump-json the report is printed as json
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let run = args.iter().any(|arg| arg == "--run");
+
+    let (files, options): (Vec<String>, Vec<String>) = args.into_iter().filter(|arg| arg != "--run").partition(|arg| !arg.starts_with('-'));
+
+    let options = match PipelineOptions::from_args(&options) {
+        Ok(options) => options,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(1);
+        }
+    };
+
+    match files.as_slice() {
+        [] => {
+            let mut repl = Repl::new().set_pipeline_options(options);
+
+            let _ = repl.run();
+        },
+
+        [path] if run => {
+            let dump_json = options.dump_json;
+
+            match run_file(path, options) {
+                Ok(report) => {
+                    if dump_json {
+                        println!("{}", report.to_json().pretty());
+                    } else {
+                        println!("{}", report);
+                    }
+
+                    if !report.agrees() {
+                        std::process::exit(1);
+                    }
+
+                    if report.inconclusive() {
+                        std::process::exit(2);
+                    }
+                },
+
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    std::process::exit(1);
+                }
+            }
+        },
 
-    let mut repl = Repl::new();
+        [path] => {
+            match compile_file(path, options) {
+                Ok(asm) => print!("{}", asm),
+                Err(msg) => {
+                    eprintln!("{}", msg);
+                    std::process::exit(1);
+                }
+            }
+        },
 
-    let _ = repl.run();
-}
\ No newline at end of file
+        _ => {
+            eprintln!("one file at a time");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,692 @@
+/*
+    the passes of the compiler and the pass manager that runs them
+
+    every pass has a name, the kind of program it takes and the kind it produces:
+
+        ast:  uniquify -> partial-eval -> dead-bindings -> decomplify
+                -> explicate-control
+        ir:   value-numbering -> copy-propagation
+                -> select-instructions
+        x64:  assign-homes -> patch-instructions -> peephole -> prologue-epilogue
+
+    the optimization level decides which of the optional passes run: value-numbering
+    and peephole from -O1 on, copy-propagation at -O2. any optional pass can be
+    turned off by name. the program can be dumped before or after a pass,
+    and every pass is timed
+
+    with verify on, the output of every pass is checked against what the next pass
+    expects, so a broken pass is caught where it happens instead of somewhere later
+*/
+
+#![allow(dead_code)]
+
+#[cfg(test)]
+mod pipeline_tests;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::types::{OptLevel, Overflow};
+use crate::interpreter::{Limits};
+
+use crate::frontend::ast::{Program};
+use crate::frontend::lexer::{Lexer};
+use crate::frontend::parser::{Parser};
+use crate::frontend::uniquify::{uniquify_program};
+use crate::frontend::partial_eval::{partially_evaluate};
+use crate::frontend::dead_bindings::{eliminate_dead_bindings};
+use crate::frontend::decomplify::{decomplify_program};
+use crate::frontend::verify::{verify_ast, verify_uniquified, verify_monadic};
+use crate::ir::explicate::{explicate_control, IRProgram};
+use crate::ir::value_numbering::{number_values};
+use crate::ir::copy_propagation::{propagate_copies};
+use crate::ir::verify::{verify_ir};
+use crate::ir::parser::{parse_ir};
+use crate::backend::x64_def::{X64Program};
+use crate::backend::x64_verify::{verify_x64, verify_x64_patched};
+use crate::backend::x64_text::{parse_x64};
+use crate::json::encode::{encode, decode};
+use crate::backend::x64_peephole::{PeepholeStats};
+use crate::backend::x64_backend::{
+    IRToX64Transformer,
+    assign_homes,
+    patch_instructions,
+    peephole,
+    add_prologue_epilogue,
+};
+
+// the different forms the program goes through, in pipeline order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IrKind {
+    Ast,
+    Ir,
+    X64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProgramForm {
+    Ast(Program),
+    Ir(IRProgram),
+    X64(X64Program),
+}
+
+impl ProgramForm {
+
+    pub fn kind(&self) -> IrKind {
+        match self {
+            ProgramForm::Ast(_) => IrKind::Ast,
+            ProgramForm::Ir(_) => IrKind::Ir,
+            ProgramForm::X64(_) => IrKind::X64,
+        }
+    }
+
+    pub fn dump(&self) -> String {
+        match self {
+            ProgramForm::Ast(p) => format!("{:#?}", p),
+            ProgramForm::Ir(p) => format!("{:#?}", p),
+            ProgramForm::X64(p) => format!("{:#?}", p),
+        }
+    }
+
+    // a program written in the text form of 'kind'
+    pub fn parse(text: &str, kind: IrKind) -> Result<ProgramForm, String> {
+        match kind {
+            IrKind::Ast => {
+                let mut parser = Parser::new(Lexer::new(text).lex());
+
+                let program = parser.parse();
+
+                if !parser.parse_success() {
+                    let errors: Vec<String> = parser.errors().iter().map(|e| format!("{:?}", e)).collect();
+
+                    return Err(errors.join("\n"));
+                }
+
+                Ok(ProgramForm::Ast(program))
+            },
+
+            IrKind::Ir => ProgramForm::Ir(parse_ir(text)?).verified(),
+            IrKind::X64 => ProgramForm::X64(parse_x64(text)?).verified(),
+        }
+    }
+
+    // a program that didn't come out of a pass has to be checked before the passes
+    // see it, they take well formed input for granted. source that was parsed can
+    // still use the definitions of the repl, so its variables aren't checked
+    pub fn verified(self) -> Result<ProgramForm, String> {
+        let checked = match &self {
+            ProgramForm::Ast(p) => verify_ast(p),
+            ProgramForm::Ir(p) => verify_ir(p),
+            ProgramForm::X64(p) => verify_x64(p),
+        };
+
+        match checked {
+            Ok(()) => Ok(self),
+            Err(errors) => Err(errors.join("\n")),
+        }
+    }
+
+    // the extension says which form the file is in: .ir for the ir, .x86v for
+    // pseudo-x86, .json for any form in its json encoding and anything else is source
+    pub fn load(path: &str) -> Result<ProgramForm, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+
+        if path.ends_with(".json") {
+            return decode(&text).map_err(|e| format!("{}: {}", path, e));
+        }
+
+        let kind =
+            if path.ends_with(".ir") {
+                IrKind::Ir
+            } else if path.ends_with(".x86v") {
+                IrKind::X64
+            } else {
+                IrKind::Ast
+            };
+
+        ProgramForm::parse(&text, kind).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // in the text form of each stage: source, the ir notation or pseudo-x86
+    pub fn print(&self) -> String {
+        match self {
+            ProgramForm::Ast(p) => p.to_string(),
+            ProgramForm::Ir(p) => p.to_string(),
+            ProgramForm::X64(p) => p.to_string(),
+        }
+    }
+
+    pub fn into_ast(self) -> Program {
+        match self {
+            ProgramForm::Ast(p) => p,
+            other => panic!("expected an ast program, got {:?}", other.kind()),
+        }
+    }
+
+    pub fn into_ir(self) -> IRProgram {
+        match self {
+            ProgramForm::Ir(p) => p,
+            other => panic!("expected an ir program, got {:?}", other.kind()),
+        }
+    }
+
+    pub fn into_x64(self) -> X64Program {
+        match self {
+            ProgramForm::X64(p) => p,
+            other => panic!("expected an x64 program, got {:?}", other.kind()),
+        }
+    }
+}
+
+// what the passes can share while the pipeline runs
+pub struct PassContext {
+    pub opt_level: OptLevel,
+    pub overflow: Overflow,
+    pub peephole_stats: PeepholeStats,
+}
+
+pub type RunFn = fn(ProgramForm, &mut PassContext) -> ProgramForm;
+
+// checks the output of a pass, returns everything that's wrong with it
+pub type VerifyFn = fn(&ProgramForm) -> Result<(), Vec<String>>;
+
+pub struct Pass {
+    pub name: &'static str,
+    pub input: IrKind,
+    pub output: IrKind,
+    // the lowest optimization level the pass runs at
+    pub level: OptLevel,
+    // later passes depend on it, so it can't be disabled
+    pub required: bool,
+    pub run: RunFn,
+    pub verify: VerifyFn,
+}
+
+fn run_uniquify(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ast(uniquify_program(p.into_ast()))
+}
+
+fn run_partial_eval(p: ProgramForm, ctx: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ast(partially_evaluate(p.into_ast(), ctx.overflow))
+}
+
+fn run_dead_bindings(p: ProgramForm, ctx: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ast(eliminate_dead_bindings(p.into_ast(), ctx.overflow))
+}
+
+fn run_decomplify(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ast(decomplify_program(p.into_ast()))
+}
+
+fn run_explicate_control(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ir(explicate_control(p.into_ast()))
+}
+
+fn run_value_numbering(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ir(number_values(p.into_ir()))
+}
+
+fn run_copy_propagation(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::Ir(propagate_copies(&p.into_ir()))
+}
+
+fn run_select_instructions(p: ProgramForm, ctx: &mut PassContext) -> ProgramForm {
+    ProgramForm::X64(IRToX64Transformer::new(p.into_ir()).set_overflow(ctx.overflow).select_instructions())
+}
+
+fn run_assign_homes(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::X64(assign_homes(p.into_x64()))
+}
+
+fn run_patch_instructions(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::X64(patch_instructions(p.into_x64()))
+}
+
+fn run_peephole(p: ProgramForm, ctx: &mut PassContext) -> ProgramForm {
+    ProgramForm::X64(peephole(p.into_x64(), &mut ctx.peephole_stats))
+}
+
+fn run_prologue_epilogue(p: ProgramForm, _: &mut PassContext) -> ProgramForm {
+    ProgramForm::X64(add_prologue_epilogue(p.into_x64()))
+}
+
+fn check_uniquified(p: &ProgramForm) -> Result<(), Vec<String>> {
+    match p {
+        ProgramForm::Ast(p) => verify_uniquified(p),
+        other => Err(vec!(format!("expected an ast program, got {:?}", other.kind()))),
+    }
+}
+
+fn check_monadic(p: &ProgramForm) -> Result<(), Vec<String>> {
+    match p {
+        ProgramForm::Ast(p) => verify_monadic(p),
+        other => Err(vec!(format!("expected an ast program, got {:?}", other.kind()))),
+    }
+}
+
+fn check_ir(p: &ProgramForm) -> Result<(), Vec<String>> {
+    match p {
+        ProgramForm::Ir(p) => verify_ir(p),
+        other => Err(vec!(format!("expected an ir program, got {:?}", other.kind()))),
+    }
+}
+
+fn check_x64(p: &ProgramForm) -> Result<(), Vec<String>> {
+    match p {
+        ProgramForm::X64(p) => verify_x64(p),
+        other => Err(vec!(format!("expected an x64 program, got {:?}", other.kind()))),
+    }
+}
+
+fn check_x64_patched(p: &ProgramForm) -> Result<(), Vec<String>> {
+    match p {
+        ProgramForm::X64(p) => verify_x64_patched(p),
+        other => Err(vec!(format!("expected an x64 program, got {:?}", other.kind()))),
+    }
+}
+
+// every pass, in the order they run
+pub const PASSES: &[Pass] = &[
+    Pass { name: "uniquify", input: IrKind::Ast, output: IrKind::Ast, level: OptLevel::O0, required: true, run: run_uniquify, verify: check_uniquified },
+    Pass { name: "partial-eval", input: IrKind::Ast, output: IrKind::Ast, level: OptLevel::O0, required: false, run: run_partial_eval, verify: check_uniquified },
+    Pass { name: "dead-bindings", input: IrKind::Ast, output: IrKind::Ast, level: OptLevel::O0, required: false, run: run_dead_bindings, verify: check_uniquified },
+    Pass { name: "decomplify", input: IrKind::Ast, output: IrKind::Ast, level: OptLevel::O0, required: true, run: run_decomplify, verify: check_monadic },
+    Pass { name: "explicate-control", input: IrKind::Ast, output: IrKind::Ir, level: OptLevel::O0, required: true, run: run_explicate_control, verify: check_ir },
+    Pass { name: "value-numbering", input: IrKind::Ir, output: IrKind::Ir, level: OptLevel::O1, required: false, run: run_value_numbering, verify: check_ir },
+    Pass { name: "copy-propagation", input: IrKind::Ir, output: IrKind::Ir, level: OptLevel::O2, required: false, run: run_copy_propagation, verify: check_ir },
+    Pass { name: "select-instructions", input: IrKind::Ir, output: IrKind::X64, level: OptLevel::O0, required: true, run: run_select_instructions, verify: check_x64 },
+    Pass { name: "assign-homes", input: IrKind::X64, output: IrKind::X64, level: OptLevel::O0, required: true, run: run_assign_homes, verify: check_x64 },
+    Pass { name: "patch-instructions", input: IrKind::X64, output: IrKind::X64, level: OptLevel::O0, required: true, run: run_patch_instructions, verify: check_x64_patched },
+    Pass { name: "peephole", input: IrKind::X64, output: IrKind::X64, level: OptLevel::O1, required: false, run: run_peephole, verify: check_x64_patched },
+    Pass { name: "prologue-epilogue", input: IrKind::X64, output: IrKind::X64, level: OptLevel::O0, required: true, run: run_prologue_epilogue, verify: check_x64_patched },
+];
+
+pub fn find_pass(name: &str) -> Option<&'static Pass> {
+    PASSES.iter().find(|pass| pass.name == name)
+}
+
+fn known_pass(name: &str) -> Result<&'static Pass, String> {
+    match find_pass(name) {
+        Some(pass) => Ok(pass),
+        None => Err(format!("unknown pass '{}'", name)),
+    }
+}
+
+// how the pipeline should be set up, e.g. from the command line
+#[derive(Clone, Debug, PartialEq)]
+pub struct PipelineOptions {
+    pub opt_level: OptLevel,
+    pub disabled: Vec<String>,
+    pub dump_before: Vec<String>,
+    pub dump_after: Vec<String>,
+    pub time_passes: bool,
+    pub verify: bool,
+    pub dump_json: bool, // dumps in the json encoding instead of the debug format
+    pub overflow: Overflow,
+    pub limits: Limits, // for running the program, at the repl or with --run
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions {
+            opt_level: OptLevel::O0,
+            disabled: vec!(),
+            dump_before: vec!(),
+            dump_after: vec!(),
+            time_passes: false,
+            verify: false,
+            dump_json: false,
+            overflow: Overflow::Wrap,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl PipelineOptions {
+
+    // returns Ok(false) if the argument isn't a pipeline option
+    pub fn parse_arg(&mut self, arg: &str) -> Result<bool, String> {
+        let pass_names = |value: &str| -> Result<Vec<String>, String> {
+            let mut names = vec!();
+
+            for name in value.split(',') {
+                known_pass(name)?;
+                names.push(name.to_owned());
+            }
+
+            Ok(names)
+        };
+
+        if let Some(value) = arg.strip_prefix("--disable-pass=") {
+            for name in pass_names(value)? {
+                if known_pass(&name)?.required {
+                    return Err(format!("pass '{}' can't be disabled", name));
+                }
+
+                self.disabled.push(name);
+            }
+        } else if let Some(value) = arg.strip_prefix("--dump-before=") {
+            self.dump_before.extend(pass_names(value)?);
+        } else if let Some(value) = arg.strip_prefix("--dump-after=") {
+            self.dump_after.extend(pass_names(value)?);
+        } else if arg == "--time-passes" {
+            self.time_passes = true;
+        } else if arg == "--verify" {
+            self.verify = true;
+        } else if arg == "--dump-json" {
+            self.dump_json = true;
+        } else if let Some(value) = arg.strip_prefix("--overflow=") {
+            self.overflow = match value {
+                "wrap" => Overflow::Wrap,
+                "checked" => Overflow::Checked,
+                _ => return Err(format!("--overflow is 'wrap' or 'checked', not '{}'", value)),
+            };
+        } else if let Some(value) = arg.strip_prefix("--fuel=") {
+            self.limits.fuel = value.parse().map_err(|_| format!("--fuel is a number of steps, not '{}'", value))?;
+        } else if let Some(value) = arg.strip_prefix("--max-depth=") {
+            self.limits.max_depth = value.parse().map_err(|_| format!("--max-depth is a number, not '{}'", value))?;
+        } else {
+            match arg {
+                "-O0" => self.opt_level = OptLevel::O0,
+                "-O1" => self.opt_level = OptLevel::O1,
+                "-O2" => self.opt_level = OptLevel::O2,
+                _ => return Ok(false),
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn from_args<S: AsRef<str>>(args: &[S]) -> Result<PipelineOptions, String> {
+        let mut options = PipelineOptions::default();
+
+        for arg in args {
+            if !options.parse_arg(arg.as_ref())? {
+                return Err(format!("unknown option '{}'", arg.as_ref()));
+            }
+        }
+
+        options.check_dumps()?;
+
+        Ok(options)
+    }
+
+    // a dump of a pass that doesn't run would never show up, e.g. --dump-after=peephole with -O0
+    pub fn check_dumps(&self) -> Result<(), String> {
+        let running = PassManager::new(self.clone()).pass_names();
+
+        for name in self.dump_before.iter().chain(&self.dump_after) {
+            if running.contains(&name.as_str()) {
+                continue;
+            }
+
+            if self.disabled.contains(name) {
+                return Err(format!("pass '{}' is disabled, it can't be dumped", name));
+            }
+
+            return Err(format!("pass '{}' doesn't run at -{:?}, it can't be dumped", name, self.opt_level));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpPoint {
+    Before,
+    After,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassDump {
+    pub pass: &'static str,
+    pub point: DumpPoint,
+    pub text: String,
+}
+
+impl fmt::Display for PassDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let point = match self.point {
+            DumpPoint::Before => "before",
+            DumpPoint::After => "after",
+        };
+
+        writeln!(f, "--- {} {} ---", point, self.pass)?;
+        writeln!(f, "{}", self.text)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PassTimings {
+    pub timings: Vec<(&'static str, Duration)>,
+}
+
+impl PassTimings {
+    pub fn total(&self) -> Duration {
+        self.timings.iter().map(|t| t.1).sum()
+    }
+}
+
+impl fmt::Display for PassTimings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, duration) in &self.timings {
+            writeln!(f, "{:>20}: {:?}", name, duration)?;
+        }
+
+        write!(f, "{:>20}: {:?}", "total", self.total())
+    }
+}
+
+// a pass produced something the passes after it can't handle
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassError {
+    pub pass: &'static str,
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pass '{}' broke the contract:", self.pass)?;
+
+        for error in &self.errors {
+            write!(f, "\n    {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PassManager {
+    passes: Vec<&'static Pass>,
+    next: usize, // the next pass to run
+    options: PipelineOptions,
+    ctx: PassContext,
+    dumps: Vec<PassDump>,
+    timings: PassTimings,
+}
+
+impl PassManager {
+
+    // the passes for the options' optimization level, minus the disabled ones
+    pub fn new(options: PipelineOptions) -> PassManager {
+        let passes =
+            PASSES
+            .iter()
+            .filter(|pass| pass.level <= options.opt_level)
+            .filter(|pass| !options.disabled.iter().any(|d| d == pass.name))
+            .collect();
+
+        PassManager::with_passes(passes, options)
+    }
+
+    pub fn for_level(opt_level: OptLevel) -> PassManager {
+        PassManager::new(
+            PipelineOptions {
+                opt_level: opt_level,
+                ..PipelineOptions::default()
+            }
+        )
+    }
+
+    // exactly the given passes, in the given order
+    pub fn custom(names: &[&str]) -> Result<PassManager, String> {
+        let mut passes: Vec<&'static Pass> = vec!();
+
+        for name in names {
+            let pass = known_pass(name)?;
+
+            if let Some(previous) = passes.last() {
+                if previous.output != pass.input {
+                    return Err(format!(
+                        "pass '{}' takes {:?} but '{}' produces {:?}",
+                        pass.name, pass.input, previous.name, previous.output
+                    ));
+                }
+            }
+
+            passes.push(pass);
+        }
+
+        Ok(PassManager::with_passes(passes, PipelineOptions::default()))
+    }
+
+    fn with_passes(passes: Vec<&'static Pass>, options: PipelineOptions) -> PassManager {
+        PassManager {
+            passes: passes,
+            next: 0,
+            ctx: PassContext {
+                opt_level: options.opt_level,
+                overflow: options.overflow,
+                peephole_stats: PeepholeStats::new(),
+            },
+            options: options,
+            dumps: vec!(),
+            timings: PassTimings::default(),
+        }
+    }
+
+    pub fn set_verify(mut self, verify: bool) -> Self {
+        self.options.verify = verify;
+        self
+    }
+
+    pub fn set_overflow(mut self, overflow: Overflow) -> Self {
+        self.options.overflow = overflow;
+        self.ctx.overflow = overflow;
+        self
+    }
+
+    pub fn pass_names(&self) -> Vec<&'static str> {
+        self.passes.iter().map(|pass| pass.name).collect()
+    }
+
+    pub fn options(&self) -> &PipelineOptions {
+        &self.options
+    }
+
+    pub fn timings(&self) -> &PassTimings {
+        &self.timings
+    }
+
+    pub fn peephole_stats(&self) -> &PeepholeStats {
+        &self.ctx.peephole_stats
+    }
+
+    // the dumps requested with dump_before/dump_after since the last call
+    pub fn take_dumps(&mut self) -> Vec<PassDump> {
+        std::mem::take(&mut self.dumps)
+    }
+
+    fn dump(&mut self, pass: &'static Pass, point: DumpPoint, program: &ProgramForm) {
+        let wanted = match point {
+            DumpPoint::Before => &self.options.dump_before,
+            DumpPoint::After => &self.options.dump_after,
+        };
+
+        if wanted.iter().any(|name| name == pass.name) {
+            self.dumps.push(
+                PassDump {
+                    pass: pass.name,
+                    point: point,
+                    text: if self.options.dump_json { encode(program) } else { program.dump() },
+                }
+            );
+        }
+    }
+
+    // the pass try_step runs next, None once they've all run
+    pub fn next_pass(&self) -> Option<&'static Pass> {
+        self.passes.get(self.next).cloned()
+    }
+
+    // run just the next pass. a pass the program is already past is skipped,
+    // e.g. select-instructions when the program given was already x64
+    pub fn try_step(&mut self, program: ProgramForm) -> Result<ProgramForm, PassError> {
+        let pass = match self.next_pass() {
+            Some(pass) => pass,
+            None => return Ok(program),
+        };
+
+        self.next += 1;
+
+        if pass.input != program.kind() {
+            if pass.input < program.kind() {
+                return Ok(program);
+            }
+
+            panic!("pass '{}' takes {:?} but got {:?}", pass.name, pass.input, program.kind());
+        }
+
+        self.dump(pass, DumpPoint::Before, &program);
+
+        let start = Instant::now();
+
+        let program = (pass.run)(program, &mut self.ctx);
+
+        self.timings.timings.push((pass.name, start.elapsed()));
+
+        self.dump(pass, DumpPoint::After, &program);
+
+        if self.options.verify {
+            if let Err(errors) = (pass.verify)(&program) {
+                return Err(PassError { pass: pass.name, errors: errors });
+            }
+        }
+
+        Ok(program)
+    }
+
+    // run the passes that produce at most 'until', i.e. run_to(p, IrKind::Ir) stops
+    // before the first pass that produces x64. a later call carries on from there,
+    // so the program can be looked at between the stages
+    // with verify on, stops at the first pass whose output doesn't verify
+    pub fn try_run_to(&mut self, program: ProgramForm, until: IrKind) -> Result<ProgramForm, PassError> {
+        let mut program = program;
+
+        while let Some(pass) = self.next_pass() {
+            if pass.output > until {
+                break;
+            }
+
+            program = self.try_step(program)?;
+        }
+
+        Ok(program)
+    }
+
+    // same as try_run_to, for when the program is known to be fine
+    pub fn run_to(&mut self, program: ProgramForm, until: IrKind) -> ProgramForm {
+        match self.try_run_to(program, until) {
+            Ok(program) => program,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn run(&mut self, program: ProgramForm) -> ProgramForm {
+        self.run_to(program, IrKind::X64)
+    }
+
+    pub fn try_run(&mut self, program: ProgramForm) -> Result<ProgramForm, PassError> {
+        self.try_run_to(program, IrKind::X64)
+    }
+}
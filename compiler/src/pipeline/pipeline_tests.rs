@@ -0,0 +1,441 @@
+use crate::utility::{test_ast_helper, test_parse_helper, test_interp_ir_helper, with_compiler_stack, AstStep};
+use crate::ir::explicate::{explicate_control};
+use crate::types::{OptLevel};
+
+use super::*;
+use crate::ir::explicate::{Tail, Exp};
+use crate::backend::x64_def::{X64Program};
+use crate::frontend::ast::{AstNode, MAX_NESTING};
+
+// (let ([v0 (read)]) (let ([v1 (+ v0 (read))]) ... v<n-1>))
+fn nested_lets(n: usize) -> &'static str {
+    let mut src = String::from("(let ([v0 (read)]) ");
+
+    for i in 1..n {
+        src += &format!("(let ([v{} (+ v{} (read))]) ", i, i - 1);
+    }
+
+    src += &format!("v{}", n - 1);
+    src += &")".repeat(n);
+
+    Box::leak(src.into_boxed_str())
+}
+
+// (+ (read) (+ (- (read)) (+ (read) ... 0)))
+fn nested_sums(n: usize) -> &'static str {
+    let mut src = String::new();
+
+    for i in 0..n {
+        if i % 2 == 0 {
+            src += "(+ (read) ";
+        } else {
+            src += "(+ (- (read)) ";
+        }
+    }
+
+    src += "0";
+    src += &")".repeat(n);
+
+    Box::leak(src.into_boxed_str())
+}
+
+fn time_pipeline(name: &str, src: &str) {
+    let mut pm = PassManager::for_level(OptLevel::O1);
+
+    pm.run(ProgramForm::Ast(test_parse_helper(src)));
+
+    println!("{}:\n{}", name, pm.timings());
+}
+
+// the innermost let of nested_lets(n) is n deep and the (read) in its binding
+// n + 2, nested_sums(n) is n + 2 deep as well
+const DEEPEST: usize = MAX_NESTING - 2;
+
+// cargo test --release pipeline_deeply_nested -- --ignored --nocapture
+#[test]
+#[ignore]
+fn pipeline_deeply_nested() {
+    with_compiler_stack(|| {
+        time_pipeline("2000 nested lets", nested_lets(2000));
+        time_pipeline("2000 nested sums", nested_sums(2000));
+        time_pipeline("10000 bindings in one let", &wide_let(10000));
+    });
+}
+
+// as deep as the parser allows
+#[test]
+fn pipeline_nested_keeps_meaning() {
+    with_compiler_stack(nested_keeps_meaning);
+}
+
+fn nested_keeps_meaning() {
+    let n = DEEPEST;
+
+    let input: Vec<String> = (1..=n).map(|i| i.to_string()).collect();
+    let input: Vec<&str> = input.iter().map(|s| &s[..]).collect();
+
+    let p = test_ast_helper(nested_lets(n), vec!());
+
+    assert_eq!(p.ast.depth(p.exp), MAX_NESTING);
+
+    let (expected, _) = crate::utility::test_interp_ast_helper(p, input.clone());
+
+    let p = test_ast_helper(nested_lets(n), vec!(AstStep::Uniquify, AstStep::PartialEvaluation, AstStep::DeadBindings, AstStep::Decomplify));
+    let (result, _) = crate::utility::test_interp_ir_helper(explicate_control(p), input.clone());
+
+    assert_eq!(expected.value, Some((n * (n + 1) / 2) as i64));
+    assert_eq!(result.value, expected.value);
+
+    let x64 = PassManager::for_level(OptLevel::O2).run(ProgramForm::Ast(test_parse_helper(nested_lets(n)))).into_x64();
+
+    assert_eq!(crate::backend::x64_emulator::emulate(&x64, input), Ok(expected.value.unwrap()));
+}
+
+// (let ([v0 (read)] [v1 (+ v0 1)] ... ) v<n-1>), flat but long
+fn wide_let(n: usize) -> String {
+    let bindings: Vec<String> = (1..n).map(|i| format!("[v{} (+ v{} 1)]", i, i - 1)).collect();
+
+    format!("(let ([v0 (read)] {}) v{})", bindings.join(" "), n - 1)
+}
+
+// a long block is walked with loops, it doesn't use up the stack however long it is
+#[test]
+fn pipeline_long_programs() {
+    let x64 = PassManager::for_level(OptLevel::O2).run(ProgramForm::Ast(test_parse_helper(&wide_let(10000)))).into_x64();
+
+    assert_eq!(crate::backend::x64_emulator::emulate(&x64, vec!("5")), Ok(10004));
+
+    let mut text = String::from("locals: x\nstart:\n    x = (read);\n");
+    text += &"    x = (+ x 1);\n".repeat(5000);
+    text += "    return x;\n";
+
+    let ir = ProgramForm::parse(&text, IrKind::Ir).unwrap().into_ir();
+    let (result, _) = test_interp_ir_helper(ir.clone(), vec!("5"));
+
+    assert_eq!(result.value, Some(5005));
+
+    let x64 = PassManager::for_level(OptLevel::O2).run(ProgramForm::Ir(ir)).into_x64();
+
+    assert_eq!(crate::backend::x64_emulator::emulate(&x64, vec!("5")), Ok(5005));
+}
+
+#[test]
+fn pipeline_too_deep_is_an_error() {
+    let p = with_compiler_stack(|| test_parse_helper(nested_lets(MAX_NESTING)));
+
+    match p.root() {
+        AstNode::Error { msg, .. } => assert_eq!(msg.to_string(), format!("Nested deeper than {} expressions", MAX_NESTING)),
+        other => panic!("{:?}", other),
+    }
+}
+
+fn compile(options: PipelineOptions, src: &str) -> (X64Program, PassManager) {
+    let mut pm = PassManager::new(options);
+
+    let x64 = pm.run(ProgramForm::Ast(test_parse_helper(src))).into_x64();
+
+    (x64, pm)
+}
+
+#[test]
+fn pipeline_levels() {
+    let o0 = PassManager::for_level(OptLevel::O0).pass_names();
+    let o1 = PassManager::for_level(OptLevel::O1).pass_names();
+
+    assert!(!o0.contains(&"value-numbering"));
+    assert!(!o0.contains(&"peephole"));
+
+    assert!(o1.contains(&"value-numbering"));
+    assert!(o1.contains(&"peephole"));
+
+    // copy-propagation is the only pass that waits for -O2
+    assert_eq!(o1.len() + 1, PASSES.len());
+    assert_eq!(PassManager::for_level(OptLevel::O2).pass_names().len(), PASSES.len());
+}
+
+#[test]
+fn pipeline_same_from_ir() {
+    let src = "(let ([x (read)]) (let ([y (+ x x)]) (+ y (- 3))))";
+
+    // the ir is handed to a second pass manager, e.g. like a loaded .ir file. it
+    // skips the ast passes, and the ir passes already ran on it
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        let options = PipelineOptions { opt_level: level, ..PipelineOptions::default() };
+
+        let ir = PassManager::new(options.clone()).run_to(ProgramForm::Ast(test_parse_helper(src)), IrKind::Ir);
+
+        let backend = PipelineOptions {
+            disabled: vec!("value-numbering".to_owned(), "copy-propagation".to_owned()),
+            ..options.clone()
+        };
+
+        assert_eq!(PassManager::new(backend).run(ir).into_x64(), compile(options, src).0);
+    }
+}
+
+#[test]
+fn pipeline_disable_pass() {
+    let options = PipelineOptions::from_args(&["-O1", "--disable-pass=partial-eval"]).unwrap();
+
+    let mut pm = PassManager::new(options);
+
+    assert!(!pm.pass_names().contains(&"partial-eval"));
+    assert!(pm.pass_names().contains(&"peephole"));
+
+    // without partial evaluation the sum is computed at run time
+    let ir = pm.run_to(ProgramForm::Ast(test_parse_helper("(+ 1 2)")), IrKind::Ir).into_ir();
+
+    let (result, _) = test_interp_ir_helper(ir.clone(), vec!());
+
+    assert_eq!(result.value, Some(3));
+    assert!(matches!(ir.labels[&crate::idstr!("start")].tail, Tail::Return(Exp::Prim { .. })));
+}
+
+#[test]
+fn pipeline_bad_options() {
+    assert_eq!(
+        PipelineOptions::from_args(&["--disable-pass=decomplify"]),
+        Err("pass 'decomplify' can't be disabled".to_owned())
+    );
+
+    assert_eq!(
+        PipelineOptions::from_args(&["--dump-after=nothing"]),
+        Err("unknown pass 'nothing'".to_owned())
+    );
+
+    assert_eq!(
+        PipelineOptions::from_args(&["-O3"]),
+        Err("unknown option '-O3'".to_owned())
+    );
+
+    // the pass has to be in the pipeline, whatever order the options come in
+    assert_eq!(
+        PipelineOptions::from_args(&["--dump-after=peephole", "-O0"]),
+        Err("pass 'peephole' doesn't run at -O0, it can't be dumped".to_owned())
+    );
+
+    assert_eq!(
+        PipelineOptions::from_args(&["-O1", "--dump-before=copy-propagation"]),
+        Err("pass 'copy-propagation' doesn't run at -O1, it can't be dumped".to_owned())
+    );
+
+    assert_eq!(
+        PipelineOptions::from_args(&["-O1", "--dump-after=partial-eval", "--disable-pass=partial-eval"]),
+        Err("pass 'partial-eval' is disabled, it can't be dumped".to_owned())
+    );
+
+    assert!(PipelineOptions::from_args(&["-O1", "--dump-after=peephole"]).is_ok());
+}
+
+#[test]
+fn pipeline_o2_has_its_own_pass() {
+    let passes = |level| PassManager::for_level(level).pass_names();
+
+    assert!(!passes(OptLevel::O1).contains(&"copy-propagation"));
+    assert!(passes(OptLevel::O2).contains(&"copy-propagation"));
+
+    let options = PipelineOptions::from_args(&["-O2"]).unwrap();
+
+    let (x64, _) = compile(options, "(let ([x (read)]) (+ (- x) (- x)))");
+    let (o1, _) = compile(PipelineOptions::from_args(&["-O1"]).unwrap(), "(let ([x (read)]) (+ (- x) (- x)))");
+
+    let start = crate::idstr!("start");
+
+    // value numbering left a copy behind at -O1
+    assert!(x64.blocks[&start].instr.len() < o1.blocks[&start].instr.len(), "{}\n{}", x64, o1);
+}
+
+#[test]
+fn pipeline_custom_checks_kinds() {
+    assert!(PassManager::custom(&["uniquify", "decomplify"]).is_ok());
+
+    assert_eq!(
+        PassManager::custom(&["explicate-control", "uniquify"]).err(),
+        Some("pass 'uniquify' takes Ast but 'explicate-control' produces Ir".to_owned())
+    );
+}
+
+#[test]
+fn pipeline_dumps() {
+    let options = PipelineOptions::from_args(&["--dump-before=decomplify", "--dump-after=decomplify,explicate-control"]).unwrap();
+
+    let (_, mut pm) = compile(options, "(+ (read) (- 2))");
+
+    let dumps = pm.take_dumps();
+
+    let points: Vec<(&str, DumpPoint)> = dumps.iter().map(|d| (d.pass, d.point)).collect();
+
+    assert_eq!(points, vec!(
+        ("decomplify", DumpPoint::Before),
+        ("decomplify", DumpPoint::After),
+        ("explicate-control", DumpPoint::After),
+    ));
+
+    // before decomplify the read is still an argument of the sum
+    assert!(!dumps[0].text.contains("tmp.0"));
+    assert!(dumps[1].text.contains("tmp.0"));
+    assert!(dumps[2].text.contains("IRProgram"));
+
+    assert!(pm.take_dumps().is_empty());
+}
+
+#[test]
+fn pipeline_timings() {
+    let (_, pm) = compile(PipelineOptions::default(), "(+ (read) 1)");
+
+    let timed: Vec<&str> = pm.timings().timings.iter().map(|t| t.0).collect();
+
+    assert_eq!(timed, pm.pass_names());
+
+    let report = format!("{}", pm.timings());
+
+    assert!(report.contains("select-instructions"));
+    assert!(report.contains("total"));
+}
+
+#[test]
+fn pipeline_resumes_between_stages() {
+    let mut pm = PassManager::for_level(OptLevel::O0);
+
+    let ast = pm.run_to(ProgramForm::Ast(test_parse_helper("(+ (read) 1)")), IrKind::Ast);
+    let ir = pm.run_to(ast, IrKind::Ir);
+    let x64 = pm.run(ir).into_x64();
+
+    assert_eq!(x64, crate::utility::test_x64_helper("(+ (read) 1)"));
+    assert_eq!(pm.timings().timings.len(), pm.pass_names().len());
+}
+
+#[test]
+fn pipeline_verify_every_pass() {
+    let programs = [
+        "(2)",
+        "(+ (read) (- 2))",
+        "(let ([x (read)]) (let ([y (read)]) (let ([z (+ x y)]) (+ z (- x)))))",
+        "(+ (let ([x 1]) x) (let ([x (let ([x 2]) x)]) (let ([x 3]) x)))",
+        "(let ([x (read)]) (+ (let ([y (+ x 1)]) y) (let ([y (read)]) (+ y x))))",
+        "(let ([a (read)]) (let ([b a]) (let ([c b]) (+ c (- (read))))))",
+        nested_lets(20),
+        nested_sums(20),
+    ];
+
+    for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+        for program in programs {
+            let mut pm = PassManager::for_level(level).set_verify(true);
+
+            if let Err(e) = pm.try_run(ProgramForm::Ast(test_parse_helper(program))) {
+                panic!("{:?} {}: {}", level, program, e);
+            }
+        }
+    }
+}
+
+#[test]
+fn pipeline_verify_names_the_pass() {
+    // without uniquify both lets bind 'x', decomplify is the first pass to be checked
+    let mut pm = PassManager::custom(&["decomplify"]).unwrap().set_verify(true);
+
+    let e = pm.try_run(ProgramForm::Ast(test_parse_helper("(+ (let ([x 1]) x) (let ([x 2]) x))"))).unwrap_err();
+
+    assert_eq!(e.pass, "decomplify");
+    assert_eq!(e.errors, vec!("'x' is bound more than once".to_owned()));
+    assert_eq!(format!("{}", e), "pass 'decomplify' broke the contract:\n    'x' is bound more than once");
+
+    // without verify nothing is checked
+    let mut pm = PassManager::custom(&["decomplify"]).unwrap();
+
+    assert!(pm.try_run(ProgramForm::Ast(test_parse_helper("(+ (let ([x 1]) x) (let ([x 2]) x))"))).is_ok());
+}
+
+#[test]
+fn pipeline_verify_option() {
+    assert!(!PipelineOptions::default().verify);
+    assert!(PipelineOptions::from_args(&["-O1", "--verify"]).unwrap().verify);
+}
+
+#[test]
+fn pipeline_step_by_step() {
+    let program = ProgramForm::Ast(test_parse_helper("(let ([x (read)]) (+ x 1))"));
+
+    let expected = PassManager::for_level(OptLevel::O1).run(program.clone());
+
+    let mut pm = PassManager::for_level(OptLevel::O1);
+    let mut program = program;
+    let mut names = vec!();
+
+    while let Some(pass) = pm.next_pass() {
+        names.push(pass.name);
+
+        program = pm.try_step(program).unwrap();
+
+        assert_eq!(program.kind(), pass.output);
+    }
+
+    assert_eq!(names, PassManager::for_level(OptLevel::O1).pass_names());
+    assert_eq!(program, expected);
+
+    // nothing left to run
+    assert_eq!(pm.try_step(program.clone()), Ok(program));
+}
+
+#[test]
+fn pipeline_step_skips_passes_already_done() {
+    let ir = PassManager::for_level(OptLevel::O0).run_to(ProgramForm::Ast(test_parse_helper("(+ 1 (read))")), IrKind::Ir);
+
+    let mut pm = PassManager::for_level(OptLevel::O0);
+
+    let mut program = ir.clone();
+
+    // the ast passes leave the ir alone
+    while pm.next_pass().unwrap().input == IrKind::Ast {
+        program = pm.try_step(program).unwrap();
+    }
+
+    assert_eq!(program, ir);
+    assert_eq!(pm.timings().timings.len(), 0);
+}
+
+#[test]
+fn pipeline_print() {
+    let program = ProgramForm::Ast(test_parse_helper("(+ 1 (read))"));
+
+    assert_eq!(program.print(), "(+ 1 (read))");
+
+    let ir = PassManager::for_level(OptLevel::O0).run_to(program, IrKind::Ir);
+
+    assert!(ir.print().starts_with("locals: tmp.0\nstart:\n"), "{}", ir.print());
+}
+
+#[test]
+fn pipeline_limit_options() {
+    let options = PipelineOptions::from_args(&["--fuel=100", "--max-depth=10"]).unwrap();
+
+    assert_eq!(options.limits, crate::interpreter::Limits { fuel: 100, max_depth: 10 });
+
+    assert_eq!(
+        PipelineOptions::from_args(&["--fuel=lots"]),
+        Err("--fuel is a number of steps, not 'lots'".to_owned())
+    );
+}
+
+#[test]
+fn pipeline_loaded_programs_are_verified() {
+    let ir = ProgramForm::parse("locals:\nstart:\n    return y;", IrKind::Ir).unwrap_err();
+
+    assert!(ir.contains("'y'"), "{}", ir);
+
+    let x64 = ProgramForm::parse("
+        externals:
+        vars:
+        start:
+            call read_int
+            jmp done
+    ", IrKind::X64).unwrap_err();
+
+    assert!(x64.contains("call to 'read_int' which isn't external"), "{}", x64);
+    assert!(x64.contains("jump to unknown label 'done'"), "{}", x64);
+
+    assert_eq!(
+        ProgramForm::parse("locals: x\nstart:\n    x = (+ 1);\n    return x;", IrKind::Ir),
+        Err("line 3: '+' takes 2 argument(s) but was given 1".to_owned())
+    );
+}
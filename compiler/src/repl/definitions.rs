@@ -0,0 +1,89 @@
+/*
+    the (define var exp) entered at the repl
+
+    a definition is evaluated once, when it's entered, and the value is kept.
+    every later entry is compiled with the definitions bound around it:
+
+        (define x (read))     x is read once, say 5
+        (define y (+ x 1))    6
+        (+ x y)               is compiled as (let ([x 5]) (let ([y 6]) (+ x y)))
+
+    so the interpreters and the compiled code all see the same values, and a
+    (read) in a definition doesn't read again on every entry
+*/
+
+use runtime::types::{RuntimeI64};
+
+use crate::types::{IdString};
+use crate::frontend::ast::{AstNode, LetBinding, Program};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Definition {
+    pub name: IdString,
+    pub value: RuntimeI64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Definitions {
+    definitions: Vec<Definition>, // in the order they were entered
+}
+
+impl Definitions {
+
+    pub fn new() -> Self {
+        Definitions {
+            definitions: vec!(),
+        }
+    }
+
+    // a new value for a name that's already defined keeps its place
+    pub fn define(&mut self, name: IdString, value: RuntimeI64) {
+        match self.definitions.iter_mut().find(|d| d.name == name) {
+            Some(definition) => definition.value = value,
+            None => self.definitions.push(Definition { name: name, value: value }),
+        }
+    }
+
+    // false if there was no such definition
+    pub fn undefine(&mut self, name: IdString) -> bool {
+        let count = self.definitions.len();
+
+        self.definitions.retain(|d| d.name != name);
+
+        self.definitions.len() != count
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Definition> {
+        self.definitions.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    // the program with every definition bound around it, the first one outermost
+    pub fn wrap(&self, program: Program) -> Program {
+        let mut ast = program.ast;
+
+        let exp =
+            self.definitions
+            .iter()
+            .rev()
+            .fold(program.exp, |body, d| {
+                let value = ast.add(AstNode::Int(d.value));
+
+                ast.add(
+                    AstNode::Let {
+                        bindings: vec!(LetBinding { identifier: d.name, expr: value }),
+                        body: body,
+                    }
+                )
+            });
+
+        Program {
+            info: (),
+            ast: ast,
+            exp: exp,
+        }
+    }
+}
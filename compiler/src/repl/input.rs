@@ -0,0 +1,75 @@
+/*
+    reads one entry at the repl, which can take more than one line
+
+    while a '(' or '[' is still open the next line is read as well, with the
+    continuation prompt:
+
+        > (let ([x (read)])
+        . . .   (+ x 1))
+
+    the lines are joined with newlines, so the line numbers in the errors are
+    the lines of the entry. a ':' command is always a single line, and an extra
+    ')' doesn't keep reading, the parser reports it
+*/
+
+use crate::frontend::lexer::{Lexer};
+use crate::frontend::token::{Token, TokenType};
+
+pub const PROMPT: &str = "> ";
+pub const CONTINUATION_PROMPT: &str = ". . . ";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    Complete(String),
+    Unterminated(String), // the input ended inside a form, the message says where it starts
+    EndOfInput,
+}
+
+// the brackets that haven't been closed yet, the outermost first
+pub fn open_brackets(text: &str) -> Vec<Token> {
+    let mut open = vec!();
+
+    for token in Lexer::new(text).lex() {
+        match token.ttype {
+            TokenType::Lparen | TokenType::Lbracket => open.push(token),
+            TokenType::Rparen | TokenType::Rbracket => { open.pop(); },
+            _ => (),
+        }
+    }
+
+    open
+}
+
+// next_line gets the prompt to show and gives None at the end of the input
+pub fn read_entry(mut next_line: impl FnMut(&str) -> Option<String>) -> Entry {
+    let mut text = match next_line(PROMPT) {
+        Some(line) => line,
+        None => return Entry::EndOfInput,
+    };
+
+    if text.trim_start().starts_with(':') {
+        return Entry::Complete(text);
+    }
+
+    loop {
+        let open = open_brackets(&text);
+
+        let outermost = match open.first() {
+            Some(token) => token.clone(),
+            None => return Entry::Complete(text),
+        };
+
+        match next_line(CONTINUATION_PROMPT) {
+            Some(line) => {
+                text.push('\n');
+                text.push_str(&line);
+            },
+
+            None => {
+                return Entry::Unterminated(
+                    format!("line {}, col {}: the input ended before this '{}' was closed", outermost.line, outermost.col, outermost.lexeme)
+                );
+            },
+        }
+    }
+}
@@ -0,0 +1,694 @@
+#![allow(unused_imports)]
+
+mod definitions;
+mod input;
+
+#[cfg(test)]
+mod repl_tests;
+
+use runtime::types::{RuntimeI64, RuntimeValue};
+
+use crate::frontend::ast::{Program};
+use crate::frontend::lexer::{Lexer};
+use crate::frontend::parser::{Parser, TopLevel};
+use crate::ir::explicate::{IRProgram};
+use crate::pipeline::{PassManager, PipelineOptions, ProgramForm, IrKind};
+use crate::interpreter::{
+    Interpreter, 
+    CachedRuntimeCall,
+    CachedFunctionResult,
+    interp_ast::AstInterpreter,
+    interp_ir::IrInterpreter,
+    debugger::{Debugger},
+    trace::{format_trace},
+};
+
+use std::io::{IsTerminal, Write};
+
+use crate::io::{read_line};
+use crate::types::{OptLevel, Overflow};
+use crate::difftest::{Outcome, execute, run_native};
+use crate::snapshot::{diff};
+use crate::backend::x64_print::{X64Printer};
+use crate::backend::x64_build::{native_toolchain};
+
+use definitions::{Definitions};
+use input::{Entry};
+
+#[derive(PartialEq)]
+enum ReplResult {
+    BackToStart,
+    KeepExecuting,
+    Stop
+}  
+
+#[derive(Copy, Clone)]
+struct ReplCommand {
+    pub cmd: &'static str,
+    pub help: &'static str,
+    pub action: fn (&mut Repl, &str) -> ReplResult, // gets what follows the command
+}
+
+fn toggle(flag: &mut bool, what: &str) -> ReplResult {
+    *flag = !*flag;
+
+    println!("--{} {}\n", what, if *flag { "on" } else { "off" });
+
+    ReplResult::BackToStart
+}
+
+pub struct Repl {
+    current_line: String,
+    commands: Vec<ReplCommand>,
+    show_ast: bool,
+    show_ir: bool,
+    show_x64: bool,
+    show_passes: bool,
+    show_asm: bool,
+    show_trace: bool,
+    run_native: bool,
+    options: PipelineOptions,
+    definitions: Definitions,
+}
+
+impl Repl {
+
+    pub fn new() -> Self {
+        let commands = vec!(
+            ReplCommand { cmd: ":help", help: "show available commands", action: Repl::print_help },
+            ReplCommand {
+                cmd: ":show-ast",
+                help: "show the abstract syntax tree",
+                action: |r, _| {
+                    r.show_ast = !r.show_ast;
+
+                    ReplResult::BackToStart
+                },
+            },
+            ReplCommand {
+                cmd: ":show-ir",
+                help: "show the intermediate representation",
+                action: |r, _| {
+                    r.show_ir = !r.show_ir;
+
+                    ReplResult::BackToStart
+                },
+            },
+            ReplCommand {
+                cmd: ":show-x64",
+                help: "show the x64 representation",
+                action: |r, _| {
+                    r.show_x64 = !r.show_x64;
+
+                    ReplResult::BackToStart
+                },
+            },
+            ReplCommand { cmd: ":passes", help: "show the program after every pass", action: |r, _| toggle(&mut r.show_passes, "passes") },
+            ReplCommand { cmd: ":asm", help: "show the assembly", action: |r, _| toggle(&mut r.show_asm, "asm") },
+            ReplCommand { cmd: ":trace", help: "trace the ast interpreter before the passes, then what every pass changes in the trace", action: |r, _| toggle(&mut r.show_trace, "trace") },
+            ReplCommand { cmd: ":time", help: "show how long every pass took", action: |r, _| toggle(&mut r.options.time_passes, "time") },
+            ReplCommand { cmd: ":run", help: "also build and run the program natively (linux)", action: |r, _| toggle(&mut r.run_native, "run") },
+            ReplCommand { cmd: ":checked", help: "make overflow a runtime error instead of wrapping around", action: Repl::toggle_checked },
+            ReplCommand { cmd: ":fuel", help: ":fuel [steps] [depth] shows or sets how far a program may run", action: Repl::fuel },
+            ReplCommand { cmd: ":debug", help: ":debug <program or file> steps through its ir, 'help' there lists the commands", action: Repl::debug },
+            ReplCommand { cmd: ":load", help: ":load <file> evaluates a source, .ir, .x86v or .json file", action: Repl::load },
+            ReplCommand { cmd: ":O0", help: "turn off optimizations", action: |r, _| r.set_opt_level(OptLevel::O0) },
+            ReplCommand { cmd: ":O1", help: "enable the optimizing passes", action: |r, _| r.set_opt_level(OptLevel::O1) },
+            ReplCommand { cmd: ":O2", help: "enable all optimizing passes, copy propagation as well", action: |r, _| r.set_opt_level(OptLevel::O2) },
+            ReplCommand { cmd: ":grammer", help: "print the grammer", action: Repl::print_grammer },
+            ReplCommand { cmd: ":env", help: "list the definitions", action: Repl::print_env },
+            ReplCommand { cmd: ":undef", help: ":undef x removes the definition of x", action: Repl::undefine },
+            ReplCommand { cmd: ":quit", help: "exit the repl", action: Repl::quit },
+        );
+
+        Repl {
+            current_line: "".to_owned(),
+            commands: commands,
+            show_ast: false,
+            show_ir: false,
+            show_x64: false,
+            show_passes: false,
+            show_asm: false,
+            show_trace: false,
+            run_native: false,
+            options: PipelineOptions::default(),
+            definitions: Definitions::new(),
+        }
+    }
+
+    // e.g. the options given on the command line
+    pub fn set_pipeline_options(mut self, options: PipelineOptions) -> Self {
+        self.options = options;
+
+        self
+    }
+
+    fn set_opt_level(&mut self, opt_level: OptLevel) -> ReplResult {
+        let options = PipelineOptions { opt_level: opt_level, ..self.options.clone() };
+
+        // the dumps asked for on the command line still have to make sense
+        match options.check_dumps() {
+            Ok(()) => {
+                println!("--optimization level {:?}\n", opt_level);
+                self.options = options;
+            },
+
+            Err(msg) => println!("{}\n", msg),
+        }
+
+        ReplResult::BackToStart
+    }
+
+    fn toggle_checked(&mut self, _: &str) -> ReplResult {
+        let mut checked = self.options.overflow == Overflow::Checked;
+
+        let result = toggle(&mut checked, "checked overflow");
+
+        self.options.overflow = if checked { Overflow::Checked } else { Overflow::Wrap };
+
+        result
+    }
+
+    fn fuel(&mut self, args: &str) -> ReplResult {
+        let numbers: Result<Vec<usize>, _> = args.split_whitespace().map(|arg| arg.parse()).collect();
+
+        match numbers.as_deref() {
+            Ok([]) => (),
+            Ok([fuel]) => self.options.limits.fuel = *fuel,
+            Ok([fuel, max_depth]) => {
+                self.options.limits.fuel = *fuel;
+                self.options.limits.max_depth = *max_depth;
+            },
+
+            _ => {
+                println!("usage: :fuel [steps] [depth]\n");
+                return ReplResult::BackToStart;
+            },
+        }
+
+        println!("--fuel {} steps, {} deep\n", self.options.limits.fuel, self.options.limits.max_depth);
+
+        ReplResult::BackToStart
+    }
+
+    fn print_dumps(pm: &mut PassManager) {
+        for dump in pm.take_dumps() {
+            println!("{}", dump);
+        }
+    }
+
+    fn print_grammer(&mut self, _: &str) -> ReplResult {
+        println!("
+expr    ::= int | (read) | ('-' exp) | ('+' exp exp)
+          | var | (let ([var exp]+) exp)
+program ::= (exp)
+define  ::= (define var exp)
+        ");
+
+        ReplResult::BackToStart
+    }
+
+    fn quit(&mut self, _: &str) -> ReplResult {
+        ReplResult::Stop
+    }
+
+    fn print_help(&mut self, _: &str) -> ReplResult {
+
+        println!();
+        for cmd in &self.commands {
+            println!("{} - {}", cmd.cmd, cmd.help);
+        }
+        println!();
+
+        ReplResult::BackToStart
+    }
+
+    fn print_env(&mut self, _: &str) -> ReplResult {
+        if self.definitions.is_empty() {
+            println!("no definitions\n");
+        } else {
+            for definition in self.definitions.iter() {
+                println!("{} = {}", definition.name, definition.value);
+            }
+            println!();
+        }
+
+        ReplResult::BackToStart
+    }
+
+    fn undefine(&mut self, args: &str) -> ReplResult {
+        let names: Vec<&str> = args.split_whitespace().collect();
+
+        if names.is_empty() {
+            println!("usage: :undef <name>...\n");
+        }
+
+        for name in names {
+            if !self.definitions.undefine(crate::idstr!(name)) {
+                println!("'{}' isn't defined", name);
+            }
+        }
+
+        ReplResult::BackToStart
+    }
+
+    // evaluated once, with the definitions made before it
+    fn define(&mut self, name: crate::types::IdString, exp: Program) {
+        let program = self.definitions.wrap(exp);
+
+        let mut runtime_cache = CachedRuntimeCall::new();
+        let mut ast_interpreter =
+            AstInterpreter::new(&program, &mut runtime_cache)
+            .set_overflow(self.options.overflow)
+            .set_limits(self.options.limits);
+
+        let result = Interpreter::new(&mut ast_interpreter).run();
+
+        match result.value {
+            Some(value) if !result.had_error => {
+                self.definitions.define(name, value);
+
+                println!("{} = {}\n", name, value);
+            },
+
+            _ => {
+                for error in result.errors {
+                    println!("{}", error);
+                }
+
+                println!("'{}' wasn't defined\n", name);
+            }
+        }
+    }
+
+    fn handle_repl_command(&mut self, input: &str) -> ReplResult {
+        let (command, args) = match input.find(char::is_whitespace) {
+            Some(i) => (&input[..i], input[i..].trim()),
+            None => (input, ""),
+        };
+
+        let cmd = self.commands.iter().position(|&c| c.cmd == command);
+
+        match cmd {
+            Some(index) => {
+                let repl_cmd = self.commands[index];
+                let action = repl_cmd.action;
+                action(self, args)
+            },
+
+            _ => ReplResult::BackToStart
+        }
+    }
+
+    fn check_input_for_command(&mut self) -> ReplResult {
+        let input = self.current_line.trim().to_owned();
+
+        if input.is_empty() {
+            ReplResult::BackToStart
+        } else if input.starts_with(":") {
+            self.handle_repl_command(&input)
+        } else {
+            ReplResult::KeepExecuting
+        }
+    }
+
+    // false at the end of the input
+    fn read_line(&mut self) -> bool {
+        let interactive = std::io::stdin().is_terminal();
+
+        let entry = input::read_entry(|prompt| {
+            if interactive {
+                print!("{}", prompt);
+                let _ = std::io::stdout().flush();
+            }
+
+            read_line()
+        });
+
+        match entry {
+            Entry::Complete(text) => {
+                self.current_line = text;
+                true
+            },
+
+            Entry::Unterminated(msg) => {
+                println!("{}\n", msg);
+                false
+            },
+
+            Entry::EndOfInput => false,
+        }
+    }
+
+    // the program goes through the pipeline a pass at a time. each form is run
+    // once the passes working on it are done: the ast by the ast interpreter, the ir
+    // by the ir interpreter and the x64 by the emulator, and all of them have to agree
+    fn evaluate(&mut self, program: ProgramForm) {
+        let mut pm = PassManager::new(self.options.clone());
+
+        let mut program = program;
+        let mut outcomes: Vec<(&'static str, Outcome)> = vec!();
+
+        // the first executor reads from stdin, the rest get the same input
+        let mut input: Option<Vec<String>> = None;
+
+        let mut last_trace: Option<String> = None;
+
+        if self.show_trace {
+            self.print_trace(None, &program, &mut input, &mut last_trace);
+        }
+
+        loop {
+            let next = pm.next_pass();
+
+            let leaving_form = match next {
+                Some(pass) => pass.input == program.kind() && pass.output != program.kind(),
+                None => true,
+            };
+
+            if leaving_form {
+                self.show(&program);
+
+                let (executor, outcome) = Repl::execute(&program, &mut input, &self.options);
+
+                if outcome.value.is_none() {
+                    for error in &outcome.errors {
+                        println!("{}", error);
+                    }
+                    println!();
+                    return;
+                }
+
+                outcomes.push((executor, outcome));
+            }
+
+            let pass = match next {
+                Some(pass) => pass,
+                None => break,
+            };
+
+            let ran = pass.input == program.kind();
+
+            program = match pm.try_step(program) {
+                Ok(program) => program,
+                Err(e) => {
+                    Repl::print_dumps(&mut pm);
+                    println!("internal error\n{}\n", e);
+                    return;
+                }
+            };
+
+            Repl::print_dumps(&mut pm);
+
+            if self.show_passes && ran {
+                println!("--- after {} ---\n{}\n", pass.name, program.print());
+            }
+
+            if self.show_trace && ran {
+                self.print_trace(Some(pass.name), &program, &mut input, &mut last_trace);
+            }
+        }
+
+        let x64 = program.into_x64();
+
+        if self.show_asm {
+            println!("{}", X64Printer::new(x64.clone()).print());
+        }
+
+        if self.run_native {
+            let reads = input.clone().unwrap_or_default();
+            let reads: Vec<&str> = reads.iter().map(|line| line.as_str()).collect();
+
+            match native_toolchain() {
+                Ok(()) => outcomes.push(("native", run_native(X64Printer::new(x64).print(), &reads))),
+                Err(msg) => println!("--can't run natively, {}", msg),
+            }
+        }
+
+        let first = &outcomes[0].1;
+
+        if outcomes.iter().all(|(_, outcome)| outcome.value == first.value) {
+            // doesn't matter which one
+            println!("> {}\n", first.value.unwrap());
+        } else {
+            println!("internal error\nthe results don't agree:");
+
+            for (executor, outcome) in &outcomes {
+                println!("{:>16}: {}", executor, outcome);
+            }
+
+            println!();
+        }
+
+        if self.show_x64 && self.options.opt_level >= OptLevel::O1 {
+            println!("peephole rewrites:");
+            println!("{}", pm.peephole_stats());
+        }
+
+        if self.options.time_passes {
+            println!("{}\n", pm.timings());
+        }
+    }
+
+    fn show(&self, program: &ProgramForm) {
+        let (show, title) = match program {
+            ProgramForm::Ast(_) => (self.show_ast, "AST:"),
+            ProgramForm::Ir(_) => (self.show_ir, "IR:"),
+            ProgramForm::X64(_) => (self.show_x64, "X64:"),
+        };
+
+        if show {
+            println!("{}", title);
+            println!("{}\n", program.print());
+        }
+    }
+
+    // the trace of an ast, reading the same input as the executors. after a pass it's
+    // only printed again if the pass changed it
+    fn trace(program: &ProgramForm, input: &mut Option<Vec<String>>, options: &PipelineOptions) -> Option<String> {
+        let program = match program {
+            ProgramForm::Ast(program) => program,
+            _ => return None,
+        };
+
+        let mut runtime_cache = match input {
+            Some(lines) => CachedRuntimeCall::new().set_input(lines.iter().map(|line| line.as_str()).collect()),
+            None => CachedRuntimeCall::new(),
+        };
+
+        let trace = {
+            let mut ast_interpreter =
+                AstInterpreter::new(program, &mut runtime_cache)
+                .set_overflow(options.overflow)
+                .set_limits(options.limits)
+                .set_trace(true);
+
+            Interpreter::new(&mut ast_interpreter).run();
+
+            format_trace(ast_interpreter.trace())
+        };
+
+        if input.is_none() {
+            let reads = runtime_cache.cached_results_of(crate::symbol::READ);
+
+            *input = Some(reads.iter().map(|value| match value { RuntimeValue::RuntimeI64(n) => n.to_string() }).collect());
+        }
+
+        Some(trace)
+    }
+
+    fn print_trace(&self, pass: Option<&str>, program: &ProgramForm, input: &mut Option<Vec<String>>, last: &mut Option<String>) {
+        let trace = match Repl::trace(program, input, &self.options) {
+            Some(trace) => trace,
+            None => return,
+        };
+
+        println!("{}", Repl::trace_report(pass, &trace, last.as_ref()));
+
+        *last = Some(trace);
+    }
+
+    // the whole trace the first time, after that only what the pass changed in it
+    fn trace_report(pass: Option<&str>, trace: &str, last: Option<&String>) -> String {
+        match (pass, last) {
+            (Some(pass), Some(last)) if last == trace => format!("--- trace after {}: unchanged ---\n", pass),
+            (Some(pass), Some(last)) => format!("--- trace after {}, changed ---\n{}", pass, diff(last, trace)),
+            (Some(pass), None) => format!("--- trace after {} ---\n{}\n", pass, trace),
+            (None, _) => format!("--- trace ---\n{}\n", trace),
+        }
+    }
+
+    fn execute(program: &ProgramForm, input: &mut Option<Vec<String>>, options: &PipelineOptions) -> (&'static str, Outcome) {
+        let executor = match program {
+            ProgramForm::Ast(_) => "ast interpreter",
+            ProgramForm::Ir(_) => "ir interpreter",
+            ProgramForm::X64(_) => "x64 emulator",
+        };
+
+        let outcome = match input {
+            Some(lines) => {
+                let lines: Vec<&str> = lines.iter().map(|line| line.as_str()).collect();
+
+                execute(program, CachedRuntimeCall::new().set_input(lines), true, options)
+            },
+
+            None => {
+                let outcome = execute(program, CachedRuntimeCall::new(), true, options);
+
+                *input = Some(outcome.reads.iter().map(|n| n.to_string()).collect());
+
+                outcome
+            },
+        };
+
+        (executor, outcome)
+    }
+
+    fn load(&mut self, path: &str) -> ReplResult {
+        if path.is_empty() {
+            println!("usage: :load <file>\n");
+            return ReplResult::BackToStart;
+        }
+
+        match ProgramForm::load(path) {
+            // a source program sees the definitions
+            Ok(ProgramForm::Ast(program)) => self.evaluate(ProgramForm::Ast(self.definitions.wrap(program))),
+            Ok(program) => self.evaluate(program),
+            Err(msg) => println!("{}\n", msg),
+        }
+
+        ReplResult::BackToStart
+    }
+
+    // a program on the line or a source or .ir file, with the passes up to the ir run on it
+    fn debugged_ir(&self, args: &str) -> Result<IRProgram, String> {
+        let program =
+            if args.starts_with('(') {
+                let mut parser = Parser::new(Lexer::new(args).lex());
+
+                let program = parser.parse();
+
+                if !parser.parse_success() {
+                    let errors: Vec<String> = parser.errors().iter().map(|e| format!("{:?}", e)).collect();
+
+                    return Err(errors.join("\n"));
+                }
+
+                ProgramForm::Ast(program)
+            } else {
+                ProgramForm::load(args)?
+            };
+
+        let program = match program {
+            ProgramForm::Ast(program) => ProgramForm::Ast(self.definitions.wrap(program)),
+            ProgramForm::Ir(program) => ProgramForm::Ir(program),
+            ProgramForm::X64(_) => return Err("only a program or its ir can be debugged".to_owned()),
+        };
+
+        let mut pm = PassManager::new(self.options.clone());
+
+        match pm.try_run_to(program, IrKind::Ir) {
+            Ok(program) => Ok(program.into_ir()),
+            Err(e) => Err(format!("internal error\n{}", e)),
+        }
+    }
+
+    fn debug(&mut self, args: &str) -> ReplResult {
+        if args.is_empty() {
+            println!("usage: :debug <program or file>\n");
+            return ReplResult::BackToStart;
+        }
+
+        let ir = match self.debugged_ir(args) {
+            Ok(ir) => ir,
+            Err(msg) => {
+                println!("{}\n", msg);
+                return ReplResult::BackToStart;
+            }
+        };
+
+        println!("{}\n", ir);
+
+        let interactive = std::io::stdin().is_terminal();
+
+        let debugger = Debugger::new(
+            |prompt| {
+                if interactive {
+                    print!("{}", prompt);
+                    let _ = std::io::stdout().flush();
+                }
+
+                read_line()
+            },
+            std::io::stdout()
+        );
+
+        let mut runtime_cache = CachedRuntimeCall::new();
+        let mut ir_interpreter =
+            IrInterpreter::new(&ir, &mut runtime_cache)
+            .set_overflow(self.options.overflow)
+            .set_limits(self.options.limits)
+            .set_debugger(debugger);
+
+        let result = Interpreter::new(&mut ir_interpreter).run();
+
+        match result.value {
+            Some(value) if !result.had_error => println!("> {}\n", value),
+            _ => {
+                for error in result.errors {
+                    println!("{}", error);
+                }
+                println!();
+            },
+        }
+
+        ReplResult::BackToStart
+    }
+
+    pub fn run(&mut self) -> std::io::Result<()> {
+        'repl_loop:loop {
+
+            if !self.read_line() {
+                println!("Goodbye!");
+                break 'repl_loop;
+            }
+
+            let what_to_do = self.check_input_for_command();
+
+            match what_to_do {
+                ReplResult::Stop => {
+                    println!("Goodbye!");
+                    break 'repl_loop;
+                },
+
+                ReplResult::BackToStart => {
+                    continue 'repl_loop;
+                },
+
+                _ => {
+                },
+            }
+
+            let mut l = Lexer::new(&self.current_line);
+
+            let tokens = l.lex();
+
+            let mut p = Parser::new(tokens.clone());
+
+            let entry = p.parse_top_level();
+
+            if !p.parse_success() {
+                p.print_errors();
+                continue 'repl_loop;
+            }
+
+            match entry {
+                TopLevel::Define(name, exp) => self.define(name, exp),
+                TopLevel::Program(program) => self.evaluate(ProgramForm::Ast(self.definitions.wrap(program))),
+            }
+
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::types::{OptLevel};
+use crate::frontend::ast::{AstNode, Program};
+use crate::pipeline::{PassManager, ProgramForm};
+use crate::backend::x64_emulator::{emulate};
+use crate::interpreter::{Limits};
+use crate::utility::{test_parse_helper, test_interp_ast_helper, test_interp_ir_helper};
+
+use super::*;
+use super::definitions::*;
+
+fn exp(source: &str) -> Program {
+    test_parse_helper(source)
+}
+
+#[test]
+fn repl_definitions_wrap() {
+    let mut definitions = Definitions::new();
+
+    definitions.define(crate::idstr!("x"), 5);
+    definitions.define(crate::idstr!("y"), -6);
+
+    assert_eq!(definitions.wrap(exp("(+ x y)")).to_string(), "(let ([x 5]) (let ([y (- 6)]) (+ x y)))");
+
+    // a new value keeps its place
+    definitions.define(crate::idstr!("x"), 7);
+
+    let names: Vec<(String, i64)> = definitions.iter().map(|d| (d.name.to_string(), d.value)).collect();
+
+    assert_eq!(names, vec!(("x".to_owned(), 7), ("y".to_owned(), -6)));
+
+    assert!(definitions.undefine(crate::idstr!("x")));
+    assert!(!definitions.undefine(crate::idstr!("x")));
+
+    assert_eq!(definitions.wrap(exp("(y)")).to_string(), "(let ([y (- 6)]) y)");
+
+    assert!(definitions.undefine(crate::idstr!("y")));
+    assert!(definitions.is_empty());
+    assert_eq!(definitions.wrap(exp("(1)")), test_parse_helper("(1)"));
+}
+
+#[test]
+fn repl_definitions_everywhere() {
+    let mut definitions = Definitions::new();
+
+    definitions.define(crate::idstr!("x"), 40);
+    definitions.define(crate::idstr!("y"), 2);
+
+    // a let inside the entry can still shadow a definition
+    let program = definitions.wrap(exp("(let ([y (read)]) (+ x y))"));
+
+    let (result, _) = test_interp_ast_helper(program.clone(), vec!("-8"));
+
+    assert_eq!(result.value, Some(32));
+
+    for level in [OptLevel::O0, OptLevel::O1] {
+        let x64 = PassManager::for_level(level).run(ProgramForm::Ast(program.clone())).into_x64();
+
+        assert_eq!(emulate(&x64, vec!("-8")), Ok(32));
+    }
+}
+
+#[test]
+fn repl_define_sees_earlier_definitions() {
+    let mut repl = Repl::new();
+
+    repl.define(crate::idstr!("x"), exp("(+ 20 1)"));
+    repl.define(crate::idstr!("y"), exp("(+ x x)"));
+
+    // an error leaves the definitions alone
+    repl.define(crate::idstr!("z"), exp("(+ y nope)"));
+
+    let values: Vec<i64> = repl.definitions.iter().map(|d| d.value).collect();
+
+    assert_eq!(values, vec!(21, 42));
+
+    repl.undefine("x y");
+
+    assert!(repl.definitions.is_empty());
+}
+
+#[test]
+fn repl_executors_share_the_input() {
+    let program = ProgramForm::Ast(test_parse_helper("(+ (read) (- (read)))"));
+
+    let ir = PassManager::for_level(OptLevel::O0).run_to(program.clone(), crate::pipeline::IrKind::Ir);
+    let x64 = PassManager::for_level(OptLevel::O0).run(program.clone());
+
+    let mut input = Some(vec!("50".to_owned(), "8".to_owned()));
+
+    for (form, expected) in [(program, "ast interpreter"), (ir, "ir interpreter"), (x64, "x64 emulator")] {
+        let (executor, outcome) = Repl::execute(&form, &mut input, &PipelineOptions::default());
+
+        assert_eq!(executor, expected);
+        assert_eq!(outcome.value, Some(42));
+        assert_eq!(outcome.reads, vec!(50, 8));
+    }
+}
+
+#[test]
+fn repl_fuel() {
+    let mut repl = Repl::new();
+
+    repl.fuel("100");
+    assert_eq!(repl.options.limits, Limits { fuel: 100, ..Limits::default() });
+
+    repl.fuel("200 20");
+    assert_eq!(repl.options.limits, Limits { fuel: 200, max_depth: 20 });
+
+    // a bad argument changes nothing
+    repl.fuel("lots");
+    assert_eq!(repl.options.limits, Limits { fuel: 200, max_depth: 20 });
+
+    let program = ProgramForm::Ast(test_parse_helper("(+ (read) (- (read)))"));
+
+    let (_, outcome) = Repl::execute(&program, &mut Some(vec!("1".to_owned(), "2".to_owned())), &PipelineOptions {
+        limits: Limits { fuel: 2, ..Limits::default() },
+        ..PipelineOptions::default()
+    });
+
+    assert!(outcome.out_of_fuel);
+    assert_eq!(outcome.errors, vec!("ran out of fuel after 2 steps".to_owned()));
+}
+
+#[test]
+fn repl_debugged_ir() {
+    let mut repl = Repl::new();
+
+    repl.definitions.define(crate::idstr!("n"), 5);
+
+    // a program sees the definitions
+    let ir = repl.debugged_ir("(+ n (read))").unwrap();
+
+    assert_eq!(test_interp_ir_helper(ir, vec!("1")).0.value, Some(6));
+
+    assert!(repl.debugged_ir("(+ 1").is_err());
+    assert!(repl.debugged_ir("no_such_file.rkt").is_err());
+
+    // a malformed .ir file is an error, not a panic in the interpreter
+    let path = std::env::temp_dir().join(format!("repl_debugged_ir_{}.ir", std::process::id()));
+
+    for text in ["locals: x\nstart:\n    x = (+ 1);\n    return x;", "locals:\nstart:\n    return y;"] {
+        std::fs::write(&path, text).unwrap();
+
+        assert!(repl.debugged_ir(path.to_str().unwrap()).is_err(), "{}", text);
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn repl_trace_shares_the_input() {
+    let program = ProgramForm::Ast(test_parse_helper("(+ (read) 1)"));
+
+    let mut input = Some(vec!("41".to_owned()));
+
+    assert_eq!(
+        Repl::trace(&program, &mut input, &PipelineOptions::default()),
+        Some("(+ _ _) => 42\n  (read) => 41    read \"41\"\n  1 => 1".to_owned())
+    );
+
+    // only an ast is traced
+    let ir = PassManager::for_level(OptLevel::O0).run_to(program, crate::pipeline::IrKind::Ir);
+
+    assert_eq!(Repl::trace(&ir, &mut input, &PipelineOptions::default()), None);
+}
+
+#[test]
+fn repl_trace_report_shows_changes() {
+    let before = "(+ _ _) => 42\n  (read) => 41    read \"41\"\n  1 => 1";
+    let after = "(+ _ _) => 42\n  (read) => 41    read \"41\"\n  1 => 1\n  x => 1";
+
+    assert_eq!(Repl::trace_report(None, before, None), format!("--- trace ---\n{}\n", before));
+
+    assert_eq!(
+        Repl::trace_report(Some("uniquify"), before, Some(&before.to_owned())),
+        "--- trace after uniquify: unchanged ---\n"
+    );
+
+    // only the lines around the change are printed again
+    assert_eq!(
+        Repl::trace_report(Some("partial-eval"), after, Some(&before.to_owned())),
+        "--- trace after partial-eval, changed ---\n  ...\n    (read) => 41    read \"41\"\n    1 => 1\n+   x => 1\n"
+    );
+}
+
+// feeds the lines to read_entry and keeps the prompts it showed
+fn read(lines: &[&str]) -> (Entry, Vec<&'static str>) {
+    let mut lines = lines.iter();
+    let mut prompts = vec!();
+
+    let entry = input::read_entry(|prompt| {
+        prompts.push(if prompt == input::PROMPT { "prompt" } else { "continue" });
+        lines.next().map(|line| line.to_string())
+    });
+
+    (entry, prompts)
+}
+
+#[test]
+fn repl_multiline_entries() {
+    assert_eq!(read(&["(+ 1 2)", "(3)"]), (Entry::Complete("(+ 1 2)".to_owned()), vec!("prompt")));
+
+    assert_eq!(
+        read(&["(let ([x (read)]", "      [y 2])", "", "  (+ x y))", "(3)"]),
+        (Entry::Complete("(let ([x (read)]\n      [y 2])\n\n  (+ x y))".to_owned()), vec!("prompt", "continue", "continue", "continue"))
+    );
+
+    // commands are one line, an extra ')' is left to the parser
+    assert_eq!(read(&[":load (", "(1)"]).0, Entry::Complete(":load (".to_owned()));
+    assert_eq!(read(&["(1))", "(2)"]).0, Entry::Complete("(1))".to_owned()));
+
+    assert_eq!(read(&[]).0, Entry::EndOfInput);
+    assert_eq!(
+        read(&["(+ 1", "   (let ([x 2])"]).0,
+        Entry::Unterminated("line 1, col 1: the input ended before this '(' was closed".to_owned())
+    );
+}
+
+#[test]
+fn repl_multiline_line_numbers() {
+    let (entry, _) = read(&["(let ([x 1])", "  (+ x ^))"]);
+
+    let text = match entry {
+        Entry::Complete(text) => text,
+        other => panic!("{:?}", other),
+    };
+
+    let mut parser = Parser::new(Lexer::new(&text).lex());
+
+    parser.parse_top_level();
+
+    assert!(!parser.parse_success());
+
+    match &parser.errors()[0] {
+        AstNode::Error { token, .. } => assert_eq!((token.line, token.col, token.lexeme.as_str()), (2, 8, "^")),
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,177 @@
+/*
+    snapshot tests: the printed output of something is kept in a file in the
+    repository, and a test fails with a diff when the output changes
+
+        UPDATE_SNAPSHOTS=1 cargo test snapshot
+
+    writes the files instead of comparing, for when the change was meant. the
+    changed files then show up in the commit like any other change
+
+    the snapshots of the passes are in tests/snapshots, one file for every
+    program in tests/ with the program after every pass and the nasm at the end
+
+    diff isn't only for the tests, the repl's :trace uses it to show what a pass
+    changed in the trace
+*/
+
+#![allow(dead_code)]
+
+#[cfg(test)]
+mod snapshot_tests;
+
+use std::fs;
+use std::path::{Path};
+
+use crate::backend::x64_print::{X64Printer};
+use crate::pipeline::{PassManager, PipelineOptions, ProgramForm};
+
+pub const UPDATE_VAR: &str = "UPDATE_SNAPSHOTS";
+
+// lines around a change that are shown in a diff
+const CONTEXT: usize = 2;
+
+pub fn updating() -> bool {
+    std::env::var(UPDATE_VAR).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+// compare 'actual' to the snapshot at 'path', or write it when updating
+pub fn check_snapshot(path: &Path, actual: &str) -> Result<(), String> {
+    if updating() {
+        return fs::write(path, actual).map_err(|e| format!("can't write '{}': {}", path.display(), e));
+    }
+
+    let expected = match fs::read_to_string(path) {
+        Ok(expected) => expected,
+        Err(_) => return Err(format!("'{}' has no snapshot yet, run with {}=1 to write it", path.display(), UPDATE_VAR)),
+    };
+
+    if expected == actual {
+        return Ok(());
+    }
+
+    let mut changes = diff(&expected, actual);
+
+    if changes.is_empty() {
+        changes = "only the line endings differ\n".to_owned();
+    }
+
+    Err(format!("'{}' doesn't match, run with {}=1 if the change is right\n{}", path.display(), UPDATE_VAR, changes))
+}
+
+// the program after every pass, the passes run one at a time
+pub fn pass_snapshot(program: ProgramForm, options: PipelineOptions) -> String {
+    let mut pm = PassManager::new(options);
+    let mut program = program;
+    let mut text = String::new();
+
+    while let Some(pass) = pm.next_pass() {
+        program = match pm.try_step(program) {
+            Ok(program) => program,
+            Err(e) => {
+                text.push_str(&format!("--- {} ---\n{}\n", pass.name, e));
+                return text;
+            }
+        };
+
+        text.push_str(&format!("--- {} ---\n{}\n\n", pass.name, program.print()));
+    }
+
+    text.push_str(&format!("--- nasm ---\n{}", X64Printer::new(program.into_x64()).print()));
+
+    text
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// the lines of both texts lined up by their longest common subsequence
+fn line_up<'a>(expected: &[&'a str], actual: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (expected.len(), actual.len());
+
+    // common[i][j] is the length of the lcs of expected[i..] and actual[j..]
+    let mut common = vec!(vec!(0usize; m + 1); n + 1);
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] =
+                if expected[i] == actual[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+        }
+    }
+
+    let mut lines = vec!();
+    let (mut i, mut j) = (0, 0);
+
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            lines.push(Line::Same(expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(Line::Removed(expected[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(actual[j]));
+            j += 1;
+        }
+    }
+
+    lines
+}
+
+// the changed lines with '-' for expected and '+' for actual, and a few
+// unchanged lines around them. '...' stands for the unchanged lines left out
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let lines = line_up(&expected, &actual);
+
+    let changed: Vec<usize> =
+        lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    let near_a_change = |i: usize| changed.iter().any(|&c| c.abs_diff(i) <= CONTEXT);
+
+    let mut text = String::new();
+    let mut skipped = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if !near_a_change(i) {
+            skipped = true;
+            continue;
+        }
+
+        if skipped {
+            text.push_str("  ...\n");
+            skipped = false;
+        }
+
+        match line {
+            Line::Same(l) => text.push_str(&format!("  {}\n", l)),
+            Line::Removed(l) => text.push_str(&format!("- {}\n", l)),
+            Line::Added(l) => text.push_str(&format!("+ {}\n", l)),
+        }
+    }
+
+    if skipped {
+        text.push_str("  ...\n");
+    }
+
+    text
+}
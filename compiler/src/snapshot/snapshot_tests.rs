@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::types::{OptLevel};
+use crate::frontend::lexer::{Lexer};
+use crate::frontend::parser::{Parser};
+use crate::pipeline::{PipelineOptions, ProgramForm};
+use crate::utility::{test_parse_helper};
+
+use super::*;
+
+#[test]
+fn snapshot_diff() {
+    assert_eq!(diff("a\nb\nc\n", "a\nb\nc\n"), "");
+
+    assert_eq!(diff("a\nb\nc", "a\nx\nc"), "  a\n- b\n+ x\n  c\n");
+
+    let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    let actual = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n";
+
+    assert_eq!(diff(expected, actual), "  ...\n  3\n  4\n- 5\n+ five\n  6\n  7\n  8\n  9\n+ 10\n");
+
+    assert_eq!(diff("", "a\n"), "+ a\n");
+    assert_eq!(diff("a\n", ""), "- a\n");
+}
+
+#[test]
+fn snapshot_passes() {
+    let text = pass_snapshot(ProgramForm::Ast(test_parse_helper("(+ 1 (read))")), PipelineOptions::default());
+
+    assert!(text.starts_with("--- uniquify ---\n(+ 1 (read))\n\n--- partial-eval ---\n"), "{}", text);
+    assert!(text.contains("\n--- select-instructions ---\nexternals: read_int\n"), "{}", text);
+    assert!(text.contains("\n--- nasm ---\n"), "{}", text);
+}
+
+fn corpus() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+// every program in the golden corpus, at -O2 so that every pass runs
+#[test]
+fn snapshot_corpus() {
+    let snapshots = corpus().join("snapshots");
+
+    let mut programs: Vec<PathBuf> =
+        fs::read_dir(corpus())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rkt"))
+        .collect();
+
+    programs.sort();
+
+    assert!(!programs.is_empty());
+
+    if updating() {
+        fs::create_dir_all(&snapshots).unwrap();
+    }
+
+    let mut failures = vec!();
+    let mut names = vec!();
+
+    for path in &programs {
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+
+        let source = fs::read_to_string(path).unwrap();
+
+        let mut parser = Parser::new(Lexer::new(&source).lex());
+        let program = parser.parse();
+
+        assert!(parser.parse_success(), "{} doesn't parse", path.display());
+
+        let options = PipelineOptions { opt_level: OptLevel::O2, ..PipelineOptions::default() };
+
+        let text = pass_snapshot(ProgramForm::Ast(program), options);
+
+        if let Err(msg) = check_snapshot(&snapshots.join(format!("{}.txt", name)), &text) {
+            failures.push(msg);
+        }
+
+        names.push(format!("{}.txt", name));
+    }
+
+    // a snapshot without its program is left over from a removed or renamed one
+    for entry in fs::read_dir(&snapshots).unwrap() {
+        let path = entry.unwrap().path();
+        let file = path.file_name().unwrap().to_string_lossy().into_owned();
+
+        if names.contains(&file) {
+            continue;
+        }
+
+        if updating() {
+            fs::remove_file(&path).unwrap();
+        } else {
+            failures.push(format!("'{}' has no program, run with {}=1 to remove it", path.display(), UPDATE_VAR));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
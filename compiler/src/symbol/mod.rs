@@ -0,0 +1,176 @@
+/*
+    interned identifiers
+
+    every name in the program (variables, labels, primitives, runtime functions) is
+    stored once in a global table, and a Symbol is just the index into it, so symbols
+    can be copied around and compared without touching the string
+
+    names generated by the compiler from a source name (e.g. x -> x.1 by uniquify)
+    remember where they came from, see Symbol::derive and Symbol::origin
+
+    the primitives are interned before anything else, so PLUS, MINUS and READ are
+    constants and the passes can match an operator without reading its name
+
+    symbols are ordered by name, which needs the table. a collection that is only
+    used for lookups, and whose order never shows in the output, is a HashMap or
+    HashSet, which hash the index
+*/
+
+#![allow(dead_code)]
+
+#[cfg(test)]
+mod symbol_tests;
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+pub const PLUS: Symbol = Symbol(0);
+pub const MINUS: Symbol = Symbol(1);
+pub const READ: Symbol = Symbol(2);
+
+// in the order of the constants above
+const PREINTERNED: &[&str] = &["+", "-", "read"];
+
+struct Interner {
+    // the strings are leaked, they live as long as the compiler does anyway
+    names: Vec<&'static str>,
+    ids: HashMap<&'static str, Symbol>,
+    // for generated names, the symbol they were generated from
+    origins: HashMap<Symbol, Symbol>,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.ids.get(name) {
+            return *symbol;
+        }
+
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        let symbol = Symbol(self.names.len() as u32);
+
+        self.names.push(name);
+        self.ids.insert(name, symbol);
+
+        symbol
+    }
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+    INTERNER.get_or_init(|| {
+        let mut interner = Interner {
+            names: vec!(),
+            ids: HashMap::new(),
+            origins: HashMap::new(),
+        };
+
+        for name in PREINTERNED {
+            interner.intern(name);
+        }
+
+        Mutex::new(interner)
+    })
+}
+
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    // a panic while holding the lock can't leave the table half updated
+    let mut guard = match interner().lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    f(&mut guard)
+}
+
+impl Symbol {
+
+    pub fn intern<S: AsRef<str>>(name: S) -> Symbol {
+        with_interner(|i| i.intern(name.as_ref()))
+    }
+
+    // 'base' + 'separator' + 'n', e.g. x.1, remembering that it came from 'base'
+    pub fn derive(base: Symbol, separator: &str, n: usize) -> Symbol {
+        let name = format!("{}{}{}", base.as_str(), separator, n);
+
+        with_interner(|i| {
+            let symbol = i.intern(&name);
+
+            let origin = *i.origins.get(&base).unwrap_or(&base);
+            i.origins.entry(symbol).or_insert(origin);
+
+            symbol
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        with_interner(|i| i.names[self.0 as usize])
+    }
+
+    // the name this symbol was (maybe indirectly) generated from, or the symbol itself
+    pub fn origin(self) -> Symbol {
+        with_interner(|i| *i.origins.get(&self).unwrap_or(&self))
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+// ordered by name, not by when they were interned, so the output doesn't depend
+// on which names happened to be seen first
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+
+        with_interner(|i| i.names[self.0 as usize].cmp(i.names[other.0 as usize]))
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
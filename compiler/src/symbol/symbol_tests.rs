@@ -0,0 +1,99 @@
+use super::*;
+
+use crate::utility::{test_ast_helper, AstStep};
+use crate::frontend::ast::{AstNode};
+
+#[test]
+fn symbol_same_name_same_symbol() {
+    let a = Symbol::intern("some-name");
+    let b = Symbol::intern(String::from("some-name"));
+
+    assert_eq!(a, b);
+    assert_eq!(a.as_str(), "some-name");
+    assert_eq!(format!("{}", a), "some-name");
+    assert_eq!(format!("{:?}", a), "\"some-name\"");
+}
+
+#[test]
+fn symbol_different_names() {
+    assert_ne!(Symbol::intern("a-name"), Symbol::intern("another-name"));
+}
+
+#[test]
+fn symbol_ordered_by_name() {
+    // interned in the opposite order of their names
+    let z = Symbol::intern("zzz-order");
+    let a = Symbol::intern("aaa-order");
+
+    assert!(a < z);
+
+    let mut symbols = vec!(z, a);
+    symbols.sort();
+
+    assert_eq!(symbols, vec!(a, z));
+}
+
+#[test]
+fn symbol_preinterned() {
+    assert_eq!(Symbol::intern("+"), PLUS);
+    assert_eq!(Symbol::intern("-"), MINUS);
+    assert_eq!(Symbol::intern("read"), READ);
+
+    assert_eq!(READ.as_str(), "read");
+
+    // still ordered by name
+    assert!(PLUS < MINUS && MINUS < READ);
+}
+
+#[test]
+fn symbol_derive_keeps_origin() {
+    let x = Symbol::intern("origin-x");
+
+    let x1 = Symbol::derive(x, ".", 1);
+    let x1_2 = Symbol::derive(x1, "_", 2);
+
+    assert_eq!(x1.as_str(), "origin-x.1");
+    assert_eq!(x1_2.as_str(), "origin-x.1_2");
+
+    assert_eq!(x1.origin(), x);
+    assert_eq!(x1_2.origin(), x);
+    assert_eq!(x.origin(), x);
+}
+
+#[test]
+fn symbol_uniquify_origin() {
+    let p = test_ast_helper("(let ([abc (read)]) abc)", vec!(AstStep::Uniquify));
+
+    match p.root() {
+        AstNode::Let { bindings, .. } => {
+            assert_eq!(bindings[0].identifier, "abc.1");
+            assert_eq!(bindings[0].identifier.origin(), "abc");
+        },
+
+        _ => panic!("expected a let, got {:?}", p),
+    }
+}
+
+// cargo test --release symbol_large_program -- --ignored --nocapture
+#[test]
+#[ignore]
+fn symbol_large_program() {
+    let n = 3000;
+
+    let mut src = String::from("(let ([v0 (read)]");
+
+    for i in 1..n {
+        src += &format!("[v{} (+ v{} (read))]", i, i - 1);
+    }
+
+    src += &format!(") v{})", n - 1);
+
+    let src: &'static str = Box::leak(src.into_boxed_str());
+
+    let start = std::time::Instant::now();
+
+    let asm = crate::utility::test_x64_helper(src);
+    let text = crate::backend::x64_print::X64Printer::new(asm).print();
+
+    println!("compiled {} bindings ({} bytes of assembly) in {:?}", n, text.len(), start.elapsed());
+}
@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use crate::frontend::ast::{AstNode};
 
-pub type IdString = std::rc::Rc<String>;
+// names are interned, see symbol
+pub type IdString = crate::symbol::Symbol;
 
 #[derive(Debug)]
 pub struct Environment {
@@ -16,23 +17,32 @@ impl Environment {
         }
     }
 
-    pub fn exists(&self, id: IdString) -> bool {
-        self.map.contains_key(&*id)
-    }
-
     pub fn insert(&mut self, id: IdString, val: AstNode) {
         self.map.insert(id, val);
     }
 
+    pub fn remove(&mut self, id: IdString) {
+        self.map.remove(&id);
+    }
+
     pub fn get(&self, id: IdString) -> Option<&AstNode> {
-        self.map.get(&*id)
+        self.map.get(&id)
+    }
+
+    // sorted by name, so they come out the same every time
+    pub fn bindings(&self) -> Vec<(IdString, &AstNode)> {
+        let mut bindings: Vec<(IdString, &AstNode)> = self.map.iter().map(|(id, val)| (*id, val)).collect();
+
+        bindings.sort_by_key(|(id, _)| id.to_string());
+
+        bindings
     }
 
     pub fn get_value_of(&self, id: IdString) -> Option<&AstNode> {
         let v = self.get(id);
         match v {
-            Some(&AstNode::Var{ ref name }) => {
-                self.get_value_of(name.clone())
+            Some(AstNode::Var{ name }) => {
+                self.get_value_of(*name)
             },
 
             Some(&AstNode::Int(_)) => {
@@ -44,4 +54,43 @@ impl Environment {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// how hard the compiler should try to improve the generated code
+// O0 is a straight translation, O1 and up enable the optimizing passes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+}
+
+// what happens when (+ e e) or (- e) doesn't fit in 64 bits. the interpreters,
+// partial-eval and the compiled code all follow the same rule
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    #[default]
+    Wrap,    // two's complement, like the hardware
+    Checked, // a runtime error
+}
+
+// the runtime error of an overflow in checked mode, the same everywhere
+pub const OVERFLOW_ERROR: &str = "integer overflow";
+
+impl Overflow {
+
+    // None if it overflowed in checked mode
+    pub fn add(self, a: i64, b: i64) -> Option<i64> {
+        match self {
+            Overflow::Wrap => Some(a.wrapping_add(b)),
+            Overflow::Checked => a.checked_add(b),
+        }
+    }
+
+    pub fn negate(self, a: i64) -> Option<i64> {
+        match self {
+            Overflow::Wrap => Some(a.wrapping_neg()),
+            Overflow::Checked => a.checked_neg(),
+        }
+    }
+}
@@ -1,58 +1,104 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
-use crate::frontend::ast::{Program};
+use crate::frontend::ast::{Program, STACK_SIZE};
 use crate::frontend::lexer::{Lexer};
 use crate::frontend::parser::{Parser};
 
-use crate::frontend::uniquify::{uniquify_program};
-use crate::frontend::partial_eval::{partially_evaluate};
-use crate::frontend::decomplify::{decomplify_program};
-use crate::ir::explicate::{explicate_control, IRProgram};
-use crate::backend::x64_backend::{IRToX64Transformer};
+use crate::ir::explicate::{IRProgram};
 use crate::backend::x64_def::{X64Program};
+use crate::pipeline::{PassManager, ProgramForm, IrKind};
+use crate::types::{OptLevel};
+use runtime::types::{RuntimeValue};
+
+use crate::interpreter::{Interpreter, InterpretResult, CachedRuntimeCall};
+use crate::interpreter::interp_ast::{AstInterpreter};
+use crate::interpreter::interp_ir::{IrInterpreter};
 
 #[derive(PartialEq, Eq, Hash, Debug)]
 pub enum AstStep {
     Uniquify,
     PartialEvaluation,
+    DeadBindings,
     Decomplify,
 }
 
-pub fn test_ast_helper(prog: &'static str, transform: Vec<AstStep>) -> Program {
-    let mut p = Parser::new(Lexer::new(prog).lex()).parse();
+impl AstStep {
+    pub fn pass_name(&self) -> &'static str {
+        match self {
+            AstStep::Uniquify => "uniquify",
+            AstStep::PartialEvaluation => "partial-eval",
+            AstStep::DeadBindings => "dead-bindings",
+            AstStep::Decomplify => "decomplify",
+        }
+    }
+}
 
-    for step in transform {
-        match step {
-            AstStep::Uniquify => {
-                p = uniquify_program(p);
-            },
+// runs 'f' on a thread with the stack a program nested MAX_NESTING deep needs
+pub fn with_compiler_stack<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(f)
+        .unwrap()
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
 
-            AstStep::PartialEvaluation => {
-                p = partially_evaluate(p);
-            },
+pub fn test_parse_helper(prog: &str) -> Program {
+    Parser::new(Lexer::new(prog).lex()).parse()
+}
 
-            AstStep::Decomplify => {
-                p = decomplify_program(p);
-            }
-        }
-    }
+pub fn test_ast_helper(prog: &'static str, transform: Vec<AstStep>) -> Program {
+    let names: Vec<&str> = transform.iter().map(|step| step.pass_name()).collect();
 
-    p
+    let mut pm = PassManager::custom(&names).unwrap();
+
+    pm.run(ProgramForm::Ast(test_parse_helper(prog))).into_ast()
 }
 
 pub fn test_ir_helper(prog: &'static str) -> IRProgram {
-    let p = test_ast_helper(prog, vec!(AstStep::Uniquify, AstStep::PartialEvaluation, AstStep::Decomplify));
-
-    explicate_control(p)
+    PassManager::for_level(OptLevel::O0)
+        .run_to(ProgramForm::Ast(test_parse_helper(prog)), IrKind::Ir)
+        .into_ir()
 }
 
 pub fn test_x64_helper(prog: &'static str) -> X64Program {
-    let ir = test_ir_helper(prog);
+    PassManager::for_level(OptLevel::O0)
+        .run(ProgramForm::Ast(test_parse_helper(prog)))
+        .into_x64()
+}
+
+// the backend passes alone, for an ir program written by hand
+pub fn test_backend_helper(p: IRProgram) -> X64Program {
+    PassManager::for_level(OptLevel::O0)
+        .run(ProgramForm::Ir(p))
+        .into_x64()
+}
 
-    IRToX64Transformer::new(ir).transform()
+// run the ast interpreter on 'p' with 'input' as the lines read by (read)
+// returns the result and the values that were read, in order
+pub fn test_interp_ast_helper(p: Program, input: Vec<&str>) -> (InterpretResult, Vec<RuntimeValue>) {
+    let mut crc = CachedRuntimeCall::new().set_input(input);
+
+    let result = {
+        let mut ast_interpreter = AstInterpreter::new(&p, &mut crc);
+        Interpreter::new(&mut ast_interpreter).run()
+    };
+
+    (result, crc.cached_results_of(crate::symbol::READ))
 }
 
+// same as test_interp_ast_helper, but for the ir
+pub fn test_interp_ir_helper(p: IRProgram, input: Vec<&str>) -> (InterpretResult, Vec<RuntimeValue>) {
+    let mut crc = CachedRuntimeCall::new().set_input(input);
+
+    let result = {
+        let mut ir_interpreter = IrInterpreter::new(&p, &mut crc);
+        Interpreter::new(&mut ir_interpreter).run()
+    };
+
+    (result, crc.cached_results_of(crate::symbol::READ))
+}
 
 // credits to https://stackoverflow.com/a/63904992 for this macro
 #[macro_export]
@@ -75,7 +121,7 @@ macro_rules! function {
 #[macro_export]
 macro_rules! idstr {
     ($s:expr) => {
-        crate::types::IdString::new($s.to_owned())
+        $crate::symbol::Symbol::intern($s)
     }
 }
 
@@ -83,7 +129,7 @@ macro_rules! idstr {
 macro_rules! map(
     { $($key:expr => $value:expr),+ } => {
         {
-            let mut m = std::collections::HashMap::new();
+            let mut m = std::collections::BTreeMap::new();
             $(
                 m.insert($key, $value);
             )+
@@ -92,7 +138,7 @@ macro_rules! map(
      };
 
     {} => {
-        std::collections::HashMap::new()
+        std::collections::BTreeMap::new()
     }
 );
 
@@ -100,7 +146,7 @@ macro_rules! map(
 macro_rules! set(
     { $($key:expr),+ } => {
         {
-            let mut m = std::collections::HashSet::new();
+            let mut m = std::collections::BTreeSet::new();
             $(
                 m.insert($key);
             )+
@@ -109,6 +155,6 @@ macro_rules! set(
      };
 
     {} => {
-        std::collections::HashSet::new()
+        std::collections::BTreeSet::new()
     }
 );
\ No newline at end of file
@@ -0,0 +1,232 @@
+/*
+    the golden tests, in the format of the book's test runner
+
+    every .rkt file in this directory is a program, the files next to it with
+    the same name are optional:
+
+        var_test_1.rkt    the program
+        var_test_1.in     the input for (read), one number per line
+        var_test_1.res    the result, a number or 'error'
+        var_test_1.out    what the executable prints, the runtime prints the result on its own line
+
+    a program is run at every stage of the compiler by 'compiler --run --dump-json',
+    at every optimization level. every stage has to read the same input as the
+    parsed program and give the result in .res, without it it only has to agree
+    with the parsed program. the executable is the only stage that prints, what it
+    printed has to be the same as .out. it's only built on linux with nasm and a
+    c compiler, otherwise that stage is skipped
+
+        cargo test --test golden            run all of them
+        cargo test --test golden -- var     only the programs with 'var' in their name
+*/
+
+// explicit field names, the same as the compiler
+#![allow(clippy::redundant_field_names)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// the compiler's json, the report is read with it
+#[path = "../src/json/value.rs"]
+mod value;
+
+use value::{Json};
+
+const OPT_LEVELS: &[&str] = &["-O0", "-O1", "-O2"];
+
+struct Case {
+    name: String,
+    program: PathBuf,
+    input: String,
+    result: Option<String>,
+    output: Option<String>,
+}
+
+// what 'compiler --run' reported for a stage
+struct Stage {
+    name: String,
+    value: String, // a number, 'error', 'out of fuel' or 'too deep'
+    reads: Vec<i64>,
+    stdout: Option<String>, // only the executable prints
+}
+
+// the report of a run, see DiffReport::to_json
+struct Report {
+    stages: Vec<Stage>,
+    native_skipped: Option<String>,
+}
+
+fn read_optional(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()
+}
+
+fn cases(dir: &Path, filter: Option<&str>) -> Vec<Case> {
+    let mut programs: Vec<PathBuf> =
+        fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rkt"))
+        .collect();
+
+    programs.sort();
+
+    programs
+        .into_iter()
+        .map(|program| {
+            Case {
+                name: program.file_stem().unwrap().to_string_lossy().into_owned(),
+                input: read_optional(&program.with_extension("in")).unwrap_or_default(),
+                result: read_optional(&program.with_extension("res")).map(|res| res.trim().to_owned()),
+                output: read_optional(&program.with_extension("out")),
+                program: program,
+            }
+        })
+        .filter(|case| filter.is_none_or(|filter| case.name.contains(filter)))
+        .collect()
+}
+
+fn optional_str(json: &Json) -> Result<Option<String>, String> {
+    match json {
+        Json::Null => Ok(None),
+        other => Ok(Some(other.as_str()?.to_owned())),
+    }
+}
+
+fn stage_from_json(json: &Json) -> Result<Stage, String> {
+    let outcome = json.get("outcome")?;
+
+    let value = match (outcome.get("value")?, optional_str(outcome.get("stopped")?)?) {
+        (Json::Null, Some(stopped)) => stopped,
+        (Json::Null, None) => "error".to_owned(),
+        (value, _) => value.as_i64()?.to_string(),
+    };
+
+    let reads: Result<Vec<i64>, String> = outcome.get("reads")?.as_array()?.iter().map(|n| n.as_i64()).collect();
+
+    Ok(Stage {
+        name: json.get("stage")?.as_str()?.to_owned(),
+        value: value,
+        reads: reads?,
+        stdout: optional_str(outcome.get("stdout")?)?,
+    })
+}
+
+fn parse_report(report: &str) -> Result<Report, String> {
+    let json = Json::parse(report)?;
+
+    let stages: Result<Vec<Stage>, String> = json.get("stages")?.as_array()?.iter().map(stage_from_json).collect();
+
+    Ok(Report {
+        stages: stages?,
+        native_skipped: optional_str(json.get("native_skipped")?)?,
+    })
+}
+
+fn run(case: &Case, opt_level: &str) -> Result<Report, String> {
+    let mut child =
+        Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg("--run")
+        .arg("--dump-json")
+        .arg(opt_level)
+        .arg(&case.program)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    child.stdin.take().unwrap().write_all(case.input.as_bytes()).map_err(|e| e.to_string())?;
+
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+
+    // nothing on stdout if the program didn't parse
+    if output.stdout.is_empty() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+
+    parse_report(&String::from_utf8_lossy(&output.stdout))
+}
+
+// what's wrong with a stage, the parsed program is the reference
+fn check(case: &Case, stage: &Stage, reference: &Stage) -> Vec<String> {
+    let mut problems = vec!();
+
+    match &case.result {
+        Some(result) if stage.value != *result => problems.push(format!("the result is {}, expected {}", stage.value, result)),
+        None if stage.value != reference.value => problems.push(format!("the result is {}, the parsed program gives {}", stage.value, reference.value)),
+        _ => (),
+    }
+
+    if let (Some(output), Some(printed)) = (&case.output, &stage.stdout) {
+        if printed != output {
+            problems.push(format!("prints {:?}, expected {:?}", printed, output));
+        }
+    }
+
+    if stage.reads != reference.reads {
+        problems.push(format!("read {:?}, the parsed program read {:?}", stage.reads, reference.reads));
+    }
+
+    problems
+}
+
+fn main() {
+    // the first argument that isn't a flag of the test harness
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let cases = cases(&dir, filter.as_deref());
+
+    let mut failed = vec!();
+
+    for case in &cases {
+        for opt_level in OPT_LEVELS {
+            let name = format!("{} {}", case.name, opt_level);
+
+            println!("{}", name);
+
+            let report = match run(case, opt_level) {
+                Ok(report) => report,
+                Err(msg) => {
+                    println!("    FAIL {}", msg);
+                    failed.push(name);
+                    continue;
+                }
+            };
+
+            let mut ok = true;
+
+            for stage in &report.stages {
+                let problems = check(case, stage, &report.stages[0]);
+
+                if problems.is_empty() {
+                    println!("    ok   {}", stage.name);
+                } else {
+                    println!("    FAIL {}: {}", stage.name, problems.join(", "));
+                    ok = false;
+                }
+            }
+
+            if let Some(reason) = &report.native_skipped {
+                println!("    skip native, {}", reason);
+            }
+
+            if !ok {
+                failed.push(name);
+            }
+        }
+    }
+
+    println!("\n{} programs, {} runs failed", cases.len(), failed.len());
+
+    for name in &failed {
+        println!("    {}", name);
+    }
+
+    if !failed.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -48,6 +48,16 @@ pub extern "C" fn read_int() -> RuntimeI64 {
     the_int
 }
 
+// the compiled code jumps here when an overflow is checked, it doesn't return
+#[no_mangle]
+pub extern "C" fn overflow_error() {
+    eprintln!("error: integer overflow");
+
+    unsafe {
+        ExitProcess(3);
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn print_int(int: RuntimeI64) {
     let printee = int.to_string();
@@ -4,7 +4,7 @@
 pub type RuntimeString = String;
 pub type RuntimeI64 = i64;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum RuntimeValue {
     RuntimeI64(RuntimeI64),
 }
\ No newline at end of file